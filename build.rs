@@ -1,8 +1,14 @@
+use std::env;
+use std::path::PathBuf;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
     // Include the google protobuf types from our tools directory
     tonic_build::configure()
         .build_server(true)
         .build_client(true) // Also build client for integration tests
+        // For the reflection service (--enable-reflection)
+        .file_descriptor_set_path(out_dir.join("csi_descriptor.bin"))
         .compile_protos(&["proto/csi.proto"], &["proto/", "tools/include/"])?;
     Ok(())
 }
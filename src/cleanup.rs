@@ -5,45 +5,390 @@
 //! 2. DeleteVolume → ConfigMap marked as cleanup pending
 //! 3. Node plugins watch for cleanup ConfigMaps
 //! 4. Each node deletes its local directory and reports in `nodes_completed`
-//! 5. Controller prunes ConfigMap when all nodes complete (or after timeout)
+//! 5. Controller prunes ConfigMap when all nodes complete (or a quorum, or after timeout)
+//!
+//! Shared caches (`volume::SHARED_NAME_KEY`) are tracked by their own ConfigMap,
+//! keyed by `volume::shared_volume_id`, which lists the referencing volumes.
+//! When DeleteVolume drops the last reference, that ConfigMap is marked for
+//! cleanup and goes through the same node cleanup / prune flow.
+//!
+//! With `--set-owner-references`, a volume's ConfigMap is owned by its
+//! PersistentVolume while active, so deleting a PV that never went through
+//! DeleteVolume (e.g. `Retain` policy) garbage-collects the ConfigMap. The
+//! owner reference is dropped once cleanup is requested, so that the PV's
+//! deletion right after DeleteVolume can't cascade into a pending cleanup.
+//!
+//! A node that finds an active volume's directory gone without cleanup (e.g.
+//! deleted by hand) records itself in `nodes_directory_absent`. Once cleanup
+//! is requested, the controller counts such nodes as completed, since there
+//! is nothing left for them to delete.
+//!
+//! Events about a volume are attached to its ConfigMap in the driver namespace,
+//! or with `--event-namespace pvc` to its PersistentVolumeClaim, when the PVC
+//! is known (provisioner `--extra-create-metadata`). Shared caches and volumes
+//! without PVC details always fall back to the driver namespace.
+//!
+//! With `--delete-broadcast-on-missing`, DeleteVolume of a volume without a
+//! ConfigMap (e.g. a node failed to register its publish) creates a cleanup
+//! ConfigMap with the `sweep` flag: every node checks for the directory and
+//! deletes it, and the controller waits for all nodes in the cluster.
+//!
+//! With `--cleanup-quorum`, the controller prunes a ConfigMap once the given
+//! share of its nodes reported, instead of waiting for all of them. The
+//! stragglers are handed the cleanup through the `stragglers` ConfigMap and
+//! delete their directories on their own, untracked.
+//!
+//! With `--immutable-stable-configmaps`, an active volume's ConfigMap is
+//! written `immutable: true` once a node registered it, so the API server
+//! and kubelets needn't watch it for changes. Changing an immutable
+//! ConfigMap's data (another node, cleanup) means deleting it, on the
+//! condition it's unchanged, and creating it again; cleanup ConfigMaps stay
+//! mutable. A writer that finds the ConfigMap missing in between creates a
+//! fresh one, which the recreating writer merges the deleted status into.
 
 use std::collections::BTreeMap;
-use std::collections::HashSet;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{LazyLock, OnceLock};
 use std::time::Duration;
 
+use clap::ValueEnum;
+
 use rand::Rng;
 
-use k8s_openapi::api::core::v1::{ConfigMap, Event, Node, ObjectReference};
+use k8s_openapi::api::core::v1::{ConfigMap, Event, Node, ObjectReference, PersistentVolume};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
 use kube::{
-    api::{Api, ListParams, PostParams},
+    api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams, Preconditions},
+    runtime::{watcher, WatchStreamExt},
     Client,
 };
 use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use tokio_stream::StreamExt;
 use tracing::{debug, error, info, warn};
+use uuid::Uuid;
+
+use crate::cleanup_journal;
+use crate::coordination::{self, CoordinationBackend};
+use crate::dir_size;
+use crate::directory::DirectoryBackend;
+use crate::health::Heartbeat;
+use crate::history;
+use crate::inventory;
+use crate::metrics;
+use crate::mount_audit;
+use crate::pending_registration::{self, PendingRegistration};
+use crate::quota::QuotaBackend;
+use crate::recent_events::{RecentEvents, EVENT_DEDUP_WINDOW, RECENT_EVENTS_CAPACITY};
+use crate::stragglers;
+use crate::tmpfs;
+use crate::volume;
+use crate::volume_lock::VolumeLocks;
 
 /// Label key for volume ConfigMaps
 pub const VOLUME_LABEL: &str = "node-local-cache.csi.io/volume";
 /// ConfigMap name prefix
 pub const VOLUME_CM_PREFIX: &str = "nlc-vol-";
 
-/// Maximum retries for optimistic concurrency conflicts
-/// High value to handle gang scheduling scenarios where many pods start simultaneously
-const MAX_RETRIES: u32 = 15;
+/// Label on the extra ConfigMaps (shards, `nlc-vol-<id>-<n>`) of a volume
+/// with too many nodes for one, holding the shard's index
+pub const SHARD_LABEL: &str = "node-local-cache.csi.io/shard";
+/// Serialized status size past which further nodes go into a shard, well
+/// below the 1 MiB object size limit
+const SHARD_THRESHOLD_BYTES: usize = 700 * 1024;
+
+/// Longest Kubernetes object name
+const MAX_NAME_LEN: usize = 253;
+/// Marks ConfigMap names derived from a hash of an over-long tracking ID
+const HASHED_NAME_INFIX: &str = "h-";
+
+/// Default maximum retries for optimistic concurrency conflicts
+/// (`--cleanup-max-retries`). High value to handle gang scheduling scenarios
+/// where many pods start simultaneously
+pub const MAX_RETRIES: u32 = 15;
+
+/// Default conflict retries per node cleanup pass (`--cleanup-retry-budget`)
+pub const DEFAULT_RETRY_BUDGET: u32 = 50;
+
+/// How often the controller checks cleanup ConfigMaps
+pub const CONTROLLER_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+/// How often nodes poll for cleanup requests, or run their other periodic
+/// tasks when watching for them
+pub const NODE_CLEANUP_INTERVAL: Duration = Duration::from_secs(10);
+/// How often a node watching for cleanup requests lists them all anyway, to
+/// retry deferred cleanups
+pub const CLEANUP_RESYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// How often nodes check volume ages (`--max-volume-age`)
+pub const AGE_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often nodes check that their active volume directories still exist
+pub const DIRECTORY_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often nodes look for orphaned volume directories (`--orphan-gc`)
+pub const ORPHAN_GC_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// How often the node retries deleting quarantined directories
+pub const QUARANTINE_SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Where volume events are emitted, set once at startup (`set_event_namespace`)
+static EVENT_NAMESPACE: OnceLock<EventNamespace> = OnceLock::new();
+
+/// Cleared by `--no-events`
+static EVENTS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Node named in audit log lines (`set_event_node`), none on the controller
+static EVENT_NODE: OnceLock<String> = OnceLock::new();
+
+/// Events bumped rather than repeated (see `recent_events`)
+static RECENT_EVENTS: LazyLock<RecentEvents> =
+    LazyLock::new(|| RecentEvents::new(EVENT_DEDUP_WINDOW, RECENT_EVENTS_CAPACITY));
+
+/// Tracing target of the audit log lines `emit_event` writes for every event
+pub const AUDIT_TARGET: &str = "nlc::audit";
+
+/// Set by `--immutable-stable-configmaps`
+static IMMUTABLE_STABLE_CONFIGMAPS: AtomicBool = AtomicBool::new(false);
 
 /// Base backoff delay in milliseconds for optimistic concurrency retries
 const BASE_BACKOFF_MS: u64 = 10;
 /// Maximum backoff delay in milliseconds
 const MAX_BACKOFF_MS: u64 = 1000;
 
+/// How ConfigMap updates retry on conflicts, set once at startup
+/// (`set_retry_config`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Attempts of one update before giving up
+    pub max_retries: u32,
+    /// Backoff before the first retry, doubled on each one
+    pub base_backoff_ms: u64,
+    /// Longest backoff
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: MAX_RETRIES,
+            base_backoff_ms: BASE_BACKOFF_MS,
+            max_backoff_ms: MAX_BACKOFF_MS,
+        }
+    }
+}
+
+static RETRY_CONFIG: OnceLock<RetryConfig> = OnceLock::new();
+
+/// Set how ConfigMap updates retry. Only the first call has an effect.
+pub fn set_retry_config(config: RetryConfig) {
+    let _ = RETRY_CONFIG.set(config);
+}
+
+fn retry_config() -> RetryConfig {
+    RETRY_CONFIG.get().copied().unwrap_or_default()
+}
+
+/// Conflict retries the node cleanup loop may spend on ConfigMap updates in
+/// one pass (`--cleanup-retry-budget`), so contention can't stretch a pass
+/// indefinitely. Once a retry is refused, the rest of the pass is deferred.
+struct RetryBudget {
+    remaining: AtomicU32,
+    exhausted: AtomicBool,
+}
+
+impl RetryBudget {
+    fn new(retries: u32) -> Self {
+        Self {
+            remaining: AtomicU32::new(retries),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    /// Take one retry, false once there are none left
+    fn take(&self) -> bool {
+        let taken = self
+            .remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if !taken {
+            self.exhausted.store(true, Ordering::Relaxed);
+        }
+        taken
+    }
+
+    /// Whether a retry was refused
+    fn exhausted(&self) -> bool {
+        self.exhausted.load(Ordering::Relaxed)
+    }
+}
+
 /// Sleep with exponential backoff and jitter to avoid thundering herd
-async fn backoff_sleep(attempt: u32) {
-    let base = BASE_BACKOFF_MS * 2u64.pow(attempt.min(6)); // cap exponent to avoid overflow
-    let max = base.min(MAX_BACKOFF_MS);
+pub(crate) async fn backoff_sleep(attempt: u32) {
+    let max = backoff_limit(&retry_config(), attempt);
     let jitter = rand::rng().random_range(0..=max);
     tokio::time::sleep(Duration::from_millis(jitter)).await;
 }
 
+/// Upper bound of the jittered backoff before retry `attempt`
+fn backoff_limit(config: &RetryConfig, attempt: u32) -> u64 {
+    // cap exponent to avoid overflow
+    let base = config.base_backoff_ms * 2u64.pow(attempt.min(6));
+    base.min(config.max_backoff_ms)
+}
+
+/// Where events about a volume are emitted (`--event-namespace`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventNamespace {
+    /// The driver's namespace, on the volume's ConfigMap
+    #[default]
+    Driver,
+    /// The PVC's namespace, on the PVC, when known; otherwise the driver's
+    Pvc,
+}
+
+/// How nodes learn about cleanup requests (`--cleanup-trigger`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CleanupTrigger {
+    /// Watch the ConfigMaps requesting cleanup, with a full resync every
+    /// `CLEANUP_RESYNC_INTERVAL`
+    #[default]
+    Watch,
+    /// List them every `NODE_CLEANUP_INTERVAL`
+    Poll,
+}
+
+/// How many of a volume's nodes must report before its ConfigMap is pruned
+/// (`--cleanup-quorum`): all of them, or a percentage. Nodes that haven't
+/// reported by then clean up on their own, untracked (see `stragglers`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CleanupQuorum {
+    #[default]
+    All,
+    /// Percentage of the nodes, 1 to 99
+    Percent(u8),
+}
+
+impl CleanupQuorum {
+    /// Parse `all` or a percentage like `90%`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Self::All);
+        }
+        let percent = s
+            .strip_suffix('%')
+            .and_then(|n| n.trim().parse::<u8>().ok())
+            .filter(|p| (1..=100).contains(p))
+            .ok_or_else(|| format!("invalid quorum `{}` (expected `all` or e.g. `90%`)", s))?;
+        Ok(match percent {
+            100 => Self::All,
+            p => Self::Percent(p),
+        })
+    }
+
+    /// Reports needed out of `nodes`, rounded up
+    pub fn required(&self, nodes: usize) -> usize {
+        match self {
+            Self::All => nodes,
+            Self::Percent(p) => (nodes * *p as usize).div_ceil(100),
+        }
+    }
+}
+
+impl std::fmt::Display for CleanupQuorum {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Percent(p) => write!(f, "{}%", p),
+        }
+    }
+}
+
+/// Set where volume events are emitted. Only the first call has an effect.
+pub fn set_event_namespace(mode: EventNamespace) {
+    let _ = EVENT_NAMESPACE.set(mode);
+}
+
+/// Turn Kubernetes event emission on or off process-wide (`--no-events`)
+pub fn set_events_enabled(enabled: bool) {
+    EVENTS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Set the node named in audit log lines. Only the first call has an effect.
+pub fn set_event_node(node_name: &str) {
+    let _ = EVENT_NODE.set(node_name.to_string());
+}
+
+/// Write stable volumes' ConfigMaps immutable (`--immutable-stable-configmaps`)
+pub fn set_immutable_stable_configmaps(enabled: bool) {
+    IMMUTABLE_STABLE_CONFIGMAPS.store(enabled, Ordering::Relaxed);
+}
+
+fn immutable_stable_configmaps() -> bool {
+    #[cfg(test)]
+    if tests::IMMUTABLE_STABLE.get() {
+        return true;
+    }
+    IMMUTABLE_STABLE_CONFIGMAPS.load(Ordering::Relaxed)
+}
+
+/// Whether events are emitted at all
+pub fn events_enabled() -> bool {
+    // Tests share the process-wide flag, so they disable events per thread
+    #[cfg(test)]
+    if tests::EVENTS_DISABLED.get() {
+        return false;
+    }
+    EVENTS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// The PersistentVolumeClaim a volume was provisioned for
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PvcRef {
+    pub namespace: String,
+    pub name: String,
+}
+
+impl PvcRef {
+    /// PVC from the volume context (`volume::PVC_NAME_KEY` and
+    /// `volume::PVC_NAMESPACE_KEY`), if both are present
+    pub fn from_volume_context(volume_context: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            namespace: volume_context.get(volume::PVC_NAMESPACE_KEY)?.clone(),
+            name: volume_context.get(volume::PVC_NAME_KEY)?.clone(),
+        })
+    }
+}
+
+/// What CreateVolume was called with, recorded with `--stateful-create`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CreateRecord {
+    /// Name the CO asked for (the PV name)
+    pub name: String,
+    pub capacity_bytes: i64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl CreateRecord {
+    /// Whether a repeated CreateVolume for the same name, asking for
+    /// `parameters` and at most `limit_bytes` (0 for no limit), is served by
+    /// the recorded volume, per CSI's idempotency rules
+    pub fn is_compatible(
+        &self,
+        parameters: &BTreeMap<String, String>,
+        required_bytes: i64,
+        limit_bytes: i64,
+    ) -> bool {
+        &self.parameters == parameters
+            && self.capacity_bytes >= required_bytes
+            && (limit_bytes == 0 || self.capacity_bytes <= limit_bytes)
+    }
+}
+
 /// Volume status stored in ConfigMap data
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VolumeStatus {
@@ -55,11 +400,77 @@ pub struct VolumeStatus {
     pub nodes_with_volume: Vec<String>,
     #[serde(default)]
     pub nodes_completed: Vec<String>,
+    /// When each node in `nodes_completed` finished its cleanup
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nodes_completed_at: BTreeMap<String, String>,
+    /// Bytes each node in `nodes_completed` freed by deleting its directory,
+    /// for those that counted them (not for btrfs subvolumes)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nodes_reclaimed_bytes: BTreeMap<String, u64>,
     #[serde(default)]
     pub nodes_failed: Vec<String>,
     /// Nodes that no longer exist in the cluster (scaled down, decommissioned)
     #[serde(default)]
     pub nodes_decommissioned: Vec<String>,
+    /// Shared cache this volume maps onto, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_name: Option<String>,
+    /// For a shared cache: the volumes currently referencing it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub references: Vec<String>,
+    /// When each node last recycled (cleared) its copy after exceeding the max age
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub recycled_at: BTreeMap<String, String>,
+    /// PVC the volume was provisioned for, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pvc: Option<PvcRef>,
+    /// Nodes that found their directory gone outside of cleanup (e.g. deleted
+    /// by hand); the controller counts them as completed once cleanup starts
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nodes_directory_absent: Vec<String>,
+    /// Mount options recorded at the first publish, applied by every later
+    /// publish (see `mount_options`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount_options: Option<Vec<String>>,
+    /// Extra labels for the ConfigMap, from the StorageClass
+    /// (`volume::LABEL_PARAM_PREFIX` parameters)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// When the controller first found each pending node missing from the
+    /// cluster (`--decommission-grace`)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub nodes_absent_since: BTreeMap<String, String>,
+    /// No node registered the volume: every node is to check for its
+    /// directory (`--delete-broadcast-on-missing`)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sweep: bool,
+    /// The CreateVolume request the volume was created for (`--stateful-create`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub create_request: Option<CreateRecord>,
+    /// Requested capacity, from the first publish's volume context; 0 when
+    /// none was requested (or the ConfigMap predates the field)
+    #[serde(default)]
+    pub capacity_bytes: i64,
+    /// Base path the volume is stored under, from the first publish's volume
+    /// context (`volume::BASE_PATH_KEY`); the node's `--base-path` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_path: Option<PathBuf>,
+    /// Disk pressure eviction priority, from the latest publish's volume
+    /// context (`volume::EVICTION_PRIORITY_KEY`); the default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eviction_priority: Option<u8>,
+    /// Number of shards holding the nodes that didn't fit (primary only)
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shards: u32,
+    /// Index of this shard, 0 for the primary ConfigMap. A shard only tracks
+    /// its nodes, with a copy of the primary's volume-wide fields (see
+    /// `follow`).
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shard: u32,
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
 }
 
 impl VolumeStatus {
@@ -70,8 +481,33 @@ impl VolumeStatus {
             cleanup_requested_at: None,
             nodes_with_volume: Vec::new(),
             nodes_completed: Vec::new(),
+            nodes_completed_at: BTreeMap::new(),
+            nodes_reclaimed_bytes: BTreeMap::new(),
             nodes_failed: Vec::new(),
             nodes_decommissioned: Vec::new(),
+            shared_name: None,
+            references: Vec::new(),
+            recycled_at: BTreeMap::new(),
+            pvc: None,
+            nodes_directory_absent: Vec::new(),
+            mount_options: None,
+            labels: BTreeMap::new(),
+            nodes_absent_since: BTreeMap::new(),
+            sweep: false,
+            create_request: None,
+            capacity_bytes: 0,
+            base_path: None,
+            eviction_priority: None,
+            shards: 0,
+            shard: 0,
+        }
+    }
+
+    /// Empty shard `shard` of a volume
+    fn new_shard(volume_id: &str, shard: u32) -> Self {
+        Self {
+            shard,
+            ..Self::new(volume_id)
         }
     }
 
@@ -81,19 +517,224 @@ impl VolumeStatus {
         serde_json::from_str(status_json).ok()
     }
 
+    /// ConfigMap data holding this status. The node and volume lists are sets
+    /// kept in arrival order; they are stored sorted, so the same sets always
+    /// serialize the same way and unchanged statuses aren't rewritten.
     pub fn to_configmap_data(&self) -> BTreeMap<String, String> {
+        let mut sorted = self.clone();
+        for list in [
+            &mut sorted.nodes_with_volume,
+            &mut sorted.nodes_completed,
+            &mut sorted.nodes_failed,
+            &mut sorted.nodes_decommissioned,
+            &mut sorted.nodes_directory_absent,
+            &mut sorted.references,
+        ] {
+            list.sort();
+        }
+
         let mut data = BTreeMap::new();
         data.insert(
             "status".to_string(),
-            serde_json::to_string(self).unwrap_or_default(),
+            serde_json::to_string(&sorted).unwrap_or_default(),
         );
         data
     }
 
+    /// Whether `node_name` is listed, or would still keep the ConfigMap below
+    /// `SHARD_THRESHOLD_BYTES` once added, counting what each node adds
+    /// when it reports its cleanup
+    fn has_room_for(&self, node_name: &str) -> bool {
+        if self.nodes_with_volume.iter().any(|n| n == node_name) {
+            return true;
+        }
+        let mut grown = self.clone();
+        grown.add_node(node_name);
+        let size: usize = grown.to_configmap_data().values().map(String::len).sum();
+        // In `nodes_completed`, `nodes_completed_at` with a timestamp and
+        // `nodes_reclaimed_bytes` with a byte count; counted again for nodes
+        // that reported already
+        let reports: usize = grown
+            .nodes_with_volume
+            .iter()
+            .map(|node| 3 * node.len() + 72)
+            .sum();
+        size + reports <= SHARD_THRESHOLD_BYTES
+    }
+
+    /// Copy the volume-wide fields of `primary` into this shard. A shard
+    /// whose volume was taken back into use (a shared cache) starts over.
+    fn follow(&mut self, primary: &VolumeStatus) {
+        if primary.cleanup_requested_at.is_none() && self.cleanup_requested_at.is_some() {
+            self.nodes_completed.clear();
+            self.nodes_completed_at.clear();
+            self.nodes_reclaimed_bytes.clear();
+            self.nodes_failed.clear();
+        }
+        self.created_at = primary.created_at.clone();
+        self.cleanup_requested_at = primary.cleanup_requested_at.clone();
+        self.shared_name = primary.shared_name.clone();
+        self.pvc = primary.pvc.clone();
+        self.base_path = primary.base_path.clone();
+        self.eviction_priority = primary.eviction_priority;
+    }
+
+    /// Register a node publishing the volume. A node that had reported its
+    /// directory absent has recreated it by now.
     pub fn add_node(&mut self, node_name: &str) {
         if !self.nodes_with_volume.contains(&node_name.to_string()) {
             self.nodes_with_volume.push(node_name.to_string());
         }
+        self.nodes_directory_absent.retain(|n| n != node_name);
+    }
+
+    pub fn mark_node_directory_absent(&mut self, node_name: &str) {
+        if !self.nodes_directory_absent.contains(&node_name.to_string()) {
+            self.nodes_directory_absent.push(node_name.to_string());
+        }
+    }
+
+    /// Add a referencing volume to a shared cache.
+    /// A shared cache that was already being cleaned up is taken back into use.
+    pub fn add_reference(&mut self, volume_id: &str) {
+        if !self.references.contains(&volume_id.to_string()) {
+            self.references.push(volume_id.to_string());
+        }
+        if self.cleanup_requested_at.is_some() {
+            self.cleanup_requested_at = None;
+            self.nodes_completed.clear();
+            self.nodes_completed_at.clear();
+            self.nodes_reclaimed_bytes.clear();
+            self.nodes_failed.clear();
+        }
+    }
+
+    pub fn remove_reference(&mut self, volume_id: &str) {
+        self.references.retain(|v| v != volume_id);
+    }
+
+    /// The capacity for event messages, e.g. `, capacity 10737418240 bytes`;
+    /// empty when unknown
+    fn capacity_note(&self) -> String {
+        if self.capacity_bytes > 0 {
+            format!(", capacity {} bytes", self.capacity_bytes)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Bytes freed by the completed nodes, in the CleanupComplete event
+    fn reclaimed_note(&self) -> String {
+        let reclaimed: Vec<u64> = self
+            .nodes_completed
+            .iter()
+            .filter_map(|node| self.nodes_reclaimed_bytes.get(node).copied())
+            .collect();
+        if reclaimed.is_empty() {
+            return String::new();
+        }
+        format!(
+            ", reclaimed {} bytes on {} node(s)",
+            reclaimed.iter().sum::<u64>(),
+            reclaimed.len()
+        )
+    }
+
+    /// Labels of the ConfigMap: the extra labels, then `VOLUME_LABEL` (and
+    /// `SHARD_LABEL` on a shard)
+    pub fn configmap_labels(&self) -> BTreeMap<String, String> {
+        let mut labels = self.labels.clone();
+        labels.insert(VOLUME_LABEL.to_string(), self.phase_label().to_string());
+        if self.shard > 0 {
+            labels.insert(SHARD_LABEL.to_string(), self.shard.to_string());
+        }
+        labels
+    }
+
+    /// Whether the volume is in use and not being cleaned up, so its
+    /// ConfigMap only changes when another node or volume joins
+    pub fn is_stable(&self) -> bool {
+        self.cleanup_requested_at.is_none() && !self.nodes_with_volume.is_empty()
+    }
+
+    /// Fold in `older`, the status of a ConfigMap deleted to be recreated,
+    /// when another writer created a fresh one in between. Sets are merged;
+    /// the older creation time and recorded mount options win.
+    pub fn merge_older(&mut self, older: &VolumeStatus) {
+        self.created_at = older.created_at.clone();
+        if self.cleanup_requested_at.is_none() {
+            self.cleanup_requested_at = older.cleanup_requested_at.clone();
+        }
+        self.merge_nodes(older);
+        for item in &older.references {
+            if !self.references.contains(item) {
+                self.references.push(item.clone());
+            }
+        }
+        for (key, value) in &older.labels {
+            self.labels
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        if self.shared_name.is_none() {
+            self.shared_name = older.shared_name.clone();
+        }
+        if self.pvc.is_none() {
+            self.pvc = older.pvc.clone();
+        }
+        if older.mount_options.is_some() {
+            self.mount_options = older.mount_options.clone();
+        }
+        self.sweep |= older.sweep;
+        if self.create_request.is_none() {
+            self.create_request = older.create_request.clone();
+        }
+        self.shards = self.shards.max(older.shards);
+    }
+
+    /// Fold in the node lists of `other`, another status of the same volume
+    /// (an older copy, or a shard). Sets are merged; per-node times already
+    /// known win.
+    pub fn merge_nodes(&mut self, other: &VolumeStatus) {
+        for (list, theirs) in [
+            (&mut self.nodes_with_volume, &other.nodes_with_volume),
+            (&mut self.nodes_completed, &other.nodes_completed),
+            (&mut self.nodes_failed, &other.nodes_failed),
+            (&mut self.nodes_decommissioned, &other.nodes_decommissioned),
+            (
+                &mut self.nodes_directory_absent,
+                &other.nodes_directory_absent,
+            ),
+        ] {
+            for item in theirs {
+                if !list.contains(item) {
+                    list.push(item.clone());
+                }
+            }
+        }
+        for (map, theirs) in [
+            (&mut self.nodes_completed_at, &other.nodes_completed_at),
+            (&mut self.recycled_at, &other.recycled_at),
+            (&mut self.nodes_absent_since, &other.nodes_absent_since),
+        ] {
+            for (key, value) in theirs {
+                map.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        for (node, bytes) in &other.nodes_reclaimed_bytes {
+            self.nodes_reclaimed_bytes
+                .entry(node.clone())
+                .or_insert(*bytes);
+        }
+    }
+
+    /// Value of `VOLUME_LABEL` for this status
+    pub fn phase_label(&self) -> &'static str {
+        if self.cleanup_requested_at.is_some() {
+            "cleanup"
+        } else {
+            "active"
+        }
     }
 
     pub fn mark_cleanup_requested(&mut self) {
@@ -105,9 +746,32 @@ impl VolumeStatus {
     pub fn mark_node_completed(&mut self, node_name: &str) {
         if !self.nodes_completed.contains(&node_name.to_string()) {
             self.nodes_completed.push(node_name.to_string());
+            self.nodes_completed_at
+                .insert(node_name.to_string(), chrono::Utc::now().to_rfc3339());
         }
     }
 
+    /// How long each completed node took to clean up after it was requested.
+    /// Nodes with an unparseable completion time, or one before the request
+    /// (clock skew), are left out.
+    pub fn cleanup_durations(&self) -> Vec<(&str, Duration)> {
+        let Some(requested) = self
+            .cleanup_requested_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        else {
+            return Vec::new();
+        };
+        self.nodes_completed_at
+            .iter()
+            .filter_map(|(node, completed)| {
+                let completed = chrono::DateTime::parse_from_rfc3339(completed).ok()?;
+                let duration = completed.signed_duration_since(requested).to_std().ok()?;
+                Some((node.as_str(), duration))
+            })
+            .collect()
+    }
+
     pub fn mark_node_failed(&mut self, node_name: &str) {
         if !self.nodes_failed.contains(&node_name.to_string()) {
             self.nodes_failed.push(node_name.to_string());
@@ -120,6 +784,32 @@ impl VolumeStatus {
         }
     }
 
+    pub fn mark_node_recycled(&mut self, node_name: &str) {
+        self.recycled_at
+            .insert(node_name.to_string(), chrono::Utc::now().to_rfc3339());
+    }
+
+    /// Age of a node's copy of the volume: time since it was created, or since
+    /// the node last recycled it. `None` if the timestamp can't be parsed.
+    pub fn age_on_node(
+        &self,
+        node_name: &str,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Option<chrono::Duration> {
+        let since = self.recycled_at.get(node_name).unwrap_or(&self.created_at);
+        let since = chrono::DateTime::parse_from_rfc3339(since).ok()?;
+        Some(now.signed_duration_since(since))
+    }
+
+    /// Nodes that still hold a copy of the volume: a node has it from its
+    /// first publish until it cleaned it up or left the cluster
+    pub fn published_nodes(&self) -> Vec<&String> {
+        self.nodes_with_volume
+            .iter()
+            .filter(|n| !self.nodes_completed.contains(n) && !self.nodes_decommissioned.contains(n))
+            .collect()
+    }
+
     /// Check if cleanup is complete (all nodes with volume have reported or are gone)
     pub fn is_cleanup_complete(&self) -> bool {
         if self.cleanup_requested_at.is_none() {
@@ -135,6 +825,79 @@ impl VolumeStatus {
         nodes_with.is_subset(&nodes_done)
     }
 
+    /// Check if enough nodes with the volume have reported (or are gone) for
+    /// `quorum`. Cleanup must have been requested.
+    pub fn has_cleanup_quorum(&self, quorum: CleanupQuorum) -> bool {
+        if self.cleanup_requested_at.is_none() {
+            return false;
+        }
+        let nodes: HashSet<_> = self.nodes_with_volume.iter().collect();
+        let pending = self.pending_nodes().len();
+        nodes.len() - pending >= quorum.required(nodes.len())
+    }
+
+    /// Whether cleanup was requested at least `timeout` ago. False if it
+    /// wasn't requested or the request time can't be parsed.
+    pub fn is_timed_out(&self, timeout: Duration) -> bool {
+        let Some(requested) = self
+            .cleanup_requested_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+        else {
+            return false;
+        };
+        let timeout = chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::MAX);
+        chrono::Utc::now().signed_duration_since(requested) >= timeout
+    }
+
+    /// Settle pending nodes that will never report: those that reported their
+    /// directory absent are completed, those missing from `existing_nodes`
+    /// for at least `grace` (since first seen missing) are decommissioned.
+    /// Does nothing unless cleanup was requested.
+    pub fn settle_pending_nodes(
+        &mut self,
+        existing_nodes: &HashSet<String>,
+        grace: Duration,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> SettledNodes {
+        let mut settled = SettledNodes::default();
+        if self.cleanup_requested_at.is_none() {
+            return settled;
+        }
+        let grace = chrono::Duration::from_std(grace).unwrap_or(chrono::Duration::MAX);
+        for node in self
+            .pending_nodes()
+            .into_iter()
+            .cloned()
+            .collect::<Vec<_>>()
+        {
+            if self.nodes_directory_absent.contains(&node) {
+                self.mark_node_completed(&node);
+                settled.directory_absent.push(node);
+            } else if existing_nodes.contains(&node) {
+                // Back before the grace elapsed
+                self.nodes_absent_since.remove(&node);
+            } else {
+                let since = self
+                    .nodes_absent_since
+                    .entry(node.clone())
+                    .or_insert_with(|| now.to_rfc3339());
+                // Unparseable: treat as just seen
+                let absent_for = chrono::DateTime::parse_from_rfc3339(since)
+                    .map(|since| now.signed_duration_since(since))
+                    .unwrap_or_default();
+                if absent_for >= grace {
+                    self.nodes_absent_since.remove(&node);
+                    self.mark_node_decommissioned(&node);
+                    settled.decommissioned.push(node);
+                } else {
+                    settled.absent.push(node);
+                }
+            }
+        }
+        settled
+    }
+
     /// Get nodes that haven't reported yet (not completed, failed, or decommissioned)
     pub fn pending_nodes(&self) -> Vec<&String> {
         self.nodes_with_volume
@@ -148,47 +911,220 @@ impl VolumeStatus {
     }
 }
 
+/// Pending nodes settled by the controller (`VolumeStatus::settle_pending_nodes`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SettledNodes {
+    /// Reported their directory absent, marked completed
+    pub directory_absent: Vec<String>,
+    /// No longer in the cluster, marked decommissioned
+    pub decommissioned: Vec<String>,
+    /// Missing from the cluster, but within the decommission grace
+    pub absent: Vec<String>,
+}
+
+/// Name of a volume's tracking ConfigMap: the tracking ID without the
+/// redundant `nlc-` prefix (`nlc-vol-<uuid>`, `nlc-vol-shared-<name>`). IDs
+/// too long for an object name are replaced by their UUIDv5 (`nlc-vol-h-<uuid>`).
+/// The tracking ID itself is stored in the status, so the name needn't be
+/// reversible.
 fn configmap_name(volume_id: &str) -> String {
-    format!("{}{}", VOLUME_CM_PREFIX, volume_id)
+    let suffix = volume_id
+        .strip_prefix(volume::VOLUME_ID_PREFIX)
+        .unwrap_or(volume_id);
+    let name = format!("{}{}", VOLUME_CM_PREFIX, suffix);
+    if name.len() <= MAX_NAME_LEN {
+        return name;
+    }
+    let hash = Uuid::new_v5(&volume::VOLUME_ID_NAMESPACE, volume_id.as_bytes());
+    format!("{}{}{}", VOLUME_CM_PREFIX, HASHED_NAME_INFIX, hash)
+}
+
+/// Name of shard `shard` of a volume's tracking ConfigMap (`nlc-vol-<uuid>-<n>`),
+/// or of the ConfigMap itself for 0
+fn shard_configmap_name(volume_id: &str, shard: u32) -> String {
+    match shard {
+        0 => configmap_name(volume_id),
+        n => format!("{}-{}", configmap_name(volume_id), n),
+    }
+}
+
+/// Name volume ConfigMaps had before the `nlc-` prefix was dropped
+/// (`nlc-vol-nlc-<uuid>`). Shared cache names didn't change.
+fn legacy_configmap_name(volume_id: &str) -> Option<String> {
+    volume_id
+        .starts_with(volume::VOLUME_ID_PREFIX)
+        .then(|| format!("{}{}", VOLUME_CM_PREFIX, volume_id))
+}
+
+/// Get a volume's tracking ConfigMap, under its current or legacy name
+async fn get_volume_configmap(
+    configmaps: &impl CoordinationBackend,
+    volume_id: &str,
+) -> Result<Option<ConfigMap>, kube::Error> {
+    if let Some(cm) = configmaps.get_opt(&configmap_name(volume_id)).await? {
+        return Ok(Some(cm));
+    }
+    match legacy_configmap_name(volume_id) {
+        Some(name) => configmaps.get_opt(&name).await,
+        None => Ok(None),
+    }
+}
+
+/// Get shard `shard` of a volume's tracking ConfigMap, or the ConfigMap
+/// itself for 0. A shared cache's shard name may also be that of another
+/// shared cache (`shared-a` shard 1, shared cache `a-1`), which isn't
+/// taken for the shard.
+async fn get_shard_configmap(
+    configmaps: &impl CoordinationBackend,
+    volume_id: &str,
+    shard: u32,
+) -> Result<Option<ConfigMap>, kube::Error> {
+    if shard == 0 {
+        return get_volume_configmap(configmaps, volume_id).await;
+    }
+    Ok(configmaps
+        .get_opt(&shard_configmap_name(volume_id, shard))
+        .await?
+        .filter(|cm| {
+            VolumeStatus::from_configmap(cm)
+                .is_none_or(|s| s.volume_id == volume_id && s.shard == shard)
+        }))
+}
+
+/// Object an event about a volume is attached to: its PVC with
+/// `EventNamespace::Pvc` when known, otherwise its ConfigMap in `namespace`
+fn event_object(
+    mode: EventNamespace,
+    namespace: &str,
+    volume_id: &str,
+    pvc: Option<&PvcRef>,
+) -> ObjectReference {
+    match (mode, pvc) {
+        (EventNamespace::Pvc, Some(pvc)) => ObjectReference {
+            api_version: Some("v1".to_string()),
+            kind: Some("PersistentVolumeClaim".to_string()),
+            name: Some(pvc.name.clone()),
+            namespace: Some(pvc.namespace.clone()),
+            ..Default::default()
+        },
+        _ => ObjectReference {
+            api_version: Some("v1".to_string()),
+            kind: Some("ConfigMap".to_string()),
+            name: Some(configmap_name(volume_id)),
+            namespace: Some(namespace.to_string()),
+            ..Default::default()
+        },
+    }
 }
 
 /// Emit a Kubernetes event for visibility
 /// Events show up in `kubectl get events` and `kubectl describe`
+///
+/// `pvc` is the volume's PVC if known, used with `--event-namespace pvc`.
+/// Every event is also written to the audit log (`AUDIT_TARGET`), which
+/// outlives the Kubernetes event; with `--no-events` it is only logged.
 pub async fn emit_event(
     client: &Client,
     namespace: &str,
     volume_id: &str,
+    pvc: Option<&PvcRef>,
     reason: &str,
     message: &str,
     event_type: &str, // "Normal" or "Warning"
 ) {
-    let events: Api<Event> = Api::namespaced(client.clone(), namespace);
-    let cm_name = configmap_name(volume_id);
+    audit_event(volume_id, reason, message, event_type);
+    if !events_enabled() {
+        debug!(volume_id = %volume_id, reason = %reason, "Event not emitted, events disabled");
+        return;
+    }
+    create_event(
+        client, namespace, volume_id, pvc, reason, message, event_type,
+    )
+    .await;
+}
+
+/// Write an event to the audit log, for long-term retention by the log
+/// pipeline (Kubernetes events are rate-limited and expire)
+fn audit_event(volume_id: &str, reason: &str, message: &str, event_type: &str) {
+    info!(
+        target: AUDIT_TARGET,
+        volume_id = %volume_id,
+        reason = %reason,
+        message = %message,
+        event_type = %event_type,
+        node = EVENT_NODE.get().map_or("", String::as_str),
+        "Volume event"
+    );
+}
+
+/// Post an event to the Kubernetes API, or count it in the identical one
+/// posted shortly before (see `recent_events`); failures are only logged
+async fn create_event(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    pvc: Option<&PvcRef>,
+    reason: &str,
+    message: &str,
+    event_type: &str,
+) {
+    let now = chrono::Utc::now();
+    if let Some(recent) = RECENT_EVENTS.bump(volume_id, reason, std::time::Instant::now()) {
+        let events: Api<Event> = Api::namespaced(client.clone(), &recent.namespace);
+        let patch = serde_json::json!({
+            "count": recent.count,
+            "lastTimestamp": k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(now),
+            "message": message,
+        });
+        match events
+            .patch(&recent.name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+        {
+            Ok(_) => return,
+            Err(e) => {
+                // Expired or deleted meanwhile
+                debug!(reason = %reason, error = %e, "Failed to update recent event, creating a new one");
+                RECENT_EVENTS.forget(volume_id, reason);
+            }
+        }
+    }
+
+    let mode = EVENT_NAMESPACE.get().copied().unwrap_or_default();
+    let involved_object = event_object(mode, namespace, volume_id, pvc);
+    let event_namespace = involved_object.namespace.clone().unwrap_or_default();
+    let events: Api<Event> = Api::namespaced(client.clone(), &event_namespace);
 
     let event = Event {
         metadata: kube::api::ObjectMeta {
             generate_name: Some("nlc-".to_string()),
-            namespace: Some(namespace.to_string()),
-            ..Default::default()
-        },
-        involved_object: ObjectReference {
-            api_version: Some("v1".to_string()),
-            kind: Some("ConfigMap".to_string()),
-            name: Some(cm_name),
-            namespace: Some(namespace.to_string()),
+            namespace: Some(event_namespace),
             ..Default::default()
         },
+        involved_object,
         reason: Some(reason.to_string()),
         message: Some(message.to_string()),
         type_: Some(event_type.to_string()),
-        first_timestamp: Some(k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(
-            chrono::Utc::now(),
-        )),
+        count: Some(1),
+        first_timestamp: Some(k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(now)),
+        last_timestamp: Some(k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(now)),
         ..Default::default()
     };
 
-    if let Err(e) = events.create(&PostParams::default(), &event).await {
-        warn!(reason = %reason, error = %e, "Failed to emit event");
+    match events.create(&PostParams::default(), &event).await {
+        Ok(created) => {
+            if let (Some(event_namespace), Some(name)) =
+                (&event.metadata.namespace, &created.metadata.name)
+            {
+                RECENT_EVENTS.insert(
+                    volume_id,
+                    reason,
+                    event_namespace,
+                    name,
+                    std::time::Instant::now(),
+                );
+            }
+        }
+        Err(e) => warn!(reason = %reason, error = %e, "Failed to emit event"),
     }
 }
 
@@ -197,76 +1133,194 @@ pub async fn emit_event(
 /// Returns the final VolumeStatus after mutation.
 ///
 /// - `create_if_missing`: if true, creates ConfigMap on 404; if false, returns error
+/// - `operation`: names the update in the conflict retry histogram
+///
+/// The `VOLUME_LABEL` value follows the mutated status (see `VolumeStatus::phase_label`).
+/// Existing owner references are kept while the volume is active.
+/// The ConfigMap isn't written when the mutation changes nothing.
 async fn with_volume_configmap<F>(
     client: &Client,
     namespace: &str,
     volume_id: &str,
-    label_value: &str,
     create_if_missing: bool,
+    operation: &str,
     mutate: F,
 ) -> Result<VolumeStatus, kube::Error>
 where
     F: Fn(&mut VolumeStatus),
 {
-    let configmaps: Api<ConfigMap> = Api::namespaced(client.clone(), namespace);
-    let cm_name = configmap_name(volume_id);
-
-    for attempt in 0..MAX_RETRIES {
-        let (mut status, resource_version) = match configmaps.get(&cm_name).await {
-            Ok(existing) => {
-                let rv = existing.metadata.resource_version.clone();
-                let status = VolumeStatus::from_configmap(&existing)
-                    .unwrap_or_else(|| VolumeStatus::new(volume_id));
-                (status, rv)
-            }
-            Err(kube::Error::Api(ref err)) if err.code == 404 => {
-                if create_if_missing {
-                    (VolumeStatus::new(volume_id), None)
-                } else {
-                    return Err(kube::Error::Api(err.clone()));
-                }
-            }
-            Err(e) => return Err(e),
+    update_volume_configmap(
+        client,
+        namespace,
+        volume_id,
+        0,
+        create_if_missing,
+        operation,
+        None,
+        None,
+        mutate,
+    )
+    .await
+}
+
+/// `with_volume_configmap` on shard `shard` (0 for the ConfigMap itself),
+/// starting from an already fetched ConfigMap (e.g. from a list) instead of
+/// getting it first. It's only re-fetched if it turns out to be stale.
+/// Conflict retries are taken from `budget`, when given, and the update
+/// fails when it runs out.
+#[allow(clippy::too_many_arguments)]
+async fn update_volume_configmap<F>(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    shard: u32,
+    create_if_missing: bool,
+    operation: &str,
+    mut fetched: Option<ConfigMap>,
+    budget: Option<&RetryBudget>,
+    mutate: F,
+) -> Result<VolumeStatus, kube::Error>
+where
+    F: Fn(&mut VolumeStatus),
+{
+    let configmaps = coordination::configmaps(client, namespace);
+    let immutable_stable = immutable_stable_configmaps();
+    // Status of the immutable ConfigMap this call deleted to recreate it
+    let mut recreating: Option<VolumeStatus> = None;
+
+    let max_retries = retry_config().max_retries;
+    for attempt in 0..max_retries {
+        let existing = match fetched.take() {
+            Some(cm) => Some(cm),
+            None => get_shard_configmap(&configmaps, volume_id, shard).await?,
+        };
+        if existing.is_none() && recreating.is_none() && !create_if_missing {
+            // Another writer may be recreating it
+            if immutable_stable && attempt == 0 {
+                backoff_sleep(attempt).await;
+                continue;
+            }
+            return Err(kube::Error::Api(kube::core::ErrorResponse {
+                status: "Failure".to_string(),
+                message: format!(
+                    "configmaps \"{}\" not found",
+                    shard_configmap_name(volume_id, shard)
+                ),
+                reason: "NotFound".to_string(),
+                code: 404,
+            }));
+        }
+        // ConfigMaps created under the legacy name keep it
+        let cm_name = existing
+            .as_ref()
+            .and_then(|e| e.metadata.name.clone())
+            .unwrap_or_else(|| shard_configmap_name(volume_id, shard));
+        let current = existing.as_ref().and_then(VolumeStatus::from_configmap);
+        let base = match (current, &recreating) {
+            (Some(mut current), Some(deleted)) => {
+                current.merge_older(deleted);
+                current
+            }
+            (None, Some(deleted)) => deleted.clone(),
+            (Some(current), None) => current,
+            (None, None) => VolumeStatus::new_shard(volume_id, shard),
         };
+        let mut status = base.clone();
 
         mutate(&mut status);
 
-        // Check before moving resource_version into struct
-        let is_update = resource_version.is_some();
-
         let cm = ConfigMap {
             metadata: kube::api::ObjectMeta {
                 name: Some(cm_name.clone()),
                 namespace: Some(namespace.to_string()),
-                resource_version,
-                labels: Some(BTreeMap::from([(
-                    VOLUME_LABEL.to_string(),
-                    label_value.to_string(),
-                )])),
+                resource_version: existing
+                    .as_ref()
+                    .and_then(|e| e.metadata.resource_version.clone()),
+                labels: Some(status.configmap_labels()),
+                owner_references: existing
+                    .as_ref()
+                    .and_then(|e| e.metadata.owner_references.clone())
+                    .filter(|_| status.cleanup_requested_at.is_none()),
                 ..Default::default()
             },
             data: Some(status.to_configmap_data()),
+            immutable: (immutable_stable && status.is_stable()).then_some(true),
             ..Default::default()
         };
-        let result = if is_update {
-            configmaps
-                .replace(&cm_name, &PostParams::default(), &cm)
-                .await
-        } else {
-            configmaps.create(&PostParams::default(), &cm).await
+        let was_immutable = existing.as_ref().is_some_and(|e| e.immutable == Some(true));
+
+        // Nothing changed (e.g. a repeated DeleteVolume): skip the write.
+        // An immutable ConfigMap isn't recreated just to make it mutable.
+        if existing.as_ref().is_some_and(|e| {
+            e.data == cm.data
+                && e.metadata.labels == cm.metadata.labels
+                && e.metadata.owner_references == cm.metadata.owner_references
+                && (was_immutable || cm.immutable.is_none())
+        }) {
+            metrics::metrics().record_conflict_retries(operation, attempt);
+            return Ok(status);
+        }
+
+        let result = match &existing {
+            // Only metadata may change in place
+            Some(e) if was_immutable && (e.data != cm.data || cm.immutable.is_none()) => {
+                match configmaps.delete(&cm_name, &unchanged_since(e)).await {
+                    Ok(_) => {
+                        debug!(configmap = %cm_name, "Deleted immutable ConfigMap to recreate it");
+                        recreating = Some(base);
+                        // Recreated under the current name
+                        let mut cm = cm;
+                        cm.metadata.name = Some(shard_configmap_name(volume_id, shard));
+                        cm.metadata.resource_version = None;
+                        configmaps.create(&cm).await
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            Some(_) => configmaps.replace(&cm_name, &cm).await,
+            None => configmaps.create(&cm).await,
         };
 
         match result {
-            Ok(_) => return Ok(status),
-            Err(kube::Error::Api(ref err)) if err.code == 409 => {
+            Ok(_) => {
+                metrics::metrics().record_conflict_retries(operation, attempt);
+                return Ok(status);
+            }
+            // 404: someone else deleted it first to recreate it
+            Err(kube::Error::Api(ref err))
+                if err.code == 409 || (err.code == 404 && was_immutable) =>
+            {
+                if budget.is_some_and(|b| !b.take()) && recreating.is_none() {
+                    metrics::metrics().record_conflict_retries(operation, attempt);
+                    return Err(kube::Error::Api(kube::core::ErrorResponse {
+                        status: "Failure".to_string(),
+                        message: "Retry budget of the cleanup pass exhausted".to_string(),
+                        reason: "Conflict".to_string(),
+                        code: 409,
+                    }));
+                }
                 debug!(attempt = attempt, "Conflict, retrying with backoff");
                 backoff_sleep(attempt).await;
                 continue;
             }
+            // The deleted status only lives on here: keep trying to write it
+            Err(e) if recreating.is_some() => {
+                warn!(volume_id = %volume_id, error = %e, "Failed to recreate ConfigMap, retrying");
+                backoff_sleep(attempt).await;
+                continue;
+            }
             Err(e) => return Err(e),
         }
     }
 
+    metrics::metrics().record_conflict_retries(operation, max_retries);
+    if let Some(deleted) = recreating {
+        error!(
+            volume_id = %volume_id,
+            status = %serde_json::to_string(&deleted).unwrap_or_default(),
+            "Gave up recreating ConfigMap; its status may be lost"
+        );
+    }
     Err(kube::Error::Api(kube::core::ErrorResponse {
         status: "Failure".to_string(),
         message: "Max retries exceeded for optimistic concurrency".to_string(),
@@ -275,538 +1329,5121 @@ where
     }))
 }
 
-/// Register that a node has published a volume (call from NodePublishVolume)
+/// Delete only `cm` as fetched, not a later version or a recreated one
+fn unchanged_since(cm: &ConfigMap) -> DeleteParams {
+    DeleteParams::default().preconditions(Preconditions {
+        resource_version: cm.metadata.resource_version.clone(),
+        uid: cm.metadata.uid.clone(),
+    })
+}
+
+/// Register that a node has published a volume (call from NodePublishVolume).
+/// For a volume mapped onto a shared cache, the node and volume are also
+/// registered on the shared cache's ConfigMap. The capacity (0 if unknown)
+/// is recorded by the first publish that knows it.
 pub async fn register_node_publish(
     client: &Client,
     namespace: &str,
-    volume_id: &str,
     node_name: &str,
+    registration: &PendingRegistration,
 ) -> Result<(), kube::Error> {
-    let node = node_name.to_string();
-    with_volume_configmap(client, namespace, volume_id, "active", true, |status| {
-        status.add_node(&node);
-    })
+    let volume_id = registration.volume_id.as_str();
+    let shared_name = registration.shared_name.as_deref();
+    add_node_sharded(
+        client,
+        namespace,
+        volume_id,
+        node_name,
+        "register_node_publish",
+        |status| {
+            if let Some(name) = shared_name {
+                status.shared_name = Some(name.to_string());
+            }
+            if let Some(pvc) = &registration.pvc {
+                status.pvc = Some(pvc.clone());
+            }
+            status.labels.extend(registration.labels.clone());
+            if status.capacity_bytes == 0 {
+                status.capacity_bytes = registration.capacity_bytes;
+            }
+            if status.base_path.is_none() {
+                status.base_path = registration.base_path.clone();
+            }
+            if registration.eviction_priority.is_some() {
+                status.eviction_priority = registration.eviction_priority;
+            }
+        },
+    )
     .await?;
 
+    if let Some(name) = shared_name {
+        let shared_id = volume::shared_volume_id(name);
+        let reopened = AtomicBool::new(false);
+        let status = add_node_sharded(
+            client,
+            namespace,
+            &shared_id,
+            node_name,
+            "register_node_publish",
+            |status| {
+                reopened.store(status.cleanup_requested_at.is_some(), Ordering::Relaxed);
+                status.add_reference(volume_id);
+                if status.base_path.is_none() {
+                    status.base_path = registration.base_path.clone();
+                }
+                // The shared directory is what gets evicted
+                if registration.eviction_priority.is_some() {
+                    status.eviction_priority = registration.eviction_priority;
+                }
+            },
+        )
+        .await?;
+        // Its shards were being cleaned up too
+        if reopened.load(Ordering::Relaxed) {
+            update_shards(client, namespace, &status, "register_node_publish", |_| {}).await?;
+        }
+    }
+
     debug!(volume_id = %volume_id, node = %node_name, "Registered node for volume");
     Ok(())
 }
 
-/// Mark a volume for cleanup (call from DeleteVolume)
-pub async fn mark_volume_for_cleanup(
+/// Apply `mutate` to a volume's tracking ConfigMap, creating it, and add
+/// `node_name` to its nodes. Nodes go into the ConfigMap itself while it
+/// stays below `SHARD_THRESHOLD_BYTES`, and into its shards after that, so
+/// a volume published on thousands of nodes doesn't hit the object size
+/// limit. Returns the status of the ConfigMap itself.
+async fn add_node_sharded<F>(
     client: &Client,
     namespace: &str,
     volume_id: &str,
-) -> Result<(), kube::Error> {
-    let result = with_volume_configmap(client, namespace, volume_id, "cleanup", false, |status| {
-        status.mark_cleanup_requested();
+    node_name: &str,
+    operation: &str,
+    mutate: F,
+) -> Result<VolumeStatus, kube::Error>
+where
+    F: Fn(&mut VolumeStatus),
+{
+    let overflowed = AtomicBool::new(false);
+    let primary = with_volume_configmap(client, namespace, volume_id, true, operation, |status| {
+        mutate(status);
+        let fits = status.has_room_for(node_name);
+        overflowed.store(!fits, Ordering::Relaxed);
+        if fits {
+            status.add_node(node_name);
+        }
     })
-    .await;
+    .await?;
+    if !overflowed.load(Ordering::Relaxed) {
+        return Ok(primary);
+    }
 
-    // If ConfigMap doesn't exist (404), nothing to clean up - that's OK
-    let status = match result {
-        Ok(s) => s,
-        Err(kube::Error::Api(ref err)) if err.code == 404 => {
-            debug!(volume_id = %volume_id, "No tracking ConfigMap, nothing to clean");
-            return Ok(());
+    // The shard listing the node already, or else the first with room
+    let configmaps = coordination::configmaps(client, namespace);
+    let (mut listed, mut room) = (None, None);
+    for shard in 1..=primary.shards {
+        let status = get_shard_configmap(&configmaps, volume_id, shard)
+            .await?
+            .as_ref()
+            .and_then(VolumeStatus::from_configmap)
+            .unwrap_or_else(|| VolumeStatus::new_shard(volume_id, shard));
+        if status.nodes_with_volume.iter().any(|n| n == node_name) {
+            listed = Some(shard);
+            break;
+        }
+        if room.is_none() && status.has_room_for(node_name) {
+            room = Some(shard);
+        }
+    }
+    let shard = match listed.or(room) {
+        Some(shard) => shard,
+        None => {
+            let shard = primary.shards + 1;
+            with_volume_configmap(client, namespace, volume_id, false, operation, |status| {
+                status.shards = status.shards.max(shard)
+            })
+            .await?;
+            info!(volume_id = %volume_id, shard = shard, "Added a tracking ConfigMap shard");
+            shard
         }
-        Err(e) => return Err(e),
     };
-
-    info!(
-        volume_id = %volume_id,
-        nodes_to_cleanup = status.nodes_with_volume.len(),
-        "Marked volume for cleanup"
-    );
-    emit_event(
+    update_volume_configmap(
         client,
         namespace,
         volume_id,
-        "CleanupRequested",
-        &format!(
-            "Volume cleanup requested, {} node(s) to clean: {:?}",
-            status.nodes_with_volume.len(),
-            status.nodes_with_volume
-        ),
-        "Normal",
+        shard,
+        true,
+        operation,
+        None,
+        None,
+        |status| {
+            status.follow(&primary);
+            status.add_node(node_name);
+        },
     )
-    .await;
-
-    Ok(())
+    .await?;
+    Ok(primary)
 }
 
-/// Mark node cleanup complete
-async fn mark_node_cleanup_complete(
+/// Apply `mutate` to each shard of `primary`'s volume, after bringing its
+/// copy of the volume-wide fields up to date. Shards that are gone are
+/// skipped. Returns the shards' statuses.
+async fn update_shards<F>(
     client: &Client,
     namespace: &str,
+    primary: &VolumeStatus,
+    operation: &str,
+    mutate: F,
+) -> Result<Vec<VolumeStatus>, kube::Error>
+where
+    F: Fn(&mut VolumeStatus),
+{
+    let mut shards = Vec::new();
+    for shard in 1..=primary.shards {
+        let result = update_volume_configmap(
+            client,
+            namespace,
+            &primary.volume_id,
+            shard,
+            false,
+            operation,
+            None,
+            None,
+            |status| {
+                status.follow(primary);
+                mutate(status);
+            },
+        )
+        .await;
+        match result {
+            Ok(status) => shards.push(status),
+            Err(kube::Error::Api(err)) if err.code == 404 => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(shards)
+}
+
+/// The shard of a volume's tracking ConfigMap listing `node_name`; 0 (the
+/// ConfigMap itself) when none does
+async fn node_shard(
+    configmaps: &impl CoordinationBackend,
     volume_id: &str,
     node_name: &str,
-    success: bool,
-) -> Result<(), kube::Error> {
-    let node = node_name.to_string();
-    with_volume_configmap(client, namespace, volume_id, "cleanup", false, |status| {
-        if success {
-            status.mark_node_completed(&node);
-        } else {
-            status.mark_node_failed(&node);
+) -> Result<u32, kube::Error> {
+    let Some(primary) = get_volume_configmap(configmaps, volume_id)
+        .await?
+        .as_ref()
+        .and_then(VolumeStatus::from_configmap)
+    else {
+        return Ok(0);
+    };
+    if primary.nodes_with_volume.iter().any(|n| n == node_name) {
+        return Ok(0);
+    }
+    for shard in 1..=primary.shards {
+        let listed = get_shard_configmap(configmaps, volume_id, shard)
+            .await?
+            .as_ref()
+            .and_then(VolumeStatus::from_configmap)
+            .is_some_and(|s| s.nodes_with_volume.iter().any(|n| n == node_name));
+        if listed {
+            return Ok(shard);
         }
-    })
-    .await?;
+    }
+    Ok(0)
+}
 
-    let (reason, msg, event_type) = if success {
-        (
-            "NodeCleanupComplete",
-            format!("Node {} completed cleanup", node_name),
-            "Normal",
-        )
-    } else {
-        (
-            "NodeCleanupFailed",
-            format!("Node {} failed cleanup", node_name),
-            "Warning",
-        )
-    };
-    emit_event(client, namespace, volume_id, reason, &msg, event_type).await;
+/// Merge the statuses of shards into those of their volume's ConfigMap.
+/// Shards whose volume's ConfigMap isn't among `statuses` are dropped.
+pub fn merge_shards(statuses: Vec<VolumeStatus>) -> Vec<VolumeStatus> {
+    let (mut primaries, shards): (Vec<_>, Vec<_>) =
+        statuses.into_iter().partition(|s| s.shard == 0);
+    for shard in shards {
+        if let Some(primary) = primaries
+            .iter_mut()
+            .find(|p| p.volume_id == shard.volume_id)
+        {
+            primary.merge_nodes(&shard);
+        }
+    }
+    primaries
+}
 
-    Ok(())
+/// Eviction priorities recorded for active volumes, by tracking ID
+pub async fn eviction_priorities(
+    client: &Client,
+    namespace: &str,
+) -> Result<HashMap<String, u8>, kube::Error> {
+    let configmaps = coordination::configmaps(client, namespace);
+    let lp = ListParams::default().labels(&format!("{}=active", VOLUME_LABEL));
+    Ok(configmaps
+        .list(&lp)
+        .await?
+        .iter()
+        .filter_map(VolumeStatus::from_configmap)
+        .filter(|status| status.shard == 0)
+        .filter_map(|status| Some((status.volume_id, status.eviction_priority?)))
+        .collect())
 }
 
-/// Controller-side cleanup operations
-pub struct CleanupController {
-    client: Client,
-    namespace: String,
+/// Mount options to publish a volume with: those recorded by its first
+/// publish, or `requested`, which are recorded if nothing was yet.
+pub async fn settle_mount_options(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    requested: &[String],
+) -> Result<Vec<String>, kube::Error> {
+    let status = with_volume_configmap(
+        client,
+        namespace,
+        volume_id,
+        true,
+        "settle_mount_options",
+        |status| {
+            if status.mount_options.is_none() {
+                status.mount_options = Some(requested.to_vec());
+            }
+        },
+    )
+    .await?;
+    Ok(status.mount_options.unwrap_or_default())
 }
 
-impl CleanupController {
-    pub fn new(client: Client, namespace: String) -> Self {
-        Self { client, namespace }
-    }
+/// Make the volume's PersistentVolume the owner of its tracking ConfigMap.
+///
+/// Skipped (returning false) when the ConfigMap already has an owner, is in
+/// cleanup, or the PV can't be found or doesn't belong to this volume.
+pub async fn set_volume_owner(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    pv_name: &str,
+) -> Result<bool, kube::Error> {
+    let configmaps = coordination::configmaps(client, namespace);
 
-    /// Create a cleanup request for a volume (legacy method, calls mark_volume_for_cleanup)
-    pub async fn create_cleanup_request(&self, volume_id: &str) -> Result<(), kube::Error> {
-        mark_volume_for_cleanup(&self.client, &self.namespace, volume_id).await
+    let cm = match get_volume_configmap(&configmaps, volume_id).await? {
+        Some(cm) => cm,
+        None => return Ok(false),
+    };
+    let cm_name = cm.metadata.name.clone().unwrap_or_default();
+    let in_cleanup = VolumeStatus::from_configmap(&cm)
+        .map(|s| s.cleanup_requested_at.is_some())
+        .unwrap_or(false);
+    if in_cleanup
+        || cm
+            .metadata
+            .owner_references
+            .as_ref()
+            .is_some_and(|refs| !refs.is_empty())
+    {
+        return Ok(false);
     }
 
-    /// Emit a Kubernetes event for a volume
-    pub async fn emit_event(&self, volume_id: &str, reason: &str, message: &str, event_type: &str) {
-        emit_event(
-            &self.client,
-            &self.namespace,
-            volume_id,
-            reason,
-            message,
-            event_type,
-        )
-        .await
+    let pvs: Api<PersistentVolume> = Api::all(client.clone());
+    let pv = match pvs.get_opt(pv_name).await? {
+        Some(pv) => pv,
+        None => {
+            debug!(volume_id = %volume_id, pv = %pv_name, "PV not found, skipping owner reference");
+            return Ok(false);
+        }
+    };
+    let handle = pv
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.csi.as_ref())
+        .map(|csi| csi.volume_handle.as_str());
+    if handle != Some(volume_id) {
+        warn!(
+            volume_id = %volume_id,
+            pv = %pv_name,
+            "PV does not reference this volume, skipping owner reference"
+        );
+        return Ok(false);
     }
+    let owner = match pv_owner_reference(&pv) {
+        Some(owner) => owner,
+        None => return Ok(false),
+    };
 
-    /// Get set of node names that exist in the cluster
-    async fn get_existing_nodes(&self) -> Result<HashSet<String>, kube::Error> {
-        let nodes: Api<Node> = Api::all(self.client.clone());
-        let node_list = nodes.list(&ListParams::default()).await?;
-        let names: HashSet<String> = node_list
-            .items
-            .iter()
-            .filter_map(|n| n.metadata.name.clone())
-            .collect();
-        Ok(names)
+    // resourceVersion makes this fail with a conflict if cleanup started meanwhile
+    let patch = serde_json::json!({
+        "metadata": {
+            "resourceVersion": cm.metadata.resource_version,
+            "ownerReferences": [owner],
+        }
+    });
+    match configmaps.patch_merge(&cm_name, &patch).await {
+        Ok(_) => {}
+        Err(kube::Error::Api(ref err)) if err.code == 409 => return Ok(false),
+        Err(e) => return Err(e),
     }
 
-    /// Mark nodes as decommissioned if they no longer exist in the cluster.
-    /// Returns true if any nodes were marked.
-    async fn mark_decommissioned_nodes(
-        &self,
-        volume_id: &str,
-        status: &VolumeStatus,
-        existing_nodes: &HashSet<String>,
-    ) -> Result<bool, kube::Error> {
-        let pending = status.pending_nodes();
-        let decommissioned: Vec<_> = pending
-            .iter()
-            .filter(|n| !existing_nodes.contains(**n))
-            .map(|n| (*n).clone())
-            .collect();
+    debug!(volume_id = %volume_id, pv = %pv_name, "Set PV as ConfigMap owner");
+    Ok(true)
+}
 
-        if decommissioned.is_empty() {
-            return Ok(false);
+/// Names of the PersistentVolumes still standing for a volume, by volume
+/// handle (`--verify-pv-deleted`). PVs being deleted or released from their
+/// claim, which is when the provisioner calls DeleteVolume, aren't listed.
+async fn live_pvs_by_handle(client: &Client) -> Result<HashMap<String, String>, kube::Error> {
+    let pvs: Api<PersistentVolume> = Api::all(client.clone());
+    let mut live = HashMap::new();
+    for pv in pvs.list(&ListParams::default()).await?.items {
+        let released = pv
+            .status
+            .as_ref()
+            .and_then(|s| s.phase.as_deref())
+            .is_some_and(|phase| phase == "Released");
+        if pv.metadata.deletion_timestamp.is_some() || released {
+            continue;
         }
+        let handle = pv
+            .spec
+            .and_then(|spec| spec.csi)
+            .map(|csi| csi.volume_handle);
+        if let (Some(handle), Some(name)) = (handle, pv.metadata.name) {
+            live.insert(handle, name);
+        }
+    }
+    Ok(live)
+}
 
-        let nodes_to_mark = decommissioned.clone();
-        with_volume_configmap(
-            &self.client,
-            &self.namespace,
-            volume_id,
-            "cleanup",
-            false,
-            |s| {
-                for node in &nodes_to_mark {
-                    s.mark_node_decommissioned(node);
-                }
-            },
-        )
-        .await?;
+/// Owner reference to a PV, or `None` if it has no name or UID yet
+fn pv_owner_reference(pv: &PersistentVolume) -> Option<OwnerReference> {
+    Some(OwnerReference {
+        api_version: "v1".to_string(),
+        kind: "PersistentVolume".to_string(),
+        name: pv.metadata.name.clone()?,
+        uid: pv.metadata.uid.clone()?,
+        ..Default::default()
+    })
+}
+
+/// Drop a deleted volume's reference to its shared cache, marking the shared
+/// cache for cleanup once nothing references it anymore
+async fn release_shared_reference(
+    client: &Client,
+    namespace: &str,
+    shared_name: &str,
+    volume_id: &str,
+) -> Result<(), kube::Error> {
+    let shared_id = volume::shared_volume_id(shared_name);
+    let already_requested = AtomicBool::new(false);
+    let result = with_volume_configmap(
+        client,
+        namespace,
+        &shared_id,
+        false,
+        "release_shared_reference",
+        |status| {
+            already_requested.store(status.cleanup_requested_at.is_some(), Ordering::Relaxed);
+            status.remove_reference(volume_id);
+            if status.references.is_empty() {
+                status.mark_cleanup_requested();
+            }
+        },
+    )
+    .await;
+
+    let status = match result {
+        Ok(s) => s,
+        Err(kube::Error::Api(ref err)) if err.code == 404 => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    let mut status = status;
+    for shard in update_shards(
+        client,
+        namespace,
+        &status,
+        "release_shared_reference",
+        |_| {},
+    )
+    .await?
+    {
+        status.merge_nodes(&shard);
+    }
 
+    if status.references.is_empty() && !already_requested.load(Ordering::Relaxed) {
         info!(
-            volume_id = %volume_id,
-            decommissioned_nodes = ?decommissioned,
-            "Marked nodes as decommissioned (no longer exist in cluster)"
+            shared_name = %shared_name,
+            nodes_to_cleanup = status.nodes_with_volume.len(),
+            "Last reference released, marked shared cache for cleanup"
         );
         emit_event(
-            &self.client,
-            &self.namespace,
-            volume_id,
-            "NodeDecommissioned",
+            client,
+            namespace,
+            &shared_id,
+            None,
+            "CleanupRequested",
             &format!(
-                "Node(s) no longer exist in cluster, marked as decommissioned: {:?}",
-                decommissioned
+                "Shared cache {} no longer referenced, {} node(s) to clean: {:?}",
+                shared_name,
+                status.nodes_with_volume.len(),
+                status.nodes_with_volume
             ),
-            "Warning",
+            "Normal",
         )
         .await;
-
-        Ok(true)
+    } else {
+        debug!(
+            shared_name = %shared_name,
+            remaining = status.references.len(),
+            "Released shared cache reference"
+        );
+    }
+
+    Ok(())
+}
+
+/// Mark a volume for cleanup (call from DeleteVolume). Returns false when the
+/// volume has no tracking ConfigMap, so no node is known to hold it.
+pub async fn mark_volume_for_cleanup(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+) -> Result<bool, kube::Error> {
+    // DeleteVolume is retried by the provisioner; only the first call reports
+    let already_requested = AtomicBool::new(false);
+    let result = with_volume_configmap(
+        client,
+        namespace,
+        volume_id,
+        false,
+        "mark_volume_for_cleanup",
+        |status| {
+            already_requested.store(status.cleanup_requested_at.is_some(), Ordering::Relaxed);
+            status.mark_cleanup_requested();
+        },
+    )
+    .await;
+
+    // If ConfigMap doesn't exist (404), nothing to clean up - that's OK
+    let status = match result {
+        Ok(s) => s,
+        Err(kube::Error::Api(ref err)) if err.code == 404 => {
+            debug!(volume_id = %volume_id, "No tracking ConfigMap, nothing to clean");
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Nodes listed in shards look for cleanup requests there
+    let mut status = status;
+    for shard in update_shards(
+        client,
+        namespace,
+        &status,
+        "mark_volume_for_cleanup",
+        |_| {},
+    )
+    .await?
+    {
+        status.merge_nodes(&shard);
+    }
+
+    if already_requested.load(Ordering::Relaxed) {
+        debug!(volume_id = %volume_id, "Cleanup already requested");
+    } else {
+        info!(
+            volume_id = %volume_id,
+            nodes_to_cleanup = status.nodes_with_volume.len(),
+            "Marked volume for cleanup"
+        );
+        emit_event(
+            client,
+            namespace,
+            volume_id,
+            status.pvc.as_ref(),
+            "CleanupRequested",
+            &format!(
+                "Volume cleanup requested, {} node(s) to clean: {:?}{}",
+                status.nodes_with_volume.len(),
+                status.nodes_with_volume,
+                status.capacity_note()
+            ),
+            "Normal",
+        )
+        .await;
+    }
+
+    if let Some(shared_name) = &status.shared_name {
+        if let Err(e) = release_shared_reference(client, namespace, shared_name, volume_id).await {
+            warn!(
+                volume_id = %volume_id,
+                shared_name = %shared_name,
+                error = %e,
+                "Failed to release shared cache reference"
+            );
+            emit_event(
+                client,
+                namespace,
+                volume_id,
+                status.pvc.as_ref(),
+                "SharedReleaseFailed",
+                &format!(
+                    "Failed to release reference to shared cache {}: {}",
+                    shared_name, e
+                ),
+                "Warning",
+            )
+            .await;
+        }
+    }
+
+    Ok(true)
+}
+
+/// Create a cleanup ConfigMap that has every node sweep for the volume's
+/// directory, for a volume deleted without a ConfigMap
+pub async fn request_sweep(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+) -> Result<(), kube::Error> {
+    let status = with_volume_configmap(
+        client,
+        namespace,
+        volume_id,
+        true,
+        "request_sweep",
+        |status| {
+            status.sweep = true;
+            status.mark_cleanup_requested();
+        },
+    )
+    .await?;
+
+    info!(volume_id = %volume_id, "No tracking ConfigMap, requested a sweep of all nodes");
+    emit_event(
+        client,
+        namespace,
+        volume_id,
+        status.pvc.as_ref(),
+        "CleanupSweepRequested",
+        "Volume deleted without a tracking ConfigMap, all nodes will check for its directory",
+        "Normal",
+    )
+    .await;
+    Ok(())
+}
+
+/// Report a node's cleanup outcome on the volume's ConfigMap, outside of a
+/// cleanup pass (`cleanup_journal`)
+pub async fn report_node_cleanup(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    node_name: &str,
+    success: bool,
+) -> Result<(), kube::Error> {
+    let configmaps = coordination::configmaps(client, namespace);
+    let shard = node_shard(&configmaps, volume_id, node_name).await?;
+    let report = NodeCleanupReport {
+        success,
+        reclaimed_bytes: None,
+    };
+    mark_node_cleanup_complete(client, namespace, volume_id, shard, node_name, report, None).await
+}
+
+/// A node's cleanup outcome, as recorded on the volume's ConfigMap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct NodeCleanupReport {
+    success: bool,
+    /// Bytes the node's deletion freed, if known
+    reclaimed_bytes: Option<u64>,
+}
+
+/// Mark node cleanup complete on the shard listing the node
+async fn mark_node_cleanup_complete(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    shard: u32,
+    node_name: &str,
+    report: NodeCleanupReport,
+    budget: Option<&RetryBudget>,
+) -> Result<(), kube::Error> {
+    let NodeCleanupReport {
+        success,
+        reclaimed_bytes,
+    } = report;
+    let node = node_name.to_string();
+    let status = update_volume_configmap(
+        client,
+        namespace,
+        volume_id,
+        shard,
+        false,
+        "mark_node_cleanup_complete",
+        None,
+        budget,
+        |status| {
+            if success {
+                status.mark_node_completed(&node);
+                if let Some(bytes) = reclaimed_bytes {
+                    status.nodes_reclaimed_bytes.insert(node.clone(), bytes);
+                }
+            } else {
+                status.mark_node_failed(&node);
+            }
+        },
+    )
+    .await?;
+
+    let (reason, msg, event_type) = if success {
+        (
+            "NodeCleanupComplete",
+            match reclaimed_bytes {
+                Some(bytes) => format!(
+                    "Node {} completed cleanup, reclaimed {} bytes",
+                    node_name, bytes
+                ),
+                None => format!("Node {} completed cleanup", node_name),
+            },
+            "Normal",
+        )
+    } else {
+        (
+            "NodeCleanupFailed",
+            format!("Node {} failed cleanup", node_name),
+            "Warning",
+        )
+    };
+    emit_event(
+        client,
+        namespace,
+        volume_id,
+        status.pvc.as_ref(),
+        reason,
+        &msg,
+        event_type,
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Result of evaluating one volume's cleanup ConfigMap
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum PruneOutcome {
+    /// Cleanup was complete and the ConfigMap was deleted
+    Pruned {
+        volume_id: String,
+        nodes_completed: Vec<String>,
+        nodes_failed: Vec<String>,
+        nodes_decommissioned: Vec<String>,
+        /// Nodes found gone and marked decommissioned during this evaluation
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        newly_decommissioned: Vec<String>,
+        /// Nodes that hadn't reported when the quorum was reached or the
+        /// cleanup timed out, left to clean up on their own
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        stragglers: Vec<String>,
+        /// Pruned because the cleanup timed out (`--cleanup-timeout`)
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        timed_out: bool,
+    },
+    /// Cleanup requested but some nodes haven't reported yet
+    Pending {
+        volume_id: String,
+        pending_nodes: Vec<String>,
+        /// Nodes found gone and marked decommissioned during this evaluation
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        newly_decommissioned: Vec<String>,
+    },
+    /// Volume is still active (no DeleteVolume yet)
+    NotRequested { volume_id: String },
+}
+
+/// What one `process_cleanups` pass did
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CleanupSummary {
+    /// Volumes whose ConfigMap was pruned
+    pub pruned: Vec<String>,
+    /// Volumes still waiting on nodes, with the number of pending nodes
+    pub pending: BTreeMap<String, usize>,
+    /// Volumes with nodes newly marked decommissioned, with those nodes
+    pub decommissioned: BTreeMap<String, Vec<String>>,
+    /// ConfigMaps that failed to process, with the error
+    pub errors: Vec<(String, String)>,
+    /// Cluster-wide cache footprint, from the ConfigMaps listed for this pass
+    pub footprint: Footprint,
+}
+
+/// Tracked volumes and the nodes holding them, across all tracking ConfigMaps
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Footprint {
+    /// Tracking ConfigMaps, not counting shards
+    pub volumes: usize,
+    /// Volumes not deleted yet
+    pub active: usize,
+    /// Volumes waiting for nodes to clean up
+    pub cleanup: usize,
+    /// Distinct nodes listed in any volume's `nodes_with_volume`
+    pub nodes: usize,
+}
+
+impl Footprint {
+    pub fn from_configmaps(cms: &[ConfigMap]) -> Self {
+        let mut footprint = Self::default();
+        let mut nodes = HashSet::new();
+        for cm in cms {
+            if let Some(status) = VolumeStatus::from_configmap(cm) {
+                nodes.extend(status.nodes_with_volume);
+            }
+            let labels = cm.metadata.labels.as_ref();
+            // A shard only adds nodes
+            if labels.is_some_and(|labels| labels.contains_key(SHARD_LABEL)) {
+                continue;
+            }
+            footprint.volumes += 1;
+            match labels
+                .and_then(|labels| labels.get(VOLUME_LABEL))
+                .map(String::as_str)
+            {
+                Some("active") => footprint.active += 1,
+                Some("cleanup") => footprint.cleanup += 1,
+                _ => {}
+            }
+        }
+        footprint.nodes = nodes.len();
+        footprint
+    }
+}
+
+impl CleanupSummary {
+    fn record(&mut self, outcome: PruneOutcome) {
+        match outcome {
+            PruneOutcome::Pruned {
+                volume_id,
+                newly_decommissioned,
+                ..
+            } => {
+                if !newly_decommissioned.is_empty() {
+                    self.decommissioned
+                        .insert(volume_id.clone(), newly_decommissioned);
+                }
+                self.pruned.push(volume_id);
+            }
+            PruneOutcome::Pending {
+                volume_id,
+                pending_nodes,
+                newly_decommissioned,
+            } => {
+                if !newly_decommissioned.is_empty() {
+                    self.decommissioned
+                        .insert(volume_id.clone(), newly_decommissioned);
+                }
+                self.pending.insert(volume_id, pending_nodes.len());
+            }
+            PruneOutcome::NotRequested { .. } => {}
+        }
+    }
+
+    /// Nodes newly marked decommissioned, across all volumes
+    pub fn decommissioned_nodes(&self) -> usize {
+        self.decommissioned.values().map(Vec::len).sum()
+    }
+
+    /// Whether the pass had no cleanup ConfigMaps to act on
+    pub fn is_empty(&self) -> bool {
+        self.pruned.is_empty()
+            && self.pending.is_empty()
+            && self.decommissioned.is_empty()
+            && self.errors.is_empty()
+    }
+}
+
+impl std::fmt::Display for CleanupSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "pruned {}, {} still pending, {} decommissioned",
+            self.pruned.len(),
+            self.pending.len(),
+            self.decommissioned_nodes()
+        )?;
+        if !self.errors.is_empty() {
+            write!(f, ", {} failed", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Controller-side cleanup operations
+pub struct CleanupController {
+    client: Client,
+    namespace: String,
+    /// How long a node must be missing before it is decommissioned
+    decommission_grace: Duration,
+    /// Sweep all nodes for volumes deleted without a ConfigMap
+    sweep_on_missing: bool,
+    /// Reports needed before pruning a volume's ConfigMap
+    quorum: CleanupQuorum,
+    /// Prune ConfigMaps whose cleanup was requested this long ago regardless
+    cleanup_timeout: Option<Duration>,
+    /// Ticked by the cleanup loop (`--health-addr`)
+    heartbeat: Heartbeat,
+}
+
+impl CleanupController {
+    pub fn new(client: Client, namespace: String) -> Self {
+        Self {
+            client,
+            namespace,
+            decommission_grace: Duration::ZERO,
+            sweep_on_missing: false,
+            quorum: CleanupQuorum::All,
+            cleanup_timeout: None,
+            heartbeat: Heartbeat::default(),
+        }
+    }
+
+    /// Only decommission nodes found missing from the cluster for `grace`,
+    /// so a transiently incomplete node list doesn't abandon their cleanup
+    pub fn with_decommission_grace(mut self, grace: Duration) -> Self {
+        self.decommission_grace = grace;
+        self
+    }
+
+    /// Have all nodes look for the directory of a volume deleted without a
+    /// tracking ConfigMap, which a node may have failed to register
+    pub fn with_sweep_on_missing(mut self, enabled: bool) -> Self {
+        self.sweep_on_missing = enabled;
+        self
+    }
+
+    /// Prune a volume's ConfigMap once `quorum` of its nodes reported,
+    /// leaving the others to clean up untracked
+    pub fn with_quorum(mut self, quorum: CleanupQuorum) -> Self {
+        self.quorum = quorum;
+        self
+    }
+
+    /// Prune a volume's ConfigMap once its cleanup was requested `timeout`
+    /// ago, even though nodes haven't reported (e.g. a wedged node); those
+    /// are left to clean up on their own, untracked
+    pub fn with_cleanup_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.cleanup_timeout = timeout;
+        self
+    }
+
+    /// Tick `heartbeat` on each pass of the cleanup loop
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Create a cleanup request for a volume (legacy method, calls mark_volume_for_cleanup)
+    pub async fn create_cleanup_request(&self, volume_id: &str) -> Result<(), kube::Error> {
+        let tracked = mark_volume_for_cleanup(&self.client, &self.namespace, volume_id).await?;
+        if !tracked && self.sweep_on_missing {
+            request_sweep(&self.client, &self.namespace, volume_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Record the CreateVolume request of a volume in its tracking ConfigMap,
+    /// creating it, unless a request is recorded already or the volume is in
+    /// cleanup. Returns the status, whose `create_request` is the first
+    /// recorded request, for the caller to check idempotency against.
+    pub async fn record_create(
+        &self,
+        volume_id: &str,
+        record: &CreateRecord,
+        pvc: Option<&PvcRef>,
+        labels: &BTreeMap<String, String>,
+    ) -> Result<VolumeStatus, kube::Error> {
+        with_volume_configmap(
+            &self.client,
+            &self.namespace,
+            volume_id,
+            true,
+            "record_create",
+            |status| {
+                if status.create_request.is_some() || status.cleanup_requested_at.is_some() {
+                    return;
+                }
+                status.create_request = Some(record.clone());
+                if let Some(pvc) = pvc {
+                    status.pvc = Some(pvc.clone());
+                }
+                status.labels.extend(labels.clone());
+            },
+        )
+        .await
+    }
+
+    /// Status of a volume from its tracking ConfigMap and its shards, `None`
+    /// if it has none
+    pub async fn volume_status(
+        &self,
+        volume_id: &str,
+    ) -> Result<Option<VolumeStatus>, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let Some(mut status) = get_volume_configmap(&configmaps, volume_id)
+            .await?
+            .as_ref()
+            .and_then(VolumeStatus::from_configmap)
+        else {
+            return Ok(None);
+        };
+        for shard in 1..=status.shards {
+            if let Some(shard) = get_shard_configmap(&configmaps, volume_id, shard)
+                .await?
+                .as_ref()
+                .and_then(VolumeStatus::from_configmap)
+            {
+                status.merge_nodes(&shard);
+            }
+        }
+        Ok(Some(status))
+    }
+
+    /// Status of every tracked volume, active or being cleaned up, by volume ID.
+    /// Shared caches are left out: they aren't CSI volumes.
+    pub async fn volume_statuses(&self) -> Result<Vec<VolumeStatus>, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let statuses = configmaps
+            .list(&ListParams::default().labels(VOLUME_LABEL))
+            .await?
+            .iter()
+            .filter_map(VolumeStatus::from_configmap)
+            .filter(|s| volume::validate_volume_id(&s.volume_id))
+            .collect();
+        let mut statuses = merge_shards(statuses);
+        statuses.sort_by(|a, b| a.volume_id.cmp(&b.volume_id));
+        Ok(statuses)
+    }
+
+    /// Emit a Kubernetes event for a volume, in the driver namespace
+    pub async fn emit_event(&self, volume_id: &str, reason: &str, message: &str, event_type: &str) {
+        emit_event(
+            &self.client,
+            &self.namespace,
+            volume_id,
+            None,
+            reason,
+            message,
+            event_type,
+        )
+        .await
+    }
+
+    /// Get set of node names that exist in the cluster
+    async fn get_existing_nodes(&self) -> Result<HashSet<String>, kube::Error> {
+        let nodes: Api<Node> = Api::all(self.client.clone());
+        let node_list = nodes.list(&ListParams::default()).await?;
+        let names: HashSet<String> = node_list
+            .items
+            .iter()
+            .filter_map(|n| n.metadata.name.clone())
+            .collect();
+        Ok(names)
     }
 
     /// Process cleanup ConfigMaps: mark decommissioned nodes and prune completed ones
-    pub async fn process_cleanups(&self) -> Result<usize, kube::Error> {
-        let configmaps: Api<ConfigMap> = Api::namespaced(self.client.clone(), &self.namespace);
-        let lp = ListParams::default().labels(&format!("{}=cleanup", VOLUME_LABEL));
+    pub async fn process_cleanups(&self) -> Result<CleanupSummary, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        // All tracking ConfigMaps, so the same list also gives the footprint
+        let lp = ListParams::default().labels(VOLUME_LABEL);
 
         let cms = configmaps.list(&lp).await?;
+        let mut summary = CleanupSummary {
+            footprint: Footprint::from_configmaps(&cms),
+            ..CleanupSummary::default()
+        };
+        // Shards are evaluated with their volume's ConfigMap
+        let cleanup: Vec<ConfigMap> = cms
+            .into_iter()
+            .filter(|cm| {
+                cm.metadata.labels.as_ref().is_some_and(|labels| {
+                    labels
+                        .get(VOLUME_LABEL)
+                        .is_some_and(|label| label == "cleanup")
+                        && !labels.contains_key(SHARD_LABEL)
+                })
+            })
+            .collect();
 
-        if cms.items.is_empty() {
-            return Ok(0);
+        if cleanup.is_empty() {
+            return Ok(summary);
+        }
+
+        // Get existing nodes once for all ConfigMaps
+        let existing_nodes = self.get_existing_nodes().await?;
+        debug!(node_count = existing_nodes.len(), "Fetched cluster nodes");
+
+        for cm in cleanup {
+            match self.evaluate_cleanup(&cm, &existing_nodes).await {
+                Ok(Some(outcome)) => {
+                    if let PruneOutcome::Pruned { volume_id, .. } = &outcome {
+                        history::history().record("prune", volume_id, &Ok::<_, String>(()));
+                    }
+                    summary.record(outcome)
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(configmap = ?cm.metadata.name, error = %e, "Failed to prune ConfigMap");
+                    let volume_id = VolumeStatus::from_configmap(&cm)
+                        .map(|s| s.volume_id)
+                        .unwrap_or_default();
+                    history::history().record("prune", &volume_id, &Err::<(), _>(&e));
+                    summary
+                        .errors
+                        .push((cm.metadata.name.unwrap_or_default(), e.to_string()));
+                }
+            }
         }
 
-        // Get existing nodes once for all ConfigMaps
-        let existing_nodes = self.get_existing_nodes().await?;
-        debug!(node_count = existing_nodes.len(), "Fetched cluster nodes");
+        Ok(summary)
+    }
+
+    /// Evaluate a single volume's ConfigMap right away (admin prune-now).
+    /// Returns `None` when the volume has no tracking ConfigMap.
+    pub async fn prune_volume(&self, volume_id: &str) -> Result<Option<PruneOutcome>, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let cm = match get_volume_configmap(&configmaps, volume_id).await? {
+            Some(cm) => cm,
+            None => return Ok(None),
+        };
+
+        let existing_nodes = self.get_existing_nodes().await?;
+        self.evaluate_cleanup(&cm, &existing_nodes).await
+    }
+
+    /// Settle pending nodes on one ConfigMap and prune it if cleanup is complete.
+    /// Returns `None` if the ConfigMap has no parseable status or disappeared meanwhile.
+    ///
+    /// The listed ConfigMap is updated in one optimistic write, re-fetched only
+    /// on conflict, and completeness is judged on what was written.
+    async fn evaluate_cleanup(
+        &self,
+        cm: &ConfigMap,
+        existing_nodes: &HashSet<String>,
+    ) -> Result<Option<PruneOutcome>, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+
+        let cm_name = match cm.metadata.name.as_ref() {
+            Some(n) => n,
+            None => return Ok(None),
+        };
+
+        let status = match VolumeStatus::from_configmap(cm) {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        if status.cleanup_requested_at.is_none() {
+            return Ok(Some(PruneOutcome::NotRequested {
+                volume_id: status.volume_id,
+            }));
+        }
+
+        // The mutation may run again on conflict; keep each shard's last
+        // attempt's result
+        let settled = std::sync::Mutex::new(BTreeMap::<u32, SettledNodes>::new());
+        let now = chrono::Utc::now();
+        let settle = |s: &mut VolumeStatus| {
+            // A sweep waits for every node in the cluster
+            if s.sweep {
+                for node in existing_nodes {
+                    s.add_node(node);
+                }
+            }
+            let nodes = s.settle_pending_nodes(existing_nodes, self.decommission_grace, now);
+            settled.lock().unwrap().insert(s.shard, nodes);
+        };
+        let mut current_status = match update_volume_configmap(
+            &self.client,
+            &self.namespace,
+            &status.volume_id,
+            0,
+            false,
+            "evaluate_cleanup",
+            Some(cm.clone()),
+            None,
+            settle,
+        )
+        .await
+        {
+            Ok(s) => s,
+            // ConfigMap may have been deleted
+            Err(kube::Error::Api(err)) if err.code == 404 => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        // Completeness is judged on the nodes of all shards
+        let shards = update_shards(
+            &self.client,
+            &self.namespace,
+            &current_status,
+            "evaluate_cleanup",
+            settle,
+        )
+        .await?;
+        for shard in &shards {
+            current_status.merge_nodes(shard);
+        }
+        let settled = settled.into_inner().unwrap().into_values().fold(
+            SettledNodes::default(),
+            |mut all, nodes| {
+                all.directory_absent.extend(nodes.directory_absent);
+                all.decommissioned.extend(nodes.decommissioned);
+                all.absent.extend(nodes.absent);
+                all
+            },
+        );
+
+        if !settled.directory_absent.is_empty() {
+            info!(
+                volume_id = %current_status.volume_id,
+                nodes = ?settled.directory_absent,
+                "Marked nodes with absent directories as completed"
+            );
+            emit_event(
+                &self.client,
+                &self.namespace,
+                &current_status.volume_id,
+                current_status.pvc.as_ref(),
+                "NodeDirectoryAbsent",
+                &format!(
+                    "Node(s) reported the volume directory already gone, marked as completed: {:?}",
+                    settled.directory_absent
+                ),
+                "Normal",
+            )
+            .await;
+        }
+        if !settled.decommissioned.is_empty() {
+            info!(
+                volume_id = %current_status.volume_id,
+                decommissioned_nodes = ?settled.decommissioned,
+                "Marked nodes as decommissioned (no longer exist in cluster)"
+            );
+            emit_event(
+                &self.client,
+                &self.namespace,
+                &current_status.volume_id,
+                current_status.pvc.as_ref(),
+                "NodeDecommissioned",
+                &format!(
+                    "Node(s) no longer exist in cluster, marked as decommissioned: {:?}",
+                    settled.decommissioned
+                ),
+                "Warning",
+            )
+            .await;
+        }
+        if !settled.absent.is_empty() {
+            debug!(
+                volume_id = %current_status.volume_id,
+                nodes = ?settled.absent,
+                "Nodes missing from the cluster, within the decommission grace"
+            );
+        }
+        let newly_decommissioned = settled.decommissioned;
+
+        if current_status.cleanup_requested_at.is_none() {
+            return Ok(Some(PruneOutcome::NotRequested {
+                volume_id: current_status.volume_id,
+            }));
+        }
+
+        let complete = current_status.is_cleanup_complete();
+        let quorum = !complete && current_status.has_cleanup_quorum(self.quorum);
+        let timed_out = !complete
+            && !quorum
+            && self
+                .cleanup_timeout
+                .is_some_and(|timeout| current_status.is_timed_out(timeout));
+        let stragglers: Vec<String> = if complete {
+            Vec::new()
+        } else if quorum || timed_out {
+            current_status
+                .pending_nodes()
+                .into_iter()
+                .cloned()
+                .collect()
+        } else {
+            return Ok(Some(PruneOutcome::Pending {
+                pending_nodes: current_status
+                    .pending_nodes()
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+                volume_id: current_status.volume_id,
+                newly_decommissioned,
+            }));
+        };
+
+        // Hand the stragglers their cleanup before the ConfigMap is gone
+        if !stragglers.is_empty() {
+            stragglers::record(
+                &self.client,
+                &self.namespace,
+                &current_status.volume_id,
+                &stragglers,
+            )
+            .await?;
+            if timed_out {
+                warn!(
+                    volume_id = %current_status.volume_id,
+                    requested_at = ?current_status.cleanup_requested_at,
+                    never_reported = ?stragglers,
+                    "Cleanup timed out, force-pruning its ConfigMap"
+                );
+                emit_event(
+                    &self.client,
+                    &self.namespace,
+                    &current_status.volume_id,
+                    current_status.pvc.as_ref(),
+                    "CleanupTimedOut",
+                    &format!(
+                        "Cleanup timed out, node(s) that never reported clean up untracked: {:?}",
+                        stragglers
+                    ),
+                    "Warning",
+                )
+                .await;
+            } else {
+                warn!(
+                    volume_id = %current_status.volume_id,
+                    quorum = %self.quorum,
+                    stragglers = ?stragglers,
+                    "Cleanup quorum reached, leaving the remaining nodes to clean up untracked"
+                );
+                emit_event(
+                    &self.client,
+                    &self.namespace,
+                    &current_status.volume_id,
+                    current_status.pvc.as_ref(),
+                    "CleanupQuorumReached",
+                    &format!(
+                        "Cleanup quorum ({}) reached, node(s) yet to report clean up untracked: {:?}",
+                        self.quorum, stragglers
+                    ),
+                    "Warning",
+                )
+                .await;
+            }
+        }
+
+        // Emit event before deleting the ConfigMap
+        emit_event(
+            &self.client,
+            &self.namespace,
+            &current_status.volume_id,
+            current_status.pvc.as_ref(),
+            "CleanupComplete",
+            &format!(
+                "{} cleanup complete. Completed: {:?}, Failed: {:?}, Decommissioned: {:?}{}{}",
+                if complete {
+                    "All"
+                } else if quorum {
+                    "Quorum"
+                } else {
+                    "Timed-out"
+                },
+                current_status.nodes_completed,
+                current_status.nodes_failed,
+                current_status.nodes_decommissioned,
+                current_status.capacity_note(),
+                current_status.reclaimed_note()
+            ),
+            "Normal",
+        )
+        .await;
+
+        // Shards first: a shard left behind would never be pruned
+        for shard in 1..=current_status.shards {
+            match configmaps
+                .delete(
+                    &shard_configmap_name(&current_status.volume_id, shard),
+                    &Default::default(),
+                )
+                .await
+            {
+                Err(kube::Error::Api(err)) if err.code == 404 => {}
+                result => {
+                    result?;
+                }
+            }
+        }
+        configmaps.delete(cm_name, &Default::default()).await?;
+        for (node, duration) in current_status.cleanup_durations() {
+            metrics::metrics().record_node_cleanup_duration(node, duration);
+        }
+        info!(
+            configmap = %cm_name,
+            volume_id = %current_status.volume_id,
+            nodes_with_volume = ?current_status.nodes_with_volume,
+            nodes_completed = ?current_status.nodes_completed,
+            nodes_failed = ?current_status.nodes_failed,
+            nodes_decommissioned = ?current_status.nodes_decommissioned,
+            "Pruned completed cleanup ConfigMap"
+        );
+
+        Ok(Some(PruneOutcome::Pruned {
+            volume_id: current_status.volume_id,
+            nodes_completed: current_status.nodes_completed,
+            nodes_failed: current_status.nodes_failed,
+            nodes_decommissioned: current_status.nodes_decommissioned,
+            newly_decommissioned,
+            stragglers,
+            timed_out,
+        }))
+    }
+}
+
+/// Run the controller cleanup processing loop
+/// Checks for decommissioned nodes and prunes completed ConfigMaps
+pub async fn run_controller_cleanup_loop(controller: CleanupController, interval: Duration) {
+    info!(
+        interval_secs = interval.as_secs(),
+        decommission_grace_secs = controller.decommission_grace.as_secs(),
+        "Starting controller cleanup processor"
+    );
+
+    loop {
+        controller.heartbeat.beat();
+        tokio::time::sleep(interval).await;
+
+        match controller.process_cleanups().await {
+            Ok(summary) => {
+                metrics::metrics().record_cleanup_summary(&summary);
+                if summary.is_empty() {
+                    debug!("No cleanup ConfigMaps to process");
+                } else if summary.pruned.is_empty()
+                    && summary.decommissioned.is_empty()
+                    && summary.errors.is_empty()
+                {
+                    // Nothing changed, only volumes still waiting on nodes
+                    debug!(pending = ?summary.pending, "Cleanup cycle: {}", summary);
+                } else {
+                    info!(
+                        pruned = ?summary.pruned,
+                        pending = ?summary.pending,
+                        decommissioned = ?summary.decommissioned,
+                        errors = ?summary.errors,
+                        "Cleanup cycle: {}",
+                        summary
+                    );
+                }
+            }
+            Err(e) => {
+                error!(error = %e, "Error processing cleanups");
+            }
+        }
+    }
+}
+
+/// Node-side cleanup operations
+pub struct CleanupNode {
+    client: Client,
+    namespace: String,
+    node_name: String,
+    base_path: std::path::PathBuf,
+    /// Other base paths volumes may be stored under (`--allowed-base-path`)
+    allowed_base_paths: Vec<PathBuf>,
+    directory_backend: DirectoryBackend,
+    /// Size limits to undo before deleting a directory
+    quota_backend: QuotaBackend,
+    /// Move directories that fail to delete into the quarantine area
+    quarantine_failed: bool,
+    /// Volumes already reported as aged by this process (avoids event spam)
+    aged_reported: std::sync::Mutex<HashSet<String>>,
+    /// Shared with NodePublishVolume, so a directory isn't mounted while deleted
+    volume_locks: VolumeLocks,
+    /// Set once the first cleanup pass succeeded (`--wait-for-cleanup-sync`)
+    synced: Option<watch::Sender<bool>>,
+    /// How often to log progress while deleting a directory; zero for never
+    progress_interval: Duration,
+    /// Conflict retries per cleanup pass
+    retry_budget: u32,
+    /// Don't delete directories of volumes whose PV still exists
+    verify_pv_deleted: bool,
+    /// Volumes already reported as blocked by their PV (avoids event spam)
+    blocked_reported: std::sync::Mutex<HashSet<String>>,
+    /// Journal cleanup outcomes locally before reporting them
+    cleanup_journal: bool,
+    /// Watch or poll for cleanup requests
+    trigger: CleanupTrigger,
+    /// Ticked by the cleanup loop (`--health-addr`)
+    heartbeat: Heartbeat,
+    /// Log the volume directories cleanup would delete instead of deleting
+    /// them (`--cleanup-dry-run`)
+    dry_run: bool,
+}
+
+impl CleanupNode {
+    pub fn new(
+        client: Client,
+        namespace: String,
+        node_name: String,
+        base_path: std::path::PathBuf,
+    ) -> Self {
+        Self {
+            client,
+            namespace,
+            node_name,
+            base_path,
+            allowed_base_paths: Vec::new(),
+            directory_backend: DirectoryBackend::default(),
+            quota_backend: QuotaBackend::default(),
+            quarantine_failed: false,
+            aged_reported: std::sync::Mutex::new(HashSet::new()),
+            volume_locks: VolumeLocks::default(),
+            synced: None,
+            progress_interval: Duration::ZERO,
+            retry_budget: DEFAULT_RETRY_BUDGET,
+            verify_pv_deleted: false,
+            blocked_reported: std::sync::Mutex::new(HashSet::new()),
+            cleanup_journal: false,
+            trigger: CleanupTrigger::default(),
+            heartbeat: Heartbeat::default(),
+            dry_run: false,
+        }
+    }
+
+    pub fn with_directory_backend(mut self, backend: DirectoryBackend) -> Self {
+        self.directory_backend = backend;
+        self
+    }
+
+    pub fn with_quota_backend(mut self, backend: QuotaBackend) -> Self {
+        self.quota_backend = backend;
+        self
+    }
+
+    /// Also clean up volumes stored under one of `paths` (see
+    /// `volume::BASE_PATH_KEY`)
+    pub fn with_allowed_base_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.allowed_base_paths = paths;
+        self
+    }
+
+    /// The base path `status`'s volume is stored under. Fails for one that
+    /// isn't (or no longer is) allowed, rather than deleting from it.
+    fn volume_base_path(&self, status: &VolumeStatus) -> std::io::Result<&Path> {
+        let Some(path) = &status.base_path else {
+            return Ok(&self.base_path);
+        };
+        match std::iter::once(&self.base_path)
+            .chain(&self.allowed_base_paths)
+            .find(|allowed| *allowed == path)
+        {
+            Some(allowed) => Ok(allowed),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Base path {} is not allowed (--allowed-base-path)",
+                    path.display()
+                ),
+            )),
+        }
+    }
+
+    /// On cleanup failure, move the directory to `<base>/.quarantine` and
+    /// report the node's cleanup as done instead of failed
+    pub fn with_quarantine(mut self, enabled: bool) -> Self {
+        self.quarantine_failed = enabled;
+        self
+    }
+
+    /// Share per-volume locks with the node service
+    pub fn with_volume_locks(mut self, locks: VolumeLocks) -> Self {
+        self.volume_locks = locks;
+        self
+    }
+
+    /// Signal `synced` once the cleanup loop completed a pass
+    pub fn with_sync_signal(mut self, synced: watch::Sender<bool>) -> Self {
+        self.synced = Some(synced);
+        self
+    }
+
+    /// Log progress every `interval` while deleting a volume directory, so a
+    /// huge cache taking minutes to delete isn't silent (zero disables)
+    pub fn with_progress_interval(mut self, interval: Duration) -> Self {
+        self.progress_interval = interval;
+        self
+    }
+
+    /// Spend at most `retries` conflict retries on ConfigMap updates per
+    /// cleanup pass, deferring the rest of the pass once they're used up
+    pub fn with_retry_budget(mut self, retries: u32) -> Self {
+        self.retry_budget = retries;
+        self
+    }
+
+    /// Before deleting a volume's directory, check that no PersistentVolume
+    /// still references the volume, guarding against spurious DeleteVolume calls
+    pub fn with_verify_pv_deleted(mut self, enabled: bool) -> Self {
+        self.verify_pv_deleted = enabled;
+        self
+    }
+
+    /// Write each cleanup outcome to the node-local journal before reporting
+    /// it, so it is reported again after an API outage or restart
+    pub fn with_cleanup_journal(mut self, enabled: bool) -> Self {
+        self.cleanup_journal = enabled;
+        self
+    }
+
+    /// Watch for cleanup requests, or poll for them (`--cleanup-trigger`)
+    pub fn with_trigger(mut self, trigger: CleanupTrigger) -> Self {
+        self.trigger = trigger;
+        self
+    }
+
+    /// Tick `heartbeat` on each pass of the cleanup loop
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = heartbeat;
+        self
+    }
+
+    /// Only log what would be deleted, still reporting each node's cleanup as
+    /// done, to try the cleanup flow against real ConfigMaps safely
+    pub fn with_dry_run(mut self, enabled: bool) -> Self {
+        self.dry_run = enabled;
+        self
+    }
+
+    /// Report a cleanup skipped because PV `pv` still exists, once per volume
+    async fn report_blocked(&self, status: &VolumeStatus, pv: &str) {
+        let first_report = self
+            .blocked_reported
+            .lock()
+            .unwrap()
+            .insert(status.volume_id.clone());
+        if !first_report {
+            return;
+        }
+        warn!(
+            volume_id = %status.volume_id,
+            node = %self.node_name,
+            pv = %pv,
+            "Cleanup requested but the volume's PersistentVolume still exists, not deleting"
+        );
+        emit_event(
+            &self.client,
+            &self.namespace,
+            &status.volume_id,
+            status.pvc.as_ref(),
+            "CleanupBlockedPVExists",
+            &format!(
+                "Node {} didn't delete the volume directory: PersistentVolume {} still references the volume",
+                self.node_name, pv
+            ),
+            "Warning",
+        )
+        .await;
+    }
+
+    /// Process all pending cleanup requests for this node
+    pub async fn process_pending_cleanups(&self) -> Result<usize, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let lp = ListParams::default().labels(&cleanup_selector());
+
+        // Outcomes of earlier passes first, so they aren't redone
+        self.retry_journal().await?;
+        let cms = configmaps.list(&lp).await?;
+        self.process_cleanup_configmaps(cms).await
+    }
+
+    /// Report the journaled outcomes of earlier passes (`--cleanup-journal`)
+    async fn retry_journal(&self) -> Result<(), kube::Error> {
+        if !self.cleanup_journal {
+            return Ok(());
+        }
+        let reported = cleanup_journal::retry(
+            &self.client,
+            &self.namespace,
+            &self.node_name,
+            &self.base_path,
+        )
+        .await?;
+        if reported > 0 {
+            info!(count = reported, "Reported journaled cleanups");
+        }
+        Ok(())
+    }
+
+    /// Clean up this node's directories of the volumes of `cms`, ConfigMaps
+    /// requesting cleanup, and report them. Returns the number processed.
+    async fn process_cleanup_configmaps(&self, cms: Vec<ConfigMap>) -> Result<usize, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let mut processed = 0;
+        let budget = RetryBudget::new(self.retry_budget);
+        // Listed once per pass, when first needed
+        let mut live_pvs: Option<HashMap<String, String>> = None;
+
+        for cm in cms {
+            // Unreported cleanups are picked up again next pass
+            if budget.exhausted() {
+                warn!(
+                    node = %self.node_name,
+                    budget = self.retry_budget,
+                    "Conflict retry budget exhausted, deferring remaining cleanups"
+                );
+                break;
+            }
+
+            let status = match VolumeStatus::from_configmap(&cm) {
+                Some(s) => s,
+                None => continue,
+            };
+
+            // Skip if this node doesn't have the volume, unless all nodes sweep
+            if !status.nodes_with_volume.contains(&self.node_name) && !status.sweep {
+                continue;
+            }
+
+            // Skip if we already processed this
+            if status.nodes_completed.contains(&self.node_name)
+                || status.nodes_failed.contains(&self.node_name)
+            {
+                continue;
+            }
+
+            // Re-check under the lock: a publish may have taken a shared cache
+            // back into use while we waited
+            let _guard = self.volume_locks.lock(&status.volume_id).await;
+            let current = get_volume_configmap(&configmaps, &status.volume_id)
+                .await?
+                .as_ref()
+                .and_then(VolumeStatus::from_configmap);
+            if current.is_none_or(|s| s.cleanup_requested_at.is_none()) {
+                debug!(volume_id = %status.volume_id, "Cleanup no longer requested, skipping");
+                continue;
+            }
+
+            if self.verify_pv_deleted {
+                if live_pvs.is_none() {
+                    match live_pvs_by_handle(&self.client).await {
+                        Ok(pvs) => live_pvs = Some(pvs),
+                        Err(e) => {
+                            // Not deleting anything we can't verify
+                            warn!(error = %e, "Failed to list PersistentVolumes, deferring cleanups");
+                            break;
+                        }
+                    }
+                }
+                if let Some(pv) = live_pvs.as_ref().and_then(|p| p.get(&status.volume_id)) {
+                    self.report_blocked(&status, pv).await;
+                    continue;
+                }
+            }
+
+            // Process cleanup
+            let result = match self.volume_base_path(&status) {
+                Ok(base_path) => {
+                    self.cleanup_volume_directory(base_path, &status.volume_id)
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            history::history().record("cleanup", &status.volume_id, &result);
+
+            let mut reclaimed_bytes = None;
+            let success = match result {
+                Ok(DirectoryCleanup::Removed {
+                    reclaimed_bytes: bytes,
+                }) => {
+                    info!(
+                        volume_id = %status.volume_id,
+                        node = %self.node_name,
+                        reclaimed_bytes = ?bytes,
+                        "Cleaned up volume directory"
+                    );
+                    reclaimed_bytes = bytes;
+                    true
+                }
+                Ok(DirectoryCleanup::Missing) => {
+                    debug!(
+                        volume_id = %status.volume_id,
+                        node = %self.node_name,
+                        "No directory to clean (already gone)"
+                    );
+                    true
+                }
+                // Logged by the dry run
+                Ok(DirectoryCleanup::DryRun) => true,
+                Ok(DirectoryCleanup::Quarantined(dest)) => {
+                    warn!(
+                        volume_id = %status.volume_id,
+                        node = %self.node_name,
+                        quarantine = %dest.display(),
+                        "Failed to delete volume directory, moved it to quarantine"
+                    );
+                    emit_event(
+                        &self.client,
+                        &self.namespace,
+                        &status.volume_id,
+                        status.pvc.as_ref(),
+                        "NodeCleanupQuarantined",
+                        &format!(
+                            "Node {} failed to delete the volume directory, moved it to {}",
+                            self.node_name,
+                            dest.display()
+                        ),
+                        "Warning",
+                    )
+                    .await;
+                    true
+                }
+                Err(e) => {
+                    error!(
+                        volume_id = %status.volume_id,
+                        node = %self.node_name,
+                        error = %e,
+                        "Failed to clean up volume directory"
+                    );
+                    false
+                }
+            };
+
+            let journaled = self.cleanup_journal
+                && {
+                    let completion = cleanup_journal::Completion::new(&status.volume_id, success);
+                    match cleanup_journal::record(&self.base_path, &completion) {
+                        Ok(()) => true,
+                        Err(e) => {
+                            warn!(volume_id = %status.volume_id, error = %e, "Failed to journal cleanup");
+                            false
+                        }
+                    }
+                };
+
+            // Update ConfigMap with completion status
+            match mark_node_cleanup_complete(
+                &self.client,
+                &self.namespace,
+                &status.volume_id,
+                status.shard,
+                &self.node_name,
+                NodeCleanupReport {
+                    success,
+                    reclaimed_bytes,
+                },
+                Some(&budget),
+            )
+            .await
+            {
+                Ok(()) if journaled => {
+                    if let Err(e) = cleanup_journal::ack(&self.base_path, &status.volume_id) {
+                        warn!(volume_id = %status.volume_id, error = %e, "Failed to ack journaled cleanup");
+                    }
+                }
+                Ok(()) => {}
+                // Don't fail cleanup for status update issues
+                Err(e) => warn!(
+                    volume_id = %status.volume_id,
+                    error = %e,
+                    journaled = journaled,
+                    "Failed to update cleanup status"
+                ),
+            }
+
+            processed += 1;
+        }
+
+        Ok(processed)
+    }
+
+    /// Delete the directories of volumes left to this node when their
+    /// ConfigMap was pruned by quorum (see `stragglers`). Volumes tracked
+    /// again are dropped untouched. Returns the number of volumes dropped.
+    pub async fn process_stragglers(&self) -> Result<usize, kube::Error> {
+        let volume_ids =
+            stragglers::pending(&self.client, &self.namespace, &self.node_name).await?;
+        if volume_ids.is_empty() {
+            return Ok(0);
+        }
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let mut done = Vec::new();
+
+        for volume_id in volume_ids {
+            let _guard = self.volume_locks.lock(&volume_id).await;
+            if get_volume_configmap(&configmaps, &volume_id)
+                .await?
+                .is_some()
+            {
+                debug!(volume_id = %volume_id, "Straggler volume tracked again, leaving it");
+                done.push(volume_id);
+                continue;
+            }
+            // The ConfigMap recording its base path is gone
+            let mut result = Ok(DirectoryCleanup::Missing);
+            for base_path in std::iter::once(&self.base_path).chain(&self.allowed_base_paths) {
+                match self.cleanup_volume_directory(base_path, &volume_id).await {
+                    Ok(DirectoryCleanup::Missing) => {}
+                    other => result = other,
+                }
+                if result.is_err() {
+                    break;
+                }
+            }
+            history::history().record("cleanup", &volume_id, &result);
+            match result {
+                Ok(_) => {
+                    info!(
+                        volume_id = %volume_id,
+                        node = %self.node_name,
+                        "Cleaned up volume directory left after quorum"
+                    );
+                    done.push(volume_id);
+                }
+                // Retried next pass
+                Err(e) => error!(
+                    volume_id = %volume_id,
+                    node = %self.node_name,
+                    error = %e,
+                    "Failed to clean up volume directory left after quorum"
+                ),
+            }
+        }
+
+        stragglers::remove(&self.client, &self.namespace, &self.node_name, &done).await?;
+        Ok(done.len())
+    }
+
+    /// Delete a volume directory under `base_path` if it exists, quarantining
+    /// it on failure if enabled
+    async fn cleanup_volume_directory(
+        &self,
+        base_path: &Path,
+        volume_id: &str,
+    ) -> Result<DirectoryCleanup, std::io::Error> {
+        // Use tokio's blocking task for potentially long rm -rf
+        let base_path = base_path.to_path_buf();
+        let path = volume::volume_path(&base_path, volume_id);
+        let backend = self.directory_backend;
+        let quota = self.quota_backend;
+        let quarantine_id = self.quarantine_failed.then(|| volume_id.to_string());
+        let volume_id = volume_id.to_string();
+        let interval = self.progress_interval;
+        if self.dry_run {
+            return tokio::task::spawn_blocking(move || {
+                dry_run_volume_directory(&base_path, &path, &volume_id)
+            })
+            .await
+            .map_err(std::io::Error::other)?;
+        }
+        tokio::task::spawn_blocking(move || {
+            // A loopback or tmpfs mount must be gone before its directory
+            // can be deleted; a tmpfs takes its content with it
+            if tmpfs::release(&path)? {
+                info!(volume_id = %volume_id, "Unmounted volume tmpfs");
+            }
+            quota.release(&base_path, &path)?;
+            if interval.is_zero() {
+                return remove_volume_directory(
+                    &base_path,
+                    &path,
+                    backend,
+                    quarantine_id.as_deref(),
+                    None,
+                );
+            }
+
+            let started = std::time::Instant::now();
+            let mut last_report = started;
+            let mut deleted = 0;
+            let result = remove_volume_directory(
+                &base_path,
+                &path,
+                backend,
+                quarantine_id.as_deref(),
+                Some(&mut |count| {
+                    deleted = count;
+                    if last_report.elapsed() >= interval {
+                        last_report = std::time::Instant::now();
+                        info!(
+                            volume_id = %volume_id,
+                            deleted = count,
+                            elapsed_secs = started.elapsed().as_secs(),
+                            "Still deleting volume directory"
+                        );
+                    }
+                }),
+            );
+            if last_report != started {
+                info!(
+                    volume_id = %volume_id,
+                    deleted = deleted,
+                    elapsed_secs = started.elapsed().as_secs(),
+                    "Finished deleting volume directory"
+                );
+            }
+            result
+        })
+        .await
+        .map_err(std::io::Error::other)?
+    }
+
+    /// Retry deleting quarantined directories
+    async fn sweep_quarantine(&self) {
+        let base_path = self.base_path.clone();
+        let backend = self.directory_backend;
+        match tokio::task::spawn_blocking(move || sweep_quarantine(&base_path, backend)).await {
+            Ok(Ok((removed, remaining))) if removed > 0 || remaining > 0 => {
+                info!(
+                    removed = removed,
+                    remaining = remaining,
+                    "Swept quarantined volume directories"
+                );
+            }
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => warn!(error = %e, "Failed to sweep quarantine"),
+            Err(e) => warn!(error = %e, "Quarantine sweep task failed"),
+        }
+    }
+
+    /// Check this node's active volumes against `max_age`.
+    ///
+    /// Aged volumes get a `VolumeAged` warning event. With `recycle`, their
+    /// directory contents are also cleared so the cache rebuilds fresh. The
+    /// directory itself (a bind mount source, possibly mounted right now) is
+    /// kept, so running pods see their cache emptied under them.
+    /// Returns the number of aged volumes found.
+    pub async fn check_volume_ages(
+        &self,
+        max_age: Duration,
+        recycle: bool,
+    ) -> Result<usize, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let lp = ListParams::default().labels(&format!("{}=active", VOLUME_LABEL));
+        let cms = configmaps.list(&lp).await?;
+
+        let max_age = chrono::Duration::from_std(max_age).unwrap_or(chrono::Duration::MAX);
+        let now = chrono::Utc::now();
+        let mut aged = 0;
+
+        for cm in cms {
+            let status = match VolumeStatus::from_configmap(&cm) {
+                Some(s) => s,
+                None => continue,
+            };
+            if !status.nodes_with_volume.contains(&self.node_name) {
+                continue;
+            }
+            let age = match status.age_on_node(&self.node_name, now) {
+                Some(age) if age >= max_age => age,
+                _ => continue,
+            };
+            let Ok(base_path) = self.volume_base_path(&status) else {
+                continue;
+            };
+            let volume_path = volume::volume_path(base_path, &status.volume_id);
+            if !volume_path.exists() {
+                continue;
+            }
+            aged += 1;
+
+            if !recycle {
+                let first_report = self
+                    .aged_reported
+                    .lock()
+                    .unwrap()
+                    .insert(status.volume_id.clone());
+                if first_report {
+                    warn!(
+                        volume_id = %status.volume_id,
+                        node = %self.node_name,
+                        age_hours = age.num_hours(),
+                        "Volume exceeded max age"
+                    );
+                    emit_event(
+                        &self.client,
+                        &self.namespace,
+                        &status.volume_id,
+                        status.pvc.as_ref(),
+                        "VolumeAged",
+                        &format!(
+                            "Volume on node {} is {}h old, exceeding the max volume age",
+                            self.node_name,
+                            age.num_hours()
+                        ),
+                        "Warning",
+                    )
+                    .await;
+                }
+                continue;
+            }
+
+            let _guard = self.volume_locks.lock(&status.volume_id).await;
+            let result = if volume_path.starts_with(base_path) && volume_path != base_path {
+                let path = volume_path.clone();
+                tokio::task::spawn_blocking(move || clear_directory_contents(&path))
+                    .await
+                    .map_err(std::io::Error::other)
+                    .and_then(|r| r)
+            } else {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Path is not under base path",
+                ))
+            };
+            history::history().record("recycle", &status.volume_id, &result);
+
+            if let Err(e) = result {
+                error!(
+                    volume_id = %status.volume_id,
+                    error = %e,
+                    "Failed to recycle aged volume"
+                );
+                continue;
+            }
+
+            let node = self.node_name.clone();
+            if let Err(e) = update_volume_configmap(
+                &self.client,
+                &self.namespace,
+                &status.volume_id,
+                status.shard,
+                false,
+                "mark_node_recycled",
+                None,
+                None,
+                |s| s.mark_node_recycled(&node),
+            )
+            .await
+            {
+                warn!(volume_id = %status.volume_id, error = %e, "Failed to record recycle");
+            }
+
+            info!(
+                volume_id = %status.volume_id,
+                node = %self.node_name,
+                age_hours = age.num_hours(),
+                "Recycled aged volume"
+            );
+            emit_event(
+                &self.client,
+                &self.namespace,
+                &status.volume_id,
+                status.pvc.as_ref(),
+                "VolumeAged",
+                &format!(
+                    "Volume on node {} was {}h old, contents cleared",
+                    self.node_name,
+                    age.num_hours()
+                ),
+                "Warning",
+            )
+            .await;
+        }
+
+        Ok(aged)
+    }
+
+    /// Report active volumes whose directory on this node is gone although
+    /// cleanup never ran (e.g. deleted by hand), so that a later cleanup
+    /// doesn't wait for this node. Returns the number of volumes reported.
+    pub async fn report_absent_directories(&self) -> Result<usize, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let lp = ListParams::default().labels(&format!("{}=active", VOLUME_LABEL));
+        let cms = configmaps.list(&lp).await?;
+        let mut reported = 0;
+
+        for cm in cms {
+            let status = match VolumeStatus::from_configmap(&cm) {
+                Some(s) => s,
+                None => continue,
+            };
+            if !status.nodes_with_volume.contains(&self.node_name)
+                || status.nodes_directory_absent.contains(&self.node_name)
+            {
+                continue;
+            }
+            let Ok(base_path) = self.volume_base_path(&status) else {
+                continue;
+            };
+            let volume_path = volume::volume_path(base_path, &status.volume_id);
+            if volume_path.exists() {
+                continue;
+            }
+
+            // Re-check under the lock: a publish may be creating it right now
+            let _guard = self.volume_locks.lock(&status.volume_id).await;
+            if volume_path.exists() {
+                continue;
+            }
+
+            let node = self.node_name.clone();
+            update_volume_configmap(
+                &self.client,
+                &self.namespace,
+                &status.volume_id,
+                status.shard,
+                false,
+                "mark_node_directory_absent",
+                None,
+                None,
+                |s| s.mark_node_directory_absent(&node),
+            )
+            .await?;
+            reported += 1;
+            warn!(
+                volume_id = %status.volume_id,
+                node = %self.node_name,
+                path = %volume_path.display(),
+                "Volume directory missing outside of cleanup"
+            );
+        }
+
+        Ok(reported)
+    }
+
+    /// Delete the volume directories under the base path that no ConfigMap
+    /// tracks any more and that weren't modified for `grace`, e.g. those of
+    /// a volume whose ConfigMap was pruned while this node was down. Only
+    /// directories named like a volume ID are considered, and never one that
+    /// is mounted or waiting for its publish to be registered. Returns the
+    /// number deleted.
+    pub async fn reconcile_orphans(&self, grace: Duration) -> Result<usize, kube::Error> {
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let tracked: HashSet<String> = configmaps
+            .list(&ListParams::default().labels(VOLUME_LABEL))
+            .await?
+            .iter()
+            .filter_map(VolumeStatus::from_configmap)
+            .map(|s| s.volume_id)
+            .collect();
+
+        let base_path = self.base_path.clone();
+        let candidates = tokio::task::spawn_blocking(move || {
+            find_orphan_candidates(&base_path, grace, std::time::SystemTime::now())
+        })
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|r| r);
+        let candidates = match candidates {
+            Ok(c) => c,
+            Err(e) => {
+                warn!(error = %e, "Failed to list volume directories");
+                return Ok(0);
+            }
+        };
+        let candidates: Vec<String> = candidates
+            .into_iter()
+            .filter(|id| !tracked.contains(id))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(0);
+        }
+
+        // Not deleting anything that may still be in use
+        let in_use = match volumes_in_use(&self.base_path) {
+            Ok(ids) => ids,
+            Err(e) => {
+                warn!(error = %e, "Failed to check volume usage, skipping orphan collection");
+                return Ok(0);
+            }
+        };
+        let mut deleted = 0;
+        for volume_id in candidates {
+            if in_use.contains(&volume_id) {
+                debug!(volume_id = %volume_id, "Untracked volume directory in use, leaving it");
+                continue;
+            }
+            // Re-check under the lock: a publish may have just registered it
+            let _guard = self.volume_locks.lock(&volume_id).await;
+            if get_volume_configmap(&configmaps, &volume_id)
+                .await?
+                .is_some()
+            {
+                continue;
+            }
+            let volume_path = volume::volume_path(&self.base_path, &volume_id);
+            let result = self
+                .cleanup_volume_directory(&self.base_path, &volume_id)
+                .await;
+            history::history().record("orphan-gc", &volume_id, &result);
+            match result {
+                Ok(DirectoryCleanup::DryRun) => {}
+                Ok(_) => {
+                    warn!(
+                        volume_id = %volume_id,
+                        node = %self.node_name,
+                        path = %volume_path.display(),
+                        "Deleted orphaned volume directory"
+                    );
+                    deleted += 1;
+                }
+                Err(e) => error!(
+                    volume_id = %volume_id,
+                    node = %self.node_name,
+                    error = %e,
+                    "Failed to delete orphaned volume directory"
+                ),
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// Run the orphaned volume directory collection loop (`--orphan-gc`)
+    pub async fn run_orphan_gc_loop(self, interval: Duration, grace: Duration) {
+        info!(
+            node = %self.node_name,
+            interval_secs = interval.as_secs(),
+            grace_secs = grace.as_secs(),
+            "Starting orphaned volume directory collection"
+        );
+
+        loop {
+            match self.reconcile_orphans(grace).await {
+                Ok(count) if count > 0 => {
+                    info!(count = count, "Deleted orphaned volume directories");
+                }
+                Ok(_) => {
+                    debug!("No orphaned volume directories");
+                }
+                Err(e) => {
+                    error!(error = %e, "Error collecting orphaned volume directories");
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Run the max volume age check loop
+    pub async fn run_age_check_loop(self, interval: Duration, max_age: Duration, recycle: bool) {
+        info!(
+            node = %self.node_name,
+            max_age_secs = max_age.as_secs(),
+            recycle = recycle,
+            "Starting volume age check"
+        );
+
+        loop {
+            match self.check_volume_ages(max_age, recycle).await {
+                Ok(count) if count > 0 => {
+                    info!(count = count, "Found volumes exceeding max age");
+                }
+                Ok(_) => {
+                    debug!("No aged volumes");
+                }
+                Err(e) => {
+                    error!(error = %e, "Error checking volume ages");
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// Run the cleanup watcher loop. Cleanup requests are watched for or
+    /// polled every `interval` (`--cleanup-trigger`); the other periodic
+    /// tasks run every `interval` either way.
+    pub async fn run_cleanup_loop(self, interval: Duration) {
+        info!(
+            node = %self.node_name,
+            interval_secs = interval.as_secs(),
+            trigger = ?self.trigger,
+            "Starting cleanup watcher"
+        );
+
+        match self.trigger {
+            CleanupTrigger::Poll => self.poll_cleanups(interval).await,
+            CleanupTrigger::Watch => self.watch_cleanups(interval).await,
+        }
+    }
+
+    async fn poll_cleanups(&self, interval: Duration) {
+        let mut housekeeping = Housekeeping::default();
+        loop {
+            let result = self.process_pending_cleanups().await;
+            self.finish_pass(&result);
+            self.run_housekeeping(&mut housekeeping).await;
+            self.heartbeat.beat();
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// React to cleanup requests as they appear. Each time the watch
+    /// (re)starts, e.g. after a disconnect or desync, the ConfigMaps it lists
+    /// get a full pass, catching up on anything missed in between.
+    async fn watch_cleanups(&self, interval: Duration) {
+        let configmaps: Api<ConfigMap> = Api::namespaced(self.client.clone(), &self.namespace);
+        let config = watcher::Config::default().labels(&cleanup_selector());
+        let mut events = std::pin::pin!(watcher(configmaps, config).default_backoff());
+        let mut housekeeping = Housekeeping::default();
+        let mut ticks = tokio::time::interval(interval);
+        ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut listed: Vec<ConfigMap> = Vec::new();
+        let mut last_resync = std::time::Instant::now();
+
+        loop {
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(Ok(watcher::Event::Init)) => listed.clear(),
+                    Some(Ok(watcher::Event::InitApply(cm))) => listed.push(cm),
+                    Some(Ok(watcher::Event::InitDone)) => {
+                        let cms = std::mem::take(&mut listed);
+                        debug!(count = cms.len(), "Cleanup watch (re)started, resyncing");
+                        let result = match self.retry_journal().await {
+                            Ok(()) => self.process_cleanup_configmaps(cms).await,
+                            Err(e) => Err(e),
+                        };
+                        self.finish_pass(&result);
+                        last_resync = std::time::Instant::now();
+                    }
+                    Some(Ok(watcher::Event::Apply(cm))) => {
+                        match self.process_cleanup_configmaps(vec![cm]).await {
+                            Ok(0) => {}
+                            Ok(count) => info!(count = count, "Processed cleanup requests"),
+                            // Retried on the next resync
+                            Err(e) => error!(error = %e, "Error processing cleanup request"),
+                        }
+                    }
+                    Some(Ok(watcher::Event::Delete(_))) => {}
+                    // The watcher backs off and restarts on its own
+                    Some(Err(e)) => warn!(error = %e, "Cleanup watch failed"),
+                    None => {
+                        error!("Cleanup watch ended");
+                        return;
+                    }
+                },
+                _ = ticks.tick() => {
+                    if last_resync.elapsed() >= CLEANUP_RESYNC_INTERVAL {
+                        let result = self.process_pending_cleanups().await;
+                        self.finish_pass(&result);
+                        last_resync = std::time::Instant::now();
+                    }
+                    self.run_housekeeping(&mut housekeeping).await;
+                    self.heartbeat.beat();
+                }
+            }
+        }
+    }
+
+    /// Log a full cleanup pass, and signal readiness after the first one
+    /// that succeeded
+    fn finish_pass(&self, result: &Result<usize, kube::Error>) {
+        match result {
+            Ok(count) if *count > 0 => {
+                info!(count = count, "Processed cleanup requests");
+            }
+            Ok(_) => {
+                debug!("No pending cleanups");
+            }
+            Err(e) => {
+                error!(error = %e, "Error processing cleanups");
+            }
+        }
+        if let (Ok(_), Some(synced)) = (result, &self.synced) {
+            if !synced.send_replace(true) {
+                info!(node = %self.node_name, "First cleanup pass done, node is ready");
+            }
+        }
+    }
+
+    /// The periodic tasks besides cleanup requests
+    async fn run_housekeeping(&self, state: &mut Housekeeping) {
+        match self.process_stragglers().await {
+            Ok(0) => {}
+            Ok(count) => info!(count = count, "Cleaned up volumes left after quorum"),
+            Err(e) => error!(error = %e, "Error processing volumes left after quorum"),
+        }
+
+        if self.quarantine_failed
+            && !self.dry_run
+            && state
+                .last_sweep
+                .is_none_or(|at| at.elapsed() >= QUARANTINE_SWEEP_INTERVAL)
+        {
+            self.sweep_quarantine().await;
+            state.last_sweep = Some(std::time::Instant::now());
+        }
+
+        match pending_registration::retry(
+            &self.client,
+            &self.namespace,
+            &self.node_name,
+            &self.base_path,
+        )
+        .await
+        {
+            Ok(0) => {}
+            Ok(count) => info!(count = count, "Registered pending publishes"),
+            Err(e) => debug!(error = %e, "Pending registrations still failing"),
+        }
+
+        if state
+            .last_directory_check
+            .is_none_or(|at| at.elapsed() >= DIRECTORY_CHECK_INTERVAL)
+        {
+            if let Err(e) = self.report_absent_directories().await {
+                error!(error = %e, "Error checking volume directories");
+            }
+            state.last_directory_check = Some(std::time::Instant::now());
+        }
+    }
+}
+
+/// When the node's less frequent periodic tasks last ran
+#[derive(Default)]
+struct Housekeeping {
+    last_sweep: Option<std::time::Instant>,
+    last_directory_check: Option<std::time::Instant>,
+}
+
+/// Label selector of the ConfigMaps requesting cleanup
+fn cleanup_selector() -> String {
+    format!("{}=cleanup", VOLUME_LABEL)
+}
+
+/// Volumes under `base_path` mounted somewhere, or published but not
+/// registered yet
+pub fn volumes_in_use(base_path: &Path) -> std::io::Result<HashSet<String>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    let mounts = mount_audit::volume_mounts(&mount_audit::parse_mountinfo(&mountinfo), base_path);
+    let pending = pending_registration::pending(base_path)?;
+    Ok(mounts
+        .into_iter()
+        .map(|m| m.tracking_id)
+        .chain(pending.into_iter().map(|p| p.volume_id))
+        .collect())
+}
+
+/// IDs of the volume directories directly under `base_path` (not shared
+/// caches, not symlinks) named like a volume ID and not modified for `grace`
+/// before `now`
+fn find_orphan_candidates(
+    base_path: &Path,
+    grace: Duration,
+    now: std::time::SystemTime,
+) -> std::io::Result<Vec<String>> {
+    let mut candidates = Vec::new();
+    for volume_id in inventory::volume_directories(base_path)? {
+        if !volume::validate_volume_id(&volume_id) {
+            continue;
+        }
+        let modified = std::fs::symlink_metadata(base_path.join(&volume_id))?.modified()?;
+        // In the future (clock skew): not old enough
+        if now.duration_since(modified).is_ok_and(|age| age >= grace) {
+            candidates.push(volume_id);
+        }
+    }
+    candidates.sort();
+    Ok(candidates)
+}
+
+/// What happened to a volume directory during node cleanup
+#[derive(Debug, PartialEq, Eq)]
+enum DirectoryCleanup {
+    /// Deleted, freeing this many bytes; `None` when not counted (a btrfs
+    /// subvolume goes at once)
+    Removed { reclaimed_bytes: Option<u64> },
+    /// Nothing to remove
+    Missing,
+    /// Deletion failed and the directory was moved here
+    Quarantined(std::path::PathBuf),
+    /// Left in place (`--cleanup-dry-run`)
+    DryRun,
+}
+
+/// Refuse to delete `path` unless it is strictly under `base_path`
+fn check_under_base_path(base_path: &Path, path: &Path) -> Result<(), std::io::Error> {
+    if !path.starts_with(base_path) || path == base_path {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Path is not under base path",
+        ));
+    }
+    Ok(())
+}
+
+/// Log the volume directory at `path` and its size instead of deleting it,
+/// after the same checks as `remove_volume_directory`
+fn dry_run_volume_directory(
+    base_path: &Path,
+    path: &Path,
+    volume_id: &str,
+) -> Result<DirectoryCleanup, std::io::Error> {
+    if std::fs::symlink_metadata(path).is_err() {
+        return Ok(DirectoryCleanup::Missing);
+    }
+    check_under_base_path(base_path, path)?;
+    let (bytes, inodes, _) = dir_size::walk_size(path)?;
+    info!(
+        volume_id = %volume_id,
+        path = %path.display(),
+        bytes = bytes,
+        inodes = inodes,
+        "Dry run: would delete volume directory"
+    );
+    Ok(DirectoryCleanup::DryRun)
+}
+
+/// Delete the volume directory at `path`. If that fails and `quarantine_id` is
+/// set, move what's left to `<base>/.quarantine/<quarantine_id>-<timestamp>`.
+fn remove_volume_directory(
+    base_path: &Path,
+    path: &Path,
+    backend: DirectoryBackend,
+    quarantine_id: Option<&str>,
+    progress: Option<&mut dyn FnMut(u64)>,
+) -> Result<DirectoryCleanup, std::io::Error> {
+    if std::fs::symlink_metadata(path).is_err() {
+        return Ok(DirectoryCleanup::Missing);
+    }
+
+    // Safety check: ensure path is under base_path
+    check_under_base_path(base_path, path)?;
+
+    // Counts the bytes as it deletes, rather than walking the tree twice
+    let err = match backend.remove_reporting(path, progress.unwrap_or(&mut |_| {})) {
+        Ok(reclaimed_bytes) => return Ok(DirectoryCleanup::Removed { reclaimed_bytes }),
+        Err(e) => e,
+    };
+    let volume_id = match quarantine_id {
+        Some(id) => id,
+        None => return Err(err),
+    };
+
+    let quarantine = base_path.join(volume::QUARANTINE_DIR);
+    let dest = quarantine.join(format!(
+        "{}-{}",
+        volume_id,
+        chrono::Utc::now().format("%Y%m%dT%H%M%S%.3fZ")
+    ));
+    // rename only touches the parent directories, so it usually works even
+    // when deleting the contents didn't
+    match std::fs::create_dir_all(&quarantine).and_then(|_| std::fs::rename(path, &dest)) {
+        Ok(()) => Ok(DirectoryCleanup::Quarantined(dest)),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "Failed to move directory to quarantine");
+            Err(err)
+        }
+    }
+}
+
+/// Try to delete everything in `<base>/.quarantine`.
+/// Returns how many entries were removed and how many remain.
+fn sweep_quarantine(
+    base_path: &Path,
+    backend: DirectoryBackend,
+) -> Result<(usize, usize), std::io::Error> {
+    let quarantine = base_path.join(volume::QUARANTINE_DIR);
+    let entries = match std::fs::read_dir(&quarantine) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e),
+    };
+
+    let (mut removed, mut remaining) = (0, 0);
+    for entry in entries {
+        let path = entry?.path();
+        let result = if path.is_dir() {
+            backend.remove(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+        match result {
+            Ok(()) => removed += 1,
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "Quarantined directory still not removable");
+                remaining += 1;
+            }
+        }
+    }
+    Ok((removed, remaining))
+}
+
+/// Remove everything inside `path`, keeping `path` itself
+fn clear_directory_contents(path: &Path) -> Result<(), std::io::Error> {
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_api::FakeApiServer;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Disables events for the current test only, see `events_enabled`
+        pub(super) static EVENTS_DISABLED: Cell<bool> = const { Cell::new(false) };
+        pub(super) static IMMUTABLE_STABLE: Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[test]
+    fn test_configmap_data_is_stable() {
+        let mut a = VolumeStatus::new("nlc-stable");
+        let mut b = a.clone();
+        for node in ["node2", "node1", "node3"] {
+            a.add_node(node);
+        }
+        for node in ["node3", "node2", "node1"] {
+            b.add_node(node);
+        }
+        a.mark_node_completed("node2");
+        a.mark_node_completed("node1");
+        b.mark_node_completed("node1");
+        b.mark_node_completed("node2");
+        // Same completion times, only the order differs
+        b.nodes_completed_at = a.nodes_completed_at.clone();
+        assert_eq!(a.to_configmap_data(), b.to_configmap_data());
+
+        // Only the stored form is sorted
+        assert_eq!(a.nodes_with_volume, vec!["node2", "node1", "node3"]);
+        let stored = VolumeStatus::from_configmap(&ConfigMap {
+            data: Some(a.to_configmap_data()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(stored.nodes_with_volume, vec!["node1", "node2", "node3"]);
+        assert_eq!(stored.nodes_completed, vec!["node1", "node2"]);
+    }
+
+    #[test]
+    fn test_volume_status_serialization() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.add_node("node1");
+        status.add_node("node2");
+        status.mark_cleanup_requested();
+        status.mark_node_completed("node1");
+        status.mark_node_decommissioned("node3");
+        status.capacity_bytes = 1 << 30;
+
+        let data = status.to_configmap_data();
+        let json = data.get("status").unwrap();
+
+        let parsed: VolumeStatus = serde_json::from_str(json).unwrap();
+        assert_eq!(parsed.volume_id, "nlc-test-123");
+        assert_eq!(parsed.nodes_with_volume.len(), 2);
+        assert_eq!(parsed.nodes_completed.len(), 1);
+        assert_eq!(parsed.nodes_decommissioned.len(), 1);
+        assert!(parsed.cleanup_requested_at.is_some());
+        assert_eq!(parsed.capacity_bytes, 1 << 30);
+
+        // Written before the capacity was recorded
+        let old: VolumeStatus = serde_json::from_str(
+            r#"{"volume_id":"nlc-test-123","created_at":"2024-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(old.capacity_bytes, 0);
+    }
+
+    #[test]
+    fn test_cleanup_complete() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.add_node("node1");
+        status.add_node("node2");
+
+        // Not complete without cleanup request
+        assert!(!status.is_cleanup_complete());
+
+        status.mark_cleanup_requested();
+
+        // Not complete without all nodes reporting
+        assert!(!status.is_cleanup_complete());
+
+        status.mark_node_completed("node1");
+        assert!(!status.is_cleanup_complete());
+
+        status.mark_node_completed("node2");
+        assert!(status.is_cleanup_complete());
+    }
+
+    #[test]
+    fn test_is_timed_out() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.add_node("node1");
+        // Not before cleanup was requested
+        assert!(!status.is_timed_out(Duration::ZERO));
+
+        status.mark_cleanup_requested();
+        assert!(!status.is_timed_out(Duration::from_secs(3600)));
+
+        let requested = chrono::Utc::now() - chrono::Duration::hours(2);
+        status.cleanup_requested_at = Some(requested.to_rfc3339());
+        assert!(status.is_timed_out(Duration::from_secs(3600)));
+        assert!(!status.is_timed_out(Duration::from_secs(3 * 3600)));
+
+        status.cleanup_requested_at = Some("not a time".to_string());
+        assert!(!status.is_timed_out(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_backoff_limit() {
+        let config = RetryConfig::default();
+        assert_eq!(config.max_retries, MAX_RETRIES);
+        assert_eq!(backoff_limit(&config, 0), 10);
+        assert_eq!(backoff_limit(&config, 3), 80);
+        // Capped
+        assert_eq!(backoff_limit(&config, 7), 640);
+        assert_eq!(backoff_limit(&config, 100), 640);
+        let config = RetryConfig {
+            max_backoff_ms: 100,
+            ..config
+        };
+        assert_eq!(backoff_limit(&config, 5), 100);
+    }
+
+    #[test]
+    fn test_cleanup_quorum() {
+        assert_eq!(CleanupQuorum::parse("all"), Ok(CleanupQuorum::All));
+        assert_eq!(CleanupQuorum::parse("100%"), Ok(CleanupQuorum::All));
+        assert_eq!(CleanupQuorum::parse("90%"), Ok(CleanupQuorum::Percent(90)));
+        for invalid in ["0%", "101%", "90", "most"] {
+            assert!(CleanupQuorum::parse(invalid).is_err(), "{}", invalid);
+        }
+        assert_eq!(CleanupQuorum::Percent(90).to_string(), "90%");
+
+        // Rounded up
+        assert_eq!(CleanupQuorum::Percent(90).required(300), 270);
+        assert_eq!(CleanupQuorum::Percent(90).required(5), 5);
+        assert_eq!(CleanupQuorum::Percent(50).required(3), 2);
+        assert_eq!(CleanupQuorum::Percent(1).required(1), 1);
+        assert_eq!(CleanupQuorum::All.required(3), 3);
+
+        let mut status = VolumeStatus::new("nlc-test-123");
+        for node in ["node1", "node2", "node3", "node4"] {
+            status.add_node(node);
+        }
+        status.mark_node_completed("node1");
+        status.mark_node_failed("node2");
+        // Not before cleanup was requested
+        assert!(!status.has_cleanup_quorum(CleanupQuorum::Percent(50)));
+        status.mark_cleanup_requested();
+        assert!(status.has_cleanup_quorum(CleanupQuorum::Percent(50)));
+        assert!(!status.has_cleanup_quorum(CleanupQuorum::Percent(75)));
+        status.mark_node_decommissioned("node3");
+        assert!(status.has_cleanup_quorum(CleanupQuorum::Percent(75)));
+        assert!(!status.has_cleanup_quorum(CleanupQuorum::All));
+    }
+
+    #[test]
+    fn test_cleanup_durations() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.add_node("node1");
+        status.add_node("node2");
+        status.mark_node_completed("node1");
+        // Not requested: nothing to measure from
+        assert!(status.cleanup_durations().is_empty());
+
+        status.cleanup_requested_at = Some("2024-01-01T00:00:00+00:00".to_string());
+        status
+            .nodes_completed_at
+            .insert("node1".to_string(), "2024-01-01T00:01:30+00:00".to_string());
+        status.mark_node_completed("node2");
+        // A repeated report keeps the first completion time
+        status.mark_node_completed("node1");
+        status
+            .nodes_completed_at
+            .insert("node3".to_string(), "2023-12-31T23:59:00+00:00".to_string());
+        status
+            .nodes_completed_at
+            .insert("node4".to_string(), "garbage".to_string());
+
+        let durations: BTreeMap<&str, Duration> = status.cleanup_durations().into_iter().collect();
+        assert_eq!(durations.len(), 2, "{:?}", durations);
+        assert_eq!(durations["node1"], Duration::from_secs(90));
+        assert!(durations["node2"] > Duration::from_secs(86400));
+
+        // Round-trips through the ConfigMap
+        let cm = ConfigMap {
+            data: Some(status.to_configmap_data()),
+            ..Default::default()
+        };
+        let parsed = VolumeStatus::from_configmap(&cm).unwrap();
+        assert_eq!(parsed.nodes_completed_at, status.nodes_completed_at);
+    }
+
+    #[test]
+    fn test_published_nodes() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        assert!(status.published_nodes().is_empty());
+        for node in ["node1", "node2", "node3", "node4"] {
+            status.add_node(node);
+        }
+        status.mark_cleanup_requested();
+        status.mark_node_completed("node1");
+        status.mark_node_decommissioned("node2");
+        // Failed to clean up: still has the data
+        status.mark_node_failed("node3");
+        assert_eq!(status.published_nodes(), vec!["node3", "node4"]);
+    }
+
+    #[test]
+    fn test_cleanup_complete_with_failures() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.add_node("node1");
+        status.add_node("node2");
+        status.mark_cleanup_requested();
+
+        status.mark_node_completed("node1");
+        status.mark_node_failed("node2"); // Failed but still "reported"
+
+        assert!(status.is_cleanup_complete());
+    }
+
+    #[test]
+    fn test_idempotent_operations() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+
+        status.add_node("node1");
+        status.add_node("node1");
+        status.add_node("node1");
+        assert_eq!(status.nodes_with_volume.len(), 1);
+
+        status.mark_node_completed("node1");
+        status.mark_node_completed("node1");
+        assert_eq!(status.nodes_completed.len(), 1);
+    }
+
+    #[test]
+    fn test_directory_absent_nodes() {
+        let mut status = VolumeStatus::new("test-vol");
+        status.add_node("node1");
+        status.add_node("node2");
+        status.mark_node_directory_absent("node2");
+        status.mark_node_directory_absent("node2");
+        assert_eq!(status.nodes_directory_absent, vec!["node2"]);
+
+        // Publishing again recreates the directory
+        status.add_node("node2");
+        assert!(status.nodes_directory_absent.is_empty());
+    }
+
+    #[test]
+    fn test_settle_pending_nodes() {
+        let mut status = VolumeStatus::new("test-vol");
+        for node in ["node1", "node2", "node3", "node4"] {
+            status.add_node(node);
+        }
+        status.mark_node_directory_absent("node2");
+        let existing: HashSet<String> = ["node1", "node2"].map(String::from).into();
+
+        let now = chrono::Utc::now();
+
+        // Nothing is settled before cleanup is requested
+        assert_eq!(
+            status.settle_pending_nodes(&existing, Duration::ZERO, now),
+            SettledNodes::default()
+        );
+
+        status.mark_cleanup_requested();
+        // A previous run already recorded node3
+        status.mark_node_decommissioned("node3");
+        let settled = status.settle_pending_nodes(&existing, Duration::ZERO, now);
+        assert_eq!(settled.directory_absent, vec!["node2"]);
+        assert_eq!(settled.decommissioned, vec!["node4"]);
+        assert_eq!(status.pending_nodes(), vec!["node1"]);
+        assert!(status.nodes_absent_since.is_empty());
+
+        // Settling again is a no-op
+        assert_eq!(
+            status.settle_pending_nodes(&existing, Duration::ZERO, now),
+            SettledNodes::default()
+        );
+    }
+
+    #[test]
+    fn test_decommission_grace() {
+        let mut status = VolumeStatus::new("test-vol");
+        for node in ["node1", "node2"] {
+            status.add_node(node);
+        }
+        status.mark_cleanup_requested();
+        let grace = Duration::from_secs(600);
+        let start = chrono::Utc::now();
+        let at = |secs: i64| start + chrono::Duration::seconds(secs);
+        let only_node1: HashSet<String> = ["node1"].map(String::from).into();
+        let both: HashSet<String> = ["node1", "node2"].map(String::from).into();
+
+        // First seen missing: only recorded
+        let settled = status.settle_pending_nodes(&only_node1, grace, at(0));
+        assert_eq!(settled.absent, vec!["node2"]);
+        assert!(settled.decommissioned.is_empty());
+        assert!(status.nodes_absent_since.contains_key("node2"));
+
+        // Still within the grace
+        let settled = status.settle_pending_nodes(&only_node1, grace, at(300));
+        assert_eq!(settled.absent, vec!["node2"]);
+        assert_eq!(status.pending_nodes(), vec!["node1", "node2"]);
+
+        // Reappears: the clock restarts
+        let settled = status.settle_pending_nodes(&both, grace, at(400));
+        assert_eq!(settled, SettledNodes::default());
+        assert!(status.nodes_absent_since.is_empty());
+        status.settle_pending_nodes(&only_node1, grace, at(700));
+        let settled = status.settle_pending_nodes(&only_node1, grace, at(1000));
+        assert!(settled.decommissioned.is_empty());
+
+        // Missing for the whole grace
+        let settled = status.settle_pending_nodes(&only_node1, grace, at(1300));
+        assert_eq!(settled.decommissioned, vec!["node2"]);
+        assert!(status.nodes_decommissioned.contains(&"node2".to_string()));
+        assert!(status.nodes_absent_since.is_empty());
+    }
+
+    #[test]
+    fn test_age_on_node() {
+        let mut status = VolumeStatus::new("nlc-test-123");
+        status.created_at = "2024-01-01T00:00:00+00:00".to_string();
+        status.add_node("node1");
+        status.add_node("node2");
+        status
+            .recycled_at
+            .insert("node2".to_string(), "2024-01-09T00:00:00+00:00".to_string());
+
+        let now = chrono::DateTime::parse_from_rfc3339("2024-01-10T00:00:00+00:00")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(
+            status.age_on_node("node1", now),
+            Some(chrono::Duration::days(9))
+        );
+        assert_eq!(
+            status.age_on_node("node2", now),
+            Some(chrono::Duration::days(1))
+        );
+
+        status.created_at = "garbage".to_string();
+        assert_eq!(status.age_on_node("node1", now), None);
+    }
+
+    #[test]
+    fn test_clear_directory_contents() {
+        let dir = std::env::temp_dir().join(format!("nlc-clear-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+        std::fs::write(dir.join("file"), b"data").unwrap();
+        std::fs::write(dir.join("sub/deeper/file"), b"data").unwrap();
+
+        clear_directory_contents(&dir).unwrap();
+
+        assert!(dir.is_dir());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_pv_owner_reference() {
+        let mut pv = PersistentVolume {
+            metadata: kube::api::ObjectMeta {
+                name: Some("pvc-123".to_string()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        // No UID (not persisted yet): no reference
+        assert!(pv_owner_reference(&pv).is_none());
+
+        pv.metadata.uid = Some("uid-1".to_string());
+        let owner = pv_owner_reference(&pv).unwrap();
+        assert_eq!(owner.kind, "PersistentVolume");
+        assert_eq!(owner.api_version, "v1");
+        assert_eq!(owner.name, "pvc-123");
+        assert_eq!(owner.uid, "uid-1");
+        assert_eq!(owner.controller, None);
+    }
+
+    fn temp_base(name: &str) -> std::path::PathBuf {
+        let base =
+            std::env::temp_dir().join(format!("nlc-cleanup-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn test_remove_volume_directory() {
+        let base = temp_base("remove");
+        let path = base.join("nlc-vol");
+        std::fs::create_dir_all(path.join("sub")).unwrap();
+        std::fs::write(path.join("sub/data"), vec![1u8; 64 * 1024]).unwrap();
+
+        let reclaimed_bytes = match remove_volume_directory(
+            &base,
+            &path,
+            DirectoryBackend::Dir,
+            Some("nlc-vol"),
+            None,
+        )
+        .unwrap()
+        {
+            DirectoryCleanup::Removed { reclaimed_bytes } => reclaimed_bytes,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(reclaimed_bytes, Some(64 * 1024));
+        assert!(!path.exists());
+        assert_eq!(
+            remove_volume_directory(&base, &path, DirectoryBackend::Dir, None, None).unwrap(),
+            DirectoryCleanup::Missing
+        );
+        assert!(remove_volume_directory(&base, &base, DirectoryBackend::Dir, None, None).is_err());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_failed_cleanup_is_quarantined() {
+        let base = temp_base("quarantine");
+        // A file where the directory should be makes the recursive delete fail
+        let path = base.join("nlc-vol");
+        std::fs::write(&path, b"not a directory").unwrap();
+
+        assert!(remove_volume_directory(&base, &path, DirectoryBackend::Dir, None, None).is_err());
+        assert!(path.exists());
+
+        let result =
+            remove_volume_directory(&base, &path, DirectoryBackend::Dir, Some("nlc-vol"), None)
+                .unwrap();
+        let dest = match result {
+            DirectoryCleanup::Quarantined(dest) => dest,
+            other => panic!("expected quarantine, got {:?}", other),
+        };
+        assert!(!path.exists());
+        assert!(dest.starts_with(base.join(volume::QUARANTINE_DIR)));
+        assert!(dest
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("nlc-vol-"));
+        assert_eq!(std::fs::read(&dest).unwrap(), b"not a directory");
+
+        assert_eq!(
+            sweep_quarantine(&base, DirectoryBackend::Dir).unwrap(),
+            (1, 0)
+        );
+        assert!(!dest.exists());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    fn cm_label(cm: &ConfigMap) -> &str {
+        cm.metadata
+            .labels
+            .as_ref()
+            .and_then(|l| l.get(VOLUME_LABEL))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    /// A node with the volume's directory present under its own base path
+    fn node_with_volume(
+        api: &FakeApiServer,
+        node_name: &str,
+        volume_id: &str,
+    ) -> (CleanupNode, std::path::PathBuf) {
+        let base = temp_base(&format!("lifecycle-{}-{}", node_name, volume_id));
+        let dir = volume::volume_path(&base, volume_id);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("cached"), b"data").unwrap();
+        let node = CleanupNode::new(api.client(), "default".into(), node_name.into(), base);
+        (node, dir)
+    }
+
+    #[tokio::test]
+    async fn test_volume_labels_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-labels");
+        let cm_name = configmap_name(&volume_id);
+        let labels = BTreeMap::from([
+            ("team".to_string(), "data".to_string()),
+            // Can't override the phase
+            (VOLUME_LABEL.to_string(), "bogus".to_string()),
+        ]);
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration {
+                labels: labels.clone(),
+                ..PendingRegistration::new(&volume_id)
+            },
+        )
+        .await
+        .unwrap();
+        let cm = api.configmap(&cm_name).unwrap();
+        assert_eq!(cm_label(&cm), "active");
+        assert_eq!(
+            cm.metadata.labels.as_ref().unwrap().get("team"),
+            Some(&"data".to_string())
+        );
+
+        // Kept through the phase change
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let cm = api.configmap(&cm_name).unwrap();
+        assert_eq!(cm_label(&cm), "cleanup");
+        assert_eq!(
+            cm.metadata.labels.as_ref().unwrap().get("team"),
+            Some(&"data".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_lifecycle_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-lifecycle");
+        let cm_name = configmap_name(&volume_id);
+
+        for node in ["node1", "node2", "node1"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let cm = api.configmap(&cm_name).unwrap();
+        assert_eq!(cm_label(&cm), "active");
+        let status = VolumeStatus::from_configmap(&cm).unwrap();
+        assert_eq!(status.nodes_with_volume, vec!["node1", "node2"]);
+
+        let (node1, dir1) = node_with_volume(&api, "node1", &volume_id);
+        let (node2, dir2) = node_with_volume(&api, "node2", &volume_id);
+        // Nothing to do before DeleteVolume
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 0);
+        assert!(dir1.exists());
+
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert_eq!(cm_label(&api.configmap(&cm_name).unwrap()), "cleanup");
+
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&2));
+        assert!(summary.pruned.is_empty());
+
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir1.exists());
+        // Already reported, not processed again
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 0);
+
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&1));
+
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir2.exists());
+        // Completion times recorded for both nodes
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        let durations = status.cleanup_durations();
+        assert_eq!(
+            durations.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec!["node1", "node2"]
+        );
+        // As were the bytes each freed
+        assert_eq!(
+            status.nodes_reclaimed_bytes.keys().collect::<Vec<_>>(),
+            vec!["node1", "node2"]
+        );
+
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id.clone()]);
+        assert!(summary.decommissioned.is_empty());
+        assert!(api.configmap(&cm_name).is_none());
+
+        let reasons = api.event_reasons();
+        assert!(reasons.contains(&"CleanupRequested".to_string()));
+        // Both nodes run in this process, so their events are aggregated
+        assert_eq!(
+            api.events()
+                .iter()
+                .filter(|e| e.reason.as_deref() == Some("NodeCleanupComplete"))
+                .map(|e| e.count.unwrap_or(1))
+                .sum::<i32>(),
+            2
+        );
+        assert_eq!(reasons.last().map(String::as_str), Some("CleanupComplete"));
+        let complete = api.events().last().unwrap().message.clone().unwrap();
+        assert!(complete.contains("on 2 node(s)"), "{}", complete);
+    }
+
+    #[test]
+    fn test_reclaimed_note() {
+        let mut status = VolumeStatus::new("nlc-a");
+        assert_eq!(status.reclaimed_note(), "");
+        status.mark_node_completed("node1");
+        status.mark_node_completed("node2");
+        status.nodes_reclaimed_bytes.insert("node1".into(), 1000);
+        status.nodes_reclaimed_bytes.insert("node2".into(), 24);
+        // Only nodes listed as completed count
+        status.nodes_reclaimed_bytes.insert("node3".into(), 5000);
+        assert_eq!(
+            status.reclaimed_note(),
+            ", reclaimed 1024 bytes on 2 node(s)"
+        );
+
+        // Shards bring their nodes' counts along
+        let mut primary = VolumeStatus::new("nlc-a");
+        primary.merge_nodes(&status);
+        assert_eq!(primary.nodes_reclaimed_bytes.len(), 3);
+        assert_eq!(primary.reclaimed_note(), status.reclaimed_note());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_allowed_base_path_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-base-path");
+        let cm_name = configmap_name(&volume_id);
+        let (node, dir) = node_with_volume(&api, "node1", &volume_id);
+        let nvme = temp_base(&format!("nvme-{}", volume_id));
+        let nvme_dir = volume::volume_path(&nvme, &volume_id);
+        std::fs::create_dir_all(&nvme_dir).unwrap();
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration {
+                base_path: Some(nvme.clone()),
+                ..PendingRegistration::new(&volume_id)
+            },
+        )
+        .await
+        .unwrap();
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.base_path.as_ref(), Some(&nvme));
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+
+        // Not allowed on this node: nothing is deleted, the cleanup fails
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(nvme_dir.exists());
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.nodes_failed, vec!["node1"]);
+
+        let (node, _) = node_with_volume(&api, "node1", &volume_id);
+        let node = node.with_allowed_base_paths(vec![nvme.clone()]);
+        with_volume_configmap(&client, "default", &volume_id, false, "test", |s| {
+            s.nodes_failed.clear()
+        })
+        .await
+        .unwrap();
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!nvme_dir.exists());
+        // Only the volume's own base path is touched
+        assert!(dir.exists());
+        let _ = std::fs::remove_dir_all(nvme);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_dry_run_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-dry-run");
+        let cm_name = configmap_name(&volume_id);
+        let (node, dir) = node_with_volume(&api, "node1", &volume_id);
+        let node = node.with_dry_run(true);
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+
+        // Reported as done, but the directory is left in place
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(dir.join("cached").exists());
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.nodes_completed, vec!["node1"]);
+
+        // Still refuses paths outside the base path
+        let base = dir.parent().unwrap();
+        assert!(dry_run_volume_directory(base, base, &volume_id).is_err());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    /// A status with `count` nodes with long names, past the shard threshold
+    fn crowded_status(volume_id: &str, count: usize) -> VolumeStatus {
+        let mut status = VolumeStatus::new(volume_id);
+        for i in 0..count {
+            status.add_node(&format!("node-{:0>195}", i));
+        }
+        status
+    }
+
+    #[tokio::test]
+    async fn test_sharded_nodes_with_api() {
+        let api = FakeApiServer::new(&["node-overflow"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-crowded");
+        let cm_name = configmap_name(&volume_id);
+        let shard_name = shard_configmap_name(&volume_id, 1);
+
+        // A few nodes stay in one ConfigMap
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        assert!(api.configmap(&shard_name).is_none());
+
+        let crowded = crowded_status(&volume_id, 1200);
+        assert!(!crowded.has_room_for("node-overflow"));
+        let configmaps: Api<ConfigMap> = Api::namespaced(client.clone(), "default");
+        configmaps
+            .delete(&cm_name, &Default::default())
+            .await
+            .unwrap();
+        configmaps
+            .create(
+                &PostParams::default(),
+                &ConfigMap {
+                    metadata: kube::api::ObjectMeta {
+                        name: Some(cm_name.clone()),
+                        labels: Some(crowded.configmap_labels()),
+                        ..Default::default()
+                    },
+                    data: Some(crowded.to_configmap_data()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        // The next node overflows into a shard, once
+        for _ in 0..2 {
+            register_node_publish(
+                &client,
+                "default",
+                "node-overflow",
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let primary = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(primary.shards, 1);
+        assert_eq!(primary.nodes_with_volume.len(), 1200);
+        let shard = api.configmap(&shard_name).unwrap();
+        assert_eq!(
+            shard
+                .metadata
+                .labels
+                .as_ref()
+                .and_then(|l| l.get(SHARD_LABEL))
+                .map(String::as_str),
+            Some("1")
+        );
+        let shard = VolumeStatus::from_configmap(&shard).unwrap();
+        assert_eq!(shard.nodes_with_volume, vec!["node-overflow"]);
+        assert!(api
+            .configmap(&shard_configmap_name(&volume_id, 2))
+            .is_none());
+
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let status = controller.volume_status(&volume_id).await.unwrap().unwrap();
+        assert_eq!(status.nodes_with_volume.len(), 1201);
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.footprint.volumes, 1);
+        assert_eq!(summary.footprint.nodes, 1201);
+
+        // The shard follows the cleanup request
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert_eq!(cm_label(&api.configmap(&shard_name).unwrap()), "cleanup");
+
+        // The other nodes are gone; the shard's node is still pending
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&1));
+        assert!(api.configmap(&cm_name).is_some());
+
+        let (node, dir) = node_with_volume(&api, "node-overflow", &volume_id);
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir.exists());
+        let shard = VolumeStatus::from_configmap(&api.configmap(&shard_name).unwrap()).unwrap();
+        assert_eq!(shard.nodes_completed, vec!["node-overflow"]);
+        let primary = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert!(primary.nodes_completed.is_empty());
+
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id.clone()]);
+        assert!(api.configmap(&cm_name).is_none());
+        assert!(api.configmap(&shard_name).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_quorum_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2", "node3"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-quorum");
+        let cm_name = configmap_name(&volume_id);
+        for node in ["node1", "node2", "node3"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let (node1, dir1) = node_with_volume(&api, "node1", &volume_id);
+        let (node2, dir2) = node_with_volume(&api, "node2", &volume_id);
+        let (node3, dir3) = node_with_volume(&api, "node3", &volume_id);
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+
+        let controller = CleanupController::new(client.clone(), "default".into())
+            .with_quorum(CleanupQuorum::Percent(60));
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&2));
+
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 1);
+        let outcome = controller.prune_volume(&volume_id).await.unwrap().unwrap();
+        match outcome {
+            PruneOutcome::Pruned { stragglers, .. } => assert_eq!(stragglers, vec!["node3"]),
+            other => panic!("expected pruned, got {:?}", other),
+        }
+        assert!(api.configmap(&cm_name).is_none());
+        assert!(api
+            .event_reasons()
+            .contains(&"CleanupQuorumReached".to_string()));
+
+        // The straggler cleans up on its own, untracked
+        assert!(dir3.exists());
+        assert_eq!(node3.process_pending_cleanups().await.unwrap(), 0);
+        assert_eq!(node3.process_stragglers().await.unwrap(), 1);
+        assert!(!dir3.exists());
+        assert!(!dir1.exists() && !dir2.exists());
+        assert_eq!(node3.process_stragglers().await.unwrap(), 0);
+        assert!(stragglers::pending(&client, "default", "node3")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_timeout_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-timeout");
+        let cm_name = configmap_name(&volume_id);
+        for node in ["node1", "node2"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let (node1, _dir1) = node_with_volume(&api, "node1", &volume_id);
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+
+        // node2 is wedged: still there, never reports
+        let controller = CleanupController::new(client.clone(), "default".into())
+            .with_cleanup_timeout(Some(Duration::from_secs(3600)));
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&1));
+        assert!(api.configmap(&cm_name).is_some());
+
+        let controller = CleanupController::new(client.clone(), "default".into())
+            .with_cleanup_timeout(Some(Duration::ZERO));
+        let outcome = controller.prune_volume(&volume_id).await.unwrap().unwrap();
+        match outcome {
+            PruneOutcome::Pruned {
+                stragglers,
+                timed_out,
+                ..
+            } => {
+                assert!(timed_out);
+                assert_eq!(stragglers, vec!["node2"]);
+            }
+            other => panic!("expected pruned, got {:?}", other),
+        }
+        assert!(api.configmap(&cm_name).is_none());
+        assert!(api.event_reasons().contains(&"CleanupTimedOut".to_string()));
+        assert_eq!(
+            stragglers::pending(&client, "default", "node2")
+                .await
+                .unwrap(),
+            std::collections::BTreeSet::from([volume_id])
+        );
+    }
+
+    #[test]
+    fn test_find_orphan_candidates() {
+        let base = temp_base("orphan-candidates");
+        let old = volume::generate_volume_id("pvc-old");
+        let other = volume::generate_volume_id("pvc-other");
+        for name in [old.as_str(), "not-a-volume", ".quarantine", "shared/cache"] {
+            std::fs::create_dir_all(base.join(name)).unwrap();
+        }
+        std::fs::write(base.join(&other), b"a file").unwrap();
+        std::os::unix::fs::symlink("/", base.join(volume::generate_volume_id("pvc-link"))).unwrap();
+
+        let now = std::time::SystemTime::now();
+        let hour = Duration::from_secs(3600);
+        // Too recent
+        assert!(find_orphan_candidates(&base, hour, now).unwrap().is_empty());
+        assert_eq!(
+            find_orphan_candidates(&base, hour, now + 2 * hour).unwrap(),
+            vec![old]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_orphans_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let tracked = volume::generate_volume_id("pvc-tracked");
+        let orphan = volume::generate_volume_id("pvc-orphan");
+        let unregistered = volume::generate_volume_id("pvc-unregistered");
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&tracked),
+        )
+        .await
+        .unwrap();
+        let base = temp_base("orphans");
+        for volume_id in [&tracked, &orphan, &unregistered] {
+            std::fs::create_dir_all(volume::volume_path(&base, volume_id)).unwrap();
+        }
+        std::fs::create_dir_all(base.join("unrelated")).unwrap();
+        // Published during an API outage
+        pending_registration::record(
+            &base,
+            &pending_registration::PendingRegistration {
+                volume_id: unregistered.clone(),
+                shared_name: None,
+                pvc: None,
+                labels: BTreeMap::new(),
+                capacity_bytes: 0,
+                base_path: None,
+                eviction_priority: None,
+            },
+        )
+        .unwrap();
+
+        let node = CleanupNode::new(
+            client.clone(),
+            "default".into(),
+            "node1".into(),
+            base.clone(),
+        );
+        assert_eq!(
+            node.reconcile_orphans(Duration::from_secs(3600))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(node.reconcile_orphans(Duration::ZERO).await.unwrap(), 1);
+        assert!(!volume::volume_path(&base, &orphan).exists());
+        for volume_id in [&tracked, &unregistered] {
+            assert!(volume::volume_path(&base, volume_id).exists());
+        }
+        assert!(base.join("unrelated").exists());
+        assert_eq!(node.reconcile_orphans(Duration::ZERO).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_straggler_tracked_again_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-straggler");
+        let (node1, dir1) = node_with_volume(&api, "node1", &volume_id);
+        stragglers::record(&client, "default", &volume_id, &["node1".to_string()])
+            .await
+            .unwrap();
+        // Published again since the quorum prune: left alone
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(node1.process_stragglers().await.unwrap(), 1);
+        assert!(dir1.exists());
+        assert!(stragglers::pending(&client, "default", "node1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_journal_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-journal");
+        let cm_name = configmap_name(&volume_id);
+        for node in ["node1", "node2"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let (node1, dir1) = node_with_volume(&api, "node1", &volume_id);
+        let node1 = node1.with_cleanup_journal(true);
+        let (node2, dir2) = node_with_volume(&api, "node2", &volume_id);
+        let node2 = node2.with_cleanup_journal(true);
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+
+        // node1 deleted its directory and journaled it, then crashed before
+        // reporting; a leftover directory shows the cleanup isn't redone
+        std::fs::remove_dir_all(&dir1).unwrap();
+        cleanup_journal::record(
+            &node1.base_path,
+            &cleanup_journal::Completion::new(&volume_id, true),
+        )
+        .unwrap();
+        std::fs::create_dir_all(&dir1).unwrap();
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert!(status.nodes_completed.is_empty());
+
+        // The restarted node reports the journaled outcome first
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 0);
+        assert!(dir1.exists());
+        assert!(cleanup_journal::unacked(&node1.base_path)
+            .unwrap()
+            .is_empty());
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.nodes_completed, vec!["node1"]);
+
+        // A normal cleanup is journaled, then acked once reported
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir2.exists());
+        assert!(cleanup_journal::unacked(&node2.base_path)
+            .unwrap()
+            .is_empty());
+
+        let controller = CleanupController::new(client.clone(), "default".into());
+        assert_eq!(
+            controller.process_cleanups().await.unwrap().pruned,
+            vec![volume_id.clone()]
+        );
+
+        // Reported after the ConfigMap was pruned: acked without error
+        cleanup_journal::record(
+            &node2.base_path,
+            &cleanup_journal::Completion::new(&volume_id, true),
+        )
+        .unwrap();
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 0);
+        assert!(cleanup_journal::unacked(&node2.base_path)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_retry_budget_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volumes: Vec<String> = (0..3)
+            .map(|i| volume::generate_volume_id(&format!("pvc-budget-{}", i)))
+            .collect();
+        let base = temp_base("retry-budget");
+        for volume_id in &volumes {
+            register_node_publish(
+                &client,
+                "default",
+                "node1",
+                &PendingRegistration::new(volume_id),
+            )
+            .await
+            .unwrap();
+            std::fs::create_dir_all(volume::volume_path(&base, volume_id)).unwrap();
+            mark_volume_for_cleanup(&client, "default", volume_id)
+                .await
+                .unwrap();
+        }
+        let node = CleanupNode::new(
+            client.clone(),
+            "default".into(),
+            "node1".into(),
+            base.clone(),
+        )
+        .with_retry_budget(2);
+        let completed = |api: &FakeApiServer| {
+            volumes
+                .iter()
+                .filter(|id| {
+                    let cm = api.configmap(&configmap_name(id)).unwrap();
+                    VolumeStatus::from_configmap(&cm)
+                        .unwrap()
+                        .nodes_completed
+                        .contains(&"node1".to_string())
+                })
+                .count()
+        };
+
+        // Contention: the first report burns the budget, the rest is deferred
+        api.inject_conflicts(100);
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert_eq!(completed(&api), 0);
+
+        // Next pass, with a fresh budget
+        api.inject_conflicts(0);
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 3);
+        assert_eq!(completed(&api), 3);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_footprint_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2", "node3"]);
+        let client = api.client();
+        let controller = CleanupController::new(client.clone(), "default".into());
+        assert_eq!(
+            controller.process_cleanups().await.unwrap().footprint,
+            Footprint::default()
+        );
+
+        let (kept, deleted) = (
+            volume::generate_volume_id("pvc-footprint-kept"),
+            volume::generate_volume_id("pvc-footprint-deleted"),
+        );
+        for (volume_id, node) in [(&kept, "node1"), (&kept, "node2"), (&deleted, "node3")] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        mark_volume_for_cleanup(&client, "default", &deleted)
+            .await
+            .unwrap();
+
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(
+            summary.footprint,
+            Footprint {
+                volumes: 2,
+                active: 1,
+                cleanup: 1,
+                nodes: 3,
+            }
+        );
+        // Active volumes are counted but not processed
+        assert_eq!(summary.pending.keys().collect::<Vec<_>>(), vec![&deleted]);
+
+        // Recomputed each pass: the deleted volume's node drops out once pruned
+        with_volume_configmap(&client, "default", &deleted, false, "test", |s| {
+            s.mark_node_completed("node3")
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            controller.process_cleanups().await.unwrap().pruned,
+            vec![deleted]
+        );
+        let footprint = controller.process_cleanups().await.unwrap().footprint;
+        assert_eq!(
+            (footprint.volumes, footprint.cleanup, footprint.nodes),
+            (1, 0, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_decommissioned_node_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-decommission");
+        let pvc = PvcRef {
+            namespace: "team-a".to_string(),
+            name: "build-cache".to_string(),
+        };
+
+        for node in ["node1", "node2"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration {
+                    pvc: Some(pvc.clone()),
+                    ..PendingRegistration::new(&volume_id)
+                },
+            )
+            .await
+            .unwrap();
+        }
+        let cm = api.configmap(&configmap_name(&volume_id)).unwrap();
+        assert_eq!(VolumeStatus::from_configmap(&cm).unwrap().pvc, Some(pvc));
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+
+        let (node1, _dir) = node_with_volume(&api, "node1", &volume_id);
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+
+        // node2 is scaled away before it cleans up
+        api.set_nodes(&["node1"]);
+        let controller = CleanupController::new(client, "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+
+        assert_eq!(summary.pruned, vec![volume_id.clone()]);
+        assert_eq!(
+            summary.decommissioned.get(&volume_id),
+            Some(&vec!["node2".to_string()])
+        );
+        assert!(api.configmap(&configmap_name(&volume_id)).is_none());
+        assert!(api
+            .event_reasons()
+            .contains(&"NodeDecommissioned".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_absent_directory_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-absent");
+        let cm_name = configmap_name(&volume_id);
+
+        for node in ["node1", "node2"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let (node1, _dir1) = node_with_volume(&api, "node1", &volume_id);
+        let (node2, dir2) = node_with_volume(&api, "node2", &volume_id);
+
+        // Directories present: nothing reported
+        assert_eq!(node2.report_absent_directories().await.unwrap(), 0);
+
+        // Deleted by hand on node2
+        std::fs::remove_dir_all(&dir2).unwrap();
+        assert_eq!(node1.report_absent_directories().await.unwrap(), 0);
+        assert_eq!(node2.report_absent_directories().await.unwrap(), 1);
+        // Already reported
+        assert_eq!(node2.report_absent_directories().await.unwrap(), 0);
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.nodes_directory_absent, vec!["node2"]);
+
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+        // Only node1 still has a directory to delete
+        assert_eq!(summary.pending.get(&volume_id), Some(&1));
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert_eq!(status.nodes_completed, vec!["node2"]);
+
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id.clone()]);
+        assert!(api
+            .event_reasons()
+            .contains(&"NodeDirectoryAbsent".to_string()));
+    }
+
+    /// Collects the JSON log lines written while it is the default subscriber
+    #[derive(Clone, Default)]
+    struct LogCapture(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogCapture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogCapture {
+        fn lines(&self) -> Vec<serde_json::Value> {
+            String::from_utf8(self.0.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_audit_log_with_api() {
+        let capture = LogCapture::default();
+        let writer = capture.clone();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::FmtSubscriber::builder()
+                .json()
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-audit");
+
+        emit_event(
+            &client, "default", &volume_id, None, "Tested", "Hello", "Normal",
+        )
+        .await;
+        // Also when the Kubernetes event isn't emitted
+        EVENTS_DISABLED.set(true);
+        emit_event(
+            &client, "default", &volume_id, None, "Muted", "Quiet", "Warning",
+        )
+        .await;
+        EVENTS_DISABLED.set(false);
+
+        assert_eq!(api.event_reasons(), vec!["Tested"]);
+        let audit: Vec<_> = capture
+            .lines()
+            .into_iter()
+            .filter(|line| line["target"] == AUDIT_TARGET)
+            .collect();
+        assert_eq!(audit.len(), 2);
+        let fields = &audit[1]["fields"];
+        assert_eq!(fields["volume_id"], volume_id.as_str());
+        assert_eq!(fields["reason"], "Muted");
+        assert_eq!(fields["message"], "Quiet");
+        assert_eq!(fields["event_type"], "Warning");
+        assert_eq!(fields["node"], "");
+    }
+
+    #[tokio::test]
+    async fn test_repeated_events_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-repeated-events");
+        for target in ["/pods/a/mount", "/pods/b/mount"] {
+            emit_event(
+                &client,
+                "default",
+                &volume_id,
+                None,
+                "VolumePublished",
+                target,
+                "Normal",
+            )
+            .await;
+        }
+        let events = api.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].count, Some(2));
+        assert_eq!(events[0].message.as_deref(), Some("/pods/b/mount"));
+        assert!(events[0].last_timestamp >= events[0].first_timestamp);
+
+        // Another reason gets its own event
+        emit_event(
+            &client,
+            "default",
+            &volume_id,
+            None,
+            "VolumeUnpublished",
+            "",
+            "Normal",
+        )
+        .await;
+        assert_eq!(api.events().len(), 2);
+
+        // A vanished event is created again
+        let api = FakeApiServer::new(&["node1"]);
+        emit_event(
+            &api.client(),
+            "default",
+            &volume_id,
+            None,
+            "VolumePublished",
+            "/pods/c/mount",
+            "Normal",
+        )
+        .await;
+        let events = api.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].count, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_no_events_with_api() {
+        EVENTS_DISABLED.set(true);
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-no-events");
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        let (node1, _dir) = node_with_volume(&api, "node1", &volume_id);
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id]);
+
+        assert!(api.event_reasons().is_empty());
+        EVENTS_DISABLED.set(false);
+    }
+
+    #[tokio::test]
+    async fn test_immutable_stable_configmaps_with_api() {
+        IMMUTABLE_STABLE.set(true);
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-immutable");
+        let cm_name = configmap_name(&volume_id);
+        let register = |node: &'static str| {
+            let (client, volume_id) = (client.clone(), volume_id.clone());
+            async move {
+                register_node_publish(
+                    &client,
+                    "default",
+                    node,
+                    &PendingRegistration::new(&volume_id),
+                )
+                .await
+                .unwrap()
+            }
+        };
+
+        // Mount options come first; no node yet, so not stable
+        settle_mount_options(&client, "default", &volume_id, &["noatime".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(api.configmap(&cm_name).unwrap().immutable, None);
+        register("node1").await;
+        let stable = api.configmap(&cm_name).unwrap();
+        assert_eq!(stable.immutable, Some(true));
+        let created_at = VolumeStatus::from_configmap(&stable).unwrap().created_at;
+
+        // Nothing to change: left alone
+        register("node1").await;
+        assert_eq!(
+            api.configmap(&cm_name).unwrap().metadata.resource_version,
+            stable.metadata.resource_version
+        );
+
+        // The API server refuses data changes
+        let configmaps: Api<ConfigMap> = Api::namespaced(client.clone(), "default");
+        let mut changed = stable.clone();
+        changed.data = Some(BTreeMap::new());
+        let err = configmaps
+            .replace(&cm_name, &PostParams::default(), &changed)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, kube::Error::Api(ref e) if e.code == 422),
+            "{:?}",
+            err
+        );
+
+        // Another node: recreated, still immutable, nothing lost
+        register("node2").await;
+        let recreated = api.configmap(&cm_name).unwrap();
+        assert_eq!(recreated.immutable, Some(true));
+        assert_ne!(recreated.metadata.uid, stable.metadata.uid);
+        let status = VolumeStatus::from_configmap(&recreated).unwrap();
+        assert_eq!(status.nodes_with_volume, vec!["node1", "node2"]);
+        assert_eq!(status.created_at, created_at);
+        assert_eq!(status.mount_options, Some(vec!["noatime".to_string()]));
+
+        // Cleanup makes it mutable again, and goes through as usual
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let cleanup = api.configmap(&cm_name).unwrap();
+        assert_eq!(cleanup.immutable, None);
+        assert_eq!(cm_label(&cleanup), "cleanup");
+        let status = VolumeStatus::from_configmap(&cleanup).unwrap();
+        assert_eq!(status.nodes_with_volume, vec!["node1", "node2"]);
+        assert_eq!(status.created_at, created_at);
+
+        let (node1, _dir1) = node_with_volume(&api, "node1", &volume_id);
+        let (node2, _dir2) = node_with_volume(&api, "node2", &volume_id);
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 1);
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id]);
+        IMMUTABLE_STABLE.set(false);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_updates_with_file_backend() {
+        let dir = temp_base("coordination-dir");
+        coordination::tests::COORDINATION_DIR.set(Some(dir.clone()));
+        let nodes: Vec<String> = (0..16).map(|i| format!("node{}", i)).collect();
+        let node_names: Vec<&str> = nodes.iter().map(String::as_str).collect();
+        let api = FakeApiServer::new(&node_names);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-file-backend");
+        let cm_name = configmap_name(&volume_id);
+
+        // All publishes race on the same file; every one must be recorded.
+        // Spawned on this thread, which has the store set.
+        let mut publishes = tokio::task::JoinSet::new();
+        for node in &nodes {
+            let (client, node, volume_id) = (client.clone(), node.clone(), volume_id.clone());
+            publishes.spawn(async move {
+                register_node_publish(
+                    &client,
+                    "default",
+                    &node,
+                    &PendingRegistration::new(&volume_id),
+                )
+                .await
+            });
+        }
+        while let Some(result) = publishes.join_next().await {
+            result.unwrap().unwrap();
+        }
+        assert!(dir.join(format!("{}.json", cm_name)).exists());
+        assert!(api.configmap(&cm_name).is_none());
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let statuses = controller.volume_statuses().await.unwrap();
+        let mut registered = statuses[0].nodes_with_volume.clone();
+        registered.sort();
+        let mut expected = nodes.clone();
+        expected.sort();
+        assert_eq!(registered, expected);
+
+        // Completions race the same way before the ConfigMap is pruned
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let mut cleanups = tokio::task::JoinSet::new();
+        for node in &nodes {
+            let (cleanup_node, _dir) = node_with_volume(&api, node, &volume_id);
+            cleanups.spawn(async move { cleanup_node.process_pending_cleanups().await });
+        }
+        while let Some(result) = cleanups.join_next().await {
+            assert_eq!(result.unwrap().unwrap(), 1);
+        }
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id]);
+        assert!(!dir.join(format!("{}.json", cm_name)).exists());
+
+        coordination::tests::COORDINATION_DIR.set(None);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_recovers_partial_decommission_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2", "node3"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-partial");
+        let cm_name = configmap_name(&volume_id);
+
+        for node in ["node1", "node2", "node3"] {
+            register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        // An earlier controller run recorded node2 before restarting
+        with_volume_configmap(&client, "default", &volume_id, false, "test", |s| {
+            s.mark_node_decommissioned("node2")
+        })
+        .await
+        .unwrap();
+        api.set_nodes(&["node1"]);
+
+        // The listed ConfigMap goes stale: node1 reports after the list
+        let configmaps: Api<ConfigMap> = Api::namespaced(client.clone(), "default");
+        let stale = configmaps.get(&cm_name).await.unwrap();
+        let report = NodeCleanupReport {
+            success: true,
+            reclaimed_bytes: None,
+        };
+        mark_node_cleanup_complete(&client, "default", &volume_id, 0, "node1", report, None)
+            .await
+            .unwrap();
+
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let existing: HashSet<String> = ["node1".to_string()].into();
+        let outcome = controller
+            .evaluate_cleanup(&stale, &existing)
+            .await
+            .unwrap()
+            .unwrap();
+        match outcome {
+            PruneOutcome::Pruned {
+                nodes_completed,
+                nodes_decommissioned,
+                newly_decommissioned,
+                ..
+            } => {
+                // node1's report survived the stale write
+                assert_eq!(nodes_completed, vec!["node1"]);
+                assert_eq!(nodes_decommissioned, vec!["node2", "node3"]);
+                assert_eq!(newly_decommissioned, vec!["node3"]);
+            }
+            other => panic!("expected pruned, got {:?}", other),
+        }
+        assert!(api.configmap(&cm_name).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_loop_signals_sync_with_api() {
+        for trigger in [CleanupTrigger::Watch, CleanupTrigger::Poll] {
+            let api = FakeApiServer::new(&["node1"]);
+            let (synced_tx, mut synced) = watch::channel(false);
+            let node = CleanupNode::new(
+                api.client(),
+                "default".into(),
+                "node1".into(),
+                temp_base("sync"),
+            )
+            .with_sync_signal(synced_tx)
+            .with_trigger(trigger);
+
+            let cleanup_loop = tokio::spawn(node.run_cleanup_loop(Duration::from_secs(3600)));
+            tokio::time::timeout(Duration::from_secs(5), synced.wait_for(|s| *s))
+                .await
+                .unwrap()
+                .unwrap();
+            cleanup_loop.abort();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_watch_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let requested = volume::generate_volume_id("pvc-watch-requested");
+        let later = volume::generate_volume_id("pvc-watch-later");
+        for volume_id in [&requested, &later] {
+            register_node_publish(
+                &client,
+                "default",
+                "node1",
+                &PendingRegistration::new(volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let base = temp_base("watch");
+        let requested_dir = volume::volume_path(&base, &requested);
+        let later_dir = volume::volume_path(&base, &later);
+        for dir in [&requested_dir, &later_dir] {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        // Requested before the watch started: found by its initial list
+        mark_volume_for_cleanup(&client, "default", &requested)
+            .await
+            .unwrap();
+
+        let (synced_tx, mut synced) = watch::channel(false);
+        let node = CleanupNode::new(client.clone(), "default".into(), "node1".into(), base)
+            .with_sync_signal(synced_tx)
+            .with_trigger(CleanupTrigger::Watch);
+        // Far apart polls, so only the watch can pick up the later request
+        let cleanup_loop = tokio::spawn(node.run_cleanup_loop(Duration::from_secs(3600)));
+        tokio::time::timeout(Duration::from_secs(5), synced.wait_for(|s| *s))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!requested_dir.exists());
+        assert!(later_dir.exists());
+
+        mark_volume_for_cleanup(&client, "default", &later)
+            .await
+            .unwrap();
+        let completed = || {
+            api.configmap(&configmap_name(&later))
+                .as_ref()
+                .and_then(VolumeStatus::from_configmap)
+                .is_some_and(|s| s.nodes_completed.contains(&"node1".to_string()))
+        };
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !completed() {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("watch did not pick up the cleanup request");
+        assert!(!later_dir.exists());
+        cleanup_loop.abort();
+    }
+
+    #[tokio::test]
+    async fn test_mount_options_recorded_then_reused_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-mount-options");
+        let noatime = vec!["noatime".to_string()];
+
+        // First publish records its options
+        let options = settle_mount_options(&client, "default", &volume_id, &noatime)
+            .await
+            .unwrap();
+        assert_eq!(options, noatime);
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+
+        // A later publish with other options gets the recorded ones
+        let options = settle_mount_options(&client, "default", &volume_id, &["nodev".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(options, noatime);
+        // No options is a choice too, and is kept
+        let other_id = volume::generate_volume_id("pvc-no-mount-options");
+        assert!(settle_mount_options(&client, "default", &other_id, &[])
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(
+            settle_mount_options(&client, "default", &other_id, &noatime)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        let cm = api.configmap(&configmap_name(&volume_id)).unwrap();
+        let status = VolumeStatus::from_configmap(&cm).unwrap();
+        assert_eq!(status.mount_options, Some(noatime));
+        assert_eq!(status.nodes_with_volume, vec!["node1"]);
+    }
+
+    #[tokio::test]
+    async fn test_shared_cache_released_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let shared_id = volume::shared_volume_id("maven");
+        let vol_a = volume::generate_volume_id("pvc-a");
+        let vol_b = volume::generate_volume_id("pvc-b");
+
+        for vol in [&vol_a, &vol_b] {
+            register_node_publish(
+                &client,
+                "default",
+                "node1",
+                &PendingRegistration {
+                    shared_name: Some("maven".to_string()),
+                    ..PendingRegistration::new(vol)
+                },
+            )
+            .await
+            .unwrap();
+        }
+        let shared = api.configmap(&configmap_name(&shared_id)).unwrap();
+        assert_eq!(
+            VolumeStatus::from_configmap(&shared)
+                .unwrap()
+                .references
+                .len(),
+            2
+        );
+
+        mark_volume_for_cleanup(&client, "default", &vol_a)
+            .await
+            .unwrap();
+        let shared = api.configmap(&configmap_name(&shared_id)).unwrap();
+        assert_eq!(cm_label(&shared), "active");
+
+        mark_volume_for_cleanup(&client, "default", &vol_b)
+            .await
+            .unwrap();
+        let shared = api.configmap(&configmap_name(&shared_id)).unwrap();
+        assert_eq!(cm_label(&shared), "cleanup");
+    }
+
+    #[tokio::test]
+    async fn test_verify_pv_deleted_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-guarded");
+        let pv = |phase: &str| PersistentVolume {
+            metadata: kube::api::ObjectMeta {
+                name: Some("pvc-guarded".to_string()),
+                ..Default::default()
+            },
+            spec: Some(k8s_openapi::api::core::v1::PersistentVolumeSpec {
+                csi: Some(k8s_openapi::api::core::v1::CSIPersistentVolumeSource {
+                    driver: "node-local-cache.csi.io".to_string(),
+                    volume_handle: volume_id.clone(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            status: Some(k8s_openapi::api::core::v1::PersistentVolumeStatus {
+                phase: Some(phase.to_string()),
+                ..Default::default()
+            }),
+        };
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        let (node, dir) = node_with_volume(&api, "node1", &volume_id);
+        let node = node.with_verify_pv_deleted(true);
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+
+        // Spurious DeleteVolume: the PV is still bound
+        api.add_persistent_volume(pv("Bound"));
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 0);
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 0);
+        assert!(dir.exists());
+        let blocked = api
+            .event_reasons()
+            .into_iter()
+            .filter(|r| r == "CleanupBlockedPVExists")
+            .count();
+        assert_eq!(blocked, 1);
+
+        // Released from its claim: what the provisioner deletes
+        api.add_persistent_volume(pv("Released"));
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir.exists());
+
+        // No PV at all
+        let other = volume::generate_volume_id("pvc-unguarded");
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&other),
+        )
+        .await
+        .unwrap();
+        let (node, dir) = node_with_volume(&api, "node1", &other);
+        let node = node.with_verify_pv_deleted(true);
+        api.remove_persistent_volume("pvc-guarded");
+        mark_volume_for_cleanup(&client, "default", &other)
+            .await
+            .unwrap();
+        assert_eq!(node.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir.exists());
+    }
+
+    #[tokio::test]
+    async fn test_set_volume_owner_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-owned");
+        let cm_name = configmap_name(&volume_id);
+
+        let pv = |handle: &str| PersistentVolume {
+            metadata: kube::api::ObjectMeta {
+                name: Some("pvc-owned".to_string()),
+                uid: Some("uid-pv".to_string()),
+                ..Default::default()
+            },
+            spec: Some(k8s_openapi::api::core::v1::PersistentVolumeSpec {
+                csi: Some(k8s_openapi::api::core::v1::CSIPersistentVolumeSource {
+                    driver: "node-local-cache.csi.io".to_string(),
+                    volume_handle: handle.to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        // PV doesn't exist (yet)
+        assert!(
+            !set_volume_owner(&client, "default", &volume_id, "pvc-owned")
+                .await
+                .unwrap()
+        );
+
+        // PV of another volume with the same name
+        api.add_persistent_volume(pv("nlc-other"));
+        assert!(
+            !set_volume_owner(&client, "default", &volume_id, "pvc-owned")
+                .await
+                .unwrap()
+        );
+
+        api.add_persistent_volume(pv(&volume_id));
+        assert!(
+            set_volume_owner(&client, "default", &volume_id, "pvc-owned")
+                .await
+                .unwrap()
+        );
+        let owners = api.configmap(&cm_name).unwrap().metadata.owner_references;
+        assert_eq!(owners.unwrap()[0].uid, "uid-pv");
+        // Already owned
+        assert!(
+            !set_volume_owner(&client, "default", &volume_id, "pvc-owned")
+                .await
+                .unwrap()
+        );
+
+        // Republishing keeps the owner, cleanup drops it
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        assert!(api
+            .configmap(&cm_name)
+            .unwrap()
+            .metadata
+            .owner_references
+            .is_some());
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let owners = api.configmap(&cm_name).unwrap().metadata.owner_references;
+        assert!(owners.is_none_or(|o| o.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_mark_for_cleanup_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-retried");
+        let cm_name = configmap_name(&volume_id);
+        let shared_id = volume::shared_volume_id("npm");
+
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration {
+                shared_name: Some("npm".to_string()),
+                ..PendingRegistration::new(&volume_id)
+            },
+        )
+        .await
+        .unwrap();
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let cm = api.configmap(&cm_name).unwrap();
+        let shared = api.configmap(&configmap_name(&shared_id)).unwrap();
+        assert_eq!(cm_label(&cm), "cleanup");
+        assert_eq!(cm_label(&shared), "cleanup");
+
+        // Provisioner retries DeleteVolume
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let requested = api
+            .event_reasons()
+            .iter()
+            .filter(|r| *r == "CleanupRequested")
+            .count();
+        // One for the volume, one for the shared cache
+        assert_eq!(requested, 2);
+        // Neither ConfigMap was rewritten
+        assert_eq!(
+            api.configmap(&cm_name).unwrap().metadata.resource_version,
+            cm.metadata.resource_version
+        );
+        assert_eq!(
+            api.configmap(&configmap_name(&shared_id))
+                .unwrap()
+                .metadata
+                .resource_version,
+            shared.metadata.resource_version
+        );
+    }
 
-        let mut pruned = 0;
+    #[tokio::test]
+    async fn test_cleanup_waits_for_concurrent_publish_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let vol_a = volume::generate_volume_id("pvc-old");
+        let vol_b = volume::generate_volume_id("pvc-new");
+        let shared_id = volume::shared_volume_id("gradle");
 
-        for cm in cms.items {
-            let cm_name = match cm.metadata.name.as_ref() {
-                Some(n) => n,
-                None => continue,
-            };
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration {
+                shared_name: Some("gradle".to_string()),
+                ..PendingRegistration::new(&vol_a)
+            },
+        )
+        .await
+        .unwrap();
+        mark_volume_for_cleanup(&client, "default", &vol_a)
+            .await
+            .unwrap();
 
-            let status = match VolumeStatus::from_configmap(&cm) {
-                Some(s) => s,
-                None => continue,
-            };
+        let locks = VolumeLocks::new();
+        let (node, dir) = node_with_volume(&api, "node1", &shared_id);
+        let node = std::sync::Arc::new(node.with_volume_locks(locks.clone()));
 
-            // First, check for decommissioned nodes
-            if !status.pending_nodes().is_empty() {
-                if let Err(e) = self
-                    .mark_decommissioned_nodes(&status.volume_id, &status, &existing_nodes)
-                    .await
-                {
-                    warn!(
-                        volume_id = %status.volume_id,
-                        error = %e,
-                        "Failed to mark decommissioned nodes"
-                    );
-                }
-            }
+        // A publish of a new volume using the same shared cache is in flight
+        let publish_guard = locks.lock(&shared_id).await;
+        let cleanup = tokio::spawn({
+            let node = node.clone();
+            async move { node.process_pending_cleanups().await.unwrap() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!cleanup.is_finished());
+        assert!(dir.exists());
 
-            // Re-fetch to get updated status after potential decommissioning
-            let current_status = match configmaps.get(cm_name).await {
-                Ok(updated_cm) => VolumeStatus::from_configmap(&updated_cm).unwrap_or(status),
-                Err(_) => continue, // ConfigMap may have been deleted
-            };
+        // The publish takes the shared cache back into use before releasing
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration {
+                shared_name: Some("gradle".to_string()),
+                ..PendingRegistration::new(&vol_b)
+            },
+        )
+        .await
+        .unwrap();
+        drop(publish_guard);
 
-            // Prune if complete
-            if current_status.is_cleanup_complete() {
-                // Emit event before deleting the ConfigMap
-                emit_event(
-                    &self.client,
-                    &self.namespace,
-                    &current_status.volume_id,
-                    "CleanupComplete",
-                    &format!(
-                        "All cleanup complete. Completed: {:?}, Failed: {:?}, Decommissioned: {:?}",
-                        current_status.nodes_completed,
-                        current_status.nodes_failed,
-                        current_status.nodes_decommissioned
-                    ),
-                    "Normal",
-                )
-                .await;
+        // Only the old volume's own tracking entry; the shared cache is kept
+        assert_eq!(cleanup.await.unwrap(), 1);
+        assert!(dir.join("cached").exists());
+    }
 
-                match configmaps.delete(cm_name, &Default::default()).await {
-                    Ok(_) => {
-                        info!(
-                            configmap = %cm_name,
-                            volume_id = %current_status.volume_id,
-                            nodes_with_volume = ?current_status.nodes_with_volume,
-                            nodes_completed = ?current_status.nodes_completed,
-                            nodes_failed = ?current_status.nodes_failed,
-                            nodes_decommissioned = ?current_status.nodes_decommissioned,
-                            "Pruned completed cleanup ConfigMap"
-                        );
-                        pruned += 1;
-                    }
-                    Err(e) => {
-                        warn!(configmap = %cm_name, error = %e, "Failed to prune ConfigMap");
-                    }
-                }
-            }
-        }
+    #[tokio::test]
+    async fn test_mark_unknown_volume_for_cleanup_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let volume_id = volume::generate_volume_id("pvc-never-published");
 
-        Ok(pruned)
+        // No ConfigMap (never published): nothing to clean, not an error
+        let tracked = mark_volume_for_cleanup(&api.client(), "default", &volume_id)
+            .await
+            .unwrap();
+        assert!(!tracked);
+        assert!(api.configmap(&configmap_name(&volume_id)).is_none());
     }
-}
 
-/// Run the controller cleanup processing loop
-/// Checks for decommissioned nodes and prunes completed ConfigMaps
-pub async fn run_controller_cleanup_loop(client: Client, namespace: String, interval: Duration) {
-    info!(
-        interval_secs = interval.as_secs(),
-        "Starting controller cleanup processor"
-    );
+    #[tokio::test]
+    async fn test_sweep_on_missing_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        // Published on node1, which failed to register it
+        let volume_id = volume::generate_volume_id("pvc-unregistered");
+        let cm_name = configmap_name(&volume_id);
+        let (node1, dir1) = node_with_volume(&api, "node1", &volume_id);
+        let base2 = temp_base("sweep-node2");
+        let node2 = CleanupNode::new(client.clone(), "default".into(), "node2".into(), base2);
 
-    let controller = CleanupController::new(client, namespace);
+        // Without the option, nothing happens
+        let controller = CleanupController::new(client.clone(), "default".into());
+        controller.create_cleanup_request(&volume_id).await.unwrap();
+        assert!(api.configmap(&cm_name).is_none());
 
-    loop {
-        tokio::time::sleep(interval).await;
+        let controller = controller.with_sweep_on_missing(true);
+        controller.create_cleanup_request(&volume_id).await.unwrap();
+        let status = VolumeStatus::from_configmap(&api.configmap(&cm_name).unwrap()).unwrap();
+        assert!(status.sweep);
+        assert!(status.cleanup_requested_at.is_some());
+        assert!(status.nodes_with_volume.is_empty());
+        assert!(api
+            .event_reasons()
+            .contains(&"CleanupSweepRequested".to_string()));
 
-        match controller.process_cleanups().await {
-            Ok(count) if count > 0 => {
-                info!(count = count, "Pruned cleanup ConfigMaps");
-            }
-            Ok(_) => {
-                debug!("No cleanup ConfigMaps to prune");
-            }
-            Err(e) => {
-                error!(error = %e, "Error processing cleanups");
-            }
-        }
+        // Not pruned before the nodes checked
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&2));
+
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir1.exists());
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 0);
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pending.get(&volume_id), Some(&1));
+
+        // node2 never had it
+        assert_eq!(node2.process_pending_cleanups().await.unwrap(), 1);
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id]);
+        assert!(api.configmap(&cm_name).is_none());
     }
-}
 
-/// Node-side cleanup operations
-pub struct CleanupNode {
-    client: Client,
-    namespace: String,
-    node_name: String,
-    base_path: std::path::PathBuf,
-}
+    #[test]
+    fn test_configmap_name() {
+        let id = "nlc-550e8400-e29b-41d4-a716-446655440000";
+        assert_eq!(
+            configmap_name(id),
+            "nlc-vol-550e8400-e29b-41d4-a716-446655440000"
+        );
+        assert_eq!(
+            legacy_configmap_name(id).as_deref(),
+            Some("nlc-vol-nlc-550e8400-e29b-41d4-a716-446655440000")
+        );
+        assert_eq!(configmap_name("shared-maven"), "nlc-vol-shared-maven");
+        assert_eq!(legacy_configmap_name("shared-maven"), None);
 
-impl CleanupNode {
-    pub fn new(
-        client: Client,
-        namespace: String,
-        node_name: String,
-        base_path: std::path::PathBuf,
-    ) -> Self {
-        Self {
-            client,
-            namespace,
-            node_name,
-            base_path,
-        }
+        // Over-long IDs are hashed, deterministically
+        let long_id = format!("shared-{}", "a".repeat(250));
+        let name = configmap_name(&long_id);
+        assert!(name.len() <= MAX_NAME_LEN);
+        assert!(name.starts_with("nlc-vol-h-"));
+        assert_eq!(name, configmap_name(&long_id));
+        assert_ne!(name, configmap_name(&format!("shared-{}", "b".repeat(250))));
+
+        // Right at the limit isn't hashed
+        let max_id = format!("shared-{}", "a".repeat(MAX_NAME_LEN - 15));
+        assert_eq!(configmap_name(&max_id).len(), MAX_NAME_LEN);
+        assert!(!configmap_name(&max_id).starts_with("nlc-vol-h-"));
     }
 
-    /// Process all pending cleanup requests for this node
-    pub async fn process_pending_cleanups(&self) -> Result<usize, kube::Error> {
-        let configmaps: Api<ConfigMap> = Api::namespaced(self.client.clone(), &self.namespace);
-        let lp = ListParams::default().labels(&format!("{}=cleanup", VOLUME_LABEL));
+    #[tokio::test]
+    async fn test_legacy_configmap_name_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-legacy");
+        let legacy_name = legacy_configmap_name(&volume_id).unwrap();
 
-        let cms = configmaps.list(&lp).await?;
-        let mut processed = 0;
+        // A ConfigMap written by an older driver version
+        let mut status = VolumeStatus::new(&volume_id);
+        status.add_node("node1");
+        let configmaps: Api<ConfigMap> = Api::namespaced(client.clone(), "default");
+        let cm = ConfigMap {
+            metadata: kube::api::ObjectMeta {
+                name: Some(legacy_name.clone()),
+                labels: Some(BTreeMap::from([(
+                    VOLUME_LABEL.to_string(),
+                    "active".to_string(),
+                )])),
+                ..Default::default()
+            },
+            data: Some(status.to_configmap_data()),
+            ..Default::default()
+        };
+        configmaps
+            .create(&PostParams::default(), &cm)
+            .await
+            .unwrap();
 
-        for cm in cms.items {
-            let status = match VolumeStatus::from_configmap(&cm) {
-                Some(s) => s,
-                None => continue,
-            };
+        // Updates find and keep the legacy ConfigMap
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        assert!(api.configmap(&configmap_name(&volume_id)).is_none());
+        assert_eq!(cm_label(&api.configmap(&legacy_name).unwrap()), "cleanup");
 
-            // Skip if this node doesn't have the volume
-            if !status.nodes_with_volume.contains(&self.node_name) {
-                continue;
-            }
+        let (node1, dir) = node_with_volume(&api, "node1", &volume_id);
+        assert_eq!(node1.process_pending_cleanups().await.unwrap(), 1);
+        assert!(!dir.exists());
 
-            // Skip if we already processed this
-            if status.nodes_completed.contains(&self.node_name)
-                || status.nodes_failed.contains(&self.node_name)
-            {
-                continue;
-            }
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let summary = controller.process_cleanups().await.unwrap();
+        assert_eq!(summary.pruned, vec![volume_id]);
+        assert!(api.configmap(&legacy_name).is_none());
+    }
 
-            // Process cleanup
-            let volume_path = self.base_path.join(&status.volume_id);
-            let result = self.cleanup_volume_directory(&volume_path).await;
+    #[test]
+    fn test_event_object() {
+        let mut ctx = HashMap::new();
+        ctx.insert(volume::PVC_NAME_KEY.to_string(), "data".to_string());
+        // Name without namespace isn't enough
+        assert_eq!(PvcRef::from_volume_context(&ctx), None);
+        ctx.insert(volume::PVC_NAMESPACE_KEY.to_string(), "team-a".to_string());
+        let pvc = PvcRef::from_volume_context(&ctx).unwrap();
 
-            let success = match result {
-                Ok(cleaned) => {
-                    if cleaned {
-                        info!(
-                            volume_id = %status.volume_id,
-                            node = %self.node_name,
-                            "Cleaned up volume directory"
-                        );
-                    } else {
-                        debug!(
-                            volume_id = %status.volume_id,
-                            node = %self.node_name,
-                            "No directory to clean (already gone)"
-                        );
-                    }
-                    true
-                }
-                Err(e) => {
-                    error!(
-                        volume_id = %status.volume_id,
-                        node = %self.node_name,
-                        error = %e,
-                        "Failed to clean up volume directory"
-                    );
-                    false
-                }
-            };
+        let obj = event_object(EventNamespace::Pvc, "nlc", "nlc-abc", Some(&pvc));
+        assert_eq!(obj.kind.as_deref(), Some("PersistentVolumeClaim"));
+        assert_eq!(obj.name.as_deref(), Some("data"));
+        assert_eq!(obj.namespace.as_deref(), Some("team-a"));
 
-            // Update ConfigMap with completion status
-            if let Err(e) = mark_node_cleanup_complete(
-                &self.client,
-                &self.namespace,
-                &status.volume_id,
-                &self.node_name,
-                success,
-            )
-            .await
-            {
-                // Don't fail cleanup for status update issues
-                warn!(
-                    volume_id = %status.volume_id,
-                    error = %e,
-                    "Failed to update cleanup status"
-                );
-            }
+        // Unknown PVC falls back to the ConfigMap in the driver namespace
+        let obj = event_object(EventNamespace::Pvc, "nlc", "nlc-abc", None);
+        assert_eq!(obj.kind.as_deref(), Some("ConfigMap"));
+        assert_eq!(obj.name.as_deref(), Some("nlc-vol-abc"));
+        assert_eq!(obj.namespace.as_deref(), Some("nlc"));
 
-            processed += 1;
-        }
+        let obj = event_object(EventNamespace::Driver, "nlc", "nlc-abc", Some(&pvc));
+        assert_eq!(obj.namespace.as_deref(), Some("nlc"));
+    }
 
-        Ok(processed)
+    #[test]
+    fn test_prune_outcome_json() {
+        let outcome = PruneOutcome::Pending {
+            volume_id: "nlc-test-123".to_string(),
+            pending_nodes: vec!["node2".to_string()],
+            newly_decommissioned: vec![],
+        };
+        let json = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(json["outcome"], "pending");
+        assert_eq!(json["volume_id"], "nlc-test-123");
+        assert_eq!(json["pending_nodes"][0], "node2");
+        assert!(json.get("newly_decommissioned").is_none());
     }
 
-    /// Delete a volume directory if it exists
-    async fn cleanup_volume_directory(&self, path: &Path) -> Result<bool, std::io::Error> {
-        if !path.exists() {
-            return Ok(false);
-        }
+    #[test]
+    fn test_cleanup_summary() {
+        let mut summary = CleanupSummary::default();
+        assert!(summary.is_empty());
 
-        // Safety check: ensure path is under base_path
-        if !path.starts_with(&self.base_path) {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
-                "Path is not under base path",
-            ));
-        }
+        summary.record(PruneOutcome::Pruned {
+            volume_id: "nlc-a".to_string(),
+            nodes_completed: vec!["node1".to_string()],
+            nodes_failed: vec![],
+            nodes_decommissioned: vec!["node2".to_string()],
+            newly_decommissioned: vec!["node2".to_string()],
+            stragglers: vec![],
+            timed_out: false,
+        });
+        summary.record(PruneOutcome::Pending {
+            volume_id: "nlc-b".to_string(),
+            pending_nodes: vec!["node1".to_string(), "node3".to_string()],
+            newly_decommissioned: vec![],
+        });
+        summary.record(PruneOutcome::NotRequested {
+            volume_id: "nlc-c".to_string(),
+        });
 
-        // Use tokio's blocking task for potentially long rm -rf
-        let path = path.to_path_buf();
-        tokio::task::spawn_blocking(move || std::fs::remove_dir_all(path))
-            .await
-            .map_err(std::io::Error::other)??;
+        assert_eq!(summary.pruned, vec!["nlc-a"]);
+        assert_eq!(summary.pending.get("nlc-b"), Some(&2));
+        assert_eq!(summary.decommissioned_nodes(), 1);
+        assert_eq!(
+            summary.to_string(),
+            "pruned 1, 1 still pending, 1 decommissioned"
+        );
 
-        Ok(true)
+        summary
+            .errors
+            .push(("nlc-vol-nlc-d".to_string(), "boom".to_string()));
+        assert_eq!(
+            summary.to_string(),
+            "pruned 1, 1 still pending, 1 decommissioned, 1 failed"
+        );
     }
 
-    /// Run the cleanup watcher loop
-    pub async fn run_cleanup_loop(self, interval: Duration) {
-        info!(
-            node = %self.node_name,
-            interval_secs = interval.as_secs(),
-            "Starting cleanup watcher"
-        );
+    #[test]
+    fn test_shared_references() {
+        let mut shared = VolumeStatus::new("shared-maven");
+        shared.add_node("node1");
+        shared.add_reference("nlc-a");
+        shared.add_reference("nlc-b");
+        shared.add_reference("nlc-a");
+        assert_eq!(shared.references, vec!["nlc-a", "nlc-b"]);
+        assert_eq!(shared.phase_label(), "active");
 
-        loop {
-            match self.process_pending_cleanups().await {
-                Ok(count) if count > 0 => {
-                    info!(count = count, "Processed cleanup requests");
-                }
-                Ok(_) => {
-                    debug!("No pending cleanups");
-                }
-                Err(e) => {
-                    error!(error = %e, "Error processing cleanups");
-                }
-            }
+        shared.remove_reference("nlc-a");
+        assert_eq!(shared.references, vec!["nlc-b"]);
 
-            tokio::time::sleep(interval).await;
-        }
+        shared.remove_reference("nlc-b");
+        shared.mark_cleanup_requested();
+        shared.mark_node_completed("node1");
+        assert_eq!(shared.phase_label(), "cleanup");
+
+        // A new reference while cleanup is in progress takes the cache back into use
+        shared.add_reference("nlc-c");
+        assert_eq!(shared.phase_label(), "active");
+        assert!(shared.nodes_completed.is_empty());
+        assert!(!shared.is_cleanup_complete());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[tokio::test]
+    async fn test_record_create_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let volume_id = volume::generate_volume_id("pvc-stateful");
+        let parameters = BTreeMap::from([("tier".to_string(), "fast".to_string())]);
+        let record = CreateRecord {
+            name: "pvc-stateful".to_string(),
+            capacity_bytes: 1 << 30,
+            parameters: parameters.clone(),
+        };
 
-    #[test]
-    fn test_volume_status_serialization() {
-        let mut status = VolumeStatus::new("nlc-test-123");
-        status.add_node("node1");
-        status.add_node("node2");
-        status.mark_cleanup_requested();
-        status.mark_node_completed("node1");
-        status.mark_node_decommissioned("node3");
+        let status = controller
+            .record_create(&volume_id, &record, None, &BTreeMap::new())
+            .await
+            .unwrap();
+        assert_eq!(status.create_request.as_ref(), Some(&record));
+        assert!(status.nodes_with_volume.is_empty());
+        assert_eq!(
+            cm_label(&api.configmap(&configmap_name(&volume_id)).unwrap()),
+            "active"
+        );
 
-        let data = status.to_configmap_data();
-        let json = data.get("status").unwrap();
+        // A retry keeps the first request, for the caller to compare
+        let other = CreateRecord {
+            capacity_bytes: 2 << 30,
+            ..record.clone()
+        };
+        let status = controller
+            .record_create(&volume_id, &other, None, &BTreeMap::new())
+            .await
+            .unwrap();
+        let recorded = status.create_request.unwrap();
+        assert_eq!(recorded, record);
+        assert!(recorded.is_compatible(&parameters, 1 << 30, 0));
+        assert!(recorded.is_compatible(&parameters, 1 << 20, 1 << 31));
+        assert!(!recorded.is_compatible(&parameters, 2 << 30, 0));
+        assert!(!recorded.is_compatible(&parameters, 0, 1 << 20));
+        assert!(!recorded.is_compatible(&BTreeMap::new(), 1 << 30, 0));
 
-        let parsed: VolumeStatus = serde_json::from_str(json).unwrap();
-        assert_eq!(parsed.volume_id, "nlc-test-123");
-        assert_eq!(parsed.nodes_with_volume.len(), 2);
-        assert_eq!(parsed.nodes_completed.len(), 1);
-        assert_eq!(parsed.nodes_decommissioned.len(), 1);
-        assert!(parsed.cleanup_requested_at.is_some());
+        // Publishing keeps the record
+        register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        let status = controller.volume_status(&volume_id).await.unwrap().unwrap();
+        assert_eq!(status.create_request, Some(record));
+
+        // Nothing recorded while in cleanup
+        mark_volume_for_cleanup(&client, "default", &volume_id)
+            .await
+            .unwrap();
+        let status = controller
+            .record_create(&volume_id, &other, None, &BTreeMap::new())
+            .await
+            .unwrap();
+        assert!(status.cleanup_requested_at.is_some());
     }
 
     #[test]
-    fn test_cleanup_complete() {
-        let mut status = VolumeStatus::new("nlc-test-123");
-        status.add_node("node1");
-        status.add_node("node2");
-
-        // Not complete without cleanup request
-        assert!(!status.is_cleanup_complete());
+    fn test_merge_older() {
+        let mut older = VolumeStatus::new("nlc-test-123");
+        older.created_at = "2024-01-01T00:00:00+00:00".to_string();
+        older.add_node("node1");
+        older.mount_options = Some(vec!["ro".to_string()]);
+        older.labels.insert("team".to_string(), "ml".to_string());
 
-        status.mark_cleanup_requested();
+        // Created while the older one was being recreated
+        let mut fresh = VolumeStatus::new("nlc-test-123");
+        fresh.add_node("node2");
+        fresh.mount_options = Some(vec!["noatime".to_string()]);
+        fresh.merge_older(&older);
 
-        // Not complete without all nodes reporting
-        assert!(!status.is_cleanup_complete());
+        assert_eq!(fresh.created_at, older.created_at);
+        assert_eq!(fresh.nodes_with_volume, vec!["node2", "node1"]);
+        assert_eq!(fresh.mount_options, older.mount_options);
+        assert_eq!(fresh.labels, older.labels);
+        assert!(fresh.is_stable());
+        fresh.mark_cleanup_requested();
+        assert!(!fresh.is_stable());
+    }
 
-        status.mark_node_completed("node1");
-        assert!(!status.is_cleanup_complete());
+    #[test]
+    fn test_merge_shards() {
+        let mut primary = VolumeStatus::new("nlc-test-123");
+        primary.add_node("node1");
+        primary.shards = 1;
+        let mut shard = VolumeStatus::new_shard("nlc-test-123", 1);
+        shard.add_node("node2");
+        shard.mark_node_recycled("node2");
+        let mut orphan = VolumeStatus::new_shard("nlc-test-456", 1);
+        orphan.add_node("node3");
 
-        status.mark_node_completed("node2");
-        assert!(status.is_cleanup_complete());
+        let merged = merge_shards(vec![shard, primary, orphan]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].nodes_with_volume, vec!["node1", "node2"]);
+        assert!(merged[0].recycled_at.contains_key("node2"));
+        assert_eq!(merged[0].shard, 0);
     }
 
     #[test]
-    fn test_cleanup_complete_with_failures() {
-        let mut status = VolumeStatus::new("nlc-test-123");
-        status.add_node("node1");
-        status.add_node("node2");
-        status.mark_cleanup_requested();
+    fn test_shard_follows_primary() {
+        let mut primary = VolumeStatus::new("shared-maven");
+        primary.shared_name = Some("maven".to_string());
+        primary.mark_cleanup_requested();
+        let mut shard = VolumeStatus::new_shard("shared-maven", 1);
+        shard.add_node("node2");
 
-        status.mark_node_completed("node1");
-        status.mark_node_failed("node2"); // Failed but still "reported"
+        shard.follow(&primary);
+        assert_eq!(shard.created_at, primary.created_at);
+        assert_eq!(shard.phase_label(), "cleanup");
+        assert_eq!(
+            shard
+                .configmap_labels()
+                .get(SHARD_LABEL)
+                .map(String::as_str),
+            Some("1")
+        );
+        shard.mark_node_completed("node2");
 
-        assert!(status.is_cleanup_complete());
+        // Taken back into use
+        primary.cleanup_requested_at = None;
+        shard.follow(&primary);
+        assert_eq!(shard.phase_label(), "active");
+        assert!(shard.nodes_completed.is_empty());
+        assert_eq!(shard.nodes_with_volume, vec!["node2"]);
     }
 
     #[test]
-    fn test_idempotent_operations() {
-        let mut status = VolumeStatus::new("nlc-test-123");
+    fn test_shared_fields_omitted_for_plain_volumes() {
+        let status = VolumeStatus::new("nlc-test-123");
+        let json = status.to_configmap_data().remove("status").unwrap();
+        assert!(!json.contains("shared_name"));
+        assert!(!json.contains("references"));
 
-        status.add_node("node1");
-        status.add_node("node1");
-        status.add_node("node1");
-        assert_eq!(status.nodes_with_volume.len(), 1);
-
-        status.mark_node_completed("node1");
-        status.mark_node_completed("node1");
-        assert_eq!(status.nodes_completed.len(), 1);
+        let parsed: VolumeStatus = serde_json::from_str(&json).unwrap();
+        assert!(parsed.shared_name.is_none());
+        assert!(parsed.references.is_empty());
     }
 }
@@ -0,0 +1,180 @@
+//! Node-local journal of cleanup completions (`--cleanup-journal`).
+//!
+//! A node that deleted a volume's directory but couldn't report it on the
+//! tracking ConfigMap (API server unreachable, or the plugin restarted in
+//! between) would leave the controller waiting on it. With the journal, the
+//! outcome is first written to `<base>/.cleanup-journal/<volume_id>` and
+//! synced, then reported; a successful report acks (removes) the record. The
+//! node cleanup loop reports un-acked records again at the start of each pass,
+//! before looking for new cleanups. Reporting is idempotent, so a record
+//! reported twice does no harm.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use kube::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::cleanup;
+
+/// Directory under the base path holding the records
+pub const JOURNAL_DIR: &str = ".cleanup-journal";
+
+/// A node's cleanup outcome for a volume, waiting to be reported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Completion {
+    pub volume_id: String,
+    /// Whether the directory was cleaned up (or quarantined)
+    pub success: bool,
+    /// When the node finished, RFC 3339
+    pub completed_at: String,
+}
+
+impl Completion {
+    pub fn new(volume_id: &str, success: bool) -> Self {
+        Self {
+            volume_id: volume_id.to_string(),
+            success,
+            completed_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+}
+
+fn record_path(base_path: &Path, volume_id: &str) -> PathBuf {
+    base_path.join(JOURNAL_DIR).join(volume_id)
+}
+
+/// Durably write a completion before reporting it. A later record for the
+/// same volume replaces the earlier one.
+pub fn record(base_path: &Path, completion: &Completion) -> io::Result<()> {
+    let dir = base_path.join(JOURNAL_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let path = record_path(base_path, &completion.volume_id);
+    let json = serde_json::to_vec(completion).map_err(io::Error::other)?;
+    // Never leave a half-written record behind
+    let tmp = path.with_file_name(format!("{}.tmp", completion.volume_id));
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(&json)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, &path)?;
+    // Make the rename itself durable
+    std::fs::File::open(&dir)?.sync_all()
+}
+
+/// Drop the record of a volume once reported, if any
+pub fn ack(base_path: &Path, volume_id: &str) -> io::Result<()> {
+    match std::fs::remove_file(record_path(base_path, volume_id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Completions not reported yet. Unreadable records are skipped.
+pub fn unacked(base_path: &Path) -> io::Result<Vec<Completion>> {
+    let entries = match std::fs::read_dir(base_path.join(JOURNAL_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut completions = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "tmp") {
+            continue;
+        }
+        let parsed = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_slice::<Completion>(&data).map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(completion) => completions.push(completion),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Skipping unreadable cleanup journal record")
+            }
+        }
+    }
+    completions.sort_by(|a, b| a.volume_id.cmp(&b.volume_id));
+    Ok(completions)
+}
+
+/// Report the un-acked completions of `node_name`, acking those that went
+/// through. Records of volumes whose ConfigMap is gone (already pruned) are
+/// acked too. Stops at the first other API error, as the rest would likely
+/// fail the same way. Returns the number reported.
+pub async fn retry(
+    client: &Client,
+    namespace: &str,
+    node_name: &str,
+    base_path: &Path,
+) -> Result<usize, kube::Error> {
+    let completions = match unacked(base_path) {
+        Ok(c) => c,
+        Err(e) => {
+            warn!(error = %e, "Failed to read the cleanup journal");
+            return Ok(0);
+        }
+    };
+    let mut reported = 0;
+
+    for completion in completions {
+        let volume_id = &completion.volume_id;
+        match cleanup::report_node_cleanup(
+            client,
+            namespace,
+            volume_id,
+            node_name,
+            completion.success,
+        )
+        .await
+        {
+            Ok(()) => {
+                info!(
+                    volume_id = %volume_id,
+                    node = %node_name,
+                    completed_at = %completion.completed_at,
+                    "Reported journaled cleanup completion"
+                );
+                reported += 1;
+            }
+            Err(kube::Error::Api(err)) if err.code == 404 => {}
+            Err(e) => return Err(e),
+        }
+        if let Err(e) = ack(base_path, volume_id) {
+            warn!(volume_id = %volume_id, error = %e, "Failed to ack cleanup journal record");
+        }
+    }
+    Ok(reported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_ack() {
+        let base =
+            std::env::temp_dir().join(format!("nlc-cleanup-journal-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+
+        let failed = Completion::new("nlc-a", false);
+        record(&base, &failed).unwrap();
+        let done = Completion::new("nlc-a", true);
+        record(&base, &done).unwrap();
+        record(&base, &Completion::new("nlc-b", true)).unwrap();
+        std::fs::write(base.join(JOURNAL_DIR).join("nlc-garbage"), b"{").unwrap();
+
+        let completions = unacked(&base).unwrap();
+        assert_eq!(completions.len(), 2);
+        assert_eq!(completions[0], done);
+
+        ack(&base, "nlc-a").unwrap();
+        ack(&base, "nlc-a").unwrap();
+        let completions = unacked(&base).unwrap();
+        assert_eq!(completions.len(), 1);
+        assert_eq!(completions[0].volume_id, "nlc-b");
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
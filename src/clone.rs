@@ -0,0 +1,268 @@
+//! Volumes cloned from another volume (a PVC `dataSource`).
+//!
+//! CreateVolume records the source volume's ID in the volume context
+//! (`volume::CLONE_FROM_KEY`), as only a node has the data to copy. When
+//! NodePublishVolume creates the volume's directory, it first copies the
+//! source volume's directory on that node into it. A node without the source
+//! volume leaves the clone empty, like any new cache.
+//!
+//! `CLONING_MARKER` sits in the volume directory while copying, so a copy
+//! interrupted by a restart is started over at the next publish instead of
+//! being published half done.
+
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use nix::fcntl::AT_FDCWD;
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use tracing::{debug, warn};
+
+/// File in a volume directory being cloned into
+pub const CLONING_MARKER: &str = ".nlc-cloning";
+
+/// What a clone copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CopyStats {
+    /// Regular files and symlinks
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Whether `dest` holds a clone that was interrupted
+pub fn interrupted(dest: &Path) -> bool {
+    dest.join(CLONING_MARKER).exists()
+}
+
+/// Copy the content of the volume directory `source` into `dest`, which is
+/// new or holds an interrupted clone. Returns `None` when there is no
+/// `source`. On failure, `dest` is emptied again.
+pub fn clone_volume(source: &Path, dest: &Path) -> io::Result<Option<CopyStats>> {
+    match std::fs::symlink_metadata(source) {
+        Ok(metadata) if metadata.is_dir() => {}
+        Ok(_) => return Ok(None),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let marker = dest.join(CLONING_MARKER);
+    if marker.exists() {
+        clear_contents(dest)?;
+    }
+    std::fs::write(&marker, b"")?;
+    match copy_contents(source, dest) {
+        Ok(stats) => {
+            std::fs::remove_file(&marker)?;
+            Ok(Some(stats))
+        }
+        Err(e) => {
+            if let Err(clear) = clear_contents(dest) {
+                warn!(path = %dest.display(), error = %clear, "Failed to clear partial clone");
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Copy the tree under `source` into the existing directory `dest`: files,
+/// directories and symlinks (not followed), with their permissions,
+/// modification times and, when permitted, owners. Hard links are copied
+/// apart, other filesystems aren't entered and special files are skipped.
+/// The source may be in use, so entries deleted while copying are skipped.
+pub fn copy_contents(source: &Path, dest: &Path) -> io::Result<CopyStats> {
+    let device = std::fs::symlink_metadata(source)?.dev();
+    let mut stats = CopyStats::default();
+    // Directories get their metadata once their content is in place, so a
+    // read-only one can still be filled
+    let mut directories = Vec::new();
+    let mut pending = vec![(source.to_path_buf(), dest.to_path_buf())];
+
+    while let Some((from_dir, to_dir)) = pending.pop() {
+        let entries = match std::fs::read_dir(&from_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            if from_dir == source && entry.file_name() == CLONING_MARKER {
+                continue;
+            }
+            let (from, to) = (entry.path(), to_dir.join(entry.file_name()));
+            let metadata = match std::fs::symlink_metadata(&from) {
+                Ok(m) => m,
+                // Deleted while copying
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if metadata.dev() != device {
+                debug!(path = %from.display(), "Not cloning across filesystems");
+                continue;
+            }
+            let file_type = metadata.file_type();
+            if file_type.is_dir() {
+                match std::fs::create_dir(&to) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                    Err(e) => return Err(e),
+                }
+                directories.push((to.clone(), metadata));
+                pending.push((from, to));
+            } else if file_type.is_file() {
+                // Also copies the permissions
+                match std::fs::copy(&from, &to) {
+                    Ok(bytes) => stats.bytes += bytes,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                }
+                copy_metadata(&to, &metadata, false)?;
+                stats.files += 1;
+            } else if file_type.is_symlink() {
+                let link = match std::fs::read_link(&from) {
+                    Ok(link) => link,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e),
+                };
+                std::os::unix::fs::symlink(link, &to)?;
+                copy_metadata(&to, &metadata, true)?;
+                stats.files += 1;
+            } else {
+                debug!(path = %from.display(), "Not cloning special file");
+            }
+        }
+    }
+
+    // Deepest first, so filling a directory doesn't touch its parent's times
+    // after they were set
+    for (path, metadata) in directories.into_iter().rev() {
+        std::fs::set_permissions(
+            &path,
+            std::os::unix::fs::PermissionsExt::from_mode(metadata.mode()),
+        )?;
+        copy_metadata(&path, &metadata, false)?;
+    }
+    Ok(stats)
+}
+
+/// Give `path` the owner (if permitted) and times of `metadata`
+fn copy_metadata(path: &Path, metadata: &std::fs::Metadata, symlink: bool) -> io::Result<()> {
+    match std::os::unix::fs::lchown(path, Some(metadata.uid()), Some(metadata.gid())) {
+        Ok(()) => {}
+        // Not root: the copy stays ours
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {}
+        Err(e) => return Err(e),
+    }
+    let flag = if symlink {
+        UtimensatFlags::NoFollowSymlink
+    } else {
+        UtimensatFlags::FollowSymlink
+    };
+    utimensat(
+        AT_FDCWD,
+        path,
+        &TimeSpec::new(metadata.atime(), metadata.atime_nsec()),
+        &TimeSpec::new(metadata.mtime(), metadata.mtime_nsec()),
+        flag,
+    )
+    .map_err(io::Error::from)
+}
+
+/// Remove everything inside `dir`, keeping `dir` itself
+fn clear_contents(dir: &Path) -> io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if std::fs::symlink_metadata(&path)?.is_dir() {
+            std::fs::remove_dir_all(&path)?;
+        } else {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    fn temp_base(name: &str) -> PathBuf {
+        let base =
+            std::env::temp_dir().join(format!("nlc-clone-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn test_copy_contents() {
+        let base = temp_base("copy");
+        let (source, dest) = (base.join("source"), base.join("dest"));
+        std::fs::create_dir_all(source.join("models/base")).unwrap();
+        std::fs::create_dir_all(&dest).unwrap();
+        std::fs::write(source.join("models/base/weights"), vec![7u8; 10_000]).unwrap();
+        std::fs::write(source.join("index"), b"v1").unwrap();
+        std::fs::set_permissions(source.join("index"), PermissionsExt::from_mode(0o640)).unwrap();
+        std::os::unix::fs::symlink("models/base", source.join("latest")).unwrap();
+        // Read-only directories are filled before they're locked down
+        std::fs::set_permissions(source.join("models"), PermissionsExt::from_mode(0o555)).unwrap();
+
+        let stats = copy_contents(&source, &dest).unwrap();
+        assert_eq!(
+            stats,
+            CopyStats {
+                files: 3,
+                bytes: 10_002
+            }
+        );
+        assert_eq!(
+            std::fs::read(dest.join("models/base/weights")).unwrap(),
+            vec![7u8; 10_000]
+        );
+        assert_eq!(
+            std::fs::read(dest.join("latest/weights")).unwrap().len(),
+            10_000
+        );
+        assert_eq!(
+            std::fs::read_link(dest.join("latest")).unwrap(),
+            Path::new("models/base")
+        );
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().mode() & 0o777;
+        assert_eq!(mode(&dest.join("index")), 0o640);
+        assert_eq!(mode(&dest.join("models")), 0o555);
+        let mtime = |path: &Path| std::fs::metadata(path).unwrap().modified().unwrap();
+        assert_eq!(mtime(&dest.join("index")), mtime(&source.join("index")));
+        assert_eq!(mtime(&dest.join("models")), mtime(&source.join("models")));
+
+        // The source is untouched
+        assert_eq!(std::fs::read(source.join("index")).unwrap(), b"v1");
+        std::fs::set_permissions(source.join("models"), PermissionsExt::from_mode(0o755)).unwrap();
+        std::fs::set_permissions(dest.join("models"), PermissionsExt::from_mode(0o755)).unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_clone_volume() {
+        let base = temp_base("clone");
+        let (source, dest) = (base.join("nlc-source"), base.join("nlc-dest"));
+        std::fs::create_dir_all(&dest).unwrap();
+
+        // No source on this node: the clone starts empty
+        assert_eq!(clone_volume(&source, &dest).unwrap(), None);
+        assert_eq!(std::fs::read_dir(&dest).unwrap().count(), 0);
+
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("data"), b"warm").unwrap();
+        // Left over by an interrupted clone
+        std::fs::write(dest.join(CLONING_MARKER), b"").unwrap();
+        std::fs::write(dest.join("partial"), b"x").unwrap();
+        assert!(interrupted(&dest));
+
+        let stats = clone_volume(&source, &dest).unwrap().unwrap();
+        assert_eq!(stats, CopyStats { files: 1, bytes: 4 });
+        assert!(!interrupted(&dest));
+        assert!(!dest.join("partial").exists());
+        assert_eq!(std::fs::read(dest.join("data")).unwrap(), b"warm");
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,981 @@
+//! Layered driver configuration.
+//!
+//! Every setting is a command-line flag on [`Args`]. Settings can also be
+//! provided through a YAML, or with a `.toml` extension TOML, config file
+//! passed with `--config`, whose keys are the long flag names (e.g.
+//! `base-path`, `log-level`).
+//!
+//! Precedence (lowest to highest): built-in defaults < config file <
+//! command-line flags < environment variables. The few settings read from
+//! the environment (`NODE_NAME`, `POD_NAMESPACE`, ...) come from the pod
+//! spec, which knows them better than a static flag or file.
+
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{ArgAction, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use serde::{Serialize, Serializer};
+use tracing::Level;
+use uuid::Uuid;
+
+use crate::cleanup;
+use crate::dedup;
+use crate::dir_size;
+use crate::directory::DirectoryBackend;
+use crate::disk_monitor;
+use crate::history;
+use crate::identity::DRIVER_NAME;
+use crate::quota::QuotaBackend;
+use crate::volume;
+
+/// Shown instead of secret values in the effective configuration
+const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Controller,
+    Node,
+}
+
+/// How log lines are written (`--log-format`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One JSON object per line, for log pipelines
+    #[default]
+    Json,
+    /// Human-readable lines, for reading in a terminal
+    Text,
+}
+
+#[derive(Parser, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[command(name = "node-local-cache")]
+#[command(about = "CSI driver for node-local ephemeral cache volumes")]
+#[command(after_help = "Run `node-local-cache selftest --help` to check a node can mount volumes.")]
+pub struct Args {
+    /// Path to a YAML config file, or TOML if it ends in `.toml`; keys are
+    /// long flag names (e.g. `base-path`)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Run mode: controller or node
+    #[arg(long, value_enum)]
+    pub mode: Mode,
+
+    /// Path to CSI socket
+    #[arg(long, default_value = "/csi/csi.sock")]
+    pub csi_socket: PathBuf,
+
+    /// Node name (required for node mode)
+    #[arg(long, env = "NODE_NAME")]
+    pub node_name: Option<String>,
+
+    /// Base path for cache volumes
+    #[arg(long, default_value = "/var/node-local-cache")]
+    pub base_path: PathBuf,
+
+    /// Other base path a StorageClass may store its volumes under with the
+    /// `node-local-cache.csi.io/base-path` parameter (node mode); repeat for
+    /// several
+    #[arg(long)]
+    pub allowed_base_path: Vec<PathBuf>,
+
+    /// How volume directories are created: plain directories or btrfs subvolumes
+    #[arg(long, value_enum, default_value = "dir")]
+    pub directory_backend: DirectoryBackend,
+
+    /// How a volume's requested capacity is enforced: not at all, with an XFS
+    /// project quota, or with a preallocated loopback ext4 image
+    #[arg(long, value_enum, default_value = "none")]
+    pub quota_backend: QuotaBackend,
+
+    /// Enforce volume capacity with XFS project quotas when the base path
+    /// supports them, and otherwise log a warning and keep capacity advisory;
+    /// `--quota-backend xfs-project` refuses to start instead (node mode)
+    #[arg(long, default_value = "false", conflicts_with = "quota_backend")]
+    pub enable_xfs_quota: bool,
+
+    /// Kubernetes namespace for cleanup coordination
+    #[arg(long, env = "POD_NAMESPACE", default_value = "node-local-cache")]
+    pub namespace: String,
+
+    /// Namespace UUID for deriving volume IDs from PVC names (controller mode).
+    /// Give instances sharing a base path or namespace distinct values so their
+    /// volume IDs can't collide. Changing it only affects newly created volumes
+    #[arg(long, default_value_t = volume::VOLUME_ID_NAMESPACE)]
+    #[serde(serialize_with = "serialize_display")]
+    pub id_namespace: Uuid,
+
+    /// Where events about a volume are emitted: the driver namespace, or the
+    /// PVC's namespace when known (requires the provisioner's `--extra-create-metadata`)
+    #[arg(long, value_enum, default_value = "driver")]
+    pub event_namespace: cleanup::EventNamespace,
+
+    /// How long a node must be missing from the cluster before the controller
+    /// marks it decommissioned and stops waiting for its cleanup (e.g. `10m`);
+    /// 0 decommissions right away (controller mode)
+    #[arg(long, default_value = "0", value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    pub decommission_grace: Duration,
+
+    /// How many of a volume's nodes must report their cleanup before its
+    /// ConfigMap is pruned: `all`, or a percentage like `90%`. Nodes that
+    /// haven't reported by then still clean up on their own, untracked
+    /// (controller mode)
+    #[arg(long, default_value = "all", value_parser = cleanup::CleanupQuorum::parse)]
+    #[serde(serialize_with = "serialize_display")]
+    pub cleanup_quorum: cleanup::CleanupQuorum,
+
+    /// Prune a volume's cleanup ConfigMap once its cleanup was requested this
+    /// long ago (e.g. `24h`), even if nodes haven't reported, with a
+    /// `CleanupTimedOut` warning event. Those nodes still clean up on their
+    /// own, untracked. Disabled when unset (controller mode)
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub cleanup_timeout: Option<Duration>,
+
+    /// Capacity in bytes to report for volumes created without a requested
+    /// capacity, instead of 0 ("unknown"). Only reported, e.g. for monitoring;
+    /// quotas only apply to requested capacity (controller mode)
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub default_capacity_bytes: Option<i64>,
+
+    /// Reject volumes requesting more than this many bytes (CreateVolume and
+    /// expansion fail with OutOfRange), e.g. the size of the nodes' cache
+    /// disks. Unlimited when unset (controller mode)
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub max_volume_bytes: Option<i64>,
+
+    /// On DeleteVolume of a volume without a tracking ConfigMap, have every
+    /// node check for its directory and delete it, in case a node failed to
+    /// register its publish (controller mode)
+    #[arg(long, default_value = "false")]
+    pub delete_broadcast_on_missing: bool,
+
+    /// Record each volume's CreateVolume request in its tracking ConfigMap and
+    /// enforce CSI idempotency against it: a repeated CreateVolume with other
+    /// capacity or parameters fails with AlreadyExists. Without it, CreateVolume
+    /// keeps no state (controller mode, needs the cleanup service)
+    #[arg(long, default_value = "false", conflicts_with = "no_cleanup_service")]
+    pub stateful_create: bool,
+
+    /// Don't create Kubernetes events; what they would report is still logged
+    #[arg(long, default_value = "false")]
+    pub no_events: bool,
+
+    /// On startup, emit a `DriverStarted` event describing this instance
+    /// (mode, version, enabled features) on its pod, or on the Node when the
+    /// pod name is unknown. Repeated restarts update it at most every 10 minutes
+    #[arg(long, default_value = "false")]
+    pub emit_startup_event: bool,
+
+    /// Name of the driver's pod, which the startup event is attached to
+    #[arg(long, env = "POD_NAME")]
+    pub pod_name: Option<String>,
+
+    /// Make tracking ConfigMaps immutable while their volume is in use and
+    /// not being cleaned up, to spare the API server watching them; changing
+    /// one then means recreating it
+    #[arg(long, default_value = "false")]
+    pub immutable_stable_configmaps: bool,
+
+    /// Keep the tracking ConfigMaps as files in this directory instead of in
+    /// the Kubernetes API. It must be shared by the controller and all nodes,
+    /// with working `flock`; nodes then poll for cleanup requests
+    #[arg(long)]
+    pub coordination_dir: Option<PathBuf>,
+
+    /// Log level
+    #[arg(long, default_value = "info")]
+    #[serde(serialize_with = "serialize_display")]
+    pub log_level: Level,
+
+    /// Log format: `json` lines, or human-readable `text` for local
+    /// development
+    #[arg(long, value_enum, default_value = "json")]
+    pub log_format: LogFormat,
+
+    /// Disable cleanup service (for testing only - will leak disk space)
+    #[arg(long, default_value = "false")]
+    pub no_cleanup_service: bool,
+
+    /// Serve gRPC reflection on the CSI socket, so tools like grpcurl can
+    /// list and call its services without the proto files (debugging aid)
+    #[arg(long, default_value = "false")]
+    pub enable_reflection: bool,
+
+    /// Move volume directories that fail to delete into `<base-path>/.quarantine`
+    /// and report the node's cleanup as done; deletion is retried hourly (node mode)
+    #[arg(long, default_value = "false")]
+    pub quarantine_failed: bool,
+
+    /// Log the volume directories node cleanup and orphan collection would
+    /// delete, with their size, instead of deleting them; cleanups are still
+    /// reported as done (node mode)
+    #[arg(long, default_value = "false")]
+    pub cleanup_dry_run: bool,
+
+    /// Idmap bind mounts of volumes whose context carries a UID/GID mapping,
+    /// for pods in user namespaces (node mode, Linux 5.12+)
+    #[arg(long, default_value = "false")]
+    pub enable_idmapped_mounts: bool,
+
+    /// After unmounting, remove the target directory if NodePublishVolume created
+    /// it and it is empty (node mode). The kubelet normally removes it itself
+    #[arg(long, default_value = "false")]
+    pub remove_target_on_unpublish: bool,
+
+    /// Take an exclusive lock in the base path before serving mounts, so only
+    /// one node plugin instance per node mounts, unmounts and cleans up at a
+    /// time (e.g. during DaemonSet rollovers); others wait (node mode)
+    #[arg(long, default_value = "false")]
+    pub node_singleton_lock: bool,
+
+    /// Record the base path's device at startup and refuse to publish volumes
+    /// whose directory is on another device, e.g. when the cache disk failed
+    /// to mount (node mode)
+    #[arg(long, default_value = "false")]
+    pub verify_base_device: bool,
+
+    /// Report not ready (Probe, `/readyz`) until the cleanup watcher's first
+    /// pass over pending cleanups completed (node mode)
+    #[arg(long, default_value = "false")]
+    pub wait_for_cleanup_sync: bool,
+
+    /// Make each volume's PersistentVolume the owner of its cleanup ConfigMap
+    /// while active, so deleting the PV garbage-collects it (node mode).
+    /// Requires the provisioner's `--extra-create-metadata`
+    #[arg(long, default_value = "false")]
+    pub set_owner_references: bool,
+
+    /// Periodically check base path disk usage, export it as a metric and emit
+    /// DiskPressureWarning/DiskPressureCritical events on the Node (node mode)
+    #[arg(long, default_value = "false")]
+    pub disk_usage_monitor: bool,
+
+    /// Base path usage (percent) that triggers a DiskPressureWarning event
+    #[arg(long, default_value_t = 80, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub disk_warning_threshold: u8,
+
+    /// Base path usage (percent) that triggers a DiskPressureCritical event
+    #[arg(long, default_value_t = 95, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub disk_critical_threshold: u8,
+
+    /// Base path usage (percent) above which the least recently used volume
+    /// directories that aren't mounted are deleted, with a VolumeEvicted
+    /// warning event each (node mode). Each disk holding the base path or an
+    /// `--allowed-base-path` is checked on its own, and again by a publish
+    /// that runs out of space creating its volume directory, which then
+    /// retries once. Disabled when unset
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub eviction_threshold: Option<u8>,
+
+    /// Base path usage (percent) eviction stops at; defaults to 10 below
+    /// `--eviction-threshold`
+    #[arg(long, requires = "eviction_threshold", value_parser = clap::value_parser!(u8).range(0..=100))]
+    pub eviction_low_watermark: Option<u8>,
+
+    /// Maximum age of a volume directory on a node (e.g. `7d`, `12h`); aged
+    /// volumes get a `VolumeAged` warning event. Disabled when unset
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub max_volume_age: Option<Duration>,
+
+    /// Clear the contents of volumes exceeding `--max-volume-age` so the cache
+    /// rebuilds fresh. Directories that are currently mounted are emptied in place
+    #[arg(long, default_value = "false", requires = "max_volume_age")]
+    pub recycle_aged: bool,
+
+    /// Hourly, delete volume directories under the base path that no
+    /// ConfigMap tracks and that weren't modified for `--orphan-gc-grace`,
+    /// e.g. left behind when a volume's ConfigMap was pruned while the node
+    /// was down. Destructive, so off by default (node mode)
+    #[arg(long, default_value = "false")]
+    pub orphan_gc: bool,
+
+    /// How long an untracked volume directory must be left unmodified before
+    /// `--orphan-gc` deletes it
+    #[arg(long, default_value = "24h", value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    pub orphan_gc_grace: Duration,
+
+    /// How often to compare the volume bind mounts on the node with the
+    /// tracking ConfigMaps and report discrepancies (e.g. `10m`); disabled when
+    /// unset (node mode)
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub mount_audit_interval: Option<Duration>,
+
+    /// Repair what the mount audit finds: register untracked volumes for the
+    /// node and unmount volumes whose cleanup was requested
+    #[arg(long, default_value = "false", requires = "mount_audit_interval")]
+    pub mount_audit_repair: bool,
+
+    /// Keep a cached size of each volume directory for volume stats and the
+    /// `nlc_volume_size_bytes` metric; directories without a quota are walked
+    /// at most this often (e.g. `1h`). Disabled when unset (node mode)
+    #[arg(long, value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub du_interval: Option<Duration>,
+
+    /// Hourly, replace identical files below `.nlc-seeded` marker files in
+    /// the volume directories with hardlinks to one copy (node mode). Only
+    /// mark content that pods never write: links share their content
+    #[arg(long, default_value = "false")]
+    pub dedup_compaction: bool,
+
+    /// How often to log progress while deleting a volume directory during
+    /// cleanup, for caches that take minutes to delete; 0 disables (node mode)
+    #[arg(long, default_value = "30s", value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    pub cleanup_progress_interval: Duration,
+
+    /// Before deleting a volume's directory on cleanup, check that no
+    /// PersistentVolume still references the volume; if one does, keep the
+    /// directory and emit a `CleanupBlockedPVExists` event (node mode)
+    #[arg(long, default_value = "false")]
+    pub verify_pv_deleted: bool,
+
+    /// How the node learns about cleanup requests: `watch` the ConfigMaps
+    /// requesting cleanup, or `poll` them every `--node-cleanup-interval`, a
+    /// fallback in case the watch misbehaves (node mode)
+    #[arg(long, value_enum, default_value = "watch")]
+    pub cleanup_trigger: cleanup::CleanupTrigger,
+
+    /// Write each cleanup outcome to a journal under the base path before
+    /// reporting it on the volume's ConfigMap, and report un-acked outcomes
+    /// again on the next pass, so a cleanup finished during an API outage or
+    /// right before a restart still gets reported (node mode)
+    #[arg(long, default_value = "false")]
+    pub cleanup_journal: bool,
+
+    /// Conflict retries of ConfigMap updates one node cleanup pass may spend
+    /// in total; once used up, the rest of the pass waits for the next one
+    /// (node mode)
+    #[arg(long, default_value_t = cleanup::DEFAULT_RETRY_BUDGET)]
+    pub cleanup_retry_budget: u32,
+
+    /// How often the controller checks cleanup ConfigMaps, pruning those of
+    /// finished cleanups; every minute when unset (controller mode)
+    #[arg(long, value_parser = parse_interval)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub controller_cleanup_interval: Option<Duration>,
+
+    /// How often nodes poll for cleanup requests (`--cleanup-trigger poll`)
+    /// and run their other periodic cleanup tasks; every 10 seconds when
+    /// unset (node mode)
+    #[arg(long, value_parser = parse_interval)]
+    #[serde(serialize_with = "serialize_opt_duration")]
+    pub node_cleanup_interval: Option<Duration>,
+
+    /// Attempts of a tracking ConfigMap update that keeps conflicting with
+    /// other writers before it fails
+    #[arg(long, default_value_t = cleanup::MAX_RETRIES, value_parser = clap::value_parser!(u32).range(1..))]
+    pub cleanup_max_retries: u32,
+
+    /// Address for the HTTP endpoint serving /metrics (e.g. 0.0.0.0:9808); disabled when unset
+    #[arg(long)]
+    pub http_addr: Option<SocketAddr>,
+
+    /// Address for the health endpoint serving /healthz and /readyz for the
+    /// pod's probes (e.g. 0.0.0.0:9809); disabled when unset
+    #[arg(long)]
+    pub health_addr: Option<SocketAddr>,
+
+    /// How long the cleanup loop may go without a tick before /readyz fails
+    #[arg(long, default_value = "5m", value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    pub health_stale_after: Duration,
+
+    /// Bearer token for admin HTTP endpoints; admin endpoints are disabled when unset
+    #[arg(long, env = "NLC_ADMIN_TOKEN", hide_env_values = true)]
+    #[serde(serialize_with = "serialize_redacted")]
+    pub admin_token: Option<String>,
+
+    /// Reject NodePublishVolume when the volume capability's fs_type doesn't
+    /// match the volume directory's filesystem, instead of only logging it
+    #[arg(long, default_value = "false")]
+    pub strict_fstype: bool,
+
+    /// Executable run by NodePublishVolume before mounting a volume, with
+    /// NLC_VOLUME_ID, NLC_SOURCE_PATH and NLC_TARGET_PATH set; a failure fails
+    /// the publish. Runs as the (privileged) driver
+    #[arg(long)]
+    pub pre_publish_hook: Option<PathBuf>,
+
+    /// How long the pre-publish hook may run before it is killed
+    #[arg(long, default_value = "5m", value_parser = parse_duration)]
+    #[serde(serialize_with = "serialize_duration")]
+    pub pre_publish_hook_timeout: Duration,
+
+    /// Target path prefix NodePublishVolume may mount under (also once
+    /// symlinks are resolved) and the admin `/unmount` endpoint may unmount
+    /// under (node mode); repeat for several
+    #[arg(long, default_value = "/var/lib/kubelet/pods")]
+    pub allowed_target_prefix: Vec<PathBuf>,
+
+    /// Prepare each volume once per node in NodeStageVolume and publish it
+    /// to pods from its staging target path (node mode)
+    #[arg(long, default_value = "false")]
+    pub enable_staging: bool,
+
+    /// Staging target path prefix NodeStageVolume may mount under, like
+    /// `--allowed-target-prefix` (node mode, `--enable-staging`); repeat for several
+    #[arg(long, default_value = "/var/lib/kubelet/plugins")]
+    pub allowed_staging_prefix: Vec<PathBuf>,
+
+    /// Number of recent operations (publish, cleanup, ...) kept in memory for
+    /// the admin `/recent` endpoint; 0 disables the history
+    #[arg(long, default_value_t = history::DEFAULT_CAPACITY)]
+    pub recent_operations: usize,
+}
+
+impl Args {
+    /// Parse the process arguments, layering in the `--config` file if given.
+    /// Exits with a usage message on error, like `Args::parse()`.
+    pub fn load() -> Self {
+        Self::try_load_from(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+
+    /// Parse `argv`, layering in the `--config` file if given.
+    ///
+    /// Flags for settings set in the environment are dropped, so clap takes
+    /// the environment value. File values are injected as flags in front of
+    /// the real arguments, but only for settings not already given on the
+    /// command line or via the environment. This keeps clap as the single
+    /// source of parsing and validation.
+    pub fn try_load_from<I, T>(argv: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let argv: Vec<OsString> = argv.into_iter().map(Into::into).collect();
+        let argv = without_env_overridden(&Self::command(), argv, |name| std::env::var_os(name));
+
+        // First pass only finds --config and which settings are explicitly set;
+        // required flags may legitimately come from the file.
+        let first = Self::command()
+            .ignore_errors(true)
+            .try_get_matches_from(&argv)?;
+
+        let Some(config_path) = first.get_one::<PathBuf>("config").cloned() else {
+            let matches = Self::command().try_get_matches_from(&argv)?;
+            return Self::from_arg_matches(&matches);
+        };
+
+        let file = read_config_file(&config_path)?;
+        let cmd = Self::command();
+        let mut merged: Vec<OsString> = argv.iter().take(1).cloned().collect();
+
+        for (key, value) in file {
+            let arg = cmd
+                .get_arguments()
+                .find(|a| a.get_long() == Some(key.as_str()) && a.get_id() != "config")
+                .ok_or_else(|| {
+                    config_error(
+                        ErrorKind::UnknownArgument,
+                        &config_path,
+                        format!("unknown key `{}`", key),
+                    )
+                })?;
+
+            let explicit = matches!(
+                first.value_source(arg.get_id().as_str()),
+                Some(ValueSource::CommandLine) | Some(ValueSource::EnvVariable)
+            );
+            if explicit {
+                continue;
+            }
+
+            push_file_value(&mut merged, &key, arg.get_action(), value)
+                .map_err(|msg| config_error(ErrorKind::InvalidValue, &config_path, msg))?;
+        }
+
+        merged.extend(argv.into_iter().skip(1));
+        let matches = Self::command().try_get_matches_from(merged)?;
+        Self::from_arg_matches(&matches)
+    }
+}
+
+impl Args {
+    /// `--controller-cleanup-interval`, or its default
+    pub fn controller_cleanup_interval(&self) -> Duration {
+        self.controller_cleanup_interval
+            .unwrap_or(cleanup::CONTROLLER_CLEANUP_INTERVAL)
+    }
+
+    /// `--node-cleanup-interval`, or its default
+    pub fn node_cleanup_interval(&self) -> Duration {
+        self.node_cleanup_interval
+            .unwrap_or(cleanup::NODE_CLEANUP_INTERVAL)
+    }
+
+    /// Effective configuration as served on the admin `/config` endpoint.
+    /// Settings use the config file key names; secrets are redacted.
+    pub fn effective_config(&self) -> serde_json::Value {
+        serde_json::json!({
+            "driver_name": DRIVER_NAME,
+            "version": env!("CARGO_PKG_VERSION"),
+            "settings": self,
+            "intervals": {
+                "controller-cleanup": format_duration(self.controller_cleanup_interval()),
+                "node-cleanup": format_duration(self.node_cleanup_interval()),
+                "cleanup-resync": format_duration(cleanup::CLEANUP_RESYNC_INTERVAL),
+                "age-check": format_duration(cleanup::AGE_CHECK_INTERVAL),
+                "directory-check": format_duration(cleanup::DIRECTORY_CHECK_INTERVAL),
+                "disk-usage-monitor": format_duration(disk_monitor::DISK_MONITOR_INTERVAL),
+                "quarantine-sweep": format_duration(cleanup::QUARANTINE_SWEEP_INTERVAL),
+                "orphan-gc": format_duration(cleanup::ORPHAN_GC_INTERVAL),
+                "size-refresh": format_duration(dir_size::SIZE_REFRESH_INTERVAL),
+                "dedup-compaction": format_duration(dedup::DEDUP_INTERVAL),
+            },
+        })
+    }
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{}s", d.as_secs())
+}
+
+fn serialize_display<T: std::fmt::Display, S: Serializer>(v: &T, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(v)
+}
+
+fn serialize_duration<S: Serializer>(v: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&format_duration(*v))
+}
+
+fn serialize_opt_duration<S: Serializer>(v: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+    v.map(format_duration).serialize(s)
+}
+
+fn serialize_redacted<S: Serializer>(v: &Option<String>, s: S) -> Result<S::Ok, S::Error> {
+    v.as_ref().map(|_| REDACTED).serialize(s)
+}
+
+/// Parse the duration of a loop interval, which can't be zero
+fn parse_interval(s: &str) -> Result<Duration, String> {
+    match parse_duration(s)? {
+        Duration::ZERO => Err("interval must be greater than zero".to_string()),
+        interval => Ok(interval),
+    }
+}
+
+/// Parse a duration like `90s`, `15m`, `12h`, `7d` or a plain number of seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let (number, unit_secs) = match s.char_indices().last() {
+        Some((i, 's')) => (&s[..i], 1),
+        Some((i, 'm')) => (&s[..i], 60),
+        Some((i, 'h')) => (&s[..i], 60 * 60),
+        Some((i, 'd')) => (&s[..i], 24 * 60 * 60),
+        _ => (s, 1),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{}` (expected e.g. 90s, 15m, 12h, 7d)", s))?;
+    number
+        .checked_mul(unit_secs)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("duration `{}` is too large", s))
+}
+
+fn config_error(kind: ErrorKind, path: &Path, msg: String) -> clap::Error {
+    Args::command().error(kind, format!("{}: {}", path.display(), msg))
+}
+
+/// `argv` without the flags (and their values) of settings whose environment
+/// variable is set according to `env`, which take precedence
+fn without_env_overridden(
+    cmd: &clap::Command,
+    argv: Vec<OsString>,
+    env: impl Fn(&OsStr) -> Option<OsString>,
+) -> Vec<OsString> {
+    let overridden: Vec<(String, bool)> = cmd
+        .get_arguments()
+        .filter(|arg| {
+            arg.get_env()
+                .and_then(&env)
+                .is_some_and(|value| !value.is_empty())
+        })
+        .filter_map(|arg| {
+            let long = arg.get_long()?;
+            Some((format!("--{}", long), arg.get_action().takes_values()))
+        })
+        .collect();
+    if overridden.is_empty() {
+        return argv;
+    }
+
+    let mut kept = Vec::with_capacity(argv.len());
+    let mut args = argv.into_iter();
+    kept.extend(args.next());
+    while let Some(arg) = args.next() {
+        let text = arg.to_string_lossy();
+        if text == "--" {
+            kept.push(arg);
+            kept.extend(args);
+            break;
+        }
+        let flag = overridden.iter().find(|(flag, _)| {
+            text == flag.as_str()
+                || text
+                    .strip_prefix(flag.as_str())
+                    .is_some_and(|rest| rest.starts_with('='))
+        });
+        match flag {
+            // `--flag value`: drop the value too
+            Some((flag, true)) if text == flag.as_str() => {
+                args.next();
+            }
+            Some(_) => {}
+            None => kept.push(arg),
+        }
+    }
+    kept
+}
+
+/// Read a config file into a flat key → value map: TOML if its name ends in
+/// `.toml`, YAML (or JSON) otherwise
+fn read_config_file(path: &Path) -> Result<BTreeMap<String, serde_json::Value>, clap::Error> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| config_error(ErrorKind::Io, path, e.to_string()))?;
+    if contents.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        return toml::from_str(&contents)
+            .map_err(|e| config_error(ErrorKind::InvalidValue, path, e.to_string()));
+    }
+    serde_yaml::from_str(&contents)
+        .map_err(|e| config_error(ErrorKind::InvalidValue, path, e.to_string()))
+}
+
+/// Translate one config file entry into the equivalent command-line flag(s)
+fn push_file_value(
+    argv: &mut Vec<OsString>,
+    key: &str,
+    action: &ArgAction,
+    value: serde_json::Value,
+) -> Result<(), String> {
+    use serde_json::Value;
+
+    let flag = format!("--{}", key);
+    match (action, value) {
+        (ArgAction::SetTrue, Value::Bool(true)) => argv.push(flag.into()),
+        (ArgAction::SetTrue, Value::Bool(false)) => {}
+        (ArgAction::SetTrue, _) => return Err(format!("`{}` must be a boolean", key)),
+        (ArgAction::Append, Value::Array(items)) => {
+            for item in items {
+                argv.push(format!("{}={}", flag, scalar(key, item)?).into());
+            }
+        }
+        (_, value) => argv.push(format!("{}={}", flag, scalar(key, value)?).into()),
+    }
+    Ok(())
+}
+
+fn scalar(key: &str, value: serde_json::Value) -> Result<String, String> {
+    use serde_json::Value;
+
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(format!("`{}` must be a string, number or boolean", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn write_config(name: &str, contents: &str) -> PathBuf {
+        write_config_as(name, "yaml", contents)
+    }
+
+    fn write_config_as(name: &str, extension: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "nlc-config-test-{}-{}.{}",
+            std::process::id(),
+            name,
+            extension
+        ));
+        let mut f = std::fs::File::create(&path).unwrap();
+        f.write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_defaults_without_config() {
+        let args = Args::try_load_from(["nlc", "--mode", "controller"]).unwrap();
+        assert_eq!(args.base_path, PathBuf::from("/var/node-local-cache"));
+        assert_eq!(args.log_level, Level::INFO);
+        assert_eq!(args.log_format, LogFormat::Json);
+        assert!(!args.no_cleanup_service);
+        assert_eq!(
+            args.controller_cleanup_interval(),
+            cleanup::CONTROLLER_CLEANUP_INTERVAL
+        );
+        assert_eq!(args.node_cleanup_interval(), cleanup::NODE_CLEANUP_INTERVAL);
+        assert_eq!(args.cleanup_max_retries, cleanup::MAX_RETRIES);
+    }
+
+    #[test]
+    fn test_cleanup_tuning() {
+        let args = Args::try_load_from([
+            "nlc",
+            "--mode",
+            "node",
+            "--node-cleanup-interval",
+            "30s",
+            "--controller-cleanup-interval",
+            "5m",
+            "--cleanup-max-retries",
+            "30",
+        ])
+        .unwrap();
+        assert_eq!(args.node_cleanup_interval(), Duration::from_secs(30));
+        assert_eq!(args.controller_cleanup_interval(), Duration::from_secs(300));
+        assert_eq!(args.cleanup_max_retries, 30);
+
+        for invalid in [
+            ["--node-cleanup-interval", "0"],
+            ["--controller-cleanup-interval", "0s"],
+            ["--cleanup-max-retries", "0"],
+        ] {
+            let argv = ["nlc", "--mode", "node", invalid[0], invalid[1]];
+            assert!(Args::try_load_from(argv).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_config_file_overrides_defaults() {
+        let path = write_config(
+            "file",
+            "mode: node\nbase-path: /mnt/cache\nlog-level: debug\nlog-format: text\n\
+             no-cleanup-service: true\n",
+        );
+        let args =
+            Args::try_load_from(["nlc".into(), "--config".into(), path.as_os_str().to_owned()])
+                .unwrap();
+        assert!(matches!(args.mode, Mode::Node));
+        assert_eq!(args.base_path, PathBuf::from("/mnt/cache"));
+        assert_eq!(args.log_level, Level::DEBUG);
+        assert_eq!(args.log_format, LogFormat::Text);
+        assert!(args.no_cleanup_service);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_flags_override_config_file() {
+        let path = write_config("flags", "mode: node\nbase-path: /mnt/cache\n");
+        let args = Args::try_load_from([
+            "nlc".into(),
+            "--config".into(),
+            path.as_os_str().to_owned(),
+            "--mode".into(),
+            "controller".into(),
+            "--base-path".into(),
+            "/mnt/other".into(),
+        ])
+        .unwrap();
+        assert!(matches!(args.mode, Mode::Controller));
+        assert_eq!(args.base_path, PathBuf::from("/mnt/other"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_toml_config_file() {
+        let path = write_config_as(
+            "toml",
+            "toml",
+            "mode = \"node\"\nbase-path = \"/mnt/cache\"\nno-cleanup-service = true\n\
+             allowed-base-path = [\"/mnt/nvme1\", \"/mnt/nvme2\"]\n",
+        );
+        let args =
+            Args::try_load_from(["nlc".into(), "--config".into(), path.as_os_str().to_owned()])
+                .unwrap();
+        assert!(matches!(args.mode, Mode::Node));
+        assert_eq!(args.base_path, PathBuf::from("/mnt/cache"));
+        assert!(args.no_cleanup_service);
+        assert_eq!(
+            args.allowed_base_path,
+            [PathBuf::from("/mnt/nvme1"), PathBuf::from("/mnt/nvme2")]
+        );
+        let _ = std::fs::remove_file(path);
+
+        let path = write_config_as("toml-invalid", "toml", "mode: node\n");
+        let err =
+            Args::try_load_from(["nlc".into(), "--config".into(), path.as_os_str().to_owned()])
+                .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_env_overrides_flags() {
+        let cmd = clap::Command::new("nlc")
+            .arg(
+                clap::Arg::new("node-name")
+                    .long("node-name")
+                    .env("NLC_TEST_CONFIG_NODE_NAME"),
+            )
+            .arg(
+                clap::Arg::new("dry-run")
+                    .long("dry-run")
+                    .env("NLC_TEST_CONFIG_DRY_RUN")
+                    .action(ArgAction::SetTrue),
+            )
+            .arg(clap::Arg::new("mode").long("mode"));
+        let argv = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &OsStr| {
+                vars.iter()
+                    .find(|(var, _)| name == *var)
+                    .map(|(_, value)| OsString::from(value))
+            }
+        };
+        let full = argv(&[
+            "nlc",
+            "--node-name",
+            "flag-a",
+            "--mode",
+            "node",
+            "--dry-run",
+            "--node-name=flag-b",
+            "--",
+            "--node-name",
+        ]);
+
+        // Not set, or set empty: flags stay
+        assert_eq!(without_env_overridden(&cmd, full.clone(), env(&[])), full);
+        assert_eq!(
+            without_env_overridden(
+                &cmd,
+                full.clone(),
+                env(&[("NLC_TEST_CONFIG_NODE_NAME", "")])
+            ),
+            full
+        );
+        // Both forms go, with their value; a flag without one leaves the next
+        // argument alone
+        assert_eq!(
+            without_env_overridden(
+                &cmd,
+                full,
+                env(&[
+                    ("NLC_TEST_CONFIG_NODE_NAME", "from-env"),
+                    ("NLC_TEST_CONFIG_DRY_RUN", "true"),
+                ])
+            ),
+            argv(&["nlc", "--mode", "node", "--", "--node-name"])
+        );
+        let flag_only = argv(&["nlc", "--dry-run", "--mode", "node"]);
+        assert_eq!(
+            without_env_overridden(&cmd, flag_only, env(&[("NLC_TEST_CONFIG_DRY_RUN", "1")])),
+            argv(&["nlc", "--mode", "node"])
+        );
+    }
+
+    #[test]
+    fn test_id_namespace() {
+        let args = Args::try_load_from(["nlc", "--mode", "controller"]).unwrap();
+        assert_eq!(args.id_namespace, volume::VOLUME_ID_NAMESPACE);
+
+        let ns = "0b6e4c2a-3f1d-4e8b-9a7c-5d2f1e0a9b3c";
+        let args =
+            Args::try_load_from(["nlc", "--mode", "controller", "--id-namespace", ns]).unwrap();
+        assert_eq!(args.id_namespace.to_string(), ns);
+
+        let err = Args::try_load_from(["nlc", "--mode", "controller", "--id-namespace", "tier-1"])
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("12h"), Ok(Duration::from_secs(43200)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604800)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("-1h").is_err());
+    }
+
+    #[test]
+    fn test_effective_config_redacts_secrets() {
+        let args = Args::try_load_from([
+            "nlc",
+            "--mode",
+            "node",
+            "--admin-token",
+            "s3cret",
+            "--max-volume-age",
+            "7d",
+        ])
+        .unwrap();
+        let config = args.effective_config();
+
+        assert_eq!(config["driver_name"], DRIVER_NAME);
+        assert_eq!(config["settings"]["mode"], "node");
+        assert_eq!(config["settings"]["base-path"], "/var/node-local-cache");
+        assert_eq!(config["settings"]["directory-backend"], "dir");
+        assert_eq!(config["settings"]["quota-backend"], "none");
+        assert_eq!(config["settings"]["log-level"], "INFO");
+        assert_eq!(config["settings"]["max-volume-age"], "604800s");
+        assert_eq!(config["settings"]["admin-token"], REDACTED);
+        assert_eq!(config["intervals"]["node-cleanup"], "10s");
+        assert!(!config.to_string().contains("s3cret"));
+    }
+
+    #[test]
+    fn test_enable_xfs_quota() {
+        let args = Args::try_load_from(["nlc", "--mode", "node", "--enable-xfs-quota"]).unwrap();
+        assert!(args.enable_xfs_quota);
+        assert_eq!(args.quota_backend, QuotaBackend::None);
+
+        let err = Args::try_load_from([
+            "nlc",
+            "--mode",
+            "node",
+            "--enable-xfs-quota",
+            "--quota-backend",
+            "loopback",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn test_unknown_key_rejected() {
+        let path = write_config("unknown", "mode: node\nbase-pth: /mnt/cache\n");
+        let err =
+            Args::try_load_from(["nlc".into(), "--config".into(), path.as_os_str().to_owned()])
+                .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnknownArgument);
+        assert!(err.to_string().contains("base-pth"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_invalid_value_rejected() {
+        let path = write_config("invalid", "mode: node\nno-cleanup-service: yes please\n");
+        let err =
+            Args::try_load_from(["nlc".into(), "--config".into(), path.as_os_str().to_owned()])
+                .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidValue);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,58 +1,344 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
 use tonic::{Request, Response, Status};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
 
-use crate::cleanup::CleanupController;
+use crate::cleanup::{self, CleanupController, CreateRecord};
 use crate::csi::{
-    controller_server::Controller, controller_service_capability, ControllerExpandVolumeRequest,
-    ControllerExpandVolumeResponse, ControllerGetCapabilitiesRequest,
-    ControllerGetCapabilitiesResponse, ControllerGetVolumeRequest, ControllerGetVolumeResponse,
-    ControllerModifyVolumeRequest, ControllerModifyVolumeResponse, ControllerPublishVolumeRequest,
+    controller_get_volume_response,
+    controller_server::Controller,
+    controller_service_capability, list_volumes_response,
+    volume_capability::{self, access_mode},
+    volume_content_source, ControllerExpandVolumeRequest, ControllerExpandVolumeResponse,
+    ControllerGetCapabilitiesRequest, ControllerGetCapabilitiesResponse,
+    ControllerGetVolumeRequest, ControllerGetVolumeResponse, ControllerModifyVolumeRequest,
+    ControllerModifyVolumeResponse, ControllerPublishVolumeRequest,
     ControllerPublishVolumeResponse, ControllerServiceCapability, ControllerUnpublishVolumeRequest,
     ControllerUnpublishVolumeResponse, CreateSnapshotRequest, CreateSnapshotResponse,
     CreateVolumeRequest, CreateVolumeResponse, DeleteSnapshotRequest, DeleteSnapshotResponse,
     DeleteVolumeRequest, DeleteVolumeResponse, GetCapacityRequest, GetCapacityResponse,
     ListSnapshotsRequest, ListSnapshotsResponse, ListVolumesRequest, ListVolumesResponse,
     ValidateVolumeCapabilitiesRequest, ValidateVolumeCapabilitiesResponse, Volume,
+    VolumeCapability, VolumeCondition,
 };
 
+use crate::history;
+use crate::idmap;
+use crate::mount_options;
+use crate::ownership;
+use crate::shutdown::InFlight;
+use crate::tmpfs;
 use crate::volume;
 
+/// Cleanup requests DeleteVolume failed to create that can wait for a retry
+const CLEANUP_RETRY_QUEUE: usize = 1024;
+/// Attempts at creating a cleanup request, DeleteVolume's included, before
+/// giving up on it
+const CLEANUP_RETRY_ATTEMPTS: u32 = 20;
+/// Delay before the first retry, doubling up to `CLEANUP_RETRY_MAX_DELAY`
+const CLEANUP_RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+const CLEANUP_RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
 pub struct ControllerService {
     cleanup: Option<Arc<RwLock<CleanupController>>>,
+    /// Volumes whose cleanup request is to be retried in the background
+    cleanup_retries: Option<mpsc::Sender<String>>,
+    /// Record CreateVolume requests and enforce idempotency against them
+    stateful_create: bool,
+    /// Capacity reported for volumes requested without one
+    default_capacity_bytes: Option<i64>,
+    /// Largest capacity a volume may be created or expanded to
+    max_volume_bytes: Option<i64>,
+    /// CreateVolume/DeleteVolume calls being served, drained on shutdown
+    in_flight: InFlight,
 }
 
 impl ControllerService {
     pub fn new() -> Self {
-        Self { cleanup: None }
+        Self {
+            cleanup: None,
+            cleanup_retries: None,
+            stateful_create: false,
+            default_capacity_bytes: None,
+            max_volume_bytes: None,
+            in_flight: InFlight::new(),
+        }
     }
 
+    /// Track volumes through `cleanup`. Cleanup requests DeleteVolume fails
+    /// to create are retried by a background task (needs a Tokio runtime)
     pub fn with_cleanup(cleanup: CleanupController) -> Self {
+        let cleanup = Arc::new(RwLock::new(cleanup));
+        let (retries, queue) = mpsc::channel(CLEANUP_RETRY_QUEUE);
+        tokio::spawn(retry_cleanup_requests(cleanup.clone(), queue));
         Self {
-            cleanup: Some(Arc::new(RwLock::new(cleanup))),
+            cleanup: Some(cleanup),
+            cleanup_retries: Some(retries),
+            stateful_create: false,
+            default_capacity_bytes: None,
+            max_volume_bytes: None,
+            in_flight: InFlight::new(),
         }
     }
-}
 
-#[tonic::async_trait]
-impl Controller for ControllerService {
-    async fn create_volume(
+    /// Record each created volume's request in its tracking ConfigMap, so a
+    /// repeated CreateVolume with other parameters fails with AlreadyExists
+    /// (needs the cleanup service)
+    pub fn with_stateful_create(mut self, enabled: bool) -> Self {
+        self.stateful_create = enabled;
+        self
+    }
+
+    /// Report `capacity` for volumes requested without a capacity, instead
+    /// of 0 ("unknown"). Only reported: nothing enforces it on the node
+    pub fn with_default_capacity(mut self, capacity: Option<i64>) -> Self {
+        self.default_capacity_bytes = capacity;
+        self
+    }
+
+    /// Reject volumes asking for more than `max` bytes with OutOfRange,
+    /// e.g. more than the nodes' disks hold. Unlimited when `None`
+    pub fn with_max_volume_bytes(mut self, max: Option<i64>) -> Self {
+        self.max_volume_bytes = max;
+        self
+    }
+
+    /// Count CreateVolume/DeleteVolume calls in `in_flight`
+    pub fn with_in_flight(mut self, in_flight: InFlight) -> Self {
+        self.in_flight = in_flight;
+        self
+    }
+
+    /// Record the request of a volume (`--stateful-create`), or check it
+    /// against the one recorded. Returns the volume's capacity.
+    #[allow(clippy::result_large_err)]
+    async fn record_create(
         &self,
-        request: Request<CreateVolumeRequest>,
+        volume_id: &str,
+        req: &CreateVolumeRequest,
+    ) -> Result<i64, Status> {
+        let Some(cleanup) = &self.cleanup else {
+            return Err(Status::failed_precondition(
+                "Stateful CreateVolume needs the cleanup service",
+            ));
+        };
+        let (required_bytes, limit_bytes) = req
+            .capacity_range
+            .as_ref()
+            .map_or((0, 0), |c| (c.required_bytes, c.limit_bytes));
+        let parameters: BTreeMap<String, String> = req.parameters.clone().into_iter().collect();
+        let record = CreateRecord {
+            name: req.name.clone(),
+            capacity_bytes: required_bytes,
+            parameters: parameters.clone(),
+        };
+        let status = cleanup
+            .read()
+            .await
+            .record_create(
+                volume_id,
+                &record,
+                cleanup::PvcRef::from_volume_context(&req.parameters).as_ref(),
+                &volume::labels_from_parameters(&req.parameters),
+            )
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to record volume: {}", e)))?;
+
+        if status.cleanup_requested_at.is_some() {
+            return Err(Status::aborted(format!(
+                "Volume {} is still being deleted",
+                req.name
+            )));
+        }
+        match status.create_request {
+            Some(existing) if existing.is_compatible(&parameters, required_bytes, limit_bytes) => {
+                Ok(existing.capacity_bytes)
+            }
+            Some(existing) => {
+                warn!(
+                    name = %req.name,
+                    volume_id = %volume_id,
+                    recorded_capacity = existing.capacity_bytes,
+                    "CreateVolume repeated with incompatible parameters"
+                );
+                Err(Status::already_exists(format!(
+                    "Volume {} already exists with a different capacity or parameters",
+                    req.name
+                )))
+            }
+            // Only in cleanup, checked above
+            None => Ok(required_bytes),
+        }
+    }
+
+    /// CreateVolume; the RPC handler records its result in the history
+    async fn provision_volume(
+        &self,
+        req: CreateVolumeRequest,
     ) -> Result<Response<CreateVolumeResponse>, Status> {
-        let req = request.into_inner();
         info!(name = %req.name, "CreateVolume called");
 
         // Generate deterministic volume ID from request name (which is pvc-<uid> from external-provisioner)
         // This ensures idempotency - retries produce the same volume ID
         let volume_id = volume::generate_volume_id(&req.name);
+        check_capabilities(&req.volume_capabilities)?;
+        volume::check_capacity_range(req.capacity_range.as_ref(), self.max_volume_bytes)
+            .map_err(Status::out_of_range)?;
         let capacity_bytes = req
             .capacity_range
             .as_ref()
             .map(|c| c.required_bytes)
             .unwrap_or(0);
 
+        // Pass the shared cache name from the StorageClass through to the node
+        let mut volume_context = std::collections::HashMap::new();
+        if let Some(name) = req.parameters.get(volume::SHARED_NAME_KEY) {
+            if !volume::validate_shared_name(name) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} parameter: {}",
+                    volume::SHARED_NAME_KEY,
+                    name
+                )));
+            }
+            volume_context.insert(volume::SHARED_NAME_KEY.to_string(), name.clone());
+        }
+        // ID mappings for idmapped mounts (--enable-idmapped-mounts on the node)
+        for key in [volume::UID_MAP_KEY, volume::GID_MAP_KEY] {
+            if let Some(map) = req.parameters.get(key) {
+                if let Err(e) = idmap::parse_mappings(map) {
+                    return Err(Status::invalid_argument(format!(
+                        "Invalid {} parameter: {}",
+                        key, e
+                    )));
+                }
+                volume_context.insert(key.to_string(), map.clone());
+            }
+        }
+        if let Some(subpaths) = req.parameters.get(volume::READONLY_SUBPATHS_KEY) {
+            if let Err(e) = volume::parse_readonly_subpaths(subpaths) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} parameter: {}",
+                    volume::READONLY_SUBPATHS_KEY,
+                    e
+                )));
+            }
+            volume_context.insert(volume::READONLY_SUBPATHS_KEY.to_string(), subpaths.clone());
+        }
+        if let Some(backing) = req.parameters.get(volume::BACKING_KEY) {
+            match tmpfs::Backing::parse(backing) {
+                Err(e) => return Err(Status::invalid_argument(e)),
+                Ok(tmpfs::Backing::Tmpfs) if capacity_bytes <= 0 => {
+                    return Err(Status::invalid_argument(
+                        "tmpfs-backed volumes need a requested capacity",
+                    ))
+                }
+                Ok(_) => {}
+            }
+            volume_context.insert(volume::BACKING_KEY.to_string(), backing.clone());
+        }
+        ownership::Ownership::from_volume_context(&req.parameters)
+            .map_err(Status::invalid_argument)?;
+        for key in [volume::UID_KEY, volume::GID_KEY, volume::MODE_KEY] {
+            if let Some(value) = req.parameters.get(key) {
+                volume_context.insert(key.to_string(), value.clone());
+            }
+        }
+        for (key, _) in mount_options::CONTEXT_FLAGS {
+            if let Some(value) = req.parameters.get(key) {
+                mount_options::parse_context_flag(key, value).map_err(Status::invalid_argument)?;
+                volume_context.insert(key.to_string(), value.clone());
+            }
+        }
+        if let Some(path) = req.parameters.get(volume::WAIT_FOR_PATH_KEY) {
+            if let Err(e) = volume::parse_wait_for_path(path) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} parameter: {}",
+                    volume::WAIT_FOR_PATH_KEY,
+                    e
+                )));
+            }
+            volume_context.insert(volume::WAIT_FOR_PATH_KEY.to_string(), path.clone());
+        }
+        // Checked against the node's --allowed-base-path at publish
+        if let Some(path) = req.parameters.get(volume::BASE_PATH_KEY) {
+            if let Err(e) = volume::parse_base_path(path) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} parameter: {}",
+                    volume::BASE_PATH_KEY,
+                    e
+                )));
+            }
+            volume_context.insert(volume::BASE_PATH_KEY.to_string(), path.clone());
+        }
+        // Recorded on the tracking ConfigMap at publish, for --eviction-threshold
+        if let Some(priority) = req.parameters.get(volume::EVICTION_PRIORITY_KEY) {
+            if let Err(e) = volume::parse_eviction_priority(priority) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid {} parameter: {}",
+                    volume::EVICTION_PRIORITY_KEY,
+                    e
+                )));
+            }
+            volume_context.insert(volume::EVICTION_PRIORITY_KEY.to_string(), priority.clone());
+        }
+        // Enforced on the node when it runs with a --quota-backend
+        if capacity_bytes > 0 {
+            volume_context.insert(volume::CAPACITY_KEY.to_string(), capacity_bytes.to_string());
+        }
+        // Labels for the tracking ConfigMap, which the node creates
+        for (key, value) in volume::labels_from_parameters(&req.parameters) {
+            volume_context.insert(format!("{}{}", volume::LABEL_PARAM_PREFIX, key), value);
+        }
+        // Lets the node find the PV to own the tracking ConfigMap (--set-owner-references)
+        if let Some(pv_name) = req.parameters.get(volume::PV_NAME_KEY) {
+            volume_context.insert(volume::PV_NAME_KEY.to_string(), pv_name.clone());
+        }
+        // Lets events about the volume go to the PVC's namespace (--event-namespace pvc)
+        for key in [volume::PVC_NAME_KEY, volume::PVC_NAMESPACE_KEY] {
+            if let Some(value) = req.parameters.get(key) {
+                volume_context.insert(key.to_string(), value.clone());
+            }
+        }
+        // Copied on the node, from its own copy of the source volume
+        if let Some(source) = &req.volume_content_source {
+            match &source.r#type {
+                Some(volume_content_source::Type::Volume(source)) => {
+                    if !volume::validate_volume_id(&source.volume_id)
+                        || source.volume_id == volume_id
+                    {
+                        return Err(Status::invalid_argument(format!(
+                            "Invalid source volume ID: {}",
+                            source.volume_id
+                        )));
+                    }
+                    if volume_context.contains_key(volume::SHARED_NAME_KEY) {
+                        return Err(Status::invalid_argument(
+                            "Volumes of a shared cache can't be cloned into",
+                        ));
+                    }
+                    volume_context
+                        .insert(volume::CLONE_FROM_KEY.to_string(), source.volume_id.clone());
+                }
+                Some(volume_content_source::Type::Snapshot(_)) => {
+                    return Err(Status::invalid_argument("Snapshots are not supported"));
+                }
+                None => {}
+            }
+        }
+
+        let capacity_bytes = if self.stateful_create {
+            self.record_create(&volume_id, &req).await?
+        } else {
+            capacity_bytes
+        };
+        // No capacity requested: the default if configured, else 0 (unknown)
+        let capacity_bytes = match (capacity_bytes, self.default_capacity_bytes) {
+            (0, Some(default)) => default,
+            (capacity, _) => capacity,
+        };
+
         info!(volume_id = %volume_id, capacity = capacity_bytes, "Volume created");
 
         Ok(Response::new(CreateVolumeResponse {
@@ -61,33 +347,65 @@ impl Controller for ControllerService {
                 capacity_bytes,
                 // No topology constraints - accessible from any node
                 accessible_topology: vec![],
-                volume_context: Default::default(),
-                content_source: None,
+                volume_context,
+                content_source: req.volume_content_source,
             }),
         }))
     }
+}
+
+#[tonic::async_trait]
+impl Controller for ControllerService {
+    async fn create_volume(
+        &self,
+        request: Request<CreateVolumeRequest>,
+    ) -> Result<Response<CreateVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = volume::generate_volume_id(&req.name);
+        let result = self.provision_volume(req).await;
+        history::history().record(
+            "create",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
+    }
 
     async fn delete_volume(
         &self,
         request: Request<DeleteVolumeRequest>,
     ) -> Result<Response<DeleteVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
         let req = request.into_inner();
         info!(volume_id = %req.volume_id, "DeleteVolume called");
 
         // Create cleanup request if cleanup controller is available
         if let Some(cleanup) = &self.cleanup {
             let cleanup = cleanup.read().await;
-            if let Err(e) = cleanup.create_cleanup_request(&req.volume_id).await {
+            let result = cleanup.create_cleanup_request(&req.volume_id).await;
+            history::history().record("delete", &req.volume_id, &result);
+            if let Err(e) = result {
+                // The volume is gone for the CO either way; retry in the background
+                let queued = self
+                    .cleanup_retries
+                    .as_ref()
+                    .is_some_and(|retries| retries.try_send(req.volume_id.clone()).is_ok());
                 warn!(
                     volume_id = %req.volume_id,
                     error = %e,
+                    retrying = queued,
                     "Failed to create cleanup request, continuing anyway"
                 );
                 cleanup
                     .emit_event(
                         &req.volume_id,
                         "CleanupRequestFailed",
-                        &format!("Failed to create cleanup request: {}", e),
+                        &format!(
+                            "Failed to create cleanup request{}: {}",
+                            if queued { ", retrying" } else { "" },
+                            e
+                        ),
                         "Warning",
                     )
                     .await;
@@ -103,13 +421,26 @@ impl Controller for ControllerService {
     ) -> Result<Response<ControllerGetCapabilitiesResponse>, Status> {
         info!("ControllerGetCapabilities called");
 
-        let capabilities = vec![ControllerServiceCapability {
-            r#type: Some(controller_service_capability::Type::Rpc(
-                controller_service_capability::Rpc {
-                    r#type: controller_service_capability::rpc::Type::CreateDeleteVolume as i32,
-                },
-            )),
-        }];
+        let mut rpcs = vec![
+            controller_service_capability::rpc::Type::CreateDeleteVolume,
+            controller_service_capability::rpc::Type::ExpandVolume,
+            controller_service_capability::rpc::Type::CloneVolume,
+        ];
+        // Volumes are only known through their tracking ConfigMaps
+        if self.cleanup.is_some() {
+            rpcs.push(controller_service_capability::rpc::Type::GetVolume);
+            rpcs.push(controller_service_capability::rpc::Type::ListVolumes);
+            rpcs.push(controller_service_capability::rpc::Type::ListVolumesPublishedNodes);
+            rpcs.push(controller_service_capability::rpc::Type::VolumeCondition);
+        }
+        let capabilities = rpcs
+            .into_iter()
+            .map(|rpc| ControllerServiceCapability {
+                r#type: Some(controller_service_capability::Type::Rpc(
+                    controller_service_capability::Rpc { r#type: rpc as i32 },
+                )),
+            })
+            .collect();
 
         Ok(Response::new(ControllerGetCapabilitiesResponse {
             capabilities,
@@ -123,26 +454,17 @@ impl Controller for ControllerService {
         let req = request.into_inner();
         info!(volume_id = %req.volume_id, "ValidateVolumeCapabilities called");
 
-        // Validate each capability - we only support filesystem mounts, not block volumes
-        for cap in &req.volume_capabilities {
-            if let Some(access_type) = &cap.access_type {
-                match access_type {
-                    crate::csi::volume_capability::AccessType::Mount(_) => {
-                        // Filesystem mounts are supported with any access mode
-                        // Note: for this driver, "multi-node" access modes work but each node
-                        // sees its own independent cache (that's the feature, not a bug)
-                    }
-                    crate::csi::volume_capability::AccessType::Block(_) => {
-                        // Block volumes not supported
-                        info!(volume_id = %req.volume_id, "Rejecting block volume capability");
-                        return Ok(Response::new(ValidateVolumeCapabilitiesResponse {
-                            confirmed: None,
-                            message: "Block volumes are not supported, only filesystem mounts"
-                                .to_string(),
-                        }));
-                    }
-                }
+        if let Err(status) = check_capabilities(&req.volume_capabilities) {
+            // A missing field is an error, an unsupported capability only
+            // goes unconfirmed
+            if req.volume_capabilities.is_empty() {
+                return Err(status);
             }
+            info!(volume_id = %req.volume_id, reason = %status.message(), "Rejecting volume capabilities");
+            return Ok(Response::new(ValidateVolumeCapabilitiesResponse {
+                confirmed: None,
+                message: status.message().to_string(),
+            }));
         }
 
         // All capabilities validated - confirm them
@@ -181,9 +503,52 @@ impl Controller for ControllerService {
 
     async fn list_volumes(
         &self,
-        _request: Request<ListVolumesRequest>,
+        request: Request<ListVolumesRequest>,
     ) -> Result<Response<ListVolumesResponse>, Status> {
-        Err(Status::unimplemented("ListVolumes not supported"))
+        let req = request.into_inner();
+        info!(
+            max_entries = req.max_entries,
+            starting_token = %req.starting_token,
+            "ListVolumes called"
+        );
+
+        let Some(cleanup) = &self.cleanup else {
+            return Err(Status::unimplemented(
+                "ListVolumes needs the cleanup service",
+            ));
+        };
+        if req.max_entries < 0 {
+            return Err(Status::invalid_argument("max_entries must not be negative"));
+        }
+        let statuses = cleanup
+            .read()
+            .await
+            .volume_statuses()
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to list volumes: {}", e)))?;
+        let (page, next_token) =
+            list_page(statuses, &req.starting_token, req.max_entries as usize)?;
+
+        let entries = page
+            .into_iter()
+            .map(|status| list_volumes_response::Entry {
+                volume: Some(Volume {
+                    capacity_bytes: tracked_capacity(&status),
+                    volume_id: status.volume_id.clone(),
+                    accessible_topology: vec![],
+                    volume_context: Default::default(),
+                    content_source: None,
+                }),
+                status: Some(list_volumes_response::VolumeStatus {
+                    published_node_ids: status.published_nodes().into_iter().cloned().collect(),
+                    volume_condition: Some(volume_condition(&status)),
+                }),
+            })
+            .collect();
+        Ok(Response::new(ListVolumesResponse {
+            entries,
+            next_token,
+        }))
     }
 
     async fn get_capacity(
@@ -216,18 +581,80 @@ impl Controller for ControllerService {
 
     async fn controller_expand_volume(
         &self,
-        _request: Request<ControllerExpandVolumeRequest>,
+        request: Request<ControllerExpandVolumeRequest>,
     ) -> Result<Response<ControllerExpandVolumeResponse>, Status> {
-        Err(Status::unimplemented(
-            "ControllerExpandVolume not supported",
-        ))
+        let req = request.into_inner();
+        info!(volume_id = %req.volume_id, "ControllerExpandVolume called");
+
+        if req.volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID is required"));
+        }
+        if !volume::validate_volume_id(&req.volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                req.volume_id
+            )));
+        }
+        if req.capacity_range.is_none() {
+            return Err(Status::invalid_argument("Capacity range is required"));
+        }
+        // Nothing to resize here: each node raises the limit of its own copy
+        let capacity =
+            volume::expanded_capacity(req.capacity_range.as_ref()).map_err(Status::out_of_range)?;
+        if let Some(max) = self.max_volume_bytes {
+            if capacity > max as u64 {
+                return Err(Status::out_of_range(format!(
+                    "Capacity {} exceeds the maximum volume size of {} bytes",
+                    capacity, max
+                )));
+            }
+        }
+        Ok(Response::new(ControllerExpandVolumeResponse {
+            capacity_bytes: capacity as i64,
+            node_expansion_required: true,
+        }))
     }
 
     async fn controller_get_volume(
         &self,
-        _request: Request<ControllerGetVolumeRequest>,
+        request: Request<ControllerGetVolumeRequest>,
     ) -> Result<Response<ControllerGetVolumeResponse>, Status> {
-        Err(Status::unimplemented("ControllerGetVolume not supported"))
+        let req = request.into_inner();
+        info!(volume_id = %req.volume_id, "ControllerGetVolume called");
+
+        let Some(cleanup) = &self.cleanup else {
+            return Err(Status::unimplemented(
+                "ControllerGetVolume needs the cleanup service",
+            ));
+        };
+        if !volume::validate_volume_id(&req.volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                req.volume_id
+            )));
+        }
+        let status = cleanup
+            .read()
+            .await
+            .volume_status(&req.volume_id)
+            .await
+            .map_err(|e| Status::unavailable(format!("Failed to get volume status: {}", e)))?
+            .ok_or_else(|| Status::not_found(format!("Volume {} not found", req.volume_id)))?;
+
+        Ok(Response::new(ControllerGetVolumeResponse {
+            volume: Some(Volume {
+                volume_id: req.volume_id,
+                capacity_bytes: tracked_capacity(&status),
+                accessible_topology: vec![],
+                volume_context: Default::default(),
+                content_source: None,
+            }),
+            status: Some(controller_get_volume_response::VolumeStatus {
+                // Nodes that have the volume until they clean it up
+                published_node_ids: status.published_nodes().into_iter().cloned().collect(),
+                volume_condition: Some(volume_condition(&status)),
+            }),
+        }))
     }
 
     async fn controller_modify_volume(
@@ -239,3 +666,850 @@ impl Controller for ControllerService {
         ))
     }
 }
+
+/// Retry the cleanup requests DeleteVolume failed to create, each in its own
+/// task, until the queue's sender is dropped
+async fn retry_cleanup_requests(
+    cleanup: Arc<RwLock<CleanupController>>,
+    mut queue: mpsc::Receiver<String>,
+) {
+    while let Some(volume_id) = queue.recv().await {
+        tokio::spawn(retry_cleanup_request(cleanup.clone(), volume_id));
+    }
+}
+
+/// Retry the cleanup request of a volume with exponential backoff. Giving
+/// up leaves the volume's directories on its nodes, reported by a Warning
+/// event.
+async fn retry_cleanup_request(cleanup: Arc<RwLock<CleanupController>>, volume_id: String) {
+    let mut delay = CLEANUP_RETRY_BASE_DELAY;
+    // The first attempt was DeleteVolume's
+    for attempt in 2..=CLEANUP_RETRY_ATTEMPTS {
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(CLEANUP_RETRY_MAX_DELAY);
+
+        let cleanup = cleanup.read().await;
+        let result = cleanup.create_cleanup_request(&volume_id).await;
+        match &result {
+            Ok(()) => {
+                info!(volume_id = %volume_id, attempt = attempt, "Created cleanup request on retry");
+                history::history().record("delete", &volume_id, &result);
+                return;
+            }
+            Err(e) if attempt < CLEANUP_RETRY_ATTEMPTS => {
+                warn!(volume_id = %volume_id, attempt = attempt, error = %e, "Failed to create cleanup request, retrying");
+            }
+            Err(e) => {
+                error!(volume_id = %volume_id, attempts = attempt, error = %e, "Giving up creating cleanup request");
+                history::history().record("delete", &volume_id, &result);
+                cleanup
+                    .emit_event(
+                        &volume_id,
+                        "CleanupRequestAbandoned",
+                        &format!(
+                            "Gave up creating cleanup request after {} attempts, the volume's \
+                             directories stay on its nodes: {}",
+                            attempt, e
+                        ),
+                        "Warning",
+                    )
+                    .await;
+            }
+        }
+    }
+}
+
+/// Check that `caps` are present and all supported: filesystem mounts only,
+/// in any access mode but ReadOnlyMany
+#[allow(clippy::result_large_err)]
+fn check_capabilities(caps: &[VolumeCapability]) -> Result<(), Status> {
+    if caps.is_empty() {
+        return Err(Status::invalid_argument("Volume capabilities missing"));
+    }
+    for cap in caps {
+        match &cap.access_type {
+            // Note: for this driver, "multi-node" access modes work but each
+            // node sees its own independent cache (that's the feature, not a bug)
+            Some(volume_capability::AccessType::Mount(_)) | None => {}
+            Some(volume_capability::AccessType::Block(_)) => {
+                return Err(Status::invalid_argument(
+                    "Block volumes are not supported, only filesystem mounts",
+                ));
+            }
+        }
+        let mode = cap
+            .access_mode
+            .as_ref()
+            .and_then(|m| access_mode::Mode::try_from(m.mode).ok());
+        match mode {
+            // ReadOnlyMany suggests one shared read-only source, which
+            // doesn't exist: every node gets its own, independent cache
+            Some(access_mode::Mode::MultiNodeReaderOnly) => {
+                return Err(Status::invalid_argument(
+                    "ReadOnlyMany (MULTI_NODE_READER_ONLY) is not supported: \
+                     each node sees its own independent storage, not a shared \
+                     read-only source",
+                ));
+            }
+            Some(access_mode::Mode::SingleNodeWriter)
+            | Some(access_mode::Mode::SingleNodeReaderOnly)
+            | Some(access_mode::Mode::SingleNodeSingleWriter)
+            | Some(access_mode::Mode::SingleNodeMultiWriter)
+            | Some(access_mode::Mode::MultiNodeSingleWriter)
+            | Some(access_mode::Mode::MultiNodeMultiWriter)
+            | Some(access_mode::Mode::Unknown)
+            | None => {}
+        }
+    }
+    Ok(())
+}
+
+/// Capacity of a volume, as recorded by its first publish or by
+/// `--stateful-create`; 0 (unknown) otherwise
+fn tracked_capacity(status: &cleanup::VolumeStatus) -> i64 {
+    match (status.capacity_bytes, &status.create_request) {
+        (0, Some(record)) => record.capacity_bytes,
+        (capacity, _) => capacity,
+    }
+}
+
+/// Condition of a volume: abnormal once a node failed to clean it up
+fn volume_condition(status: &cleanup::VolumeStatus) -> VolumeCondition {
+    if !status.nodes_failed.is_empty() {
+        return VolumeCondition {
+            abnormal: true,
+            message: format!("Cleanup failed on {}", status.nodes_failed.join(", ")),
+        };
+    }
+    let message = if status.cleanup_requested_at.is_some() {
+        let pending: Vec<&str> = status
+            .published_nodes()
+            .into_iter()
+            .map(String::as_str)
+            .collect();
+        if pending.is_empty() {
+            "Cleaned up".to_string()
+        } else {
+            format!("Cleanup pending on {}", pending.join(", "))
+        }
+    } else {
+        "Active".to_string()
+    };
+    VolumeCondition {
+        abnormal: false,
+        message,
+    }
+}
+
+/// The page of `statuses` (sorted by volume ID) a ListVolumes call asked
+/// for, and the token of the next page ("" after the last one). The token is
+/// the ID of the first volume of the next page, so volumes created or
+/// deleted between calls don't shift the pages.
+#[allow(clippy::result_large_err)]
+fn list_page(
+    statuses: Vec<cleanup::VolumeStatus>,
+    starting_token: &str,
+    max_entries: usize,
+) -> Result<(Vec<cleanup::VolumeStatus>, String), Status> {
+    if !starting_token.is_empty() && !volume::validate_volume_id(starting_token) {
+        return Err(Status::aborted(format!(
+            "Invalid starting token: {}",
+            starting_token
+        )));
+    }
+    let mut page: Vec<_> = statuses
+        .into_iter()
+        .filter(|s| s.volume_id.as_str() >= starting_token)
+        .collect();
+    let next_token = if max_entries > 0 && page.len() > max_entries {
+        page.split_off(max_entries)[0].volume_id.clone()
+    } else {
+        String::new()
+    };
+    Ok((page, next_token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::csi::CapacityRange;
+    use crate::pending_registration::PendingRegistration;
+    use std::collections::HashMap;
+
+    fn mount_capability(mode: access_mode::Mode) -> VolumeCapability {
+        VolumeCapability {
+            access_mode: Some(volume_capability::AccessMode { mode: mode as i32 }),
+            access_type: Some(volume_capability::AccessType::Mount(
+                volume_capability::MountVolume::default(),
+            )),
+        }
+    }
+
+    fn create_request(capacity_range: Option<CapacityRange>) -> CreateVolumeRequest {
+        CreateVolumeRequest {
+            name: "pvc-capacity".to_string(),
+            capacity_range,
+            volume_capabilities: vec![mount_capability(access_mode::Mode::SingleNodeWriter)],
+            ..Default::default()
+        }
+    }
+
+    async fn created(service: &ControllerService, req: CreateVolumeRequest) -> Volume {
+        service
+            .create_volume(Request::new(req))
+            .await
+            .unwrap()
+            .into_inner()
+            .volume
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_default_capacity() {
+        let requested = Some(CapacityRange {
+            required_bytes: 1 << 30,
+            limit_bytes: 0,
+        });
+
+        // Without a default, no request reports 0 (unknown)
+        let service = ControllerService::new();
+        let volume = created(&service, create_request(None)).await;
+        assert_eq!(volume.capacity_bytes, 0);
+        assert!(!volume.volume_context.contains_key(volume::CAPACITY_KEY));
+
+        let service = ControllerService::new().with_default_capacity(Some(10 << 30));
+        let volume = created(&service, create_request(None)).await;
+        assert_eq!(volume.capacity_bytes, 10 << 30);
+        // Reported only, no quota for the node to enforce
+        assert!(!volume.volume_context.contains_key(volume::CAPACITY_KEY));
+
+        // An explicit request wins
+        let volume = created(&service, create_request(requested)).await;
+        assert_eq!(volume.capacity_bytes, 1 << 30);
+        assert_eq!(
+            volume.volume_context.get(volume::CAPACITY_KEY),
+            Some(&(1i64 << 30).to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_max_volume_bytes() {
+        let requested = |required_bytes| {
+            create_request(Some(CapacityRange {
+                required_bytes,
+                limit_bytes: 0,
+            }))
+        };
+
+        // Unlimited by default
+        let service = ControllerService::new();
+        let volume = created(&service, requested(1 << 50)).await;
+        assert_eq!(volume.capacity_bytes, 1 << 50);
+
+        let service = ControllerService::new().with_max_volume_bytes(Some(10 << 30));
+        let volume = created(&service, requested(10 << 30)).await;
+        assert_eq!(volume.capacity_bytes, 10 << 30);
+        created(&service, create_request(None)).await;
+        let err = service
+            .create_volume(Request::new(requested((10 << 30) + 1)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
+        assert!(err.message().contains("10737418240"), "{}", err.message());
+
+        let expand = |required_bytes| ControllerExpandVolumeRequest {
+            volume_id: volume::generate_volume_id("pvc-capacity"),
+            capacity_range: Some(CapacityRange {
+                required_bytes,
+                limit_bytes: 0,
+            }),
+            ..Default::default()
+        };
+        assert!(service
+            .controller_expand_volume(Request::new(expand(10 << 30)))
+            .await
+            .is_ok());
+        let err = service
+            .controller_expand_volume(Request::new(expand(11 << 30)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
+    }
+
+    #[tokio::test]
+    async fn test_backing_parameter() {
+        let service = ControllerService::new();
+        let request = |backing: &str, required_bytes: i64| CreateVolumeRequest {
+            parameters: HashMap::from([(volume::BACKING_KEY.to_string(), backing.to_string())]),
+            ..create_request(Some(CapacityRange {
+                required_bytes,
+                limit_bytes: 0,
+            }))
+        };
+
+        let volume = created(&service, request("tmpfs", 1 << 30)).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::BACKING_KEY)
+                .map(String::as_str),
+            Some("tmpfs")
+        );
+        let volume = created(&service, request("disk", 0)).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::BACKING_KEY)
+                .map(String::as_str),
+            Some("disk")
+        );
+
+        // tmpfs needs a size; unknown backings are rejected
+        for (backing, required_bytes) in [("tmpfs", 0), ("ram", 1 << 30)] {
+            let err = service
+                .create_volume(Request::new(request(backing, required_bytes)))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_mount_flag_parameters() {
+        let service = ControllerService::new();
+        let request = |value: &str| CreateVolumeRequest {
+            parameters: HashMap::from([
+                (volume::NOEXEC_KEY.to_string(), value.to_string()),
+                (volume::NODEV_KEY.to_string(), "false".to_string()),
+            ]),
+            ..create_request(None)
+        };
+
+        let volume = created(&service, request("true")).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::NOEXEC_KEY)
+                .map(String::as_str),
+            Some("true")
+        );
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::NODEV_KEY)
+                .map(String::as_str),
+            Some("false")
+        );
+        assert!(!volume.volume_context.contains_key(volume::NOSUID_KEY));
+
+        let err = service
+            .create_volume(Request::new(request("yes")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_ownership_parameters() {
+        let service = ControllerService::new();
+        let request = |mode: &str| CreateVolumeRequest {
+            parameters: HashMap::from([
+                (volume::UID_KEY.to_string(), "1000".to_string()),
+                (volume::MODE_KEY.to_string(), mode.to_string()),
+            ]),
+            ..create_request(None)
+        };
+
+        let volume = created(&service, request("0770")).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::UID_KEY)
+                .map(String::as_str),
+            Some("1000")
+        );
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::MODE_KEY)
+                .map(String::as_str),
+            Some("0770")
+        );
+        assert!(!volume.volume_context.contains_key(volume::GID_KEY));
+
+        let err = service
+            .create_volume(Request::new(request("u+rwx")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_priority_parameter() {
+        let service = ControllerService::new();
+        let request = |priority: &str| CreateVolumeRequest {
+            parameters: HashMap::from([(
+                volume::EVICTION_PRIORITY_KEY.to_string(),
+                priority.to_string(),
+            )]),
+            ..create_request(None)
+        };
+
+        let volume = created(&service, request("90")).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::EVICTION_PRIORITY_KEY)
+                .map(String::as_str),
+            Some("90")
+        );
+        let err = service
+            .create_volume(Request::new(request("101")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_base_path_parameter() {
+        let service = ControllerService::new();
+        let request = |path: &str| CreateVolumeRequest {
+            parameters: HashMap::from([(volume::BASE_PATH_KEY.to_string(), path.to_string())]),
+            ..create_request(None)
+        };
+
+        let volume = created(&service, request("/mnt/nvme1/cache")).await;
+        assert_eq!(
+            volume
+                .volume_context
+                .get(volume::BASE_PATH_KEY)
+                .map(String::as_str),
+            Some("/mnt/nvme1/cache")
+        );
+
+        for invalid in ["mnt/nvme1", "/", "/mnt/../etc"] {
+            let err = service
+                .create_volume(Request::new(request(invalid)))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument, "{}", invalid);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_volume_content_source() {
+        use crate::csi::{volume_content_source::VolumeSource, VolumeContentSource};
+
+        let service = ControllerService::new();
+        let source_id = volume::generate_volume_id("pvc-source");
+        let from = |volume_id: &str| VolumeContentSource {
+            r#type: Some(volume_content_source::Type::Volume(VolumeSource {
+                volume_id: volume_id.to_string(),
+            })),
+        };
+        let request = |source| CreateVolumeRequest {
+            volume_content_source: Some(source),
+            ..create_request(None)
+        };
+
+        let volume = created(&service, request(from(&source_id))).await;
+        assert_eq!(
+            volume.volume_context.get(volume::CLONE_FROM_KEY),
+            Some(&source_id)
+        );
+        assert_eq!(volume.content_source, Some(from(&source_id)));
+
+        let own_id = volume::generate_volume_id("pvc-capacity");
+        for invalid in [from("../etc"), from(&own_id)] {
+            let err = service
+                .create_volume(Request::new(request(invalid)))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        }
+        let shared = CreateVolumeRequest {
+            parameters: HashMap::from([(volume::SHARED_NAME_KEY.to_string(), "maven".into())]),
+            ..request(from(&source_id))
+        };
+        let err = service
+            .create_volume(Request::new(shared))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_expand_volume() {
+        let service = ControllerService::new();
+        let capabilities = service
+            .controller_get_capabilities(Request::new(ControllerGetCapabilitiesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .capabilities;
+        assert!(capabilities.iter().any(|c| c.r#type
+            == Some(controller_service_capability::Type::Rpc(
+                controller_service_capability::Rpc {
+                    r#type: controller_service_capability::rpc::Type::ExpandVolume as i32,
+                }
+            ))));
+
+        let volume = created(&service, create_request(None)).await;
+        let request = |required_bytes, limit_bytes| ControllerExpandVolumeRequest {
+            volume_id: volume.volume_id.clone(),
+            capacity_range: Some(CapacityRange {
+                required_bytes,
+                limit_bytes,
+            }),
+            ..Default::default()
+        };
+
+        let expanded = service
+            .controller_expand_volume(Request::new(request(2 << 30, 0)))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(expanded.capacity_bytes, 2 << 30);
+        assert!(expanded.node_expansion_required);
+
+        let err = service
+            .controller_expand_volume(Request::new(request(4 << 30, 2 << 30)))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::OutOfRange);
+        let err = service
+            .controller_expand_volume(Request::new(ControllerExpandVolumeRequest {
+                capacity_range: None,
+                ..request(0, 0)
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = service
+            .controller_expand_volume(Request::new(ControllerExpandVolumeRequest {
+                volume_id: "../etc".into(),
+                ..request(2 << 30, 0)
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_validate_access_modes() {
+        let service = ControllerService::new();
+        let modes = [
+            (access_mode::Mode::Unknown, true),
+            (access_mode::Mode::SingleNodeWriter, true),
+            (access_mode::Mode::SingleNodeReaderOnly, true),
+            (access_mode::Mode::MultiNodeReaderOnly, false),
+            (access_mode::Mode::MultiNodeSingleWriter, true),
+            (access_mode::Mode::MultiNodeMultiWriter, true),
+            (access_mode::Mode::SingleNodeSingleWriter, true),
+            (access_mode::Mode::SingleNodeMultiWriter, true),
+        ];
+        for (mode, confirmed) in modes {
+            let response = service
+                .validate_volume_capabilities(Request::new(ValidateVolumeCapabilitiesRequest {
+                    volume_id: "nlc-test".to_string(),
+                    volume_capabilities: vec![mount_capability(mode)],
+                    ..Default::default()
+                }))
+                .await
+                .unwrap()
+                .into_inner();
+            assert_eq!(response.confirmed.is_some(), confirmed, "{:?}", mode);
+            assert_eq!(response.message.is_empty(), confirmed, "{:?}", mode);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_volume_capabilities() {
+        let service = ControllerService::new();
+        let block = VolumeCapability {
+            access_type: Some(volume_capability::AccessType::Block(
+                volume_capability::BlockVolume::default(),
+            )),
+            ..mount_capability(access_mode::Mode::SingleNodeWriter)
+        };
+        let create = |volume_capabilities| CreateVolumeRequest {
+            volume_capabilities,
+            ..create_request(None)
+        };
+
+        for caps in [
+            vec![],
+            vec![block.clone()],
+            vec![
+                mount_capability(access_mode::Mode::SingleNodeWriter),
+                block.clone(),
+            ],
+        ] {
+            let err = service
+                .create_volume(Request::new(create(caps.clone())))
+                .await
+                .unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument, "{:?}", caps);
+        }
+        created(
+            &service,
+            create(vec![mount_capability(
+                access_mode::Mode::MultiNodeMultiWriter,
+            )]),
+        )
+        .await;
+
+        // The same checks confirm nothing in ValidateVolumeCapabilities, and
+        // missing capabilities are an error there too
+        let validate = |volume_capabilities| ValidateVolumeCapabilitiesRequest {
+            volume_id: "nlc-test".to_string(),
+            volume_capabilities,
+            ..Default::default()
+        };
+        let response = service
+            .validate_volume_capabilities(Request::new(validate(vec![block])))
+            .await
+            .unwrap()
+            .into_inner();
+        assert!(response.confirmed.is_none());
+        assert!(response.message.contains("Block"), "{}", response.message);
+        let err = service
+            .validate_volume_capabilities(Request::new(validate(vec![])))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_list_volumes() {
+        use crate::fake_api::FakeApiServer;
+
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let mut ids: Vec<String> = (0..3)
+            .map(|i| volume::generate_volume_id(&format!("pvc-list-{}", i)))
+            .collect();
+        ids.sort();
+        // Only the second one's capacity is known to its nodes
+        let publishes = [
+            (&["node1"][..], 0),
+            (&["node1", "node2"], 2 << 30),
+            (&[], 0),
+        ];
+        for (id, (nodes, capacity)) in ids.iter().zip(publishes) {
+            for node in nodes {
+                cleanup::register_node_publish(
+                    &client,
+                    "default",
+                    node,
+                    &PendingRegistration {
+                        capacity_bytes: capacity,
+                        ..PendingRegistration::new(id)
+                    },
+                )
+                .await
+                .unwrap();
+            }
+        }
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let record = CreateRecord {
+            name: "pvc-list-2".to_string(),
+            capacity_bytes: 1 << 30,
+            parameters: BTreeMap::new(),
+        };
+        controller
+            .record_create(&ids[2], &record, None, &BTreeMap::new())
+            .await
+            .unwrap();
+        // Deleted, still listed until its nodes cleaned up
+        cleanup::mark_volume_for_cleanup(&client, "default", &ids[1])
+            .await
+            .unwrap();
+        let service = ControllerService::with_cleanup(controller);
+        let list = |max_entries: i32, starting_token: &str| {
+            service.list_volumes(Request::new(ListVolumesRequest {
+                max_entries,
+                starting_token: starting_token.to_string(),
+            }))
+        };
+
+        let response = list(0, "").await.unwrap().into_inner();
+        assert!(response.next_token.is_empty());
+        let listed: Vec<_> = response
+            .entries
+            .iter()
+            .map(|e| {
+                let volume = e.volume.as_ref().unwrap();
+                let status = e.status.as_ref().unwrap();
+                (
+                    volume.volume_id.clone(),
+                    volume.capacity_bytes,
+                    status.published_node_ids.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            listed,
+            vec![
+                (ids[0].clone(), 0, vec!["node1".to_string()]),
+                (
+                    ids[1].clone(),
+                    2 << 30,
+                    vec!["node1".into(), "node2".into()]
+                ),
+                (ids[2].clone(), 1 << 30, vec![]),
+            ]
+        );
+
+        // Paged
+        let first = list(2, "").await.unwrap().into_inner();
+        assert_eq!(first.entries.len(), 2);
+        assert_eq!(first.next_token, ids[2]);
+        let second = list(2, &first.next_token).await.unwrap().into_inner();
+        assert_eq!(second.entries.len(), 1);
+        assert_eq!(second.entries[0].volume.as_ref().unwrap().volume_id, ids[2]);
+        assert!(second.next_token.is_empty());
+
+        let err = list(0, "bogus").await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Aborted);
+        let err = list(-1, "").await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        // Not without the cleanup service
+        let err = ControllerService::new()
+            .list_volumes(Request::new(ListVolumesRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+    }
+
+    #[tokio::test]
+    async fn test_get_volume() {
+        use crate::fake_api::FakeApiServer;
+
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-get");
+        for node in ["node1", "node2"] {
+            cleanup::register_node_publish(
+                &client,
+                "default",
+                node,
+                &PendingRegistration::new(&volume_id),
+            )
+            .await
+            .unwrap();
+        }
+        let controller = CleanupController::new(client.clone(), "default".into());
+        let record = CreateRecord {
+            name: "pvc-get".to_string(),
+            capacity_bytes: 1 << 30,
+            parameters: BTreeMap::new(),
+        };
+        controller
+            .record_create(&volume_id, &record, None, &BTreeMap::new())
+            .await
+            .unwrap();
+        let service = ControllerService::with_cleanup(controller);
+        let get = |volume_id: &str| {
+            service.controller_get_volume(Request::new(ControllerGetVolumeRequest {
+                volume_id: volume_id.to_string(),
+            }))
+        };
+
+        let response = get(&volume_id).await.unwrap().into_inner();
+        let volume = response.volume.unwrap();
+        assert_eq!(volume.volume_id, volume_id);
+        assert_eq!(volume.capacity_bytes, 1 << 30);
+        let status = response.status.unwrap();
+        assert_eq!(status.published_node_ids, vec!["node1", "node2"]);
+        let condition = status.volume_condition.unwrap();
+        assert!(!condition.abnormal);
+
+        let err = get(&volume::generate_volume_id("pvc-missing"))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        let err = get("bogus").await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn test_volume_condition() {
+        let mut status = cleanup::VolumeStatus::new("nlc-test");
+        status.add_node("node1");
+        status.add_node("node2");
+        let condition = volume_condition(&status);
+        assert!(!condition.abnormal);
+        assert_eq!(condition.message, "Active");
+
+        status.cleanup_requested_at = Some(chrono::Utc::now().to_rfc3339());
+        status.mark_node_completed("node1");
+        let condition = volume_condition(&status);
+        assert!(!condition.abnormal);
+        assert_eq!(condition.message, "Cleanup pending on node2");
+
+        status.mark_node_failed("node2");
+        let condition = volume_condition(&status);
+        assert!(condition.abnormal);
+        assert_eq!(condition.message, "Cleanup failed on node2");
+    }
+
+    #[tokio::test]
+    async fn test_delete_volume_retries_cleanup_request() {
+        use crate::fake_api::FakeApiServer;
+
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-delete-retry");
+        cleanup::register_node_publish(
+            &client,
+            "default",
+            "node1",
+            &PendingRegistration::new(&volume_id),
+        )
+        .await
+        .unwrap();
+        let service = ControllerService::with_cleanup(CleanupController::new(
+            client.clone(),
+            "default".into(),
+        ));
+        let in_cleanup = || async {
+            service
+                .cleanup
+                .as_ref()
+                .unwrap()
+                .read()
+                .await
+                .volume_status(&volume_id)
+                .await
+                .unwrap()
+                .is_some_and(|status| status.cleanup_requested_at.is_some())
+        };
+
+        // The API server is briefly unavailable: DeleteVolume still succeeds
+        api.inject_unavailable(1);
+        service
+            .delete_volume(Request::new(DeleteVolumeRequest {
+                volume_id: volume_id.clone(),
+                ..Default::default()
+            }))
+            .await
+            .unwrap();
+        assert!(!in_cleanup().await);
+        assert!(api
+            .event_reasons()
+            .contains(&"CleanupRequestFailed".to_string()));
+
+        // Marked by the first retry
+        for _ in 0..50 {
+            if in_cleanup().await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(in_cleanup().await);
+        assert!(api
+            .event_reasons()
+            .contains(&"CleanupRequested".to_string()));
+    }
+}
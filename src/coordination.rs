@@ -0,0 +1,716 @@
+//! Where the volume tracking ConfigMaps (see `cleanup`) are stored.
+//!
+//! - The Kubernetes API (default)
+//! - `--coordination-dir`: one JSON file per ConfigMap in a directory shared
+//!   by the controller and all nodes (e.g. an NFS export), for clusters that
+//!   don't want cleanup state in a remote or unreliable API server
+//!
+//! Both implement `CoordinationBackend`, the ConfigMap calls cleanup needs,
+//! with the API's semantics: writes carry the `resourceVersion` they were
+//! based on and fail with a 409 conflict when it is stale, so the optimistic
+//! retry loops work unchanged. `FileBackend` makes each check and write
+//! atomic with an exclusive `flock` on a lock file in the directory.
+//!
+//! The file store trades cross-node visibility for API independence:
+//! - Its consistency is that of the shared filesystem. The locks and renames
+//!   must reach every client, e.g. NFSv4 or NFSv3 with lockd, and attribute
+//!   caching can delay a node seeing another's write until its next pass.
+//! - Nodes poll for cleanup requests: `--cleanup-trigger` is forced to
+//!   `poll`, as there is nothing to watch.
+//! - `--set-owner-references` is ignored (`set_volume_owner` isn't called),
+//!   as nothing would garbage-collect the files by their owner.
+//! - Still from the API: events (best-effort), PersistentVolume lookups
+//!   (`--verify-pv-deleted`) and the controller's Node list, which tells
+//!   decommissioned nodes apart.
+
+use std::fs::File;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{Api, DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::Client;
+use nix::fcntl::{Flock, FlockArg};
+use uuid::Uuid;
+
+/// Lock file in the coordination directory
+pub const LOCK_FILE: &str = ".lock";
+
+/// Set by `--coordination-dir`
+static COORDINATION_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Store tracking ConfigMaps as files in `dir` (`--coordination-dir`). Only
+/// the first call has an effect.
+pub fn set_coordination_dir(dir: PathBuf) {
+    let _ = COORDINATION_DIR.set(dir);
+}
+
+fn coordination_dir() -> Option<PathBuf> {
+    #[cfg(test)]
+    if let Some(dir) = tests::COORDINATION_DIR.with_borrow(Clone::clone) {
+        return Some(dir);
+    }
+    COORDINATION_DIR.get().cloned()
+}
+
+/// Whether tracking ConfigMaps are stored as files (`--coordination-dir`)
+pub fn file_backed() -> bool {
+    coordination_dir().is_some()
+}
+
+/// The ConfigMap calls cleanup coordination makes, as on `Api<ConfigMap>`
+pub trait CoordinationBackend: Send + Sync {
+    fn get_opt(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<ConfigMap>, kube::Error>> + Send;
+
+    /// ConfigMaps matching the label selector of `lp`
+    fn list(
+        &self,
+        lp: &ListParams,
+    ) -> impl Future<Output = Result<Vec<ConfigMap>, kube::Error>> + Send;
+
+    fn create(&self, cm: &ConfigMap)
+        -> impl Future<Output = Result<ConfigMap, kube::Error>> + Send;
+
+    fn replace(
+        &self,
+        name: &str,
+        cm: &ConfigMap,
+    ) -> impl Future<Output = Result<ConfigMap, kube::Error>> + Send;
+
+    /// JSON merge patch; a `metadata.resourceVersion` in `patch` is a
+    /// precondition, like on the API
+    fn patch_merge(
+        &self,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> impl Future<Output = Result<ConfigMap, kube::Error>> + Send;
+
+    fn delete(
+        &self,
+        name: &str,
+        dp: &DeleteParams,
+    ) -> impl Future<Output = Result<(), kube::Error>> + Send;
+}
+
+impl CoordinationBackend for Api<ConfigMap> {
+    async fn get_opt(&self, name: &str) -> Result<Option<ConfigMap>, kube::Error> {
+        Api::get_opt(self, name).await
+    }
+
+    async fn list(&self, lp: &ListParams) -> Result<Vec<ConfigMap>, kube::Error> {
+        Ok(Api::list(self, lp).await?.items)
+    }
+
+    async fn create(&self, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        Api::create(self, &PostParams::default(), cm).await
+    }
+
+    async fn replace(&self, name: &str, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        Api::replace(self, name, &PostParams::default(), cm).await
+    }
+
+    async fn patch_merge(
+        &self,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> Result<ConfigMap, kube::Error> {
+        Api::patch(self, name, &PatchParams::default(), &Patch::Merge(patch)).await
+    }
+
+    async fn delete(&self, name: &str, dp: &DeleteParams) -> Result<(), kube::Error> {
+        Api::delete(self, name, dp).await.map(|_| ())
+    }
+}
+
+/// Where a component keeps its tracking ConfigMaps
+pub enum ConfigMapStore {
+    Api(Api<ConfigMap>),
+    File(FileBackend),
+}
+
+/// The tracking ConfigMap store, in `namespace` for the API
+pub fn configmaps(client: &Client, namespace: &str) -> ConfigMapStore {
+    match coordination_dir() {
+        Some(dir) => ConfigMapStore::File(FileBackend::new(dir)),
+        None => ConfigMapStore::Api(Api::namespaced(client.clone(), namespace)),
+    }
+}
+
+impl CoordinationBackend for ConfigMapStore {
+    async fn get_opt(&self, name: &str) -> Result<Option<ConfigMap>, kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::get_opt(api, name).await,
+            ConfigMapStore::File(file) => file.get_opt(name).await,
+        }
+    }
+
+    async fn list(&self, lp: &ListParams) -> Result<Vec<ConfigMap>, kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::list(api, lp).await,
+            ConfigMapStore::File(file) => file.list(lp).await,
+        }
+    }
+
+    async fn create(&self, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::create(api, cm).await,
+            ConfigMapStore::File(file) => file.create(cm).await,
+        }
+    }
+
+    async fn replace(&self, name: &str, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::replace(api, name, cm).await,
+            ConfigMapStore::File(file) => file.replace(name, cm).await,
+        }
+    }
+
+    async fn patch_merge(
+        &self,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> Result<ConfigMap, kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::patch_merge(api, name, patch).await,
+            ConfigMapStore::File(file) => file.patch_merge(name, patch).await,
+        }
+    }
+
+    async fn delete(&self, name: &str, dp: &DeleteParams) -> Result<(), kube::Error> {
+        match self {
+            ConfigMapStore::Api(api) => CoordinationBackend::delete(api, name, dp).await,
+            ConfigMapStore::File(file) => file.delete(name, dp).await,
+        }
+    }
+}
+
+/// ConfigMaps as `<name>.json` files in a directory. Reads take no lock:
+/// files are replaced by renaming, so a reader sees a whole version.
+#[derive(Debug, Clone)]
+pub struct FileBackend {
+    dir: PathBuf,
+}
+
+impl FileBackend {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Run `f` on a blocking thread
+    async fn blocking<T, F>(&self, f: F) -> Result<T, kube::Error>
+    where
+        T: Send + 'static,
+        F: FnOnce(&FileBackend) -> Result<T, kube::Error> + Send + 'static,
+    {
+        let backend = self.clone();
+        tokio::task::spawn_blocking(move || f(&backend))
+            .await
+            .map_err(|e| io_error(io::Error::other(e)))?
+    }
+
+    /// Run `f` holding the directory's exclusive lock
+    fn locked<T>(&self, f: impl FnOnce() -> Result<T, kube::Error>) -> Result<T, kube::Error> {
+        std::fs::create_dir_all(&self.dir).map_err(io_error)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.dir.join(LOCK_FILE))
+            .map_err(io_error)?;
+        // Released when dropped
+        let _lock = Flock::lock(file, FlockArg::LockExclusive)
+            .map_err(|(_, errno)| io_error(errno.into()))?;
+        f()
+    }
+
+    fn read(&self, name: &str) -> Result<Option<ConfigMap>, kube::Error> {
+        read_file(&self.path(name))
+    }
+
+    /// Write `cm` as the next version of `name`, after `previous`
+    fn write(
+        &self,
+        name: &str,
+        mut cm: ConfigMap,
+        previous: Option<&ConfigMap>,
+    ) -> Result<ConfigMap, kube::Error> {
+        let version = previous
+            .and_then(|p| p.metadata.resource_version.as_deref())
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        cm.metadata.name = Some(name.to_string());
+        cm.metadata.resource_version = Some((version + 1).to_string());
+        cm.metadata.uid = previous
+            .and_then(|p| p.metadata.uid.clone())
+            .or_else(|| Some(Uuid::new_v4().to_string()));
+        let json = serde_json::to_vec(&cm).map_err(kube::Error::SerdeError)?;
+        let path = self.path(name);
+        let tmp = self.dir.join(format!(".{}.json.tmp", name));
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, &path))
+            .map_err(io_error)?;
+        Ok(cm)
+    }
+
+    async fn get_opt(&self, name: &str) -> Result<Option<ConfigMap>, kube::Error> {
+        let name = name.to_string();
+        self.blocking(move |backend| backend.read(&name)).await
+    }
+
+    async fn list(&self, lp: &ListParams) -> Result<Vec<ConfigMap>, kube::Error> {
+        let selector = LabelSelector::parse(lp.label_selector.as_deref().unwrap_or(""))?;
+        self.blocking(move |backend| {
+            let entries = match std::fs::read_dir(&backend.dir) {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(io_error(e)),
+            };
+            let mut cms = Vec::new();
+            for entry in entries {
+                let path = entry.map_err(io_error)?.path();
+                let visible = path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.ends_with(".json") && !n.starts_with('.'));
+                if !visible {
+                    continue;
+                }
+                // Deleted since listed
+                if let Some(cm) = read_file(&path)? {
+                    if selector.matches(cm.metadata.labels.as_ref()) {
+                        cms.push(cm);
+                    }
+                }
+            }
+            cms.sort_by(|a, b| a.metadata.name.cmp(&b.metadata.name));
+            Ok(cms)
+        })
+        .await
+    }
+
+    async fn create(&self, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        let cm = cm.clone();
+        self.blocking(move |backend| {
+            let name = cm.metadata.name.clone().unwrap_or_default();
+            backend.locked(|| {
+                if backend.read(&name)?.is_some() {
+                    return Err(api_error(
+                        409,
+                        "AlreadyExists",
+                        format!("configmaps \"{}\" already exists", name),
+                    ));
+                }
+                backend.write(&name, cm, None)
+            })
+        })
+        .await
+    }
+
+    async fn replace(&self, name: &str, cm: &ConfigMap) -> Result<ConfigMap, kube::Error> {
+        let (name, cm) = (name.to_string(), cm.clone());
+        self.blocking(move |backend| {
+            backend.locked(|| {
+                let existing = backend.read(&name)?.ok_or_else(|| not_found(&name))?;
+                check_version(&name, &existing, cm.metadata.resource_version.as_deref())?;
+                if existing.immutable == Some(true)
+                    && (existing.data != cm.data || cm.immutable != Some(true))
+                {
+                    return Err(api_error(
+                        422,
+                        "Invalid",
+                        format!("configmaps \"{}\" is immutable", name),
+                    ));
+                }
+                backend.write(&name, cm, Some(&existing))
+            })
+        })
+        .await
+    }
+
+    async fn patch_merge(
+        &self,
+        name: &str,
+        patch: &serde_json::Value,
+    ) -> Result<ConfigMap, kube::Error> {
+        let (name, patch) = (name.to_string(), patch.clone());
+        self.blocking(move |backend| {
+            backend.locked(|| {
+                let existing = backend.read(&name)?.ok_or_else(|| not_found(&name))?;
+                let expected = patch
+                    .pointer("/metadata/resourceVersion")
+                    .and_then(|v| v.as_str());
+                check_version(&name, &existing, expected)?;
+                let mut json = serde_json::to_value(&existing).map_err(kube::Error::SerdeError)?;
+                merge_patch(&mut json, &patch);
+                let cm = serde_json::from_value(json).map_err(kube::Error::SerdeError)?;
+                backend.write(&name, cm, Some(&existing))
+            })
+        })
+        .await
+    }
+
+    async fn delete(&self, name: &str, dp: &DeleteParams) -> Result<(), kube::Error> {
+        let (name, preconditions) = (name.to_string(), dp.preconditions.clone());
+        self.blocking(move |backend| {
+            backend.locked(|| {
+                let existing = backend.read(&name)?.ok_or_else(|| not_found(&name))?;
+                if let Some(preconditions) = preconditions {
+                    check_version(&name, &existing, preconditions.resource_version.as_deref())?;
+                    if preconditions.uid.is_some() && preconditions.uid != existing.metadata.uid {
+                        return Err(api_error(
+                            409,
+                            "Conflict",
+                            format!("configmaps \"{}\" has another UID", name),
+                        ));
+                    }
+                }
+                std::fs::remove_file(backend.path(&name)).map_err(io_error)
+            })
+        })
+        .await
+    }
+}
+
+fn read_file(path: &Path) -> Result<Option<ConfigMap>, kube::Error> {
+    match std::fs::read(path) {
+        Ok(json) => serde_json::from_slice(&json)
+            .map(Some)
+            .map_err(kube::Error::SerdeError),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error(e)),
+    }
+}
+
+/// Fail with a conflict unless `expected` (if any) is `existing`'s version
+fn check_version(
+    name: &str,
+    existing: &ConfigMap,
+    expected: Option<&str>,
+) -> Result<(), kube::Error> {
+    match expected {
+        Some(version) if Some(version) != existing.metadata.resource_version.as_deref() => {
+            Err(api_error(
+                409,
+                "Conflict",
+                format!(
+                    "Operation cannot be fulfilled on configmaps \"{}\": the object has been modified",
+                    name
+                ),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Apply a JSON merge patch (RFC 7386)
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    use serde_json::Value;
+
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let Value::Object(target) = target else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// An equality-based label selector (`a`, `!a`, `a=b`, `a!=b`, comma-separated)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LabelSelector(Vec<(String, Requirement)>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Requirement {
+    Exists,
+    NotExists,
+    Equals(String),
+    NotEquals(String),
+}
+
+impl LabelSelector {
+    fn parse(selector: &str) -> Result<Self, kube::Error> {
+        let mut requirements = Vec::new();
+        for term in selector.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let requirement = if let Some((key, value)) = term.split_once("!=") {
+                (key, Requirement::NotEquals(value.trim().to_string()))
+            } else if let Some((key, value)) =
+                term.split_once("==").or_else(|| term.split_once('='))
+            {
+                (key, Requirement::Equals(value.trim().to_string()))
+            } else if let Some(key) = term.strip_prefix('!') {
+                (key, Requirement::NotExists)
+            } else {
+                (term, Requirement::Exists)
+            };
+            let key = requirement.0.trim();
+            if key.is_empty() || key.contains(char::is_whitespace) {
+                return Err(api_error(
+                    400,
+                    "BadRequest",
+                    format!("unsupported label selector `{}`", selector),
+                ));
+            }
+            requirements.push((key.to_string(), requirement.1));
+        }
+        Ok(Self(requirements))
+    }
+
+    fn matches(&self, labels: Option<&std::collections::BTreeMap<String, String>>) -> bool {
+        self.0.iter().all(|(key, requirement)| {
+            let value = labels.and_then(|l| l.get(key));
+            match requirement {
+                Requirement::Exists => value.is_some(),
+                Requirement::NotExists => value.is_none(),
+                Requirement::Equals(expected) => value == Some(expected),
+                Requirement::NotEquals(expected) => value != Some(expected),
+            }
+        })
+    }
+}
+
+fn api_error(code: u16, reason: &str, message: String) -> kube::Error {
+    kube::Error::Api(kube::core::ErrorResponse {
+        status: "Failure".to_string(),
+        message,
+        reason: reason.to_string(),
+        code,
+    })
+}
+
+fn not_found(name: &str) -> kube::Error {
+    api_error(
+        404,
+        "NotFound",
+        format!("configmaps \"{}\" not found", name),
+    )
+}
+
+fn io_error(e: io::Error) -> kube::Error {
+    kube::Error::Service(Box::new(e))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    thread_local! {
+        /// Stores tracking ConfigMaps in this directory for the current test only
+        pub(crate) static COORDINATION_DIR: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "nlc-coordination-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn configmap(name: &str, labels: &[(&str, &str)], value: &str) -> ConfigMap {
+        ConfigMap {
+            metadata: kube::api::ObjectMeta {
+                name: Some(name.to_string()),
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            data: Some(BTreeMap::from([("value".to_string(), value.to_string())])),
+            ..Default::default()
+        }
+    }
+
+    fn code(result: Result<impl std::fmt::Debug, kube::Error>) -> u16 {
+        match result {
+            Err(kube::Error::Api(err)) => err.code,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_optimistic_concurrency() {
+        let dir = temp_dir("occ");
+        let backend = FileBackend::new(dir.clone());
+        assert!(backend.get_opt("a").await.unwrap().is_none());
+        assert_eq!(
+            code(backend.replace("a", &configmap("a", &[], "1")).await),
+            404
+        );
+
+        let created = backend.create(&configmap("a", &[], "1")).await.unwrap();
+        assert_eq!(created.metadata.resource_version.as_deref(), Some("1"));
+        assert_eq!(code(backend.create(&configmap("a", &[], "1")).await), 409);
+        assert_eq!(backend.get_opt("a").await.unwrap(), Some(created.clone()));
+
+        // Based on the current version
+        let mut update = created.clone();
+        update.data = configmap("a", &[], "2").data;
+        let replaced = backend.replace("a", &update).await.unwrap();
+        assert_eq!(replaced.metadata.resource_version.as_deref(), Some("2"));
+        assert_eq!(replaced.metadata.uid, created.metadata.uid);
+        // Based on a stale one
+        assert_eq!(code(backend.replace("a", &update).await), 409);
+        let patch = serde_json::json!({"metadata": {"resourceVersion": "1", "labels": {"x": "y"}}});
+        assert_eq!(code(backend.patch_merge("a", &patch).await), 409);
+        let patch = serde_json::json!({"metadata": {"resourceVersion": "2", "labels": {"x": "y"}}});
+        let patched = backend.patch_merge("a", &patch).await.unwrap();
+        assert_eq!(patched.metadata.labels.unwrap()["x"], "y");
+        assert_eq!(patched.data, update.data);
+
+        let stale = DeleteParams::default().preconditions(kube::api::Preconditions {
+            resource_version: Some("2".to_string()),
+            uid: None,
+        });
+        assert_eq!(code(backend.delete("a", &stale).await), 409);
+        backend.delete("a", &DeleteParams::default()).await.unwrap();
+        assert_eq!(
+            code(backend.delete("a", &DeleteParams::default()).await),
+            404
+        );
+        assert!(backend.get_opt("a").await.unwrap().is_none());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_immutable() {
+        let dir = temp_dir("immutable");
+        let backend = FileBackend::new(dir.clone());
+        let cm = ConfigMap {
+            immutable: Some(true),
+            ..configmap("a", &[], "1")
+        };
+        let created = backend.create(&cm).await.unwrap();
+        let mut update = created.clone();
+        update.data = configmap("a", &[], "2").data;
+        assert_eq!(code(backend.replace("a", &update).await), 422);
+        // Metadata may still change
+        let mut relabeled = created;
+        relabeled.metadata.labels = Some(BTreeMap::from([("x".to_string(), "y".to_string())]));
+        backend.replace("a", &relabeled).await.unwrap();
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_file_backend_list() {
+        let dir = temp_dir("list");
+        let backend = FileBackend::new(dir.clone());
+        assert!(backend
+            .list(&ListParams::default())
+            .await
+            .unwrap()
+            .is_empty());
+        backend
+            .create(&configmap("b", &[("phase", "cleanup")], ""))
+            .await
+            .unwrap();
+        backend
+            .create(&configmap("a", &[("phase", "active")], ""))
+            .await
+            .unwrap();
+        backend.create(&configmap("c", &[], "")).await.unwrap();
+
+        let names = |cms: Vec<ConfigMap>| -> Vec<String> {
+            cms.into_iter().filter_map(|cm| cm.metadata.name).collect()
+        };
+        let list = |selector: &str| {
+            let (backend, lp) = (backend.clone(), ListParams::default().labels(selector));
+            async move { backend.list(&lp).await }
+        };
+        assert_eq!(names(list("").await.unwrap()), ["a", "b", "c"]);
+        assert_eq!(names(list("phase").await.unwrap()), ["a", "b"]);
+        assert_eq!(names(list("!phase").await.unwrap()), ["c"]);
+        assert_eq!(names(list("phase=active").await.unwrap()), ["a"]);
+        assert_eq!(names(list("phase!=active").await.unwrap()), ["b", "c"]);
+        assert_eq!(code(list("phase in (active)").await), 400);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        let mut target = serde_json::json!({"a": {"b": 1, "c": 2}, "d": [1]});
+        merge_patch(
+            &mut target,
+            &serde_json::json!({"a": {"b": null, "e": 3}, "d": [2]}),
+        );
+        assert_eq!(target, serde_json::json!({"a": {"c": 2, "e": 3}, "d": [2]}));
+    }
+
+    /// Writers in separate threads, each with its own lock file handle like
+    /// separate processes, incrementing a counter with optimistic retries
+    #[test]
+    fn test_file_backend_concurrent_updates() {
+        let dir = temp_dir("concurrent");
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(FileBackend::new(dir.clone()).create(&configmap("counter", &[], "0")))
+            .unwrap();
+
+        let writers: Vec<_> = (0..8)
+            .map(|_| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread()
+                        .enable_all()
+                        .build()
+                        .unwrap();
+                    let backend = FileBackend::new(dir);
+                    runtime.block_on(async {
+                        let mut conflicts = 0;
+                        for _ in 0..25 {
+                            loop {
+                                let mut cm = backend.get_opt("counter").await.unwrap().unwrap();
+                                let data = cm.data.as_mut().unwrap();
+                                let value: u32 = data["value"].parse().unwrap();
+                                data.insert("value".to_string(), (value + 1).to_string());
+                                match backend.replace("counter", &cm).await {
+                                    Ok(_) => break,
+                                    Err(kube::Error::Api(err)) if err.code == 409 => conflicts += 1,
+                                    Err(e) => panic!("{}", e),
+                                }
+                            }
+                        }
+                        conflicts
+                    })
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let cm = runtime
+            .block_on(FileBackend::new(dir.clone()).get_opt("counter"))
+            .unwrap()
+            .unwrap();
+        // No update was lost
+        assert_eq!(cm.data.unwrap()["value"], "200");
+        assert_eq!(cm.metadata.resource_version.as_deref(), Some("201"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,371 @@
+//! Hardlink deduplication of seeded cache content (`--dedup-compaction`).
+//!
+//! Many per-volume caches on a node often hold the same large files (base
+//! image layers, model weights, ...). Every `DEDUP_INTERVAL`, the node
+//! plugin scans its volume directories for identical files and replaces the
+//! duplicates with hardlinks to one copy, so the data is stored once.
+//!
+//! Hardlinked files share their content: a write through one link changes
+//! every copy. Only content that is never written is safe to link, so only
+//! files below a directory holding a `SEEDED_MARKER` file are considered.
+//! Whoever seeds a cache (an init job, a pre-publish hook) drops the marker
+//! once the content is complete, and pods must not write below it, e.g.
+//! because it's mounted read-only (`readonly-subpaths`).
+//!
+//! Files are grouped by size and ownership, hashed, and compared byte by
+//! byte before linking. Links never cross filesystems, and a file is only
+//! replaced (link to a temporary name, then rename over it) if it is still
+//! the file that was scanned.
+
+use std::collections::HashMap;
+use std::hash::Hasher;
+use std::io::{self, Read};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tracing::{debug, error, info, warn};
+
+use crate::inventory;
+use crate::metrics;
+use crate::volume;
+
+/// How often the volume directories are compacted
+pub const DEDUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// File marking a directory tree as seeded: complete and never written again
+pub const SEEDED_MARKER: &str = ".nlc-seeded";
+
+/// Smaller files aren't worth a link
+pub const MIN_FILE_SIZE: u64 = 64 * 1024;
+
+/// What a compaction pass did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Compaction {
+    /// Paths replaced by a link
+    pub files_linked: usize,
+    /// Size of the copies no longer referenced by any path
+    pub bytes_reclaimed: u64,
+}
+
+/// A seeded file, as scanned
+#[derive(Debug, Clone)]
+struct Candidate {
+    path: PathBuf,
+    device: u64,
+    inode: u64,
+    size: u64,
+    links: u64,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+}
+
+impl Candidate {
+    fn from_metadata(path: PathBuf, m: &std::fs::Metadata) -> Self {
+        Self {
+            path,
+            device: m.dev(),
+            inode: m.ino(),
+            size: m.len(),
+            links: m.nlink(),
+            mode: m.mode(),
+            uid: m.uid(),
+            gid: m.gid(),
+        }
+    }
+
+    /// Files that may share an inode: same filesystem, size and ownership
+    /// (a link has one set of permissions)
+    fn group_key(&self) -> (u64, u64, u32, u32, u32) {
+        (self.device, self.size, self.mode, self.uid, self.gid)
+    }
+
+    /// Whether `path` is still this file
+    fn is_current(&self) -> bool {
+        std::fs::symlink_metadata(&self.path)
+            .is_ok_and(|m| m.ino() == self.inode && m.dev() == self.device && m.len() == self.size)
+    }
+}
+
+/// Regular files of at least `MIN_FILE_SIZE` below a `SEEDED_MARKER` in
+/// the tree at `root`. Symlinks and other filesystems aren't followed.
+fn seeded_files(root: &Path) -> io::Result<Vec<Candidate>> {
+    let device = std::fs::symlink_metadata(root)?.dev();
+    let mut files = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), false)];
+
+    while let Some((dir, inherited)) = pending.pop() {
+        let seeded = inherited || dir.join(SEEDED_MARKER).is_file();
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            // Deleted while scanning
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = entry?;
+            let metadata = match entry.metadata() {
+                Ok(m) => m,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if metadata.dev() != device {
+                continue;
+            }
+            if metadata.is_dir() {
+                pending.push((entry.path(), seeded));
+            } else if seeded
+                && metadata.is_file()
+                && metadata.len() >= MIN_FILE_SIZE
+                && entry.file_name() != SEEDED_MARKER
+            {
+                files.push(Candidate::from_metadata(entry.path(), &metadata));
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Hash of a file's content, to find candidates for a byte comparison
+pub fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&buf[..n]);
+    }
+}
+
+/// Whether two files have the same content
+pub fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    let (mut a, mut b) = (std::fs::File::open(a)?, std::fs::File::open(b)?);
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+    let (mut buf_a, mut buf_b) = (vec![0u8; 64 * 1024], vec![0u8; 64 * 1024]);
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Replace `path` with a hardlink to `keep`, atomically
+fn replace_with_link(keep: &Path, path: &Path) -> io::Result<()> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.nlc-dedup", name));
+    let _ = std::fs::remove_file(&tmp);
+    std::fs::hard_link(keep, &tmp)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
+/// Link identical files among `files` to one copy each
+fn link_duplicates(files: Vec<Candidate>) -> Compaction {
+    let mut result = Compaction::default();
+
+    let mut groups: HashMap<_, Vec<Candidate>> = HashMap::new();
+    for file in files {
+        groups.entry(file.group_key()).or_default().push(file);
+    }
+
+    for (_, group) in groups {
+        // Paths of each inode; already linked paths share one
+        let mut inodes: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for file in group {
+            inodes.entry(file.inode).or_default().push(file);
+        }
+        if inodes.len() < 2 {
+            continue;
+        }
+
+        let mut by_hash: HashMap<u64, Vec<Vec<Candidate>>> = HashMap::new();
+        for (_, paths) in inodes {
+            match content_hash(&paths[0].path) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(paths),
+                Err(e) => {
+                    debug!(path = %paths[0].path.display(), error = %e, "Failed to hash file")
+                }
+            }
+        }
+
+        for (_, mut copies) in by_hash {
+            // Keep the most linked copy, relinking as few paths as possible
+            copies.sort_by_key(|paths| std::cmp::Reverse(paths.len()));
+            let mut copies = copies.into_iter();
+            let Some(keep) = copies.next() else { continue };
+            let keep = &keep[0];
+
+            for paths in copies {
+                match same_content(&keep.path, &paths[0].path) {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        debug!(path = %paths[0].path.display(), error = %e, "Failed to compare files");
+                        continue;
+                    }
+                }
+                let mut replaced = 0;
+                for file in &paths {
+                    if !keep.is_current() {
+                        break;
+                    }
+                    if !file.is_current() {
+                        continue;
+                    }
+                    match replace_with_link(&keep.path, &file.path) {
+                        Ok(()) => replaced += 1,
+                        Err(e) => {
+                            warn!(
+                                path = %file.path.display(),
+                                keep = %keep.path.display(),
+                                error = %e,
+                                "Failed to replace duplicate with a hardlink"
+                            );
+                            break;
+                        }
+                    }
+                }
+                result.files_linked += replaced;
+                // Freed unless linked from somewhere the scan didn't see
+                if replaced as u64 == paths[0].links {
+                    result.bytes_reclaimed += paths[0].size;
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Deduplicate the seeded files of the volume directories under `base_path`
+pub fn compact(base_path: &Path) -> io::Result<Compaction> {
+    let mut files = Vec::new();
+    for id in inventory::volume_directories(base_path)? {
+        files.extend(seeded_files(&volume::volume_path(base_path, &id))?);
+    }
+    Ok(link_duplicates(files))
+}
+
+/// Compact every `interval` until the process exits
+pub async fn run(base_path: PathBuf, interval: Duration) {
+    info!(path = %base_path.display(), "Starting dedup compaction");
+
+    loop {
+        let base = base_path.clone();
+        match tokio::task::spawn_blocking(move || compact(&base)).await {
+            Ok(Ok(compaction)) => {
+                metrics::metrics().record_dedup(&compaction);
+                if compaction.files_linked > 0 {
+                    info!(
+                        files_linked = compaction.files_linked,
+                        bytes_reclaimed = compaction.bytes_reclaimed,
+                        "Replaced duplicate files with hardlinks"
+                    );
+                }
+            }
+            Ok(Err(e)) => error!(error = %e, "Error compacting volume directories"),
+            Err(e) => error!(error = %e, "Dedup compaction task failed"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_base(name: &str) -> PathBuf {
+        let base =
+            std::env::temp_dir().join(format!("nlc-dedup-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    fn content(seed: u8) -> Vec<u8> {
+        (0..MIN_FILE_SIZE as usize * 2)
+            .map(|i| (i as u8).wrapping_mul(seed))
+            .collect()
+    }
+
+    #[test]
+    fn test_content_comparison() {
+        let base = temp_base("hash");
+        let (a, b, c) = (base.join("a"), base.join("b"), base.join("c"));
+        std::fs::write(&a, content(3)).unwrap();
+        std::fs::write(&b, content(3)).unwrap();
+        let mut other = content(3);
+        *other.last_mut().unwrap() ^= 1;
+        std::fs::write(&c, other).unwrap();
+
+        assert_eq!(content_hash(&a).unwrap(), content_hash(&b).unwrap());
+        assert_ne!(content_hash(&a).unwrap(), content_hash(&c).unwrap());
+        assert!(same_content(&a, &b).unwrap());
+        assert!(!same_content(&a, &c).unwrap());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_compact() {
+        let base = temp_base("compact");
+        for id in ["nlc-a", "nlc-b", "nlc-c"] {
+            let seed = base.join(id).join("seed");
+            std::fs::create_dir_all(seed.join("layers")).unwrap();
+            std::fs::write(seed.join(SEEDED_MARKER), b"").unwrap();
+            std::fs::write(seed.join("layers/base"), content(3)).unwrap();
+            std::fs::write(seed.join("small"), b"tiny").unwrap();
+            // Writable: never linked
+            std::fs::write(base.join(id).join("scratch"), content(3)).unwrap();
+        }
+        // Same size, other content
+        std::fs::write(base.join("nlc-c/seed/layers/base"), content(5)).unwrap();
+        std::fs::write(base.join("nlc-a/seed/layers/other"), content(5)).unwrap();
+
+        let compaction = compact(&base).unwrap();
+        assert_eq!(
+            compaction,
+            Compaction {
+                files_linked: 2,
+                bytes_reclaimed: 2 * 2 * MIN_FILE_SIZE,
+            }
+        );
+        let inode = |path: &str| std::fs::metadata(base.join(path)).unwrap().ino();
+        assert_eq!(
+            inode("nlc-a/seed/layers/base"),
+            inode("nlc-b/seed/layers/base")
+        );
+        assert_eq!(
+            inode("nlc-a/seed/layers/other"),
+            inode("nlc-c/seed/layers/base")
+        );
+        assert_ne!(
+            inode("nlc-a/seed/layers/base"),
+            inode("nlc-c/seed/layers/base")
+        );
+        assert_ne!(inode("nlc-a/scratch"), inode("nlc-b/scratch"));
+        assert_ne!(inode("nlc-a/seed/small"), inode("nlc-b/seed/small"));
+        assert_eq!(
+            std::fs::read(base.join("nlc-b/seed/layers/base")).unwrap(),
+            content(3)
+        );
+
+        // Nothing left to do, and no temporary files behind
+        assert_eq!(compact(&base).unwrap(), Compaction::default());
+        let leftovers = std::fs::read_dir(base.join("nlc-b/seed/layers"))
+            .unwrap()
+            .count();
+        assert_eq!(leftovers, 1);
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,269 @@
+//! Cached volume directory sizes (`--du-interval`).
+//!
+//! Walking a multi-terabyte cache to add up its size is expensive, so each
+//! volume's size is measured in the background and cached:
+//!
+//! - directories with a project quota (`--quota-backend xfs-project`) read
+//!   the quota's usage, and loopback volumes their own filesystem's: both
+//!   are cheap and refreshed every `SIZE_REFRESH_INTERVAL`
+//! - other directories are walked like `du`, at most once per
+//!   `--du-interval`
+//!
+//! The tradeoff is staleness: a walked size can be up to `--du-interval`
+//! (plus the walk itself) old, so a volume that grew since reads small.
+//! Longer intervals mean less I/O and staler sizes. Sizes are exported as
+//! `nlc_volume_size_bytes` and used for NodeGetVolumeStats' used bytes and
+//! inodes of volumes without a quota.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tracing::{debug, error, info};
+
+use crate::inventory;
+use crate::loopback;
+use crate::metrics;
+use crate::quota;
+use crate::volume;
+
+/// How often cached sizes are refreshed (walks only when due)
+pub const SIZE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// `st_blocks` unit
+const BLOCK_SIZE: u64 = 512;
+
+/// Where a size came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeSource {
+    /// The directory's project quota usage
+    Quota,
+    /// The directory is its own filesystem (loopback image)
+    Filesystem,
+    /// A walk of the tree
+    Walk,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CachedSize {
+    pub bytes: u64,
+    pub inodes: u64,
+    pub source: SizeSource,
+    pub measured_at: Instant,
+}
+
+/// Sizes of the volume directories under a base path, by tracking ID
+#[derive(Clone)]
+pub struct SizeCache {
+    base_path: std::path::PathBuf,
+    /// Minimum time between walks of the same directory
+    walk_interval: Duration,
+    sizes: Arc<Mutex<HashMap<String, CachedSize>>>,
+}
+
+impl SizeCache {
+    pub fn new(base_path: std::path::PathBuf, walk_interval: Duration) -> Self {
+        Self {
+            base_path,
+            walk_interval,
+            sizes: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Cached size of a volume, if measured yet
+    pub fn get(&self, tracking_id: &str) -> Option<CachedSize> {
+        self.sizes.lock().unwrap().get(tracking_id).copied()
+    }
+
+    /// Refresh the cache as of `now`: cheap sizes always, walks when due.
+    /// Directories that are gone are dropped. Returns the number of walks.
+    pub fn refresh(&self, now: Instant) -> io::Result<usize> {
+        let ids = inventory::volume_directories(&self.base_path)?;
+        let base_device = volume::device_id(&self.base_path)?;
+        let present: HashSet<&String> = ids.iter().collect();
+        let mut walks = 0;
+
+        for id in &ids {
+            let dir = volume::volume_path(&self.base_path, id);
+            let size = match cheap_size(&self.base_path, &dir, base_device)? {
+                Some(size) => size,
+                None if self.walk_due(id, now) => {
+                    walks += 1;
+                    walk_size(&dir)?
+                }
+                None => continue,
+            };
+            let (bytes, inodes, source) = size;
+            self.sizes.lock().unwrap().insert(
+                id.clone(),
+                CachedSize {
+                    bytes,
+                    inodes,
+                    source,
+                    measured_at: now,
+                },
+            );
+            metrics::metrics().record_volume_size(id, bytes);
+        }
+
+        self.sizes.lock().unwrap().retain(|id, _| {
+            let keep = present.contains(id);
+            if !keep {
+                metrics::metrics().remove_volume_size(id);
+            }
+            keep
+        });
+        Ok(walks)
+    }
+
+    fn walk_due(&self, tracking_id: &str, now: Instant) -> bool {
+        self.get(tracking_id).is_none_or(|cached| {
+            cached.source != SizeSource::Walk
+                || now.saturating_duration_since(cached.measured_at) >= self.walk_interval
+        })
+    }
+
+    /// Refresh every `interval` until the process exits
+    pub async fn run(self, interval: Duration) {
+        info!(
+            path = %self.base_path.display(),
+            walk_interval_secs = self.walk_interval.as_secs(),
+            "Starting volume size accounting"
+        );
+
+        loop {
+            let cache = self.clone();
+            match tokio::task::spawn_blocking(move || cache.refresh(Instant::now())).await {
+                Ok(Ok(walks)) => debug!(walks = walks, "Refreshed volume sizes"),
+                Ok(Err(e)) => error!(error = %e, "Error refreshing volume sizes"),
+                Err(e) => error!(error = %e, "Volume size task failed"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+/// Size of `dir` without walking it: from its project quota, or its own
+/// filesystem when it is a mounted loopback image. (btrfs subvolumes also
+/// have their own device, but share the filesystem's usage.)
+fn cheap_size(
+    base: &Path,
+    dir: &Path,
+    base_device: u64,
+) -> io::Result<Option<(u64, u64, SizeSource)>> {
+    if let Some(dq) = quota::project_quota(dir)? {
+        return Ok(Some((dq.dqb_curspace, dq.dqb_curinodes, SizeSource::Quota)));
+    }
+    if volume::device_id(dir)? != base_device && loopback::image_path(base, dir).exists() {
+        let usage = quota::Usage::from_statvfs(dir)?;
+        return Ok(Some((
+            usage.used_bytes,
+            usage.used_inodes,
+            SizeSource::Filesystem,
+        )));
+    }
+    Ok(None)
+}
+
+/// Disk usage of the tree under `dir`, like `du`: allocated blocks, hard
+/// links counted once, other filesystems not entered
+pub fn walk_size(dir: &Path) -> io::Result<(u64, u64, SizeSource)> {
+    let device = std::fs::symlink_metadata(dir)?.dev();
+    let mut seen = HashSet::new();
+    let (mut bytes, mut inodes) = (0, 0);
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(path) = pending.pop() {
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(m) => m,
+            // Deleted while walking
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        if metadata.dev() != device {
+            continue;
+        }
+        if metadata.nlink() > 1 && !metadata.is_dir() && !seen.insert(metadata.ino()) {
+            continue;
+        }
+        bytes += metadata.blocks() * BLOCK_SIZE;
+        inodes += 1;
+        if metadata.is_dir() {
+            match std::fs::read_dir(&path) {
+                Ok(entries) => {
+                    for entry in entries {
+                        pending.push(entry?.path());
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok((bytes, inodes, SizeSource::Walk))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_base(name: &str) -> std::path::PathBuf {
+        let base =
+            std::env::temp_dir().join(format!("nlc-dir-size-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn test_walk_size() {
+        let base = temp_base("walk");
+        std::fs::create_dir_all(base.join("sub")).unwrap();
+        std::fs::write(base.join("sub/data"), vec![1u8; 64 * 1024]).unwrap();
+        std::fs::hard_link(base.join("sub/data"), base.join("link")).unwrap();
+
+        let (bytes, inodes, source) = walk_size(&base).unwrap();
+        assert_eq!(source, SizeSource::Walk);
+        // base, sub and the data once
+        assert_eq!(inodes, 3);
+        assert!(bytes >= 64 * 1024, "{}", bytes);
+        assert!(bytes < 2 * 64 * 1024 + 64 * 1024, "{}", bytes);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_refresh_walks_when_due() {
+        let base = temp_base("refresh");
+        std::fs::create_dir_all(base.join("nlc-a")).unwrap();
+        std::fs::create_dir_all(base.join("shared/maven")).unwrap();
+        std::fs::write(base.join("nlc-a/data"), vec![1u8; 8192]).unwrap();
+        let cache = SizeCache::new(base.clone(), Duration::from_secs(600));
+        let start = Instant::now();
+
+        assert_eq!(cache.refresh(start).unwrap(), 2);
+        let first = cache.get("nlc-a").unwrap();
+        assert_eq!(first.inodes, 2);
+        assert!(cache.get("shared-maven").is_some());
+
+        // Not due yet: the cached size stays, however stale
+        std::fs::write(base.join("nlc-a/more"), vec![1u8; 8192]).unwrap();
+        assert_eq!(cache.refresh(start + Duration::from_secs(60)).unwrap(), 0);
+        assert_eq!(cache.get("nlc-a"), Some(first));
+
+        // Due: walked again
+        let later = start + Duration::from_secs(600);
+        assert_eq!(cache.refresh(later).unwrap(), 2);
+        let second = cache.get("nlc-a").unwrap();
+        assert_eq!(second.inodes, 3);
+        assert_eq!(second.measured_at, later);
+
+        // Gone directories are dropped
+        std::fs::remove_dir_all(base.join("nlc-a")).unwrap();
+        cache.refresh(later).unwrap();
+        assert!(cache.get("nlc-a").is_none());
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
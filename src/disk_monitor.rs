@@ -0,0 +1,260 @@
+//! Disk usage monitor for the base path (`--disk-usage-monitor`).
+//!
+//! The node plugin periodically checks how full the filesystem holding the
+//! base path is, exports it as the `nlc_disk_usage_ratio` gauge, and emits a
+//! `DiskPressureWarning` / `DiskPressureCritical` event on the Node when usage
+//! crosses the warning / critical threshold. One event fires per crossing;
+//! the threshold re-arms once usage drops back below it.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use k8s_openapi::api::core::v1::{Event, ObjectReference};
+use kube::{
+    api::{Api, PostParams},
+    Client,
+};
+use tracing::{debug, info, warn};
+
+use crate::cleanup;
+use crate::metrics;
+use crate::quota::Usage;
+
+/// How often the base path usage is checked
+pub const DISK_MONITOR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Namespace of events about cluster-scoped objects such as Nodes
+pub const NODE_EVENT_NAMESPACE: &str = "default";
+
+/// Disk pressure level of the base path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Pressure {
+    Normal,
+    Warning,
+    Critical,
+}
+
+impl Pressure {
+    fn from_percent(percent: f64, warning: u8, critical: u8) -> Self {
+        if percent >= f64::from(critical) {
+            Pressure::Critical
+        } else if percent >= f64::from(warning) {
+            Pressure::Warning
+        } else {
+            Pressure::Normal
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Pressure::Normal => "DiskPressureResolved",
+            Pressure::Warning => "DiskPressureWarning",
+            Pressure::Critical => "DiskPressureCritical",
+        }
+    }
+}
+
+/// Fraction of the filesystem in use, as `df` reports it (space reserved for
+/// root counts as unavailable)
+pub fn usage_ratio(usage: &Usage) -> f64 {
+    let usable = usage.used_bytes + usage.available_bytes;
+    if usable == 0 {
+        return 0.0;
+    }
+    usage.used_bytes as f64 / usable as f64
+}
+
+pub struct DiskMonitor {
+    client: Client,
+    node_name: String,
+    base_path: PathBuf,
+    warning_percent: u8,
+    critical_percent: u8,
+    pressure: Pressure,
+}
+
+impl DiskMonitor {
+    pub fn new(
+        client: Client,
+        node_name: String,
+        base_path: PathBuf,
+        warning_percent: u8,
+        critical_percent: u8,
+    ) -> Self {
+        Self {
+            client,
+            node_name,
+            base_path,
+            warning_percent,
+            critical_percent,
+            pressure: Pressure::Normal,
+        }
+    }
+
+    /// Record a usage sample. Returns the level to report when usage rose
+    /// into a higher level than last reported; a drop only re-arms.
+    fn observe(&mut self, percent: f64) -> Option<Pressure> {
+        let level = Pressure::from_percent(percent, self.warning_percent, self.critical_percent);
+        let previous = std::mem::replace(&mut self.pressure, level);
+        (level > previous).then_some(level)
+    }
+
+    /// Check the base path once
+    pub async fn check(&mut self) -> std::io::Result<()> {
+        let path = self.base_path.clone();
+        let usage = tokio::task::spawn_blocking(move || Usage::from_statvfs(&path))
+            .await
+            .map_err(std::io::Error::other)??;
+        let ratio = usage_ratio(&usage);
+        metrics::metrics().disk_usage_ratio.set(ratio);
+
+        let percent = ratio * 100.0;
+        debug!(path = %self.base_path.display(), percent = percent, "Checked disk usage");
+        let previous = self.pressure;
+        match self.observe(percent) {
+            Some(level) => {
+                warn!(
+                    path = %self.base_path.display(),
+                    percent = format!("{:.1}", percent),
+                    level = ?level,
+                    "Base path disk usage crossed threshold"
+                );
+                self.emit_event(
+                    level.reason(),
+                    &format!(
+                        "{} is {:.1}% full on node {} (warning at {}%, critical at {}%)",
+                        self.base_path.display(),
+                        percent,
+                        self.node_name,
+                        self.warning_percent,
+                        self.critical_percent
+                    ),
+                )
+                .await;
+            }
+            None if self.pressure < previous => {
+                info!(
+                    path = %self.base_path.display(),
+                    percent = format!("{:.1}", percent),
+                    level = ?self.pressure,
+                    "Base path disk usage back below threshold"
+                );
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    async fn emit_event(&self, reason: &str, message: &str) {
+        // The crossing is already logged
+        if !cleanup::events_enabled() {
+            return;
+        }
+        let events: Api<Event> = Api::namespaced(self.client.clone(), NODE_EVENT_NAMESPACE);
+        let event = Event {
+            metadata: kube::api::ObjectMeta {
+                generate_name: Some("nlc-".to_string()),
+                namespace: Some(NODE_EVENT_NAMESPACE.to_string()),
+                ..Default::default()
+            },
+            involved_object: ObjectReference {
+                api_version: Some("v1".to_string()),
+                kind: Some("Node".to_string()),
+                name: Some(self.node_name.clone()),
+                ..Default::default()
+            },
+            reason: Some(reason.to_string()),
+            message: Some(message.to_string()),
+            type_: Some("Warning".to_string()),
+            first_timestamp: Some(k8s_openapi::apimachinery::pkg::apis::meta::v1::Time(
+                chrono::Utc::now(),
+            )),
+            ..Default::default()
+        };
+
+        if let Err(e) = events.create(&PostParams::default(), &event).await {
+            warn!(reason = %reason, error = %e, "Failed to emit event");
+        }
+    }
+
+    /// Run the disk usage monitor loop
+    pub async fn run(mut self, interval: Duration) {
+        info!(
+            path = %self.base_path.display(),
+            warning_percent = self.warning_percent,
+            critical_percent = self.critical_percent,
+            "Starting disk usage monitor"
+        );
+
+        loop {
+            if let Err(e) = self.check().await {
+                warn!(path = %self.base_path.display(), error = %e, "Failed to check disk usage");
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_api::FakeApiServer;
+
+    #[test]
+    fn test_usage_ratio() {
+        let usage = Usage {
+            total_bytes: 1000,
+            used_bytes: 750,
+            available_bytes: 200,
+            total_inodes: 0,
+            used_inodes: 0,
+            available_inodes: 0,
+        };
+        // Reserved blocks (50) don't count as usable
+        assert!((usage_ratio(&usage) - 750.0 / 950.0).abs() < f64::EPSILON);
+
+        let empty = Usage {
+            used_bytes: 0,
+            available_bytes: 0,
+            ..usage
+        };
+        assert_eq!(usage_ratio(&empty), 0.0);
+    }
+
+    #[test]
+    fn test_pressure_from_percent() {
+        assert_eq!(Pressure::from_percent(79.9, 80, 95), Pressure::Normal);
+        assert_eq!(Pressure::from_percent(80.0, 80, 95), Pressure::Warning);
+        assert_eq!(Pressure::from_percent(99.0, 80, 95), Pressure::Critical);
+    }
+
+    #[tokio::test]
+    async fn test_one_event_per_crossing() {
+        let api = FakeApiServer::new(&["node1"]);
+        let mut monitor = DiskMonitor::new(api.client(), "node1".into(), "/".into(), 80, 95);
+
+        assert_eq!(monitor.observe(50.0), None);
+        assert_eq!(monitor.observe(85.0), Some(Pressure::Warning));
+        assert_eq!(monitor.observe(90.0), None);
+        assert_eq!(monitor.observe(96.0), Some(Pressure::Critical));
+        assert_eq!(monitor.observe(97.0), None);
+        // Dropping to warning re-arms critical, but doesn't warn again
+        assert_eq!(monitor.observe(90.0), None);
+        assert_eq!(monitor.observe(96.0), Some(Pressure::Critical));
+        // Full recovery re-arms warning
+        assert_eq!(monitor.observe(10.0), None);
+        assert_eq!(monitor.observe(81.0), Some(Pressure::Warning));
+    }
+
+    #[tokio::test]
+    async fn test_check_emits_node_event() {
+        let api = FakeApiServer::new(&["node1"]);
+        let base = std::env::temp_dir();
+        // Thresholds of 0% always trip on the first check
+        let mut monitor = DiskMonitor::new(api.client(), "node1".into(), base, 0, 0);
+
+        monitor.check().await.unwrap();
+        monitor.check().await.unwrap();
+        assert_eq!(api.event_reasons(), vec!["DiskPressureCritical"]);
+    }
+}
@@ -0,0 +1,649 @@
+//! In-memory fake of the Kubernetes API for tests.
+//!
+//! Implements just the endpoints the cleanup coordination uses: ConfigMaps
+//! (with resourceVersion conflicts, delete preconditions, immutability, label
+//! selectors and watches), Events, Nodes and PersistentVolumes. The router is handed to `kube::Client::new` as its
+//! service, so no HTTP server or network is involved.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, State},
+    http::{StatusCode, Uri},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use k8s_openapi::api::core::v1::{ConfigMap, Event, Node, PersistentVolume};
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+use tokio_stream::{wrappers::UnboundedReceiverStream, StreamExt};
+
+/// A `labelSelector`: `key=value` or `key`
+type Selector = Option<(String, Option<String>)>;
+
+#[derive(Default)]
+struct ApiState {
+    configmaps: BTreeMap<String, ConfigMap>,
+    events: Vec<Event>,
+    nodes: Vec<String>,
+    persistent_volumes: BTreeMap<String, PersistentVolume>,
+    resource_version: u64,
+    /// ConfigMap replaces still to fail with a conflict
+    conflicts: u32,
+    /// ConfigMap gets still to fail as if the API server were unavailable
+    unavailable: u32,
+    /// Open ConfigMap watches, sent each change as a watch event line
+    watchers: Vec<(Selector, mpsc::UnboundedSender<Bytes>)>,
+    /// Every ConfigMap change (resourceVersion after, before, after), for
+    /// watches starting at an earlier resourceVersion
+    changes: Vec<(u64, Option<ConfigMap>, Option<ConfigMap>)>,
+}
+
+impl ApiState {
+    /// Store a created or updated ConfigMap, notifying watches
+    fn store_configmap(&mut self, name: String, cm: ConfigMap) {
+        let previous = self.configmaps.insert(name, cm.clone());
+        self.record_change(previous, Some(cm));
+    }
+
+    fn remove_configmap(&mut self, name: &str) {
+        let previous = self.configmaps.remove(name);
+        self.resource_version += 1;
+        self.record_change(previous, None);
+    }
+
+    fn record_change(&mut self, previous: Option<ConfigMap>, current: Option<ConfigMap>) {
+        self.watchers.retain(|(selector, tx)| {
+            match watch_event(previous.as_ref(), current.as_ref(), selector) {
+                Some(line) => tx.send(line).is_ok(),
+                None => !tx.is_closed(),
+            }
+        });
+        self.changes
+            .push((self.resource_version, previous, current));
+    }
+}
+
+/// The watch event line a change is to a watch with `selector`, if any. A
+/// ConfigMap that stops matching is seen as deleted, one that starts matching
+/// as added.
+fn watch_event(
+    previous: Option<&ConfigMap>,
+    current: Option<&ConfigMap>,
+    selector: &Selector,
+) -> Option<Bytes> {
+    let was = previous.filter(|cm| matches_selector(cm, selector));
+    let is = current.filter(|cm| matches_selector(cm, selector));
+    let event = match (was, is) {
+        (None, Some(cm)) => json!({ "type": "ADDED", "object": to_value(cm) }),
+        (Some(_), Some(cm)) => json!({ "type": "MODIFIED", "object": to_value(cm) }),
+        (Some(cm), None) => json!({ "type": "DELETED", "object": to_value(cm) }),
+        (None, None) => return None,
+    };
+    let mut line = serde_json::to_vec(&event).expect("serializable");
+    line.push(b'\n');
+    Some(Bytes::from(line))
+}
+
+type Shared = Arc<Mutex<ApiState>>;
+
+#[derive(Clone, Default)]
+pub struct FakeApiServer {
+    state: Shared,
+}
+
+impl FakeApiServer {
+    /// A fake cluster with the given nodes
+    pub fn new(nodes: &[&str]) -> Self {
+        let server = Self::default();
+        server.set_nodes(nodes);
+        server
+    }
+
+    /// A client talking to this fake, with `default` as default namespace
+    pub fn client(&self) -> kube::Client {
+        kube::Client::new(self.router(), "default")
+    }
+
+    pub fn set_nodes(&self, nodes: &[&str]) {
+        self.state.lock().unwrap().nodes = nodes.iter().map(|n| n.to_string()).collect();
+    }
+
+    pub fn add_persistent_volume(&self, pv: PersistentVolume) {
+        let name = pv.metadata.name.clone().unwrap_or_default();
+        self.state
+            .lock()
+            .unwrap()
+            .persistent_volumes
+            .insert(name, pv);
+    }
+
+    pub fn remove_persistent_volume(&self, name: &str) {
+        self.state.lock().unwrap().persistent_volumes.remove(name);
+    }
+
+    /// Fail the next `count` ConfigMap replaces with a conflict, as if
+    /// someone else kept updating them
+    pub fn inject_conflicts(&self, count: u32) {
+        self.state.lock().unwrap().conflicts = count;
+    }
+
+    /// Fail the next `count` ConfigMap gets with 503 Service Unavailable
+    pub fn inject_unavailable(&self, count: u32) {
+        self.state.lock().unwrap().unavailable = count;
+    }
+
+    pub fn configmap(&self, name: &str) -> Option<ConfigMap> {
+        self.state.lock().unwrap().configmaps.get(name).cloned()
+    }
+
+    /// All events emitted so far, in order
+    pub fn events(&self) -> Vec<Event> {
+        self.state.lock().unwrap().events.clone()
+    }
+
+    /// Reasons of all events emitted so far, in order
+    pub fn event_reasons(&self) -> Vec<String> {
+        self.state
+            .lock()
+            .unwrap()
+            .events
+            .iter()
+            .filter_map(|e| e.reason.clone())
+            .collect()
+    }
+
+    fn router(&self) -> Router {
+        Router::new()
+            .route(
+                "/api/v1/namespaces/:ns/configmaps",
+                get(list_configmaps).post(create_configmap),
+            )
+            .route(
+                "/api/v1/namespaces/:ns/configmaps/:name",
+                get(get_configmap)
+                    .put(replace_configmap)
+                    .patch(patch_configmap)
+                    .delete(delete_configmap),
+            )
+            .route("/api/v1/namespaces/:ns/events", post(create_event))
+            .route(
+                "/api/v1/namespaces/:ns/events/:name",
+                get(get_event).put(replace_event).patch(patch_event),
+            )
+            .route("/api/v1/nodes", get(list_nodes))
+            .route("/api/v1/persistentvolumes", get(list_persistent_volumes))
+            .route(
+                "/api/v1/persistentvolumes/:name",
+                get(get_persistent_volume),
+            )
+            .fallback(|uri: Uri| async move { status(StatusCode::NOT_FOUND, &uri.to_string()) })
+            .with_state(self.state.clone())
+    }
+}
+
+/// A `metav1.Status` error response, as kube expects it
+fn status(code: StatusCode, message: &str) -> Response {
+    let reason = match code {
+        StatusCode::NOT_FOUND => "NotFound",
+        StatusCode::CONFLICT => "Conflict",
+        StatusCode::UNPROCESSABLE_ENTITY => "Invalid",
+        StatusCode::SERVICE_UNAVAILABLE => "ServiceUnavailable",
+        _ => "Unknown",
+    };
+    let body = json!({
+        "kind": "Status",
+        "apiVersion": "v1",
+        "metadata": {},
+        "status": "Failure",
+        "message": message,
+        "reason": reason,
+        "code": code.as_u16(),
+    });
+    (code, Json(body)).into_response()
+}
+
+/// A list at `resource_version` (nodes and PVs aren't versioned: always 1)
+fn list(kind: &str, items: Vec<Value>, resource_version: u64) -> Response {
+    Json(json!({
+        "apiVersion": "v1",
+        "kind": kind,
+        "metadata": { "resourceVersion": resource_version.to_string() },
+        "items": items,
+    }))
+    .into_response()
+}
+
+/// Decode `labelSelector=key=value` or `labelSelector=key` (the only selector
+/// forms used) from a query string
+fn label_selector(uri: &Uri) -> Selector {
+    let query = uri.query()?;
+    let raw = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("labelSelector="))?;
+    let decoded = percent_decode(raw);
+    match decoded.split_once('=') {
+        Some((key, value)) => Some((key.to_string(), Some(value.to_string()))),
+        None => Some((decoded, None)),
+    }
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                match u8::from_str_radix(hex, 16) {
+                    Ok(b) => out.push(b),
+                    Err(_) => out.extend_from_slice(&bytes[i..i + 3]),
+                }
+                i += 3;
+            }
+            b'+' => {
+                out.push(b' ');
+                i += 1;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn to_value<T: serde::Serialize>(v: &T) -> Value {
+    serde_json::to_value(v).expect("serializable")
+}
+
+fn matches_selector(cm: &ConfigMap, selector: &Selector) -> bool {
+    match selector {
+        Some((key, value)) => cm
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|l| l.get(key))
+            .is_some_and(|v| value.as_ref().is_none_or(|value| v == value)),
+        None => true,
+    }
+}
+
+/// Lists ConfigMaps, or with `watch=true` streams changes since the
+/// `resourceVersion` asked for
+async fn list_configmaps(State(state): State<Shared>, uri: Uri) -> Response {
+    let selector = label_selector(&uri);
+    let mut state = state.lock().unwrap();
+    let query: Vec<&str> = uri.query().unwrap_or_default().split('&').collect();
+    if query.contains(&"watch=true") {
+        let since = query
+            .iter()
+            .find_map(|pair| pair.strip_prefix("resourceVersion="))
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(state.resource_version);
+        let (tx, rx) = mpsc::unbounded_channel();
+        for (version, previous, current) in &state.changes {
+            if *version > since {
+                if let Some(line) = watch_event(previous.as_ref(), current.as_ref(), &selector) {
+                    let _ = tx.send(line);
+                }
+            }
+        }
+        state.watchers.push((selector, tx));
+        let stream = UnboundedReceiverStream::new(rx).map(Ok::<_, std::convert::Infallible>);
+        return Body::from_stream(stream).into_response();
+    }
+    let items = state
+        .configmaps
+        .values()
+        .filter(|cm| matches_selector(cm, &selector))
+        .map(to_value)
+        .collect();
+    let resource_version = state.resource_version;
+    list("ConfigMapList", items, resource_version)
+}
+
+async fn get_configmap(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    if state.unavailable > 0 {
+        state.unavailable -= 1;
+        return status(StatusCode::SERVICE_UNAVAILABLE, "the server is unavailable");
+    }
+    match state.configmaps.get(&name) {
+        Some(cm) => Json(to_value(cm)).into_response(),
+        None => status(
+            StatusCode::NOT_FOUND,
+            &format!("configmaps \"{}\" not found", name),
+        ),
+    }
+}
+
+async fn create_configmap(State(state): State<Shared>, body: Bytes) -> Response {
+    let mut cm: ConfigMap = match serde_json::from_slice(&body) {
+        Ok(cm) => cm,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+    let name = cm.metadata.name.clone().unwrap_or_default();
+
+    let mut state = state.lock().unwrap();
+    if state.configmaps.contains_key(&name) {
+        return status(
+            StatusCode::CONFLICT,
+            &format!("configmaps \"{}\" already exists", name),
+        );
+    }
+    state.resource_version += 1;
+    cm.metadata.resource_version = Some(state.resource_version.to_string());
+    // Unique per incarnation, like the API server's
+    cm.metadata.uid = Some(format!("uid-{}-{}", name, state.resource_version));
+    state.store_configmap(name, cm.clone());
+    (StatusCode::CREATED, Json(to_value(&cm))).into_response()
+}
+
+async fn replace_configmap(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    let mut cm: ConfigMap = match serde_json::from_slice(&body) {
+        Ok(cm) => cm,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+
+    let mut state = state.lock().unwrap();
+    if !state.configmaps.contains_key(&name) {
+        return status(
+            StatusCode::NOT_FOUND,
+            &format!("configmaps \"{}\" not found", name),
+        );
+    }
+    if state.conflicts > 0 {
+        state.conflicts -= 1;
+        return status(
+            StatusCode::CONFLICT,
+            "the object has been modified (injected)",
+        );
+    }
+    let current = &state.configmaps[&name];
+    if cm.metadata.resource_version.is_some()
+        && cm.metadata.resource_version != current.metadata.resource_version
+    {
+        return status(
+            StatusCode::CONFLICT,
+            "the object has been modified; please apply your changes to the latest version",
+        );
+    }
+    if let Some(message) = immutability_violation(current, &cm) {
+        return status(StatusCode::UNPROCESSABLE_ENTITY, &message);
+    }
+    cm.metadata.uid = current.metadata.uid.clone();
+    state.resource_version += 1;
+    cm.metadata.resource_version = Some(state.resource_version.to_string());
+    state.store_configmap(name, cm.clone());
+    Json(to_value(&cm)).into_response()
+}
+
+async fn patch_configmap(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    let patch: Value = match serde_json::from_slice(&body) {
+        Ok(p) => p,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+
+    let mut state = state.lock().unwrap();
+    let mut current = match state.configmaps.get(&name) {
+        Some(cm) => to_value(cm),
+        None => {
+            return status(
+                StatusCode::NOT_FOUND,
+                &format!("configmaps \"{}\" not found", name),
+            )
+        }
+    };
+    let expected_version = patch.pointer("/metadata/resourceVersion").cloned();
+    if let Some(expected) = expected_version.filter(|v| !v.is_null()) {
+        if current.pointer("/metadata/resourceVersion") != Some(&expected) {
+            return status(
+                StatusCode::CONFLICT,
+                "the object has been modified; please apply your changes to the latest version",
+            );
+        }
+    }
+
+    merge_patch(&mut current, &patch);
+    let mut cm: ConfigMap = match serde_json::from_value(current) {
+        Ok(cm) => cm,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+    if let Some(message) = immutability_violation(&state.configmaps[&name], &cm) {
+        return status(StatusCode::UNPROCESSABLE_ENTITY, &message);
+    }
+    state.resource_version += 1;
+    cm.metadata.resource_version = Some(state.resource_version.to_string());
+    state.store_configmap(name, cm.clone());
+    Json(to_value(&cm)).into_response()
+}
+
+/// Why updating `current` to `updated` isn't allowed, if it isn't: only the
+/// metadata of an immutable ConfigMap can change
+fn immutability_violation(current: &ConfigMap, updated: &ConfigMap) -> Option<String> {
+    if current.immutable != Some(true) {
+        return None;
+    }
+    let name = current.metadata.name.as_deref().unwrap_or_default();
+    if updated.immutable != Some(true) {
+        return Some(format!(
+            "ConfigMap \"{}\" is invalid: immutable: field is immutable when `immutable` is set",
+            name
+        ));
+    }
+    if updated.data != current.data || updated.binary_data != current.binary_data {
+        return Some(format!(
+            "ConfigMap \"{}\" is invalid: data: field is immutable when `immutable` is set",
+            name
+        ));
+    }
+    None
+}
+
+/// RFC 7386 JSON merge patch
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    let target = target.as_object_mut().expect("object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+async fn delete_configmap(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    let options: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    let mut state = state.lock().unwrap();
+    let Some(current) = state.configmaps.get(&name) else {
+        return status(
+            StatusCode::NOT_FOUND,
+            &format!("configmaps \"{}\" not found", name),
+        );
+    };
+    let current = to_value(current);
+    for field in ["resourceVersion", "uid"] {
+        let expected = options.pointer(&format!("/preconditions/{}", field));
+        if let Some(expected) = expected.filter(|v| !v.is_null()) {
+            if current.pointer(&format!("/metadata/{}", field)) != Some(expected) {
+                return status(
+                    StatusCode::CONFLICT,
+                    &format!(
+                        "Precondition failed: {} in precondition: {}",
+                        field, expected
+                    ),
+                );
+            }
+        }
+    }
+    state.remove_configmap(&name);
+    Json(current).into_response()
+}
+
+async fn create_event(State(state): State<Shared>, body: Bytes) -> Response {
+    let mut event: Event = match serde_json::from_slice(&body) {
+        Ok(e) => e,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+    let mut state = state.lock().unwrap();
+    match &event.metadata.name {
+        Some(name)
+            if state
+                .events
+                .iter()
+                .any(|e| e.metadata.name.as_ref() == Some(name)) =>
+        {
+            return status(
+                StatusCode::CONFLICT,
+                &format!("events \"{}\" already exists", name),
+            )
+        }
+        Some(_) => {}
+        None => event.metadata.name = Some(format!("nlc-{}", state.events.len())),
+    }
+    state.events.push(event.clone());
+    (StatusCode::CREATED, Json(to_value(&event))).into_response()
+}
+
+async fn get_event(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+) -> Response {
+    let state = state.lock().unwrap();
+    match state
+        .events
+        .iter()
+        .find(|e| e.metadata.name.as_deref() == Some(name.as_str()))
+    {
+        Some(event) => Json(to_value(event)).into_response(),
+        None => status(
+            StatusCode::NOT_FOUND,
+            &format!("events \"{}\" not found", name),
+        ),
+    }
+}
+
+async fn replace_event(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    let event: Event = match serde_json::from_slice(&body) {
+        Ok(e) => e,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+    let mut state = state.lock().unwrap();
+    match state
+        .events
+        .iter_mut()
+        .find(|e| e.metadata.name.as_deref() == Some(name.as_str()))
+    {
+        Some(existing) => {
+            *existing = event.clone();
+            Json(to_value(&event)).into_response()
+        }
+        None => status(
+            StatusCode::NOT_FOUND,
+            &format!("events \"{}\" not found", name),
+        ),
+    }
+}
+
+async fn patch_event(
+    State(state): State<Shared>,
+    Path((_ns, name)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    let patch: Value = match serde_json::from_slice(&body) {
+        Ok(p) => p,
+        Err(e) => return status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    };
+    let mut state = state.lock().unwrap();
+    let Some(existing) = state
+        .events
+        .iter_mut()
+        .find(|e| e.metadata.name.as_deref() == Some(name.as_str()))
+    else {
+        return status(
+            StatusCode::NOT_FOUND,
+            &format!("events \"{}\" not found", name),
+        );
+    };
+    let mut current = to_value(&*existing);
+    merge_patch(&mut current, &patch);
+    match serde_json::from_value(current) {
+        Ok(event) => {
+            *existing = event;
+            Json(to_value(&*existing)).into_response()
+        }
+        Err(e) => status(StatusCode::UNPROCESSABLE_ENTITY, &e.to_string()),
+    }
+}
+
+async fn list_nodes(State(state): State<Shared>) -> Response {
+    let items = state
+        .lock()
+        .unwrap()
+        .nodes
+        .iter()
+        .map(|name| {
+            to_value(&Node {
+                metadata: kube::api::ObjectMeta {
+                    name: Some(name.clone()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        })
+        .collect();
+    list("NodeList", items, 1)
+}
+
+async fn list_persistent_volumes(State(state): State<Shared>) -> Response {
+    let items = state
+        .lock()
+        .unwrap()
+        .persistent_volumes
+        .values()
+        .map(to_value)
+        .collect();
+    list("PersistentVolumeList", items, 1)
+}
+
+async fn get_persistent_volume(State(state): State<Shared>, Path(name): Path<String>) -> Response {
+    match state.lock().unwrap().persistent_volumes.get(&name) {
+        Some(pv) => Json(to_value(pv)).into_response(),
+        None => status(
+            StatusCode::NOT_FOUND,
+            &format!("persistentvolumes \"{}\" not found", name),
+        ),
+    }
+}
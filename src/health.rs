@@ -0,0 +1,200 @@
+//! Liveness and readiness endpoint (`--health-addr`), separate from the CSI
+//! socket and the metrics endpoint, for the pod's probes.
+//!
+//! - `GET /healthz`: 200 as long as the process serves HTTP
+//! - `GET /readyz`: 200 when the Kubernetes API is reachable, the cleanup
+//!   loop ticked recently and (node, `--wait-for-cleanup-sync`) the first
+//!   cleanup pass is done; otherwise 503 with the failed checks
+//!
+//! The cleanup loops update a `Heartbeat` on each tick; one that hasn't moved
+//! for `--health-stale-after` means the loop is wedged.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use kube::Client;
+use tokio::sync::watch;
+use tracing::warn;
+
+/// How long `/readyz` waits for the Kubernetes API
+const API_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Unix time of a loop's last tick, shared with the health endpoint
+#[derive(Debug, Clone)]
+pub struct Heartbeat(Arc<AtomicU64>);
+
+impl Default for Heartbeat {
+    /// Counts as a tick, so a loop has until its first one to start
+    fn default() -> Self {
+        Self(Arc::new(AtomicU64::new(now_secs())))
+    }
+}
+
+impl Heartbeat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn beat(&self) {
+        self.0.store(now_secs(), Ordering::Relaxed);
+    }
+
+    /// Time since the last tick, as of `now` (Unix seconds)
+    pub fn age(&self, now: u64) -> Duration {
+        Duration::from_secs(now.saturating_sub(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+/// What `/readyz` checks
+#[derive(Clone)]
+pub struct Health {
+    client: Option<Client>,
+    heartbeats: Vec<(&'static str, Heartbeat)>,
+    stale_after: Duration,
+    synced: Option<watch::Receiver<bool>>,
+}
+
+impl Health {
+    /// Readiness fails once a heartbeat is older than `stale_after`
+    pub fn new(stale_after: Duration) -> Self {
+        Self {
+            client: None,
+            heartbeats: Vec::new(),
+            stale_after,
+            synced: None,
+        }
+    }
+
+    /// Check that the Kubernetes API answers
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Check the heartbeat of the loop `name`
+    pub fn with_heartbeat(mut self, name: &'static str, heartbeat: Heartbeat) -> Self {
+        self.heartbeats.push((name, heartbeat));
+        self
+    }
+
+    /// Not ready until `synced` is true (`--wait-for-cleanup-sync`)
+    pub fn with_synced(mut self, synced: watch::Receiver<bool>) -> Self {
+        self.synced = Some(synced);
+        self
+    }
+
+    /// The failed checks, as of `now` (Unix seconds), leaving out the API
+    fn local_failures(&self, now: u64) -> Vec<String> {
+        let mut failures = Vec::new();
+        if self.synced.as_ref().is_some_and(|synced| !*synced.borrow()) {
+            failures.push("first cleanup pass not done".to_string());
+        }
+        for (name, heartbeat) in &self.heartbeats {
+            let age = heartbeat.age(now);
+            if age > self.stale_after {
+                failures.push(format!("{} loop last ticked {}s ago", name, age.as_secs()));
+            }
+        }
+        failures
+    }
+
+    /// All failed checks; empty when ready
+    async fn failures(&self) -> Vec<String> {
+        let mut failures = self.local_failures(now_secs());
+        if let Some(client) = &self.client {
+            match tokio::time::timeout(API_CHECK_TIMEOUT, client.apiserver_version()).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => failures.push(format!("Kubernetes API unreachable: {}", e)),
+                Err(_) => failures.push("Kubernetes API unreachable: timed out".to_string()),
+            }
+        }
+        failures
+    }
+}
+
+/// Build the health router
+pub fn router(health: Health) -> Router {
+    Router::new()
+        .route("/healthz", get(|| async { (StatusCode::OK, "ok") }))
+        .route("/readyz", get(readyz_handler))
+        .with_state(Arc::new(health))
+}
+
+async fn readyz_handler(State(health): State<Arc<Health>>) -> Response {
+    let failures = health.failures().await;
+    if failures.is_empty() {
+        (StatusCode::OK, "ok").into_response()
+    } else {
+        warn!(failures = ?failures, "Not ready");
+        (StatusCode::SERVICE_UNAVAILABLE, failures.join("\n")).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeat_staleness() {
+        let heartbeat = Heartbeat::new();
+        let health =
+            Health::new(Duration::from_secs(60)).with_heartbeat("cleanup", heartbeat.clone());
+        let now = now_secs();
+
+        assert!(health.local_failures(now).is_empty());
+        assert!(health.local_failures(now + 60).is_empty());
+        let failures = health.local_failures(now + 61);
+        assert_eq!(failures.len(), 1);
+        assert!(failures[0].starts_with("cleanup loop"), "{:?}", failures);
+
+        heartbeat.0.store(now + 30, Ordering::Relaxed);
+        assert!(health.local_failures(now + 61).is_empty());
+    }
+
+    #[test]
+    fn test_waits_for_sync() {
+        let (synced_tx, synced) = watch::channel(false);
+        let health = Health::new(Duration::from_secs(60)).with_synced(synced);
+        assert_eq!(health.local_failures(now_secs()).len(), 1);
+        synced_tx.send_replace(true);
+        assert!(health.local_failures(now_secs()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() {
+        use axum::extract::Request;
+        use tower::ServiceExt;
+
+        let heartbeat = Heartbeat::new();
+        let router = router(
+            Health::new(Duration::from_secs(60)).with_heartbeat("cleanup", heartbeat.clone()),
+        );
+        let get = |path: &str| Request::get(path).body(axum::body::Body::empty()).unwrap();
+
+        let response = router.clone().oneshot(get("/healthz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = router.clone().oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        heartbeat.0.store(now_secs() - 120, Ordering::Relaxed);
+        let response = router.clone().oneshot(get("/readyz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        // Still alive
+        let response = router.oneshot(get("/healthz")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,135 @@
+//! Bounded in-memory history of recent operations, for debugging.
+//!
+//! RPC handlers and cleanup loops record each operation (publish, unpublish,
+//! cleanup, ...) with its volume and result. The last `--recent-operations`
+//! entries are kept and served as JSON at the admin `/recent` endpoint. The
+//! history only lives as long as the process.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::sync::{LazyLock, Mutex};
+
+use serde::Serialize;
+
+/// Default number of operations kept
+pub const DEFAULT_CAPACITY: usize = 100;
+
+/// One recorded operation
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Operation {
+    /// RFC 3339 time the operation finished
+    pub timestamp: String,
+    /// e.g. `publish`, `unpublish`, `cleanup`
+    pub operation: &'static str,
+    pub volume_id: String,
+    /// `ok` or `error`
+    pub result: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+struct Entries {
+    capacity: usize,
+    operations: VecDeque<Operation>,
+}
+
+/// Ring buffer of the most recent operations
+pub struct History {
+    entries: Mutex<Entries>,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Entries {
+                capacity,
+                operations: VecDeque::with_capacity(capacity),
+            }),
+        }
+    }
+
+    /// Change how many operations are kept (0 disables the history),
+    /// dropping the oldest ones if needed
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.capacity = capacity;
+        while entries.operations.len() > capacity {
+            entries.operations.pop_front();
+        }
+    }
+
+    /// Record the outcome of an operation on a volume
+    pub fn record<T, E: Display>(
+        &self,
+        operation: &'static str,
+        volume_id: &str,
+        result: &Result<T, E>,
+    ) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.capacity == 0 {
+            return;
+        }
+        if entries.operations.len() >= entries.capacity {
+            entries.operations.pop_front();
+        }
+        entries.operations.push_back(Operation {
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            operation,
+            volume_id: volume_id.to_string(),
+            result: if result.is_ok() { "ok" } else { "error" },
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+    }
+
+    /// Recorded operations, most recent first
+    pub fn recent(&self) -> Vec<Operation> {
+        let entries = self.entries.lock().unwrap();
+        entries.operations.iter().rev().cloned().collect()
+    }
+}
+
+static HISTORY: LazyLock<History> = LazyLock::new(|| History::new(DEFAULT_CAPACITY));
+
+/// Process-wide operation history
+pub fn history() -> &'static History {
+    &HISTORY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_is_bounded() {
+        let history = History::new(3);
+        for i in 0..5 {
+            history.record("publish", &format!("nlc-{}", i), &Ok::<_, String>(()));
+        }
+        let recent = history.recent();
+        let ids: Vec<_> = recent.iter().map(|op| op.volume_id.as_str()).collect();
+        assert_eq!(ids, vec!["nlc-4", "nlc-3", "nlc-2"]);
+
+        history.set_capacity(1);
+        assert_eq!(history.recent().len(), 1);
+        assert_eq!(history.recent()[0].volume_id, "nlc-4");
+
+        history.set_capacity(0);
+        history.record("publish", "nlc-5", &Ok::<_, String>(()));
+        assert!(history.recent().is_empty());
+    }
+
+    #[test]
+    fn test_history_records_errors() {
+        let history = History::new(10);
+        history.record("unpublish", "nlc-a", &Err::<(), _>("device busy"));
+        let json = serde_json::to_value(history.recent()).unwrap();
+        assert_eq!(json[0]["operation"], "unpublish");
+        assert_eq!(json[0]["result"], "error");
+        assert_eq!(json[0]["error"], "device busy");
+
+        history.record("cleanup", "nlc-a", &Ok::<_, String>(()));
+        let json = serde_json::to_value(history.recent()).unwrap();
+        assert_eq!(json[0]["result"], "ok");
+        assert!(json[0].get("error").is_none());
+    }
+}
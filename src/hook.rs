@@ -0,0 +1,160 @@
+//! Pre-publish hook (`--pre-publish-hook`).
+//!
+//! An executable run by NodePublishVolume once the volume directory is ready
+//! and before it is bind-mounted, e.g. to warm a cache from a mirror or set
+//! ACLs. It gets the volume in its environment:
+//!
+//! - `NLC_VOLUME_ID`: volume ID
+//! - `NLC_SOURCE_PATH`: volume directory on the node
+//! - `NLC_TARGET_PATH`: where the directory is about to be mounted
+//!
+//! A non-zero exit or running past the timeout fails the publish, which the
+//! kubelet retries, so hooks must be idempotent. Output goes to the driver's
+//! own stdout/stderr.
+//!
+//! The hook runs as the driver, i.e. as root in a privileged container with
+//! the host's kubelet and cache directories mounted: whoever can change the
+//! hook file or the flag controls the node. Keep it on a path only root can
+//! write, such as a ConfigMap mounted read-only.
+//!
+//! The release image is built `FROM scratch`: a script hook needs an image
+//! with its interpreter, otherwise use a static executable.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+/// How often a running hook is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Run `hook` with the volume's environment, waiting up to `timeout`
+pub async fn run_pre_publish(
+    hook: PathBuf,
+    timeout: Duration,
+    volume_id: &str,
+    source_path: &Path,
+    target_path: &Path,
+) -> Result<(), String> {
+    let mut command = Command::new(&hook);
+    command
+        .env("NLC_VOLUME_ID", volume_id)
+        .env("NLC_SOURCE_PATH", source_path)
+        .env("NLC_TARGET_PATH", target_path)
+        .stdin(Stdio::null());
+
+    let volume_id = volume_id.to_string();
+    tokio::task::spawn_blocking(move || run(&hook, command, timeout, &volume_id))
+        .await
+        .map_err(|e| format!("Pre-publish hook task failed: {}", e))?
+}
+
+fn run(
+    hook: &Path,
+    mut command: Command,
+    timeout: Duration,
+    volume_id: &str,
+) -> Result<(), String> {
+    let started = Instant::now();
+    let mut child = command
+        .spawn()
+        .map_err(|e| format!("Failed to run pre-publish hook {}: {}", hook.display(), e))?;
+
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) if status.success() => {
+                info!(
+                    volume_id = %volume_id,
+                    hook = %hook.display(),
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "Pre-publish hook succeeded"
+                );
+                return Ok(());
+            }
+            Ok(Some(status)) => {
+                return Err(format!(
+                    "Pre-publish hook {} failed: {}",
+                    hook.display(),
+                    status
+                ));
+            }
+            Ok(None) if started.elapsed() >= timeout => {
+                warn!(volume_id = %volume_id, hook = %hook.display(), "Pre-publish hook timed out, killing it");
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "Pre-publish hook {} timed out after {}s",
+                    hook.display(),
+                    timeout.as_secs_f64()
+                ));
+            }
+            Ok(None) => std::thread::sleep(POLL_INTERVAL),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "Failed to wait for pre-publish hook {}: {}",
+                    hook.display(),
+                    e
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn write_hook(dir: &Path, name: &str, script: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn test_pre_publish_hook() {
+        let dir = std::env::temp_dir().join(format!("nlc-hook-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let source = dir.join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        let target = dir.join("target");
+        let timeout = Duration::from_secs(10);
+
+        let hook = write_hook(
+            &dir,
+            "ok.sh",
+            "echo \"$NLC_VOLUME_ID $NLC_TARGET_PATH\" > \"$NLC_SOURCE_PATH/warmed\"",
+        );
+        run_pre_publish(hook, timeout, "nlc-a", &source, &target)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(source.join("warmed")).unwrap(),
+            format!("nlc-a {}\n", target.display())
+        );
+
+        let hook = write_hook(&dir, "fail.sh", "exit 3");
+        let err = run_pre_publish(hook, timeout, "nlc-a", &source, &target)
+            .await
+            .unwrap_err();
+        assert!(err.contains("exit status: 3"), "{}", err);
+
+        let hook = write_hook(&dir, "slow.sh", "sleep 30");
+        let started = Instant::now();
+        let err = run_pre_publish(hook, Duration::from_millis(200), "nlc-a", &source, &target)
+            .await
+            .unwrap_err();
+        assert!(err.contains("timed out"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let err = run_pre_publish(dir.join("missing.sh"), timeout, "nlc-a", &source, &target)
+            .await
+            .unwrap_err();
+        assert!(err.contains("Failed to run"), "{}", err);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,346 @@
+//! HTTP endpoint served alongside the CSI socket (metrics, admin actions).
+//!
+//! `GET /readyz` (node) answers 503 until the node is ready, i.e. with
+//! `--wait-for-cleanup-sync` until the cleanup watcher's first pass.
+//!
+//! Admin endpoints are only mounted when `--admin-token` is set, and require
+//! `Authorization: Bearer <token>`:
+//! - `GET /config`: effective configuration
+//! - `GET /recent`: recent operations, most recent first (see `history`)
+//! - `POST /prune/<volume_id>`: evaluate a volume's cleanup now (controller)
+//! - `POST /unmount`: unmount one wedged target path, given as
+//!   `{"target_path": "..."}` under an `--allowed-target-prefix` (node)
+
+use std::net::SocketAddr;
+use std::path::{Component, PathBuf};
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+
+use crate::cleanup::CleanupController;
+use crate::history;
+use crate::metrics;
+use crate::volume;
+
+/// Content type for OpenMetrics text exposition
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Build the HTTP router
+pub fn router() -> Router {
+    Router::new().route("/metrics", get(metrics_handler))
+}
+
+/// Readiness endpoint, ready once `ready` is true
+pub fn readiness_router(ready: watch::Receiver<bool>) -> Router {
+    Router::new()
+        .route("/readyz", get(readyz_handler))
+        .with_state(ready)
+}
+
+async fn readyz_handler(State(ready): State<watch::Receiver<bool>>) -> Response {
+    if *ready.borrow() {
+        (StatusCode::OK, "ok").into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready").into_response()
+    }
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)],
+        metrics::metrics().encode(),
+    )
+}
+
+/// Admin endpoints for both modes, protected by `token`
+pub fn admin_router(token: &str, effective_config: serde_json::Value) -> Router {
+    protect(
+        Router::new()
+            .route("/config", get(config_handler))
+            .route("/recent", get(recent_handler))
+            .with_state(Arc::new(effective_config)),
+        token,
+    )
+}
+
+/// Controller admin endpoints, protected by `token`
+pub fn controller_admin_router(token: &str, cleanup: CleanupController) -> Router {
+    protect(
+        Router::new()
+            .route("/prune/:volume_id", post(prune_handler))
+            .with_state(Arc::new(cleanup)),
+        token,
+    )
+}
+
+/// Node admin endpoints, protected by `token`. `/unmount` only touches paths
+/// under `allowed_prefixes`.
+pub fn node_admin_router(token: &str, allowed_prefixes: Vec<PathBuf>) -> Router {
+    protect(
+        Router::new()
+            .route("/unmount", post(unmount_handler))
+            .with_state(Arc::new(allowed_prefixes)),
+        token,
+    )
+}
+
+/// Require the bearer `token` on all routes of `router`
+fn protect(router: Router, token: &str) -> Router {
+    router.route_layer(middleware::from_fn_with_state(
+        Arc::<str>::from(token),
+        require_token,
+    ))
+}
+
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    if !is_authorized(request.headers(), &token) {
+        warn!(path = %request.uri().path(), "Rejected unauthorized admin request");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+/// Check the bearer token, comparing in constant time
+fn is_authorized(headers: &HeaderMap, token: &str) -> bool {
+    let Some(provided) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+async fn config_handler(State(config): State<Arc<serde_json::Value>>) -> Response {
+    Json(config.as_ref().clone()).into_response()
+}
+
+async fn recent_handler() -> Response {
+    Json(history::history().recent()).into_response()
+}
+
+async fn prune_handler(
+    State(cleanup): State<Arc<CleanupController>>,
+    Path(volume_id): Path<String>,
+) -> Response {
+    if !volume::validate_tracking_id(&volume_id) {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid volume ID: {}", volume_id),
+        )
+            .into_response();
+    }
+
+    info!(volume_id = %volume_id, "Admin prune requested");
+    match cleanup.prune_volume(&volume_id).await {
+        Ok(Some(outcome)) => Json(outcome).into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            format!("No tracking ConfigMap for volume {}", volume_id),
+        )
+            .into_response(),
+        Err(e) => {
+            error!(volume_id = %volume_id, error = %e, "Admin prune failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UnmountRequest {
+    target_path: PathBuf,
+}
+
+#[derive(Debug, Serialize)]
+struct UnmountResponse {
+    target_path: PathBuf,
+    /// Whether anything was mounted at the path
+    was_mounted: bool,
+    /// How it was unmounted, if it was
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<volume::Unmounted>,
+}
+
+/// Whether `path` is strictly below one of `prefixes`. Paths with `..` are
+/// refused outright rather than resolved.
+fn is_allowed_target(path: &std::path::Path, prefixes: &[PathBuf]) -> bool {
+    path.is_absolute()
+        && !path.components().any(|c| c == Component::ParentDir)
+        && prefixes
+            .iter()
+            .any(|prefix| path.starts_with(prefix) && path != prefix.as_path())
+}
+
+async fn unmount_handler(
+    State(allowed_prefixes): State<Arc<Vec<PathBuf>>>,
+    Json(request): Json<UnmountRequest>,
+) -> Response {
+    let target_path = request.target_path;
+    if !is_allowed_target(&target_path, &allowed_prefixes) {
+        warn!(target_path = %target_path.display(), "Refused admin unmount outside the allowed prefixes");
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Target path {} is not under an allowed prefix ({:?})",
+                target_path.display(),
+                allowed_prefixes
+            ),
+        )
+            .into_response();
+    }
+
+    info!(target_path = %target_path.display(), "Admin unmount requested");
+    let path = target_path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        if !volume::is_mounted(&path).map_err(|s| s.message().to_string())? {
+            return Ok(None);
+        }
+        volume::unmount(&path)
+            .map(Some)
+            .map_err(|e| format!("Failed to unmount {}: {}", path.display(), e))
+    })
+    .await
+    .unwrap_or_else(|e| Err(format!("Unmount task failed: {}", e)));
+
+    match result {
+        Ok(method) => {
+            info!(target_path = %target_path.display(), method = ?method, "Admin unmount done");
+            Json(UnmountResponse {
+                target_path,
+                was_mounted: method.is_some(),
+                method,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!(target_path = %target_path.display(), error = %e, "Admin unmount failed");
+            (StatusCode::INTERNAL_SERVER_ERROR, e).into_response()
+        }
+    }
+}
+
+/// Bind the HTTP endpoint. Done up front so a bad address fails startup.
+pub async fn bind(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let listener = TcpListener::bind(addr).await?;
+    info!(addr = %addr, "HTTP endpoint listening");
+    Ok(listener)
+}
+
+/// Serve `router` on `listener` until the process exits
+pub async fn serve(listener: TcpListener, router: Router) {
+    if let Err(e) = axum::serve(listener, router).await {
+        error!(error = %e, "HTTP endpoint failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_is_authorized() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_authorized(&headers, "s3cret"));
+
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_static("Bearer s3cret"),
+        );
+        assert!(is_authorized(&headers, "s3cret"));
+        assert!(!is_authorized(&headers, "s3cret2"));
+        assert!(!is_authorized(&headers, "other!"));
+
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("s3cret"));
+        assert!(!is_authorized(&headers, "s3cret"));
+    }
+
+    #[test]
+    fn test_is_allowed_target() {
+        let prefixes = vec![PathBuf::from("/var/lib/kubelet/pods")];
+        let allowed = |p: &str| is_allowed_target(std::path::Path::new(p), &prefixes);
+
+        assert!(allowed(
+            "/var/lib/kubelet/pods/uid/volumes/kubernetes.io~csi/pvc/mount"
+        ));
+        assert!(!allowed("/var/lib/kubelet/pods"));
+        assert!(!allowed("/var/lib/kubelet/pods-other/x"));
+        assert!(!allowed("/var/lib/kubelet/pods/../plugins/x"));
+        assert!(!allowed("var/lib/kubelet/pods/x"));
+        assert!(!allowed("/etc"));
+    }
+
+    #[tokio::test]
+    async fn test_unmount_endpoint() {
+        use tower::ServiceExt;
+
+        let base = std::env::temp_dir().join(format!("nlc-http-unmount-{}", std::process::id()));
+        let target = base.join("pod").join("mount");
+        std::fs::create_dir_all(&target).unwrap();
+        let router = node_admin_router("s3cret", vec![base.clone()]);
+        let unmount = |path: &std::path::Path| {
+            Request::post("/unmount")
+                .header(header::AUTHORIZATION, "Bearer s3cret")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(axum::body::Body::from(
+                    serde_json::json!({ "target_path": path }).to_string(),
+                ))
+                .unwrap()
+        };
+
+        // Not mounted: nothing to do, and reported as such
+        let response = router.clone().oneshot(unmount(&target)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["was_mounted"], false);
+        assert!(body.get("method").is_none());
+
+        let response = router
+            .oneshot(unmount(std::path::Path::new("/etc")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_readyz() {
+        use tower::ServiceExt;
+
+        let (ready_tx, ready) = watch::channel(false);
+        let router = readiness_router(ready);
+        let readyz = || {
+            Request::get("/readyz")
+                .body(axum::body::Body::empty())
+                .unwrap()
+        };
+
+        let response = router.clone().oneshot(readyz()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        ready_tx.send_replace(true);
+        let response = router.oneshot(readyz()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -1,6 +1,8 @@
+use tokio::sync::watch;
 use tonic::{Request, Response, Status};
 use tracing::info;
 
+use crate::config::{Args, Mode};
 use crate::csi::{
     identity_server::Identity, plugin_capability, GetPluginCapabilitiesRequest,
     GetPluginCapabilitiesResponse, GetPluginInfoRequest, GetPluginInfoResponse, PluginCapability,
@@ -10,16 +12,79 @@ use crate::csi::{
 pub const DRIVER_NAME: &str = "node-local-cache.csi.io";
 pub const DRIVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Optional features advertised by GetPluginCapabilities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PluginCapabilities {
+    /// The ControllerService is served (controller mode)
+    pub controller_service: bool,
+    /// Volumes can be expanded, online or offline
+    pub volume_expansion: Option<plugin_capability::volume_expansion::Type>,
+    /// Volumes report accessible topology
+    pub volume_accessibility_constraints: bool,
+}
+
+impl PluginCapabilities {
+    /// The capabilities of an instance configured by `args`. Volumes can
+    /// always be expanded while in use (the node raises its quota, if any);
+    /// topology is never advertised, volumes are accessible from any node.
+    pub fn from_args(args: &Args) -> Self {
+        Self {
+            controller_service: matches!(args.mode, Mode::Controller),
+            volume_expansion: Some(plugin_capability::volume_expansion::Type::Online),
+            volume_accessibility_constraints: false,
+        }
+    }
+
+    fn to_csi(self) -> Vec<PluginCapability> {
+        let service = |r#type: plugin_capability::service::Type| PluginCapability {
+            r#type: Some(plugin_capability::Type::Service(
+                plugin_capability::Service {
+                    r#type: r#type as i32,
+                },
+            )),
+        };
+        let mut capabilities = Vec::new();
+        if self.controller_service {
+            capabilities.push(service(plugin_capability::service::Type::ControllerService));
+        }
+        if self.volume_accessibility_constraints {
+            capabilities.push(service(
+                plugin_capability::service::Type::VolumeAccessibilityConstraints,
+            ));
+        }
+        if let Some(expansion) = self.volume_expansion {
+            capabilities.push(PluginCapability {
+                r#type: Some(plugin_capability::Type::VolumeExpansion(
+                    plugin_capability::VolumeExpansion {
+                        r#type: expansion as i32,
+                    },
+                )),
+            });
+        }
+        capabilities
+    }
+}
+
 pub struct IdentityService {
-    /// Whether this instance is running in controller mode (vs node mode)
-    is_controller: bool,
+    /// What GetPluginCapabilities advertises
+    capabilities: PluginCapabilities,
+    /// Probe reports ready once this is true (`--wait-for-cleanup-sync`)
+    ready: Option<watch::Receiver<bool>>,
 }
 
 impl IdentityService {
-    /// Create a new IdentityService
-    /// - `is_controller`: true if running in controller mode, false for node mode
-    pub fn new(is_controller: bool) -> Self {
-        Self { is_controller }
+    /// Create a new IdentityService advertising `capabilities`
+    pub fn new(capabilities: PluginCapabilities) -> Self {
+        Self {
+            capabilities,
+            ready: None,
+        }
+    }
+
+    /// Report not ready from Probe until `ready` turns true
+    pub fn with_readiness(mut self, ready: watch::Receiver<bool>) -> Self {
+        self.ready = Some(ready);
+        self
     }
 }
 
@@ -42,21 +107,8 @@ impl Identity for IdentityService {
         &self,
         _request: Request<GetPluginCapabilitiesRequest>,
     ) -> Result<Response<GetPluginCapabilitiesResponse>, Status> {
-        info!(is_controller = %self.is_controller, "GetPluginCapabilities called");
-
-        // Only advertise ControllerService when running in controller mode
-        let capabilities = if self.is_controller {
-            vec![PluginCapability {
-                r#type: Some(plugin_capability::Type::Service(
-                    plugin_capability::Service {
-                        r#type: plugin_capability::service::Type::ControllerService as i32,
-                    },
-                )),
-            }]
-        } else {
-            // Node mode - no controller capabilities
-            vec![]
-        };
+        info!(capabilities = ?self.capabilities, "GetPluginCapabilities called");
+        let capabilities = self.capabilities.to_csi();
 
         Ok(Response::new(GetPluginCapabilitiesResponse {
             capabilities,
@@ -67,7 +119,87 @@ impl Identity for IdentityService {
         &self,
         _request: Request<ProbeRequest>,
     ) -> Result<Response<ProbeResponse>, Status> {
-        // Always ready
-        Ok(Response::new(ProbeResponse { ready: Some(true) }))
+        let ready = self.ready.as_ref().is_none_or(|ready| *ready.borrow());
+        Ok(Response::new(ProbeResponse { ready: Some(ready) }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_probe_readiness() {
+        let identity = IdentityService::new(PluginCapabilities::default());
+        let probe = identity.probe(Request::new(ProbeRequest {})).await.unwrap();
+        assert_eq!(probe.get_ref().ready, Some(true));
+
+        let (ready_tx, ready) = watch::channel(false);
+        let identity = IdentityService::new(PluginCapabilities::default()).with_readiness(ready);
+        let probe = identity.probe(Request::new(ProbeRequest {})).await.unwrap();
+        assert_eq!(probe.get_ref().ready, Some(false));
+
+        ready_tx.send_replace(true);
+        let probe = identity.probe(Request::new(ProbeRequest {})).await.unwrap();
+        assert_eq!(probe.get_ref().ready, Some(true));
+    }
+
+    async fn advertised(args: &[&str]) -> Vec<PluginCapability> {
+        let args = Args::try_load_from(args.iter().copied()).unwrap();
+        IdentityService::new(PluginCapabilities::from_args(&args))
+            .get_plugin_capabilities(Request::new(GetPluginCapabilitiesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .capabilities
+    }
+
+    #[tokio::test]
+    async fn test_capabilities_follow_config() {
+        let controller_service = PluginCapability {
+            r#type: Some(plugin_capability::Type::Service(
+                plugin_capability::Service {
+                    r#type: plugin_capability::service::Type::ControllerService as i32,
+                },
+            )),
+        };
+
+        let online_expansion = PluginCapability {
+            r#type: Some(plugin_capability::Type::VolumeExpansion(
+                plugin_capability::VolumeExpansion {
+                    r#type: plugin_capability::volume_expansion::Type::Online as i32,
+                },
+            )),
+        };
+
+        assert_eq!(
+            advertised(&["nlc", "--mode", "controller"]).await,
+            vec![controller_service, online_expansion]
+        );
+        assert_eq!(
+            advertised(&["nlc", "--mode", "node"]).await,
+            vec![online_expansion]
+        );
+        assert_eq!(
+            advertised(&["nlc", "--mode", "node", "--quota-backend", "xfs-project"]).await,
+            vec![online_expansion]
+        );
+
+        let all = PluginCapabilities {
+            controller_service: true,
+            volume_expansion: Some(plugin_capability::volume_expansion::Type::Online),
+            volume_accessibility_constraints: true,
+        };
+        let types: Vec<_> = all
+            .to_csi()
+            .into_iter()
+            .map(|c| c.r#type.unwrap())
+            .collect();
+        assert_eq!(types.len(), 3);
+        assert!(types.contains(&plugin_capability::Type::VolumeExpansion(
+            plugin_capability::VolumeExpansion {
+                r#type: plugin_capability::volume_expansion::Type::Online as i32,
+            }
+        )));
     }
 }
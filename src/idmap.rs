@@ -0,0 +1,339 @@
+//! Idmapped bind mounts for pods running in user namespaces.
+//!
+//! Cache directories are owned by host IDs. A pod in a user namespace sees
+//! them as owned by `nobody`, unless the bind mount is idmapped with the pod's
+//! ID mapping. The mapping comes from the volume context
+//! (`volume::UID_MAP_KEY` / `volume::GID_MAP_KEY`, in `/proc/<pid>/uid_map`
+//! order: `<first id in pod>:<first host id>:<count>`, comma-separated).
+//!
+//! Requires Linux 5.12+ (`mount_setattr`) and a filesystem supporting idmapped
+//! mounts for the base path. There is no chown fallback: shifting ownership of
+//! the directory tree would break it for every other consumer, so publishing
+//! fails instead when idmapped mounts aren't available.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use nix::libc;
+use nix::sched::CloneFlags;
+use nix::sys::signal::Signal;
+
+use crate::volume;
+
+/// `MOVE_MOUNT_F_EMPTY_PATH` from linux/mount.h
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x00000004;
+/// Stack size for the short-lived child owning the user namespace
+const CHILD_STACK_SIZE: usize = 64 * 1024;
+
+/// One line of a uid_map/gid_map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdMapping {
+    /// First ID as seen inside the pod
+    pub inside: u32,
+    /// First host ID it maps to
+    pub outside: u32,
+    pub count: u32,
+}
+
+/// Parse `inside:outside:count[,inside:outside:count...]`
+pub fn parse_mappings(s: &str) -> Result<Vec<IdMapping>, String> {
+    let mappings = s
+        .split(',')
+        .map(|entry| {
+            let parts: Vec<&str> = entry.trim().split(':').collect();
+            let [inside, outside, count] = parts[..] else {
+                return Err(format!(
+                    "invalid ID mapping `{}` (expected inside:outside:count)",
+                    entry
+                ));
+            };
+            let parse = |v: &str| {
+                v.parse::<u32>()
+                    .map_err(|_| format!("invalid ID `{}` in mapping `{}`", v, entry))
+            };
+            let mapping = IdMapping {
+                inside: parse(inside)?,
+                outside: parse(outside)?,
+                count: parse(count)?,
+            };
+            let fits = |start: u32| start.checked_add(mapping.count).is_some();
+            if mapping.count == 0 || !fits(mapping.inside) || !fits(mapping.outside) {
+                return Err(format!("invalid ID range in mapping `{}`", entry));
+            }
+            Ok(mapping)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // The kernel accepts at most 340 lines; anything near that is a mistake here
+    if mappings.len() > 16 {
+        return Err("too many ID mappings".to_string());
+    }
+    Ok(mappings)
+}
+
+/// Host ID that `id`, as seen inside the user namespace, maps to
+pub fn to_host(mappings: &[IdMapping], id: u32) -> Option<u32> {
+    mappings
+        .iter()
+        .find(|m| id >= m.inside && id - m.inside < m.count)
+        .map(|m| m.outside + (id - m.inside))
+}
+
+/// UID and GID mappings of a user namespace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdMaps {
+    pub uid_map: Vec<IdMapping>,
+    pub gid_map: Vec<IdMapping>,
+}
+
+/// UID and GID mappings requested by a volume context, if any.
+/// A volume with only one of the two uses it for both.
+pub fn from_volume_context(
+    volume_context: &HashMap<String, String>,
+) -> Result<Option<IdMaps>, String> {
+    let parse = |key: &str| {
+        volume_context
+            .get(key)
+            .map(|map| parse_mappings(map).map_err(|e| format!("Invalid {}: {}", key, e)))
+            .transpose()
+    };
+    let (uid_map, gid_map) = match (parse(volume::UID_MAP_KEY)?, parse(volume::GID_MAP_KEY)?) {
+        (None, None) => return Ok(None),
+        (Some(uid), None) => (uid.clone(), uid),
+        (None, Some(gid)) => (gid.clone(), gid),
+        (Some(uid), Some(gid)) => (uid, gid),
+    };
+    Ok(Some(IdMaps { uid_map, gid_map }))
+}
+
+/// Render mappings in `/proc/<pid>/uid_map` format
+fn format_map(mappings: &[IdMapping]) -> String {
+    mappings
+        .iter()
+        .map(|m| format!("{} {} {}\n", m.inside, m.outside, m.count))
+        .collect()
+}
+
+/// Whether the kernel supports `mount_setattr` (and thus idmapped mounts)
+pub fn is_supported() -> bool {
+    // SAFETY: invalid fd and null attr; the kernel only validates arguments
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            -1,
+            std::ptr::null::<libc::c_char>(),
+            0,
+            std::ptr::null::<libc::mount_attr>(),
+            0,
+        )
+    };
+    ret == 0 || io::Error::last_os_error().raw_os_error() != Some(libc::ENOSYS)
+}
+
+/// Bind mount `source` on `target`, idmapped with the given mappings
+pub fn idmapped_bind_mount(
+    source: &Path,
+    target: &Path,
+    maps: &IdMaps,
+    readonly: bool,
+) -> io::Result<()> {
+    let userns = create_userns(maps)?;
+
+    let source = path_cstring(source)?;
+    let target = path_cstring(target)?;
+    let empty = c"";
+
+    // SAFETY: valid NUL-terminated path; returns a new fd we take ownership of
+    let tree = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            source.as_ptr(),
+            libc::OPEN_TREE_CLONE | libc::OPEN_TREE_CLOEXEC,
+        )
+    };
+    if tree < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: open_tree returned a valid, owned fd
+    let tree = unsafe { OwnedFd::from_raw_fd(tree as libc::c_int) };
+
+    let mut attr_set = libc::MOUNT_ATTR_IDMAP;
+    if readonly {
+        attr_set |= libc::MOUNT_ATTR_RDONLY;
+    }
+    let attr = libc::mount_attr {
+        attr_set,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: userns.as_raw_fd() as u64,
+    };
+    // SAFETY: `attr` is a valid mount_attr and its size is passed along
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            tree.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_EMPTY_PATH,
+            &attr as *const libc::mount_attr,
+            std::mem::size_of::<libc::mount_attr>(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: valid fds and NUL-terminated paths
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_move_mount,
+            tree.as_raw_fd(),
+            empty.as_ptr(),
+            libc::AT_FDCWD,
+            target.as_ptr(),
+            MOVE_MOUNT_F_EMPTY_PATH,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn path_cstring(path: &Path) -> io::Result<std::ffi::CString> {
+    std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+/// Create a user namespace with the given mappings and return an fd to it.
+///
+/// A process can't unshare a user namespace once it has threads, so a
+/// short-lived child is cloned into a new one, its maps are written, and the
+/// namespace is kept alive through the `/proc/<pid>/ns/user` fd.
+fn create_userns(maps: &IdMaps) -> io::Result<File> {
+    let mut stack = vec![0u8; CHILD_STACK_SIZE];
+    let child = Box::new(|| loop {
+        nix::unistd::pause();
+    });
+    // SAFETY: the child only calls pause() until it is killed
+    let pid = unsafe {
+        nix::sched::clone(
+            child,
+            &mut stack,
+            CloneFlags::CLONE_NEWUSER,
+            Some(Signal::SIGCHLD as libc::c_int),
+        )
+    }
+    .map_err(io::Error::from)?;
+
+    let result = (|| {
+        std::fs::write(format!("/proc/{}/uid_map", pid), format_map(&maps.uid_map))?;
+        std::fs::write(format!("/proc/{}/gid_map", pid), format_map(&maps.gid_map))?;
+        File::open(format!("/proc/{}/ns/user", pid))
+    })();
+
+    let _ = nix::sys::signal::kill(pid, Signal::SIGKILL);
+    let _ = nix::sys::wait::waitpid(pid, None);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_host() {
+        let maps = parse_mappings("0:100000:65536,70000:200000:10").unwrap();
+        assert_eq!(to_host(&maps, 0), Some(100000));
+        assert_eq!(to_host(&maps, 1000), Some(101000));
+        assert_eq!(to_host(&maps, 70009), Some(200009));
+        assert_eq!(to_host(&maps, 65536), None);
+        assert_eq!(to_host(&maps, 70010), None);
+    }
+
+    #[test]
+    fn test_parse_mappings() {
+        assert_eq!(
+            parse_mappings("0:100000:65536").unwrap(),
+            vec![IdMapping {
+                inside: 0,
+                outside: 100000,
+                count: 65536
+            }]
+        );
+        assert_eq!(parse_mappings("0:1000:1, 1:100000:65535").unwrap().len(), 2);
+
+        assert!(parse_mappings("").is_err());
+        assert!(parse_mappings("0:100000").is_err());
+        assert!(parse_mappings("0:100000:0").is_err());
+        assert!(parse_mappings("a:100000:1").is_err());
+        assert!(parse_mappings("0:4294967295:2").is_err());
+    }
+
+    #[test]
+    fn test_from_volume_context() {
+        let mut ctx = HashMap::new();
+        assert_eq!(from_volume_context(&ctx), Ok(None));
+
+        ctx.insert(
+            volume::UID_MAP_KEY.to_string(),
+            "0:100000:65536".to_string(),
+        );
+        let maps = from_volume_context(&ctx).unwrap().unwrap();
+        assert_eq!(maps.uid_map, maps.gid_map);
+
+        ctx.insert(
+            volume::GID_MAP_KEY.to_string(),
+            "0:200000:65536".to_string(),
+        );
+        let maps = from_volume_context(&ctx).unwrap().unwrap();
+        assert_eq!(maps.uid_map[0].outside, 100000);
+        assert_eq!(maps.gid_map[0].outside, 200000);
+
+        ctx.insert(volume::GID_MAP_KEY.to_string(), "bogus".to_string());
+        assert!(from_volume_context(&ctx).unwrap_err().contains("gid-map"));
+    }
+
+    #[test]
+    fn test_format_map() {
+        let mappings = parse_mappings("0:1000:1,1:100000:65535").unwrap();
+        assert_eq!(format_map(&mappings), "0 1000 1\n1 100000 65535\n");
+    }
+
+    #[test]
+    fn test_idmapped_bind_mount() {
+        if !nix::unistd::geteuid().is_root() || !is_supported() {
+            // Skip test unless running as root on a kernel with mount_setattr
+            return;
+        }
+
+        let base = std::env::temp_dir().join(format!("nlc-idmap-test-{}", std::process::id()));
+        let source = base.join("source");
+        let target = base.join("target");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+
+        let map = parse_mappings("0:100000:65536").unwrap();
+        let maps = IdMaps {
+            uid_map: map.clone(),
+            gid_map: map,
+        };
+        match idmapped_bind_mount(&source, &target, &maps, false) {
+            Ok(()) => {
+                use std::os::unix::fs::MetadataExt;
+                // Source is owned by root (0), which the mapping shows as 100000
+                assert_eq!(std::fs::metadata(&target).unwrap().uid(), 100000);
+                nix::mount::umount(&target).unwrap();
+            }
+            // Filesystem of the temp dir doesn't support idmapped mounts, or no
+            // permission to create user namespaces (nested containers)
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::EPERM)) => {}
+            Err(e) => panic!("idmapped mount failed: {}", e),
+        }
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,134 @@
+//! Startup inventory of the base path.
+//!
+//! Right after the node plugin starts, one scan counts the volume directories
+//! under the base path and how many of them are mounted, and takes the base
+//! path filesystem's usage, so the gauges are accurate before any background
+//! loop ran. It only lists two directory levels, parses mountinfo once and
+//! calls statvfs: usage is the filesystem's, not a per-directory sum.
+
+use std::io;
+use std::path::Path;
+
+use crate::disk_monitor;
+use crate::metrics;
+use crate::mount_audit;
+use crate::quota::Usage;
+use crate::volume;
+
+/// What the base path holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Inventory {
+    /// Volume and shared cache directories
+    pub directories: usize,
+    /// Directories with at least one mount outside the base path
+    pub mounted: usize,
+    /// Bytes in use on the base path's filesystem
+    pub used_bytes: u64,
+}
+
+/// Tracking IDs of the volume directories under `base_path`; hidden
+/// directories (`.quarantine`, `.images`, ...) are not volumes
+pub fn volume_directories(base_path: &Path) -> io::Result<Vec<String>> {
+    let mut ids = Vec::new();
+    let entries = match std::fs::read_dir(base_path) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ids),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let Some(name) = entry.file_name().to_str().map(str::to_string) else {
+            continue;
+        };
+        if name.starts_with('.') {
+            continue;
+        }
+        if name == volume::SHARED_DIR {
+            for shared in std::fs::read_dir(entry.path())? {
+                let shared = shared?;
+                if shared.file_type()?.is_dir() {
+                    if let Some(name) = shared.file_name().to_str() {
+                        ids.push(volume::shared_volume_id(name));
+                    }
+                }
+            }
+        } else {
+            ids.push(name);
+        }
+    }
+    Ok(ids)
+}
+
+/// Count the volume directories under `base_path` and those mounted
+/// according to `mountinfo`; `used_bytes` is left to the caller
+pub fn scan(base_path: &Path, mountinfo: &str) -> io::Result<Inventory> {
+    let directories = volume_directories(base_path)?;
+    let mounts = mount_audit::volume_mounts(&mount_audit::parse_mountinfo(mountinfo), base_path);
+    let mounted = directories
+        .iter()
+        .filter(|id| mounts.iter().any(|m| &m.tracking_id == *id))
+        .count();
+    Ok(Inventory {
+        directories: directories.len(),
+        mounted,
+        used_bytes: 0,
+    })
+}
+
+/// Take the inventory of `base_path` and publish it as metrics
+pub fn record(base_path: &Path) -> io::Result<Inventory> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    let mut inventory = scan(base_path, &mountinfo)?;
+    let m = metrics::metrics();
+    if base_path.exists() {
+        let usage = Usage::from_statvfs(base_path)?;
+        inventory.used_bytes = usage.used_bytes;
+        m.disk_usage_ratio.set(disk_monitor::usage_ratio(&usage));
+    }
+    m.record_inventory(&inventory);
+    Ok(inventory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan() {
+        let base = std::env::temp_dir().join(format!("nlc-inventory-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        for dir in [
+            "nlc-a",
+            "nlc-b",
+            "shared/maven",
+            ".quarantine/nlc-c",
+            ".images",
+        ] {
+            std::fs::create_dir_all(base.join(dir)).unwrap();
+        }
+        std::fs::write(base.join("stray-file"), b"data").unwrap();
+
+        let mountinfo = format!(
+            "\
+22 1 8:1 / / rw - ext4 /dev/sda1 rw
+40 22 8:2 / {base} rw - xfs /dev/sdb1 rw
+50 22 8:2 /nlc-a /var/lib/kubelet/pods/p1/mount rw - xfs /dev/sdb1 rw
+51 22 8:2 /nlc-a /var/lib/kubelet/pods/p2/mount ro - xfs /dev/sdb1 rw
+52 22 8:2 /shared/maven /var/lib/kubelet/pods/p3/mount rw - xfs /dev/sdb1 rw
+53 22 8:2 /nlc-gone /var/lib/kubelet/pods/p4/mount rw - xfs /dev/sdb1 rw
+",
+            base = base.display()
+        );
+        let inventory = scan(&base, &mountinfo).unwrap();
+        assert_eq!(inventory.directories, 3);
+        assert_eq!(inventory.mounted, 2);
+
+        // Not created yet
+        let inventory = scan(&base.join("missing"), &mountinfo).unwrap();
+        assert_eq!(inventory, Inventory::default());
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,398 @@
+//! Loopback-backed volume directories (`--quota-backend loopback`).
+//!
+//! For filesystems without project quotas, each volume with a requested
+//! capacity gets a preallocated image file under `<base>/.images`,
+//! formatted as ext4 and mounted at the volume directory through a loop
+//! device. Writes past the capacity fail with ENOSPC, like a real disk.
+//!
+//! Loop devices are set up with ioctls on `/dev/loop-control` (Linux 5.8+
+//! for `LOOP_CONFIGURE`) and auto-clear, so unmounting the volume directory
+//! releases them. Formatting needs `mkfs.ext4` on the PATH.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use nix::libc;
+use nix::mount::MsFlags;
+use nix::sys::stat::{Mode, SFlag};
+use tracing::{info, warn};
+
+use crate::volume;
+
+/// Directory under the base path holding the image files
+pub const IMAGE_DIR: &str = ".images";
+
+/// Smallest image created; mkfs.ext4 needs room for its metadata
+pub const MIN_IMAGE_SIZE: u64 = 16 << 20;
+
+const LOOP_CONTROL: &str = "/dev/loop-control";
+const LOOP_MAJOR: u64 = 7;
+const MKFS: &str = "mkfs.ext4";
+/// Attempts at grabbing a free loop device another process may race us for
+const ATTACH_ATTEMPTS: usize = 5;
+
+/// `LO_FLAGS_AUTOCLEAR`: detach once the last user (the mount) is gone
+const LO_FLAGS_AUTOCLEAR: u32 = 4;
+
+/// `struct loop_info64`
+#[repr(C)]
+pub struct LoopInfo64 {
+    lo_device: u64,
+    lo_inode: u64,
+    lo_rdevice: u64,
+    lo_offset: u64,
+    lo_sizelimit: u64,
+    lo_number: u32,
+    lo_encrypt_type: u32,
+    lo_encrypt_key_size: u32,
+    lo_flags: u32,
+    lo_file_name: [u8; 64],
+    lo_crypt_name: [u8; 64],
+    lo_encrypt_key: [u8; 32],
+    lo_init: [u64; 2],
+}
+
+/// `struct loop_config`
+#[repr(C)]
+pub struct LoopConfig {
+    fd: u32,
+    block_size: u32,
+    info: LoopInfo64,
+    reserved: [u64; 8],
+}
+
+nix::ioctl_none!(loop_ctl_get_free, 0x4C, 0x82);
+nix::ioctl_write_ptr_bad!(
+    loop_configure,
+    nix::request_code_none!(0x4C, 0x0A),
+    LoopConfig
+);
+nix::ioctl_none!(loop_clr_fd, 0x4C, 0x01);
+
+/// Check that loop devices and `mkfs.ext4` are available (called once at startup)
+pub fn check_supported() -> Result<(), String> {
+    if !Path::new(LOOP_CONTROL).exists() {
+        return Err(format!(
+            "--quota-backend loopback requires {} (loop module loaded, /dev of the host)",
+            LOOP_CONTROL
+        ));
+    }
+    let on_path = std::env::var_os("PATH")
+        .is_some_and(|path| std::env::split_paths(&path).any(|dir| dir.join(MKFS).is_file()));
+    if !on_path {
+        return Err(format!(
+            "--quota-backend loopback requires {} on the PATH",
+            MKFS
+        ));
+    }
+    Ok(())
+}
+
+/// Image file backing the volume directory `dir`
+pub fn image_path(base: &Path, dir: &Path) -> PathBuf {
+    let relative = dir.strip_prefix(base).unwrap_or(dir);
+    let mut image = base.join(IMAGE_DIR).join(relative).into_os_string();
+    image.push(".img");
+    PathBuf::from(image)
+}
+
+/// Mount the image of `dir` at `dir`, creating and formatting a `size`
+/// bytes image first if there is none. Returns whether a mount was made
+/// (false if `dir` was already mounted).
+pub fn ensure_mounted(base: &Path, dir: &Path, size: u64) -> io::Result<bool> {
+    if volume::is_mounted(dir).map_err(|s| io::Error::other(s.message().to_string()))? {
+        return Ok(false);
+    }
+    let image = image_path(base, dir);
+    if !image.exists() {
+        create_image(&image, size)?;
+    }
+
+    let loop_device = attach(&image)?;
+    let result = nix::mount::mount(
+        Some(&loop_device.path),
+        dir,
+        Some("ext4"),
+        MsFlags::empty(),
+        None::<&str>,
+    );
+    // Dropping our handle detaches the (auto-clearing) device unless mounted
+    drop(loop_device);
+    result.map_err(|e| {
+        io::Error::new(
+            io::Error::from(e).kind(),
+            format!("Failed to mount {}: {}", image.display(), e),
+        )
+    })?;
+    Ok(true)
+}
+
+/// Unmount the volume directory `dir` if it is a loopback mount, make sure
+/// its loop device is detached, and delete its image
+pub fn release(base: &Path, dir: &Path) -> io::Result<()> {
+    let image = image_path(base, dir);
+    if !image.exists() {
+        return Ok(());
+    }
+
+    if let Some(source) = mount_source(dir)? {
+        nix::mount::umount(dir).map_err(|e| {
+            io::Error::new(
+                io::Error::from(e).kind(),
+                format!("Failed to unmount {}: {}", dir.display(), e),
+            )
+        })?;
+        detach(&source)?;
+    }
+    std::fs::remove_file(&image)?;
+    info!(image = %image.display(), "Removed loopback image");
+    Ok(())
+}
+
+fn create_image(image: &Path, size: u64) -> io::Result<()> {
+    let size = size.max(MIN_IMAGE_SIZE).next_multiple_of(4096);
+    if let Some(parent) = image.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    // Formatted under a temporary name, so a crash never leaves a half-made image
+    let mut partial = image.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let file = File::create(&partial)?;
+    let len = libc::off_t::try_from(size).map_err(io::Error::other)?;
+    nix::fcntl::fallocate(&file, nix::fcntl::FallocateFlags::empty(), 0, len)?;
+    drop(file);
+
+    let output = Command::new(MKFS)
+        .args(["-F", "-q", "-m", "0"])
+        .arg(&partial)
+        .output();
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            return Err(io::Error::new(
+                e.kind(),
+                format!("Failed to run {}: {}", MKFS, e),
+            ));
+        }
+    };
+    if !output.status.success() {
+        let _ = std::fs::remove_file(&partial);
+        return Err(io::Error::other(format!(
+            "{} failed ({}): {}",
+            MKFS,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    std::fs::rename(&partial, image)?;
+    info!(image = %image.display(), size = size, "Created loopback image");
+    Ok(())
+}
+
+/// An attached loop device, open until dropped
+struct LoopDevice {
+    path: PathBuf,
+    _file: File,
+}
+
+fn attach(image: &Path) -> io::Result<LoopDevice> {
+    let backing = File::options().read(true).write(true).open(image)?;
+    let control = File::options().read(true).write(true).open(LOOP_CONTROL)?;
+
+    let mut last_err = None;
+    for _ in 0..ATTACH_ATTEMPTS {
+        // SAFETY: LOOP_CTL_GET_FREE takes no argument
+        let number = unsafe { loop_ctl_get_free(control.as_raw_fd()) }.map_err(|e| {
+            io::Error::new(
+                io::Error::from(e).kind(),
+                format!("Failed to get a free loop device: {}", e),
+            )
+        })?;
+        let path = PathBuf::from(format!("/dev/loop{}", number));
+        let device = open_loop_device(&path, number as u64)?;
+
+        let config = LoopConfig {
+            fd: backing.as_raw_fd() as u32,
+            block_size: 0,
+            info: LoopInfo64 {
+                lo_device: 0,
+                lo_inode: 0,
+                lo_rdevice: 0,
+                lo_offset: 0,
+                lo_sizelimit: 0,
+                lo_number: 0,
+                lo_encrypt_type: 0,
+                lo_encrypt_key_size: 0,
+                lo_flags: LO_FLAGS_AUTOCLEAR,
+                lo_file_name: file_name_field(image),
+                lo_crypt_name: [0; 64],
+                lo_encrypt_key: [0; 32],
+                lo_init: [0; 2],
+            },
+            reserved: [0; 8],
+        };
+        // SAFETY: `config` is a valid struct loop_config that outlives the call
+        match unsafe { loop_configure(device.as_raw_fd(), &config) } {
+            Ok(_) => {
+                return Ok(LoopDevice {
+                    path,
+                    _file: device,
+                })
+            }
+            // Someone else took this device between GET_FREE and CONFIGURE
+            Err(nix::errno::Errno::EBUSY) => last_err = Some(nix::errno::Errno::EBUSY),
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::Error::from(e).kind(),
+                    format!(
+                        "Failed to attach {} to {}: {}",
+                        image.display(),
+                        path.display(),
+                        e
+                    ),
+                ))
+            }
+        }
+    }
+    Err(io::Error::other(format!(
+        "Failed to attach {}: no free loop device ({})",
+        image.display(),
+        last_err.map(|e| e.to_string()).unwrap_or_default()
+    )))
+}
+
+/// Open `/dev/loopN`, creating the node if the container's /dev predates it
+fn open_loop_device(path: &Path, number: u64) -> io::Result<File> {
+    match File::options().read(true).write(true).open(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            nix::sys::stat::mknod(
+                path,
+                SFlag::S_IFBLK,
+                Mode::from_bits_truncate(0o660),
+                nix::sys::stat::makedev(LOOP_MAJOR, number),
+            )?;
+            File::options().read(true).write(true).open(path)
+        }
+        result => result,
+    }
+}
+
+/// Detach a loop device, if it still is attached
+fn detach(device: &Path) -> io::Result<()> {
+    let file = match File::options().read(true).write(true).open(device) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    // SAFETY: LOOP_CLR_FD takes no argument
+    match unsafe { loop_clr_fd(file.as_raw_fd()) } {
+        // ENXIO: already detached by auto-clear
+        Ok(_) | Err(nix::errno::Errno::ENXIO) => Ok(()),
+        Err(e) => {
+            warn!(device = %device.display(), error = %e, "Failed to detach loop device");
+            Err(e.into())
+        }
+    }
+}
+
+/// Source device of the mount at `dir`, if it is a loop device mount
+fn mount_source(dir: &Path) -> io::Result<Option<PathBuf>> {
+    for mount in proc_mounts::MountIter::new()?.flatten() {
+        if mount.dest == dir {
+            let is_loop = mount
+                .source
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("loop"));
+            return Ok(is_loop.then_some(mount.source));
+        }
+    }
+    Ok(None)
+}
+
+fn file_name_field(image: &Path) -> [u8; 64] {
+    use std::os::unix::ffi::OsStrExt;
+
+    let mut field = [0; 64];
+    let bytes = image.as_os_str().as_bytes();
+    // Informational only (losetup -l); keep the NUL terminator
+    let len = bytes.len().min(field.len() - 1);
+    field[..len].copy_from_slice(&bytes[..len]);
+    field
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_path() {
+        let base = Path::new("/var/node-local-cache");
+        assert_eq!(
+            image_path(base, &base.join("nlc-abc")),
+            Path::new("/var/node-local-cache/.images/nlc-abc.img")
+        );
+        // Dots in shared cache names don't turn into extensions
+        assert_eq!(
+            image_path(base, &base.join("shared").join("maven.v2")),
+            Path::new("/var/node-local-cache/.images/shared/maven.v2.img")
+        );
+    }
+
+    #[test]
+    fn test_struct_layout() {
+        assert_eq!(std::mem::size_of::<LoopInfo64>(), 232);
+        assert_eq!(std::mem::size_of::<LoopConfig>(), 304);
+    }
+
+    #[test]
+    fn test_release_without_image() {
+        let base = std::env::temp_dir().join(format!("nlc-loopback-test-{}", std::process::id()));
+        let dir = base.join("nlc-vol");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Plain directory volumes are left alone
+        release(&base, &dir).unwrap();
+        assert!(dir.exists());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_loopback_mount_release() {
+        if check_supported().is_err() || !nix::unistd::geteuid().is_root() {
+            // Skip test unless running as root with loop devices and mkfs.ext4
+            return;
+        }
+        let base = std::env::temp_dir().join(format!("nlc-loopback-mount-{}", std::process::id()));
+        let dir = base.join("nlc-vol");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mounted = match ensure_mounted(&base, &dir, MIN_IMAGE_SIZE) {
+            Ok(mounted) => mounted,
+            Err(e) => {
+                // e.g. a sandbox with /dev/loop-control but no usable devices
+                eprintln!("skipping: {}", e);
+                let _ = std::fs::remove_dir_all(base);
+                return;
+            }
+        };
+        assert!(mounted);
+        assert!(volume::is_mounted(&dir).unwrap());
+        assert!(!ensure_mounted(&base, &dir, MIN_IMAGE_SIZE).unwrap());
+
+        // Writes stop at the image size
+        let big = vec![0u8; (MIN_IMAGE_SIZE * 2) as usize];
+        let err = std::fs::write(dir.join("big"), big).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::ENOSPC));
+
+        release(&base, &dir).unwrap();
+        assert!(!volume::is_mounted(&dir).unwrap());
+        assert!(!image_path(&base, &dir).exists());
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -1,68 +1,78 @@
-use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
-use tracing::{info, Level};
+use tracing::info;
 use tracing_subscriber::FmtSubscriber;
 
+use config::{Args, LogFormat, Mode};
+
 mod cleanup;
+mod cleanup_journal;
+mod clone;
+mod config;
 mod controller;
+mod coordination;
+mod dedup;
+mod dir_size;
+mod directory;
+mod disk_monitor;
+#[cfg(test)]
+mod fake_api;
+mod health;
+mod history;
+mod hook;
+mod http;
 mod identity;
+mod idmap;
+mod inventory;
+mod loopback;
+mod metrics;
+mod mount_audit;
+mod mount_group;
+mod mount_options;
 mod node;
+mod node_lock;
+mod ownership;
+mod pending_registration;
+mod quota;
+mod recent_events;
+mod selftest;
+mod shutdown;
+mod staging;
+mod startup_event;
+mod stragglers;
+mod supervisor;
+mod tmpfs;
 mod volume;
+mod volume_lock;
 
 #[allow(clippy::doc_overindented_list_items)]
 #[allow(clippy::doc_lazy_continuation)]
 pub mod csi {
     tonic::include_proto!("csi.v1");
-}
-
-#[derive(Debug, Clone, ValueEnum)]
-enum Mode {
-    Controller,
-    Node,
-}
 
-#[derive(Parser, Debug)]
-#[command(name = "node-local-cache")]
-#[command(about = "CSI driver for node-local ephemeral cache volumes")]
-struct Args {
-    /// Run mode: controller or node
-    #[arg(long, value_enum)]
-    mode: Mode,
-
-    /// Path to CSI socket
-    #[arg(long, default_value = "/csi/csi.sock")]
-    csi_socket: PathBuf,
-
-    /// Node name (required for node mode)
-    #[arg(long, env = "NODE_NAME")]
-    node_name: Option<String>,
-
-    /// Base path for cache volumes
-    #[arg(long, default_value = "/var/node-local-cache")]
-    base_path: PathBuf,
-
-    /// Kubernetes namespace for cleanup coordination
-    #[arg(long, env = "POD_NAMESPACE", default_value = "node-local-cache")]
-    namespace: String,
-
-    /// Log level
-    #[arg(long, default_value = "info")]
-    log_level: Level,
-
-    /// Disable cleanup service (for testing only - will leak disk space)
-    #[arg(long, default_value = "false")]
-    no_cleanup_service: bool,
+    /// Encoded file descriptor set of `csi.v1`, for the reflection service
+    pub const FILE_DESCRIPTOR_SET: &[u8] =
+        include_bytes!(concat!(env!("OUT_DIR"), "/csi_descriptor.bin"));
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    // Standalone, without the driver's flags or config file
+    if std::env::args_os()
+        .nth(1)
+        .is_some_and(|arg| arg == "selftest")
+    {
+        use clap::Parser;
+        let args = selftest::SelftestArgs::parse_from(std::env::args_os().skip(1));
+        std::process::exit(selftest::main(&args));
+    }
+
+    let mut args = Args::load();
 
-    // Initialize logging
-    FmtSubscriber::builder()
-        .with_max_level(args.log_level)
-        .json()
-        .init();
+    // Initialize logging; each format is its own subscriber type
+    let logging = FmtSubscriber::builder().with_max_level(args.log_level);
+    match args.log_format {
+        LogFormat::Json => logging.json().init(),
+        LogFormat::Text => logging.init(),
+    }
 
     info!(
         mode = ?args.mode,
@@ -70,6 +80,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         "Starting node-local-cache CSI driver"
     );
 
+    if matches!(args.mode, Mode::Node) && args.enable_xfs_quota {
+        args.quota_backend = quota::QuotaBackend::xfs_project_if_supported(&args.base_path);
+    }
+
+    cleanup::set_event_namespace(args.event_namespace);
+    cleanup::set_events_enabled(!args.no_events);
+    cleanup::set_immutable_stable_configmaps(args.immutable_stable_configmaps);
+    if let Some(dir) = args.coordination_dir.clone() {
+        info!(dir = %dir.display(), "Keeping tracking ConfigMaps as files");
+        coordination::set_coordination_dir(dir);
+    }
+    cleanup::set_retry_config(cleanup::RetryConfig {
+        max_retries: args.cleanup_max_retries,
+        ..Default::default()
+    });
+    volume::set_id_namespace(args.id_namespace);
+    history::history().set_capacity(args.recent_operations);
+
+    if args.emit_startup_event {
+        match startup_event::StartupEvent::from_args(&args) {
+            Some(event) => match kube::Client::try_default().await {
+                Ok(client) => {
+                    tokio::spawn(startup_event::emit(client, event));
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to create Kubernetes client for the startup event")
+                }
+            },
+            None => tracing::warn!("Not emitting a startup event: POD_NAME isn't set"),
+        }
+    }
+
     match args.mode {
         Mode::Controller => {
             info!("Running in controller mode");
@@ -81,6 +123,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .clone()
                 .ok_or("--node-name is required in node mode")?;
             info!(node = %node_name, "Running in node mode");
+            cleanup::set_event_node(&node_name);
             run_node(&args, &node_name).await?;
         }
     }
@@ -91,10 +134,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 async fn run_controller(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     use csi::controller_server::ControllerServer;
     use csi::identity_server::IdentityServer;
-    use std::time::Duration;
     use tonic::transport::Server;
 
-    let identity_service = identity::IdentityService::new(true); // controller mode
+    let identity_service =
+        identity::IdentityService::new(identity::PluginCapabilities::from_args(args));
+    let mut http_router = http::router();
+    // Background loops, cancelled on shutdown
+    let mut background = tokio::task::JoinSet::new();
+    let in_flight = shutdown::InFlight::new();
+    let mut health_checks = health::Health::new(args.health_stale_after);
+    if let Some(token) = &args.admin_token {
+        http_router = http_router.merge(http::admin_router(token, args.effective_config()));
+    }
 
     // Create kube client for cleanup coordination
     let controller_service = if args.no_cleanup_service {
@@ -102,6 +153,8 @@ async fn run_controller(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
             "Cleanup service disabled via --no-cleanup-service flag. This will leak disk space!"
         );
         controller::ControllerService::new()
+            .with_default_capacity(args.default_capacity_bytes)
+            .with_max_volume_bytes(args.max_volume_bytes)
     } else {
         let client = kube::Client::try_default().await.map_err(|e| {
             format!(
@@ -114,15 +167,56 @@ async fn run_controller(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
         info!(namespace = %args.namespace, "Kubernetes client initialized, cleanup enabled");
 
         // Start cleanup processor in background (checks for decommissioned nodes, prunes completed)
-        tokio::spawn(cleanup::run_controller_cleanup_loop(
-            client.clone(),
-            args.namespace.clone(),
-            Duration::from_secs(60), // check interval
-        ));
+        let loop_client = client.clone();
+        let loop_namespace = args.namespace.clone();
+        let decommission_grace = args.decommission_grace;
+        let cleanup_quorum = args.cleanup_quorum;
+        let cleanup_timeout = args.cleanup_timeout;
+        let interval = args.controller_cleanup_interval();
+        let heartbeat = health::Heartbeat::new();
+        health_checks = health_checks
+            .with_client(client.clone())
+            .with_heartbeat("controller-cleanup", heartbeat.clone());
+        background.spawn(supervisor::supervise("controller-cleanup", move || {
+            cleanup::run_controller_cleanup_loop(
+                cleanup::CleanupController::new(loop_client.clone(), loop_namespace.clone())
+                    .with_decommission_grace(decommission_grace)
+                    .with_quorum(cleanup_quorum)
+                    .with_cleanup_timeout(cleanup_timeout)
+                    .with_heartbeat(heartbeat.clone()),
+                interval,
+            )
+        }));
+
+        if let Some(token) = &args.admin_token {
+            http_router = http_router.merge(http::controller_admin_router(
+                token,
+                cleanup::CleanupController::new(client.clone(), args.namespace.clone())
+                    .with_decommission_grace(args.decommission_grace)
+                    .with_quorum(args.cleanup_quorum)
+                    .with_cleanup_timeout(args.cleanup_timeout),
+            ));
+        }
 
-        let cleanup_ctrl = cleanup::CleanupController::new(client, args.namespace.clone());
+        let cleanup_ctrl = cleanup::CleanupController::new(client, args.namespace.clone())
+            .with_decommission_grace(args.decommission_grace)
+            .with_sweep_on_missing(args.delete_broadcast_on_missing);
         controller::ControllerService::with_cleanup(cleanup_ctrl)
+            .with_stateful_create(args.stateful_create)
+            .with_default_capacity(args.default_capacity_bytes)
+            .with_max_volume_bytes(args.max_volume_bytes)
     };
+    let controller_service = controller_service.with_in_flight(in_flight.clone());
+
+    if let Some(addr) = args.http_addr {
+        tokio::spawn(http::serve(http::bind(addr).await?, http_router));
+    }
+    if let Some(addr) = args.health_addr {
+        tokio::spawn(http::serve(
+            http::bind(addr).await?,
+            health::router(health_checks),
+        ));
+    }
 
     // Remove existing socket if present
     let _ = std::fs::remove_file(&args.csi_socket);
@@ -137,23 +231,113 @@ async fn run_controller(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
     // Use UDS (Unix Domain Socket)
     let uds = tokio::net::UnixListener::bind(&args.csi_socket)?;
     let uds_stream = tokio_stream::wrappers::UnixListenerStream::new(uds);
+    let (drained_tx, drained_rx) = tokio::sync::oneshot::channel();
 
     Server::builder()
         .add_service(IdentityServer::new(identity_service))
         .add_service(ControllerServer::new(controller_service))
-        .serve_with_incoming(uds_stream)
+        .add_optional_service(reflection_service(args)?)
+        .serve_with_incoming_shutdown(uds_stream, async move {
+            let _ = drained_tx.send(shutdown::signalled(&in_flight).await);
+        })
         .await?;
 
+    shutdown::stop(
+        background,
+        &args.csi_socket,
+        drained_rx.await.unwrap_or(0),
+        &mut std::io::stdout(),
+    )
+    .await;
     Ok(())
 }
 
 async fn run_node(args: &Args, node_name: &str) -> Result<(), Box<dyn std::error::Error>> {
     use csi::identity_server::IdentityServer;
     use csi::node_server::NodeServer;
-    use std::time::Duration;
     use tonic::transport::Server;
 
-    let identity_service = identity::IdentityService::new(false); // node mode
+    // Ready right away unless waiting for the cleanup watcher (which
+    // --no-cleanup-service doesn't run)
+    let (synced_tx, synced) =
+        tokio::sync::watch::channel(!args.wait_for_cleanup_sync || args.no_cleanup_service);
+    let identity_service =
+        identity::IdentityService::new(identity::PluginCapabilities::from_args(args))
+            .with_readiness(synced.clone());
+    // Background loops, cancelled on shutdown
+    let mut background = tokio::task::JoinSet::new();
+    let in_flight = shutdown::InFlight::new();
+    let mut health_checks =
+        health::Health::new(args.health_stale_after).with_synced(synced.clone());
+
+    args.directory_backend.check_supported(&args.base_path)?;
+    args.quota_backend.check_supported(&args.base_path)?;
+    if args.enable_idmapped_mounts && !idmap::is_supported() {
+        return Err("--enable-idmapped-mounts requires Linux 5.12+ (mount_setattr)".into());
+    }
+    if args.disk_usage_monitor && args.disk_warning_threshold > args.disk_critical_threshold {
+        return Err("--disk-warning-threshold must not exceed --disk-critical-threshold".into());
+    }
+
+    // Accurate gauges right after a restart, without holding up serving
+    let inventory_base = args.base_path.clone();
+    tokio::task::spawn_blocking(move || match inventory::record(&inventory_base) {
+        Ok(inventory) => info!(
+            directories = inventory.directories,
+            mounted = inventory.mounted,
+            used_bytes = inventory.used_bytes,
+            "Base path inventory"
+        ),
+        Err(e) => tracing::warn!(error = %e, "Failed to take base path inventory"),
+    });
+
+    let base_device = if args.verify_base_device {
+        std::fs::create_dir_all(&args.base_path)?;
+        let device = volume::device_id(&args.base_path)?;
+        info!(
+            path = %args.base_path.display(),
+            device = format!("{:#x}", device),
+            "Publishing only from the base path's device"
+        );
+        Some(device)
+    } else {
+        None
+    };
+
+    // Background size accounting, shared with volume stats
+    let size_cache = args
+        .du_interval
+        .map(|interval| dir_size::SizeCache::new(args.base_path.clone(), interval));
+    if let Some(cache) = &size_cache {
+        let cache = cache.clone();
+        background.spawn(supervisor::supervise("node-size-accounting", move || {
+            cache.clone().run(dir_size::SIZE_REFRESH_INTERVAL)
+        }));
+    }
+
+    // Without the singleton lock, serve right away
+    let (serving_tx, serving) = tokio::sync::watch::channel(!args.node_singleton_lock);
+    if args.node_singleton_lock {
+        std::fs::create_dir_all(&args.base_path)?;
+        let base_path = args.base_path.clone();
+        tokio::spawn(async move {
+            let _lock =
+                node_lock::NodeLock::acquire(base_path, node_lock::LOCK_RETRY_INTERVAL).await;
+            serving_tx.send_replace(true);
+            // Hold the lock until the process exits
+            std::future::pending::<()>().await;
+        });
+    }
+
+    if args.dedup_compaction {
+        let base_path = args.base_path.clone();
+        background.spawn(node_lock::when_serving(
+            serving.clone(),
+            supervisor::supervise("node-dedup", move || {
+                dedup::run(base_path.clone(), dedup::DEDUP_INTERVAL)
+            }),
+        ));
+    }
 
     // Create node service, optionally with cleanup tracking
     let node_service = if args.no_cleanup_service {
@@ -161,6 +345,17 @@ async fn run_node(args: &Args, node_name: &str) -> Result<(), Box<dyn std::error
             "Cleanup service disabled via --no-cleanup-service flag. This will leak disk space!"
         );
         node::NodeService::new(node_name.to_string(), args.base_path.clone())
+            .with_directory_backend(args.directory_backend)
+            .with_quota_backend(args.quota_backend)
+            .with_idmapped_mounts(args.enable_idmapped_mounts)
+            .with_remove_target_on_unpublish(args.remove_target_on_unpublish)
+            .with_serving(serving)
+            .with_base_device(base_device)
+            .with_strict_fs_type(args.strict_fstype)
+            .with_pre_publish_hook(args.pre_publish_hook.clone(), args.pre_publish_hook_timeout)
+            .with_size_cache(size_cache.clone())
+            .with_target_roots(args.allowed_target_prefix.clone())
+            .with_allowed_base_paths(args.allowed_base_path.clone())
     } else {
         let client = kube::Client::try_default().await.map_err(|e| {
             format!(
@@ -176,19 +371,231 @@ async fn run_node(args: &Args, node_name: &str) -> Result<(), Box<dyn std::error
             "Starting cleanup watcher"
         );
 
-        // Start cleanup watcher in background (every 10 seconds)
-        let cleanup_node = cleanup::CleanupNode::new(
-            client.clone(),
-            args.namespace.clone(),
-            node_name.to_string(),
-            args.base_path.clone(),
-        );
-        tokio::spawn(cleanup_node.run_cleanup_loop(Duration::from_secs(10)));
+        // Serializes publish and cleanup/recycle of the same volume directory
+        let volume_locks = volume_lock::VolumeLocks::new();
+
+        // Start cleanup watcher in background (--cleanup-trigger)
+        let loop_client = client.clone();
+        let loop_namespace = args.namespace.clone();
+        let loop_node_name = node_name.to_string();
+        let loop_base_path = args.base_path.clone();
+        let allowed_base_paths = args.allowed_base_path.clone();
+        let directory_backend = args.directory_backend;
+        let quota_backend = args.quota_backend;
+        let quarantine_failed = args.quarantine_failed;
+        let dry_run = args.cleanup_dry_run;
+        let progress_interval = args.cleanup_progress_interval;
+        let retry_budget = args.cleanup_retry_budget;
+        let verify_pv_deleted = args.verify_pv_deleted;
+        let cleanup_journal = args.cleanup_journal;
+        // Files can't be watched
+        let cleanup_trigger = if coordination::file_backed() {
+            cleanup::CleanupTrigger::Poll
+        } else {
+            args.cleanup_trigger
+        };
+        let interval = args.node_cleanup_interval();
+        let loop_locks = volume_locks.clone();
+        let heartbeat = health::Heartbeat::new();
+        health_checks = health_checks
+            .with_client(client.clone())
+            .with_heartbeat("node-cleanup", heartbeat.clone());
+        background.spawn(node_lock::when_serving(
+            serving.clone(),
+            supervisor::supervise("node-cleanup", move || {
+                cleanup::CleanupNode::new(
+                    loop_client.clone(),
+                    loop_namespace.clone(),
+                    loop_node_name.clone(),
+                    loop_base_path.clone(),
+                )
+                .with_allowed_base_paths(allowed_base_paths.clone())
+                .with_directory_backend(directory_backend)
+                .with_quota_backend(quota_backend)
+                .with_quarantine(quarantine_failed)
+                .with_dry_run(dry_run)
+                .with_volume_locks(loop_locks.clone())
+                .with_sync_signal(synced_tx.clone())
+                .with_progress_interval(progress_interval)
+                .with_retry_budget(retry_budget)
+                .with_verify_pv_deleted(verify_pv_deleted)
+                .with_cleanup_journal(cleanup_journal)
+                .with_trigger(cleanup_trigger)
+                .with_heartbeat(heartbeat.clone())
+                .run_cleanup_loop(interval)
+            }),
+        ));
+
+        if let Some(max_age) = args.max_volume_age {
+            let loop_client = client.clone();
+            let loop_namespace = args.namespace.clone();
+            let loop_node_name = node_name.to_string();
+            let loop_base_path = args.base_path.clone();
+            let allowed_base_paths = args.allowed_base_path.clone();
+            let recycle = args.recycle_aged;
+            let loop_locks = volume_locks.clone();
+            background.spawn(node_lock::when_serving(
+                serving.clone(),
+                supervisor::supervise("node-age-check", move || {
+                    cleanup::CleanupNode::new(
+                        loop_client.clone(),
+                        loop_namespace.clone(),
+                        loop_node_name.clone(),
+                        loop_base_path.clone(),
+                    )
+                    .with_allowed_base_paths(allowed_base_paths.clone())
+                    .with_volume_locks(loop_locks.clone())
+                    .run_age_check_loop(
+                        cleanup::AGE_CHECK_INTERVAL,
+                        max_age,
+                        recycle,
+                    )
+                }),
+            ));
+        }
+
+        if args.orphan_gc {
+            let loop_client = client.clone();
+            let loop_namespace = args.namespace.clone();
+            let loop_node_name = node_name.to_string();
+            let loop_base_path = args.base_path.clone();
+            let directory_backend = args.directory_backend;
+            let quota_backend = args.quota_backend;
+            let grace = args.orphan_gc_grace;
+            let dry_run = args.cleanup_dry_run;
+            let loop_locks = volume_locks.clone();
+            background.spawn(node_lock::when_serving(
+                serving.clone(),
+                supervisor::supervise("node-orphan-gc", move || {
+                    cleanup::CleanupNode::new(
+                        loop_client.clone(),
+                        loop_namespace.clone(),
+                        loop_node_name.clone(),
+                        loop_base_path.clone(),
+                    )
+                    .with_directory_backend(directory_backend)
+                    .with_quota_backend(quota_backend)
+                    .with_volume_locks(loop_locks.clone())
+                    .with_dry_run(dry_run)
+                    .run_orphan_gc_loop(cleanup::ORPHAN_GC_INTERVAL, grace)
+                }),
+            ));
+        }
+
+        if let Some(interval) = args.mount_audit_interval {
+            let loop_client = client.clone();
+            let loop_namespace = args.namespace.clone();
+            let loop_node_name = node_name.to_string();
+            let loop_base_path = args.base_path.clone();
+            let repair = args.mount_audit_repair;
+            let loop_locks = volume_locks.clone();
+            background.spawn(node_lock::when_serving(
+                serving.clone(),
+                supervisor::supervise("node-mount-audit", move || {
+                    mount_audit::MountAudit::new(
+                        loop_client.clone(),
+                        loop_namespace.clone(),
+                        loop_node_name.clone(),
+                        loop_base_path.clone(),
+                    )
+                    .with_repair(repair)
+                    .with_volume_locks(loop_locks.clone())
+                    .run(interval)
+                }),
+            ));
+        }
+
+        if args.disk_usage_monitor {
+            let loop_client = client.clone();
+            let loop_node_name = node_name.to_string();
+            let loop_base_path = args.base_path.clone();
+            let (warning, critical) = (args.disk_warning_threshold, args.disk_critical_threshold);
+            background.spawn(node_lock::when_serving(
+                serving.clone(),
+                supervisor::supervise("node-disk-monitor", move || {
+                    disk_monitor::DiskMonitor::new(
+                        loop_client.clone(),
+                        loop_node_name.clone(),
+                        loop_base_path.clone(),
+                        warning,
+                        critical,
+                    )
+                    .run(disk_monitor::DISK_MONITOR_INTERVAL)
+                }),
+            ));
+        }
+
+        // Shared with NodePublishVolume, which evicts when it runs out of space
+        let eviction = args.eviction_threshold.map(|threshold| {
+            let low_watermark = args
+                .eviction_low_watermark
+                .unwrap_or(threshold.saturating_sub(10))
+                .min(threshold);
+            std::sync::Arc::new(
+                node::EvictionManager::new(
+                    node_name.to_string(),
+                    args.base_path.clone(),
+                    threshold,
+                    low_watermark,
+                )
+                .with_allowed_base_paths(args.allowed_base_path.clone())
+                .with_directory_backend(args.directory_backend)
+                .with_quota_backend(args.quota_backend)
+                .with_volume_locks(volume_locks.clone())
+                .with_cleanup(client.clone(), args.namespace.clone()),
+            )
+        });
+        if let Some(manager) = eviction.clone() {
+            background.spawn(node_lock::when_serving(
+                serving.clone(),
+                supervisor::supervise("node-eviction", move || {
+                    let manager = manager.clone();
+                    async move { manager.run(disk_monitor::DISK_MONITOR_INTERVAL).await }
+                }),
+            ));
+        }
 
         // Create node service with cleanup tracking enabled
         node::NodeService::new(node_name.to_string(), args.base_path.clone())
+            .with_directory_backend(args.directory_backend)
+            .with_quota_backend(args.quota_backend)
+            .with_idmapped_mounts(args.enable_idmapped_mounts)
+            .with_remove_target_on_unpublish(args.remove_target_on_unpublish)
+            .with_volume_locks(volume_locks)
             .with_cleanup(client, args.namespace.clone())
+            .with_owner_references(args.set_owner_references && !coordination::file_backed())
+            .with_serving(serving)
+            .with_base_device(base_device)
+            .with_strict_fs_type(args.strict_fstype)
+            .with_pre_publish_hook(args.pre_publish_hook.clone(), args.pre_publish_hook_timeout)
+            .with_size_cache(size_cache.clone())
+            .with_target_roots(args.allowed_target_prefix.clone())
+            .with_allowed_base_paths(args.allowed_base_path.clone())
+            .with_eviction(eviction)
     };
+    let node_service = node_service
+        .with_staging(args.enable_staging)
+        .with_staging_roots(args.allowed_staging_prefix.clone())
+        .with_in_flight(in_flight.clone());
+
+    if let Some(addr) = args.http_addr {
+        let mut http_router = http::router().merge(http::readiness_router(synced));
+        if let Some(token) = &args.admin_token {
+            http_router = http_router
+                .merge(http::admin_router(token, args.effective_config()))
+                .merge(http::node_admin_router(
+                    token,
+                    args.allowed_target_prefix.clone(),
+                ));
+        }
+        tokio::spawn(http::serve(http::bind(addr).await?, http_router));
+    }
+    if let Some(addr) = args.health_addr {
+        tokio::spawn(http::serve(
+            http::bind(addr).await?,
+            health::router(health_checks),
+        ));
+    }
 
     // Remove existing socket if present
     let _ = std::fs::remove_file(&args.csi_socket);
@@ -202,12 +609,44 @@ async fn run_node(args: &Args, node_name: &str) -> Result<(), Box<dyn std::error
 
     let uds = tokio::net::UnixListener::bind(&args.csi_socket)?;
     let uds_stream = tokio_stream::wrappers::UnixListenerStream::new(uds);
+    let (drained_tx, drained_rx) = tokio::sync::oneshot::channel();
 
     Server::builder()
         .add_service(IdentityServer::new(identity_service))
         .add_service(NodeServer::new(node_service))
-        .serve_with_incoming(uds_stream)
+        .add_optional_service(reflection_service(args)?)
+        .serve_with_incoming_shutdown(uds_stream, async move {
+            let _ = drained_tx.send(shutdown::signalled(&in_flight).await);
+        })
         .await?;
 
+    shutdown::stop(
+        background,
+        &args.csi_socket,
+        drained_rx.await.unwrap_or(0),
+        &mut std::io::stdout(),
+    )
+    .await;
     Ok(())
 }
+
+/// The gRPC reflection service, with `--enable-reflection`
+fn reflection_service(
+    args: &Args,
+) -> Result<
+    Option<
+        tonic_reflection::server::v1::ServerReflectionServer<
+            impl tonic_reflection::server::v1::ServerReflection,
+        >,
+    >,
+    tonic_reflection::server::Error,
+> {
+    if !args.enable_reflection {
+        return Ok(None);
+    }
+    info!("Serving gRPC reflection");
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(csi::FILE_DESCRIPTOR_SET)
+        .build_v1()
+        .map(Some)
+}
@@ -0,0 +1,503 @@
+//! Prometheus metrics for the driver.
+//!
+//! A single process-wide registry, exposed in OpenMetrics text format on the
+//! `/metrics` HTTP endpoint (see `--http-addr`).
+
+use std::sync::atomic::AtomicU64;
+use std::sync::LazyLock;
+
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::EncodeLabelSet;
+use prometheus_client::metrics::counter::Counter;
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::metrics::histogram::Histogram;
+use prometheus_client::registry::Registry;
+
+use crate::cleanup::CleanupSummary;
+use crate::dedup;
+use crate::inventory::Inventory;
+
+/// Metric name prefix
+const PREFIX: &str = "nlc";
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct TaskLabels {
+    pub task: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct VolumeLabels {
+    pub volume_id: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct OperationLabels {
+    pub operation: String,
+}
+
+/// Buckets of the conflict retry histogram (retries are capped at 15)
+const CONFLICT_RETRY_BUCKETS: [f64; 6] = [0.0, 1.0, 2.0, 4.0, 8.0, 15.0];
+
+fn conflict_retry_histogram() -> Histogram {
+    Histogram::new(CONFLICT_RETRY_BUCKETS)
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct NodeLabels {
+    pub node: String,
+}
+
+/// Buckets of the node cleanup duration histogram, 10s to a day
+const CLEANUP_DURATION_BUCKETS: [f64; 9] = [
+    10.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 14400.0, 86400.0,
+];
+
+fn cleanup_duration_histogram() -> Histogram {
+    Histogram::new(CLEANUP_DURATION_BUCKETS)
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct DiscrepancyLabels {
+    pub kind: String,
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// NodePublishVolume calls that found the target already mounted
+    pub publish_skipped_already_mounted: Counter,
+    /// Supervised background task restarts (after a panic or unexpected exit)
+    pub task_restarts: Family<TaskLabels, Counter>,
+    /// Cleanup ConfigMaps pruned by the controller
+    pub cleanup_pruned: Counter,
+    /// Volumes waiting on nodes to clean up, as of the last controller pass
+    pub cleanup_pending: Gauge,
+    /// Nodes marked decommissioned on cleanup ConfigMaps
+    pub cleanup_nodes_decommissioned: Counter,
+    /// Cleanup ConfigMaps that failed to process
+    pub cleanup_errors: Counter,
+    /// Time from a cleanup request to each node's completion, recorded when
+    /// the controller prunes the ConfigMap
+    pub node_cleanup_duration: Family<NodeLabels, Histogram, fn() -> Histogram>,
+    /// Tracking ConfigMaps, as of the last controller pass
+    pub tracked_volumes: Gauge,
+    /// Tracked volumes not deleted yet
+    pub tracked_volumes_active: Gauge,
+    /// Tracked volumes waiting for nodes to clean up
+    pub tracked_volumes_cleanup: Gauge,
+    /// Distinct nodes holding any tracked volume
+    pub tracked_nodes: Gauge,
+    /// Fraction of the base path filesystem in use (`--disk-usage-monitor`)
+    pub disk_usage_ratio: Gauge<f64, AtomicU64>,
+    /// Volume directories under the base path, as of the startup inventory
+    pub volume_directories: Gauge,
+    /// Volume directories mounted, as of the startup inventory
+    pub volumes_mounted: Gauge,
+    /// Bytes in use on the base path filesystem, as of the startup inventory
+    pub base_path_used_bytes: Gauge,
+    /// Cached size of each volume directory on the node (`--du-interval`)
+    pub volume_size_bytes: Family<VolumeLabels, Gauge>,
+    /// Optimistic concurrency retries per tracking ConfigMap update
+    pub conflict_retries: Family<OperationLabels, Histogram, fn() -> Histogram>,
+    /// Mount discrepancies by kind, as of the last mount audit
+    pub mount_audit_discrepancies: Family<DiscrepancyLabels, Gauge>,
+    /// Mount discrepancies repaired by the audit (`--mount-audit-repair`)
+    pub mount_audit_repairs: Family<DiscrepancyLabels, Counter>,
+    /// Duplicate files replaced by hardlinks (`--dedup-compaction`)
+    pub dedup_files_linked: Counter,
+    /// Bytes freed by replacing duplicates with hardlinks
+    pub dedup_reclaimed_bytes: Counter,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut registry = Registry::with_prefix(PREFIX);
+
+        let publish_skipped_already_mounted = Counter::default();
+        registry.register(
+            "publish_skipped_already_mounted",
+            "NodePublishVolume calls skipped because the target was already mounted",
+            publish_skipped_already_mounted.clone(),
+        );
+
+        let task_restarts = Family::<TaskLabels, Counter>::default();
+        registry.register(
+            "task_restarts",
+            "Background task restarts after a panic or unexpected exit",
+            task_restarts.clone(),
+        );
+
+        let cleanup_pruned = Counter::default();
+        registry.register(
+            "cleanup_pruned",
+            "Cleanup ConfigMaps pruned after all nodes finished",
+            cleanup_pruned.clone(),
+        );
+
+        let cleanup_pending = Gauge::default();
+        registry.register(
+            "cleanup_pending",
+            "Volumes waiting on nodes to clean up",
+            cleanup_pending.clone(),
+        );
+
+        let cleanup_nodes_decommissioned = Counter::default();
+        registry.register(
+            "cleanup_nodes_decommissioned",
+            "Nodes marked decommissioned on cleanup ConfigMaps",
+            cleanup_nodes_decommissioned.clone(),
+        );
+
+        let cleanup_errors = Counter::default();
+        registry.register(
+            "cleanup_errors",
+            "Cleanup ConfigMaps that failed to process",
+            cleanup_errors.clone(),
+        );
+
+        let node_cleanup_duration =
+            Family::<NodeLabels, Histogram, fn() -> Histogram>::new_with_constructor(
+                cleanup_duration_histogram,
+            );
+        registry.register_with_unit(
+            "node_cleanup_duration",
+            "Time from a volume's cleanup request to a node completing it",
+            prometheus_client::registry::Unit::Seconds,
+            node_cleanup_duration.clone(),
+        );
+
+        let tracked_volumes = Gauge::default();
+        registry.register(
+            "tracked_volumes",
+            "Volumes with a tracking ConfigMap",
+            tracked_volumes.clone(),
+        );
+
+        let tracked_volumes_active = Gauge::default();
+        registry.register(
+            "tracked_volumes_active",
+            "Tracked volumes not deleted yet",
+            tracked_volumes_active.clone(),
+        );
+
+        let tracked_volumes_cleanup = Gauge::default();
+        registry.register(
+            "tracked_volumes_cleanup",
+            "Tracked volumes waiting for nodes to clean up",
+            tracked_volumes_cleanup.clone(),
+        );
+
+        let tracked_nodes = Gauge::default();
+        registry.register(
+            "tracked_nodes",
+            "Distinct nodes holding a tracked volume",
+            tracked_nodes.clone(),
+        );
+
+        let disk_usage_ratio = Gauge::<f64, AtomicU64>::default();
+        registry.register(
+            "disk_usage_ratio",
+            "Fraction of the base path filesystem in use",
+            disk_usage_ratio.clone(),
+        );
+
+        let volume_directories = Gauge::default();
+        registry.register(
+            "volume_directories",
+            "Volume directories under the base path at startup",
+            volume_directories.clone(),
+        );
+
+        let volumes_mounted = Gauge::default();
+        registry.register(
+            "volumes_mounted",
+            "Volume directories mounted at startup",
+            volumes_mounted.clone(),
+        );
+
+        let base_path_used_bytes = Gauge::default();
+        registry.register(
+            "base_path_used_bytes",
+            "Bytes in use on the base path filesystem at startup",
+            base_path_used_bytes.clone(),
+        );
+
+        let volume_size_bytes = Family::<VolumeLabels, Gauge>::default();
+        registry.register(
+            "volume_size_bytes",
+            "Cached disk usage of each volume directory on the node",
+            volume_size_bytes.clone(),
+        );
+
+        let conflict_retries =
+            Family::<OperationLabels, Histogram, fn() -> Histogram>::new_with_constructor(
+                conflict_retry_histogram,
+            );
+        registry.register(
+            "conflict_retries",
+            "Conflict retries a tracking ConfigMap update took, by operation",
+            conflict_retries.clone(),
+        );
+
+        let mount_audit_discrepancies = Family::<DiscrepancyLabels, Gauge>::default();
+        registry.register(
+            "mount_audit_discrepancies",
+            "Mount discrepancies found by the last mount audit",
+            mount_audit_discrepancies.clone(),
+        );
+
+        let mount_audit_repairs = Family::<DiscrepancyLabels, Counter>::default();
+        registry.register(
+            "mount_audit_repairs",
+            "Mount discrepancies repaired by the mount audit",
+            mount_audit_repairs.clone(),
+        );
+
+        let dedup_files_linked = Counter::default();
+        registry.register(
+            "dedup_files_linked",
+            "Duplicate seeded files replaced by hardlinks",
+            dedup_files_linked.clone(),
+        );
+
+        let dedup_reclaimed_bytes = Counter::default();
+        registry.register_with_unit(
+            "dedup_reclaimed",
+            "Disk space freed by replacing duplicate seeded files with hardlinks",
+            prometheus_client::registry::Unit::Bytes,
+            dedup_reclaimed_bytes.clone(),
+        );
+
+        Self {
+            registry,
+            publish_skipped_already_mounted,
+            task_restarts,
+            cleanup_pruned,
+            cleanup_pending,
+            cleanup_nodes_decommissioned,
+            cleanup_errors,
+            node_cleanup_duration,
+            tracked_volumes,
+            tracked_volumes_active,
+            tracked_volumes_cleanup,
+            tracked_nodes,
+            disk_usage_ratio,
+            volume_directories,
+            volumes_mounted,
+            base_path_used_bytes,
+            volume_size_bytes,
+            conflict_retries,
+            mount_audit_discrepancies,
+            mount_audit_repairs,
+            dedup_files_linked,
+            dedup_reclaimed_bytes,
+        }
+    }
+
+    pub fn record_dedup(&self, compaction: &dedup::Compaction) {
+        self.dedup_files_linked
+            .inc_by(compaction.files_linked as u64);
+        self.dedup_reclaimed_bytes
+            .inc_by(compaction.bytes_reclaimed);
+    }
+
+    pub fn record_task_restart(&self, task: &str) {
+        self.task_restarts
+            .get_or_create(&TaskLabels {
+                task: task.to_string(),
+            })
+            .inc();
+    }
+
+    pub fn record_cleanup_summary(&self, summary: &CleanupSummary) {
+        self.cleanup_pruned.inc_by(summary.pruned.len() as u64);
+        self.cleanup_pending.set(summary.pending.len() as i64);
+        self.cleanup_nodes_decommissioned
+            .inc_by(summary.decommissioned_nodes() as u64);
+        self.cleanup_errors.inc_by(summary.errors.len() as u64);
+        // Gauges are set from each pass, never accumulated
+        let footprint = &summary.footprint;
+        self.tracked_volumes.set(footprint.volumes as i64);
+        self.tracked_volumes_active.set(footprint.active as i64);
+        self.tracked_volumes_cleanup.set(footprint.cleanup as i64);
+        self.tracked_nodes.set(footprint.nodes as i64);
+    }
+
+    pub fn record_node_cleanup_duration(&self, node: &str, duration: std::time::Duration) {
+        self.node_cleanup_duration
+            .get_or_create(&NodeLabels {
+                node: node.to_string(),
+            })
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn record_inventory(&self, inventory: &Inventory) {
+        self.volume_directories.set(inventory.directories as i64);
+        self.volumes_mounted.set(inventory.mounted as i64);
+        self.base_path_used_bytes
+            .set(i64::try_from(inventory.used_bytes).unwrap_or(i64::MAX));
+    }
+
+    pub fn record_volume_size(&self, volume_id: &str, bytes: u64) {
+        self.volume_size_bytes
+            .get_or_create(&VolumeLabels {
+                volume_id: volume_id.to_string(),
+            })
+            .set(i64::try_from(bytes).unwrap_or(i64::MAX));
+    }
+
+    pub fn remove_volume_size(&self, volume_id: &str) {
+        self.volume_size_bytes.remove(&VolumeLabels {
+            volume_id: volume_id.to_string(),
+        });
+    }
+
+    pub fn record_conflict_retries(&self, operation: &str, retries: u32) {
+        self.conflict_retries
+            .get_or_create(&OperationLabels {
+                operation: operation.to_string(),
+            })
+            .observe(f64::from(retries));
+    }
+
+    pub fn record_mount_audit(&self, kind: &str, count: usize) {
+        self.mount_audit_discrepancies
+            .get_or_create(&DiscrepancyLabels {
+                kind: kind.to_string(),
+            })
+            .set(count as i64);
+    }
+
+    pub fn record_mount_audit_repair(&self, kind: &str) {
+        self.mount_audit_repairs
+            .get_or_create(&DiscrepancyLabels {
+                kind: kind.to_string(),
+            })
+            .inc();
+    }
+
+    /// Render all metrics in OpenMetrics text format
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        if let Err(e) = encode(&mut out, &self.registry) {
+            tracing::warn!(error = %e, "Failed to encode metrics");
+        }
+        out
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Process-wide metrics
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup::Footprint;
+
+    #[test]
+    fn test_encode_publish_skipped() {
+        let m = Metrics::new();
+        m.publish_skipped_already_mounted.inc();
+        m.publish_skipped_already_mounted.inc();
+
+        let text = m.encode();
+        assert!(text.contains("nlc_publish_skipped_already_mounted_total 2"));
+        assert!(text.ends_with("# EOF\n"));
+    }
+
+    #[test]
+    fn test_encode_task_restarts() {
+        let m = Metrics::new();
+        m.record_task_restart("node-cleanup");
+
+        let text = m.encode();
+        assert!(text.contains("nlc_task_restarts_total{task=\"node-cleanup\"} 1"));
+    }
+
+    #[test]
+    fn test_record_conflict_retries() {
+        let m = Metrics::new();
+        m.record_conflict_retries("register_node_publish", 0);
+        m.record_conflict_retries("register_node_publish", 0);
+        m.record_conflict_retries("register_node_publish", 3);
+
+        let text = m.encode();
+        let labels = "operation=\"register_node_publish\"";
+        assert!(text.contains(&format!("nlc_conflict_retries_count{{{}}} 3", labels)));
+        assert!(text.contains(&format!("nlc_conflict_retries_sum{{{}}} 3.0", labels)));
+        assert!(text.contains(&format!(
+            "nlc_conflict_retries_bucket{{le=\"0.0\",{}}} 2",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "nlc_conflict_retries_bucket{{le=\"4.0\",{}}} 3",
+            labels
+        )));
+    }
+
+    #[test]
+    fn test_record_node_cleanup_duration() {
+        let m = Metrics::new();
+        m.record_node_cleanup_duration("node1", std::time::Duration::from_secs(45));
+
+        let text = m.encode();
+        let labels = "node=\"node1\"";
+        assert!(text.contains(&format!(
+            "nlc_node_cleanup_duration_seconds_count{{{}}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "nlc_node_cleanup_duration_seconds_bucket{{le=\"60.0\",{}}} 1",
+            labels
+        )));
+        assert!(text.contains(&format!(
+            "nlc_node_cleanup_duration_seconds_bucket{{le=\"30.0\",{}}} 0",
+            labels
+        )));
+    }
+
+    #[test]
+    fn test_record_mount_audit() {
+        let m = Metrics::new();
+        m.record_mount_audit("orphaned", 2);
+        m.record_mount_audit("orphaned", 1);
+        m.record_mount_audit_repair("orphaned");
+
+        let text = m.encode();
+        assert!(text.contains("nlc_mount_audit_discrepancies{kind=\"orphaned\"} 1"));
+        assert!(text.contains("nlc_mount_audit_repairs_total{kind=\"orphaned\"} 1"));
+    }
+
+    #[test]
+    fn test_record_cleanup_summary() {
+        let m = Metrics::new();
+        let mut summary = CleanupSummary::default();
+        summary.pruned.push("nlc-a".to_string());
+        summary.pending.insert("nlc-b".to_string(), 2);
+        summary
+            .decommissioned
+            .insert("nlc-b".to_string(), vec!["node1".to_string()]);
+        summary.footprint = Footprint {
+            volumes: 3,
+            active: 2,
+            cleanup: 1,
+            nodes: 4,
+        };
+        m.record_cleanup_summary(&summary);
+        m.record_cleanup_summary(&summary);
+
+        let text = m.encode();
+        assert!(text.contains("nlc_cleanup_pruned_total 2"));
+        assert!(text.contains("nlc_cleanup_pending 1"));
+        assert!(text.contains("nlc_cleanup_nodes_decommissioned_total 2"));
+        assert!(text.contains("nlc_cleanup_errors_total 0"));
+        assert!(text.contains("nlc_tracked_volumes 3"));
+        assert!(text.contains("nlc_tracked_volumes_active 2"));
+        assert!(text.contains("nlc_tracked_volumes_cleanup 1"));
+        assert!(text.contains("nlc_tracked_nodes 4"));
+    }
+}
@@ -0,0 +1,562 @@
+//! Mount consistency audit (`--mount-audit-interval`).
+//!
+//! The node plugin periodically compares the bind mounts of volume
+//! directories in `/proc/self/mountinfo` with the tracking ConfigMaps that
+//! list this node, and reports what doesn't add up:
+//!
+//! - `untracked`: a volume is mounted, but its ConfigMap doesn't list this
+//!   node (e.g. registration failed after the mount)
+//! - `orphaned`: a volume is still mounted although its cleanup was requested
+//! - `missing`: an active volume is listed on this node, but is neither
+//!   mounted nor has a directory (and wasn't reported absent yet)
+//! - `readonly_mismatch`: the volume's recorded mount options include `ro`,
+//!   yet a mount of it is writable
+//!
+//! Each discrepancy gets a warning event when first seen and is counted in
+//! the `nlc_mount_audit_discrepancies` gauge. With `--mount-audit-repair`,
+//! untracked volumes are registered for this node and orphaned mounts are
+//! unmounted; the other kinds are only reported.
+//!
+//! Mounts are read before the ConfigMaps, and a publish registers its volume
+//! right after mounting, so a publish in flight can show up as `untracked`
+//! for one pass. Repairing it is harmless.
+
+use std::collections::{HashMap, HashSet};
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use kube::{api::ListParams, Client};
+use tracing::{debug, error, info, warn};
+
+use crate::cleanup::{self, VolumeStatus, VOLUME_LABEL};
+use crate::coordination::{self, CoordinationBackend};
+use crate::metrics;
+use crate::pending_registration::PendingRegistration;
+use crate::volume;
+use crate::volume_lock::VolumeLocks;
+
+/// One line of /proc/self/mountinfo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountEntry {
+    /// `major:minor` of the mounted filesystem
+    pub device: String,
+    /// Path within the filesystem that is mounted
+    pub root: PathBuf,
+    pub mount_point: PathBuf,
+    pub readonly: bool,
+}
+
+/// Parse /proc/self/mountinfo, skipping malformed lines
+pub fn parse_mountinfo(mountinfo: &str) -> Vec<MountEntry> {
+    let path = |field: &str| {
+        PathBuf::from(std::ffi::OsString::from_vec(volume::unescape_mountinfo(
+            field,
+        )))
+    };
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // id parent major:minor root mount-point mount-options ...
+            let mut fields = line.split(' ').skip(2);
+            let device = fields.next()?;
+            let root = fields.next()?;
+            let mount_point = fields.next()?;
+            let options = fields.next()?;
+            Some(MountEntry {
+                device: device.to_string(),
+                root: path(root),
+                mount_point: path(mount_point),
+                readonly: options.split(',').any(|option| option == "ro"),
+            })
+        })
+        .collect()
+}
+
+/// A mount of (part of) a volume directory outside of the base path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VolumeMount {
+    /// Volume ID, or the shared cache's tracking ID
+    pub tracking_id: String,
+    pub target: PathBuf,
+    pub readonly: bool,
+}
+
+/// Find the mounts of volume directories under `base_path`.
+///
+/// A bind mount only shows the path within its filesystem, so it is mapped
+/// back through the mounts that make up the base path: the one holding the
+/// base path itself, and any mounted under it (btrfs subvolumes, loopback
+/// images).
+pub fn volume_mounts(entries: &[MountEntry], base_path: &Path) -> Vec<VolumeMount> {
+    // (device, path within the filesystem, where that path is on the host)
+    let mut sources: Vec<(&str, PathBuf, PathBuf)> = Vec::new();
+    let holding = entries
+        .iter()
+        .filter(|e| base_path.starts_with(&e.mount_point))
+        .max_by_key(|e| e.mount_point.as_os_str().len());
+    if let Some(e) = holding {
+        let within = base_path
+            .strip_prefix(&e.mount_point)
+            .unwrap_or(Path::new(""));
+        sources.push((&e.device, e.root.join(within), base_path.to_path_buf()));
+    }
+    for e in entries {
+        if e.mount_point != base_path && e.mount_point.starts_with(base_path) {
+            sources.push((&e.device, e.root.clone(), e.mount_point.clone()));
+        }
+    }
+
+    entries
+        .iter()
+        .filter(|e| !e.mount_point.starts_with(base_path))
+        .filter_map(|e| {
+            let (_, root, host) = sources
+                .iter()
+                .filter(|(device, root, _)| *device == e.device && e.root.starts_with(root))
+                .max_by_key(|(_, root, _)| root.as_os_str().len())?;
+            let host = host.join(e.root.strip_prefix(root).ok()?);
+            let tracking_id = tracking_id_for(host.strip_prefix(base_path).ok()?)?;
+            Some(VolumeMount {
+                tracking_id,
+                target: e.mount_point.clone(),
+                readonly: e.readonly,
+            })
+        })
+        .collect()
+}
+
+/// Tracking ID of the volume directory holding `relative` (a path below the
+/// base path), if it is one
+fn tracking_id_for(relative: &Path) -> Option<String> {
+    let mut components = relative.components().filter_map(|c| match c {
+        Component::Normal(name) => name.to_str(),
+        _ => None,
+    });
+    match components.next()? {
+        "shared" => Some(volume::shared_volume_id(components.next()?)),
+        // .quarantine, .images, ...
+        name if name.starts_with('.') => None,
+        name => Some(name.to_string()),
+    }
+}
+
+/// Something the audit found that doesn't add up
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Discrepancy {
+    /// Mounted, but the ConfigMap doesn't list this node or doesn't exist
+    Untracked { volume_id: String, target: PathBuf },
+    /// Mounted although cleanup was requested
+    Orphaned { volume_id: String, target: PathBuf },
+    /// Listed on this node, but neither mounted nor present
+    Missing { volume_id: String },
+    /// Recorded mount options include `ro`, but the mount is writable
+    ReadonlyMismatch { volume_id: String, target: PathBuf },
+}
+
+impl Discrepancy {
+    /// All kinds, as used in the metric's `kind` label
+    pub const KINDS: [&'static str; 4] = ["untracked", "orphaned", "missing", "readonly_mismatch"];
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Discrepancy::Untracked { .. } => "untracked",
+            Discrepancy::Orphaned { .. } => "orphaned",
+            Discrepancy::Missing { .. } => "missing",
+            Discrepancy::ReadonlyMismatch { .. } => "readonly_mismatch",
+        }
+    }
+
+    pub fn volume_id(&self) -> &str {
+        match self {
+            Discrepancy::Untracked { volume_id, .. }
+            | Discrepancy::Orphaned { volume_id, .. }
+            | Discrepancy::Missing { volume_id }
+            | Discrepancy::ReadonlyMismatch { volume_id, .. } => volume_id,
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            Discrepancy::Untracked { .. } => "MountUntracked",
+            Discrepancy::Orphaned { .. } => "MountOrphaned",
+            Discrepancy::Missing { .. } => "VolumeMissing",
+            Discrepancy::ReadonlyMismatch { .. } => "MountNotReadonly",
+        }
+    }
+
+    fn message(&self, node_name: &str) -> String {
+        match self {
+            Discrepancy::Untracked { target, .. } => format!(
+                "Volume is mounted at {} on node {}, which its ConfigMap doesn't list",
+                target.display(),
+                node_name
+            ),
+            Discrepancy::Orphaned { target, .. } => format!(
+                "Volume is still mounted at {} on node {} although its cleanup was requested",
+                target.display(),
+                node_name
+            ),
+            Discrepancy::Missing { .. } => format!(
+                "Volume is listed on node {}, but neither mounted nor present there",
+                node_name
+            ),
+            Discrepancy::ReadonlyMismatch { target, .. } => format!(
+                "Volume mount at {} on node {} is writable although its mount options include ro",
+                target.display(),
+                node_name
+            ),
+        }
+    }
+}
+
+/// Compare the volume mounts on `node_name` with the tracking `statuses`
+/// (by tracking ID). `present` tells whether a volume's directory exists.
+pub fn find_discrepancies(
+    mounts: &[VolumeMount],
+    statuses: &HashMap<String, VolumeStatus>,
+    node_name: &str,
+    present: impl Fn(&str) -> bool,
+) -> Vec<Discrepancy> {
+    let node = node_name.to_string();
+    let mut found = Vec::new();
+
+    for mount in mounts {
+        let volume_id = mount.tracking_id.clone();
+        let target = mount.target.clone();
+        match statuses.get(&mount.tracking_id) {
+            Some(status) if status.cleanup_requested_at.is_some() => {
+                found.push(Discrepancy::Orphaned { volume_id, target });
+            }
+            Some(status) if status.nodes_with_volume.contains(&node) => {
+                let readonly_recorded = status
+                    .mount_options
+                    .as_ref()
+                    .is_some_and(|options| options.iter().any(|o| o == "ro"));
+                if readonly_recorded && !mount.readonly {
+                    found.push(Discrepancy::ReadonlyMismatch { volume_id, target });
+                }
+            }
+            _ => found.push(Discrepancy::Untracked { volume_id, target }),
+        }
+    }
+
+    let mounted: HashSet<&str> = mounts.iter().map(|m| m.tracking_id.as_str()).collect();
+    let mut missing: Vec<_> = statuses
+        .values()
+        .filter(|s| {
+            // A shared cache's volumes are covered by the cache's own status
+            s.cleanup_requested_at.is_none()
+                && s.shared_name.is_none()
+                && s.nodes_with_volume.contains(&node)
+                && !s.nodes_directory_absent.contains(&node)
+                && !mounted.contains(s.volume_id.as_str())
+                && !present(&s.volume_id)
+        })
+        .map(|s| Discrepancy::Missing {
+            volume_id: s.volume_id.clone(),
+        })
+        .collect();
+    missing.sort_by(|a, b| a.volume_id().cmp(b.volume_id()));
+    found.extend(missing);
+    found
+}
+
+/// Node-side mount audit
+pub struct MountAudit {
+    client: Client,
+    namespace: String,
+    node_name: String,
+    base_path: PathBuf,
+    /// Register untracked volumes and unmount orphaned ones
+    repair: bool,
+    /// Shared with NodePublishVolume and cleanup
+    volume_locks: VolumeLocks,
+    /// Discrepancies found by the previous pass (avoids event spam)
+    reported: HashSet<Discrepancy>,
+}
+
+impl MountAudit {
+    pub fn new(client: Client, namespace: String, node_name: String, base_path: PathBuf) -> Self {
+        Self {
+            client,
+            namespace,
+            node_name,
+            base_path,
+            repair: false,
+            volume_locks: VolumeLocks::default(),
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Repair what can be repaired (`--mount-audit-repair`)
+    pub fn with_repair(mut self, repair: bool) -> Self {
+        self.repair = repair;
+        self
+    }
+
+    /// Share per-volume locks with the node service
+    pub fn with_volume_locks(mut self, locks: VolumeLocks) -> Self {
+        self.volume_locks = locks;
+        self
+    }
+
+    /// Audit once, reporting and (optionally) repairing what was found
+    pub async fn audit(&mut self) -> Result<Vec<Discrepancy>, String> {
+        // Mounts first: see the module docs
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+            .map_err(|e| format!("Failed to read /proc/self/mountinfo: {}", e))?;
+        let mounts = volume_mounts(&parse_mountinfo(&mountinfo), &self.base_path);
+
+        let configmaps = coordination::configmaps(&self.client, &self.namespace);
+        let cms = configmaps
+            .list(&ListParams::default().labels(VOLUME_LABEL))
+            .await
+            .map_err(|e| format!("Failed to list volume ConfigMaps: {}", e))?;
+        let statuses: HashMap<String, VolumeStatus> = cleanup::merge_shards(
+            cms.iter()
+                .filter_map(VolumeStatus::from_configmap)
+                .collect(),
+        )
+        .into_iter()
+        .map(|s| (s.volume_id.clone(), s))
+        .collect();
+
+        let base_path = self.base_path.clone();
+        let found = find_discrepancies(&mounts, &statuses, &self.node_name, |id| {
+            volume::volume_path(&base_path, id).exists()
+        });
+
+        let m = metrics::metrics();
+        for kind in Discrepancy::KINDS {
+            let count = found.iter().filter(|d| d.kind() == kind).count();
+            m.record_mount_audit(kind, count);
+        }
+
+        let mut reported = HashSet::new();
+        for discrepancy in &found {
+            if !self.reported.contains(discrepancy) {
+                self.report(discrepancy, statuses.get(discrepancy.volume_id()))
+                    .await;
+            }
+            reported.insert(discrepancy.clone());
+            if self.repair {
+                self.repair(discrepancy).await;
+            }
+        }
+        self.reported = reported;
+        Ok(found)
+    }
+
+    async fn report(&self, discrepancy: &Discrepancy, status: Option<&VolumeStatus>) {
+        let message = discrepancy.message(&self.node_name);
+        warn!(
+            volume_id = %discrepancy.volume_id(),
+            kind = discrepancy.kind(),
+            "{}",
+            message
+        );
+        cleanup::emit_event(
+            &self.client,
+            &self.namespace,
+            discrepancy.volume_id(),
+            status.and_then(|s| s.pvc.as_ref()),
+            discrepancy.reason(),
+            &message,
+            "Warning",
+        )
+        .await;
+    }
+
+    async fn repair(&self, discrepancy: &Discrepancy) {
+        let volume_id = discrepancy.volume_id();
+        let result = match discrepancy {
+            Discrepancy::Untracked { .. } => {
+                let _guard = self.volume_locks.lock(volume_id).await;
+                cleanup::register_node_publish(
+                    &self.client,
+                    &self.namespace,
+                    &self.node_name,
+                    &PendingRegistration::new(volume_id),
+                )
+                .await
+                .map_err(|e| e.to_string())
+            }
+            Discrepancy::Orphaned { target, .. } => volume::unmount(target)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            Discrepancy::Missing { .. } | Discrepancy::ReadonlyMismatch { .. } => return,
+        };
+        match result {
+            Ok(()) => {
+                metrics::metrics().record_mount_audit_repair(discrepancy.kind());
+                info!(volume_id = %volume_id, kind = discrepancy.kind(), "Repaired mount discrepancy");
+            }
+            Err(e) => {
+                error!(volume_id = %volume_id, kind = discrepancy.kind(), error = %e, "Failed to repair mount discrepancy");
+            }
+        }
+    }
+
+    /// Run the audit loop
+    pub async fn run(mut self, interval: Duration) {
+        info!(
+            node = %self.node_name,
+            interval_secs = interval.as_secs(),
+            repair = self.repair,
+            "Starting mount audit"
+        );
+
+        loop {
+            match self.audit().await {
+                Ok(found) if !found.is_empty() => {
+                    info!(count = found.len(), "Mount audit found discrepancies");
+                }
+                Ok(_) => {
+                    debug!("Mounts consistent with tracking");
+                }
+                Err(e) => {
+                    error!(error = %e, "Error auditing mounts");
+                }
+            }
+
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+40 22 8:2 / /var/node-local-cache rw,relatime - xfs /dev/sdb1 rw
+41 40 7:0 / /var/node-local-cache/nlc-loop rw,relatime - ext4 /dev/loop0 rw
+50 22 8:2 /nlc-a /var/lib/kubelet/pods/p1/volumes/kubernetes.io~csi/pv-a/mount rw,relatime - xfs /dev/sdb1 rw
+51 22 8:2 /nlc-b /var/lib/kubelet/pods/p2/volumes/kubernetes.io~csi/pv-b/mount ro,relatime - xfs /dev/sdb1 rw
+52 22 8:2 /shared/maven /var/lib/kubelet/pods/p3/my\\040mount rw,relatime - xfs /dev/sdb1 rw
+53 22 7:0 / /var/lib/kubelet/pods/p4/mount rw,relatime - ext4 /dev/loop0 rw
+54 22 8:2 /.quarantine/x /mnt/x rw,relatime - xfs /dev/sdb1 rw
+55 22 8:1 /etc /mnt/etc rw,relatime - ext4 /dev/sda1 rw
+";
+
+    fn mount(id: &str, target: &str, readonly: bool) -> VolumeMount {
+        VolumeMount {
+            tracking_id: id.to_string(),
+            target: PathBuf::from(target),
+            readonly,
+        }
+    }
+
+    #[test]
+    fn test_volume_mounts() {
+        let entries = parse_mountinfo(MOUNTINFO);
+        assert_eq!(entries.len(), 9);
+        let mounts = volume_mounts(&entries, Path::new("/var/node-local-cache"));
+        assert_eq!(
+            mounts,
+            vec![
+                mount(
+                    "nlc-a",
+                    "/var/lib/kubelet/pods/p1/volumes/kubernetes.io~csi/pv-a/mount",
+                    false
+                ),
+                mount(
+                    "nlc-b",
+                    "/var/lib/kubelet/pods/p2/volumes/kubernetes.io~csi/pv-b/mount",
+                    true
+                ),
+                mount("shared-maven", "/var/lib/kubelet/pods/p3/my mount", false),
+                mount("nlc-loop", "/var/lib/kubelet/pods/p4/mount", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_volume_mounts_base_in_subdirectory() {
+        // The driver container sees the host's base path as a hostPath mount
+        let mountinfo = "\
+22 1 0:50 / / rw - overlay overlay rw
+40 22 8:2 /srv/cache /var/node-local-cache rw - xfs /dev/sdb1 rw
+50 22 8:2 /srv/cache/nlc-a /var/lib/kubelet/pods/p1/mount rw - xfs /dev/sdb1 rw
+51 22 8:2 /srv/other /var/lib/kubelet/pods/p2/mount rw - xfs /dev/sdb1 rw
+";
+        let mounts = volume_mounts(
+            &parse_mountinfo(mountinfo),
+            Path::new("/var/node-local-cache"),
+        );
+        assert_eq!(
+            mounts,
+            vec![mount("nlc-a", "/var/lib/kubelet/pods/p1/mount", false)]
+        );
+    }
+
+    fn status(id: &str, nodes: &[&str]) -> VolumeStatus {
+        let mut status = VolumeStatus::new(id);
+        for node in nodes {
+            status.add_node(node);
+        }
+        status
+    }
+
+    #[test]
+    fn test_find_discrepancies() {
+        let mut statuses: HashMap<String, VolumeStatus> = HashMap::new();
+        let mut insert = |s: VolumeStatus| statuses.insert(s.volume_id.clone(), s);
+        insert(status("nlc-ok", &["node1"]));
+        insert(status("nlc-other-node", &["node2"]));
+        let mut deleted = status("nlc-deleted", &["node1"]);
+        deleted.mark_cleanup_requested();
+        insert(deleted);
+        let mut ro = status("nlc-ro", &["node1"]);
+        ro.mount_options = Some(vec!["ro".to_string(), "noatime".to_string()]);
+        insert(ro);
+        insert(status("nlc-idle", &["node1"]));
+        let mut shared_volume = status("nlc-shared-user", &["node1"]);
+        shared_volume.shared_name = Some("maven".to_string());
+        insert(shared_volume);
+        insert(status("nlc-gone", &["node1"]));
+        let mut reported = status("nlc-reported", &["node1"]);
+        reported.mark_node_directory_absent("node1");
+        insert(reported);
+
+        let mounts = vec![
+            mount("nlc-ok", "/t/ok", false),
+            mount("nlc-other-node", "/t/other", false),
+            mount("nlc-unknown", "/t/unknown", false),
+            mount("nlc-deleted", "/t/deleted", false),
+            mount("nlc-ro", "/t/ro-ok", true),
+            mount("nlc-ro", "/t/ro-bad", false),
+        ];
+        let found = find_discrepancies(&mounts, &statuses, "node1", |id| id == "nlc-idle");
+        let target = |t: &str| PathBuf::from(t);
+        assert_eq!(
+            found,
+            vec![
+                Discrepancy::Untracked {
+                    volume_id: "nlc-other-node".to_string(),
+                    target: target("/t/other"),
+                },
+                Discrepancy::Untracked {
+                    volume_id: "nlc-unknown".to_string(),
+                    target: target("/t/unknown"),
+                },
+                Discrepancy::Orphaned {
+                    volume_id: "nlc-deleted".to_string(),
+                    target: target("/t/deleted"),
+                },
+                Discrepancy::ReadonlyMismatch {
+                    volume_id: "nlc-ro".to_string(),
+                    target: target("/t/ro-bad"),
+                },
+                Discrepancy::Missing {
+                    volume_id: "nlc-gone".to_string(),
+                },
+            ]
+        );
+
+        // Nothing tracked, nothing mounted: consistent
+        assert!(find_discrepancies(&[], &HashMap::new(), "node1", |_| false).is_empty());
+    }
+}
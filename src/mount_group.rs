@@ -0,0 +1,109 @@
+//! Volume mount group (`VOLUME_MOUNT_GROUP` node capability).
+//!
+//! The kubelet delegates a pod's `fsGroup` to the driver through the volume
+//! capability's `volume_mount_group`. Like the kubelet's own fsGroup handling,
+//! the volume directory tree is made group-owned by that GID and group
+//! read/writable, and directories get the setgid bit so new files inherit
+//! the group.
+//!
+//! Cache directories can be large, so the tree is only walked when its root
+//! doesn't have the group and setgid bit yet (the kubelet's
+//! `OnRootMismatch` policy). Symlinks are re-owned but never followed.
+
+use std::io;
+use std::os::unix::fs::{lchown, MetadataExt, PermissionsExt};
+use std::path::Path;
+
+const SETGID: u32 = 0o2000;
+const GROUP_RW: u32 = 0o060;
+const GROUP_X: u32 = 0o010;
+const OWNER_X: u32 = 0o100;
+
+/// Parse `volume_mount_group`: a numeric GID, or empty for none
+pub fn parse_group(group: &str) -> Result<Option<u32>, String> {
+    if group.is_empty() {
+        return Ok(None);
+    }
+    group
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("Invalid volume mount group: {}", group))
+}
+
+/// Give `gid` to the tree under `dir`, unless its root already has it.
+/// Returns whether the tree was walked.
+pub fn apply_group(dir: &Path, gid: u32) -> io::Result<bool> {
+    let root = std::fs::metadata(dir)?;
+    if root.gid() == gid && root.mode() & SETGID != 0 {
+        return Ok(false);
+    }
+
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(path) = pending.pop() {
+        let metadata = std::fs::symlink_metadata(&path)?;
+        if metadata.gid() != gid {
+            lchown(&path, None, Some(gid))?;
+        }
+        if metadata.file_type().is_symlink() {
+            continue;
+        }
+
+        let mode = metadata.mode() & 0o7777;
+        let mut wanted = mode | GROUP_RW;
+        if metadata.is_dir() {
+            wanted |= SETGID | GROUP_X;
+            for entry in std::fs::read_dir(&path)? {
+                pending.push(entry?.path());
+            }
+        } else if mode & OWNER_X != 0 {
+            wanted |= GROUP_X;
+        }
+        if wanted != mode {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(wanted))?;
+        }
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group() {
+        assert_eq!(parse_group(""), Ok(None));
+        assert_eq!(parse_group("2000"), Ok(Some(2000)));
+        assert!(parse_group("staff").is_err());
+        assert!(parse_group("-1").is_err());
+    }
+
+    #[test]
+    fn test_apply_group() {
+        let dir = std::env::temp_dir().join(format!("nlc-mount-group-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/data"), b"data").unwrap();
+        std::fs::set_permissions(dir.join("sub/data"), std::fs::Permissions::from_mode(0o600))
+            .unwrap();
+        std::fs::write(dir.join("tool"), b"#!/bin/sh").unwrap();
+        std::fs::set_permissions(dir.join("tool"), std::fs::Permissions::from_mode(0o700)).unwrap();
+        std::os::unix::fs::symlink("/nonexistent", dir.join("link")).unwrap();
+
+        // Our own group, so this works without privileges
+        let gid = nix::unistd::getegid().as_raw();
+        assert!(apply_group(&dir, gid).unwrap());
+
+        let mode = |p: &str| std::fs::metadata(dir.join(p)).unwrap().mode() & 0o7777;
+        assert_eq!(
+            mode("sub") & (SETGID | GROUP_RW | GROUP_X),
+            SETGID | GROUP_RW | GROUP_X
+        );
+        assert_eq!(mode("sub/data"), 0o660);
+        assert_eq!(mode("tool"), 0o770);
+        assert_eq!(std::fs::metadata(dir.join("sub/data")).unwrap().gid(), gid);
+
+        // Root already matches: not walked again
+        assert!(!apply_group(&dir, gid).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
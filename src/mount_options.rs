@@ -0,0 +1,222 @@
+//! Mount options of a volume's bind mounts.
+//!
+//! Options come from the volume capability's `mount_flags` (the
+//! StorageClass `mountOptions`). The first publish of a volume records them
+//! in its tracking ConfigMap, and every later publish, on any node, applies
+//! the recorded options instead of its own, so all copies of a volume are
+//! mounted alike. Whether a mount is read-only stays per publish: it
+//! follows the pod's `readOnly`, not the volume.
+//!
+//! Supported options: `ro`, `noatime`, `nodiratime`, `relatime`,
+//! `strictatime`, `nodev`, `noexec`, `nosuid`, and one propagation mode
+//! (`private`, `rprivate`, `slave`, `rslave`, `shared`, `rshared`).
+//!
+//! `noexec`, `nosuid` and `nodev` can also be asked for with StorageClass
+//! parameters (see `CONTEXT_FLAGS`), which reach the node in the volume
+//! context and apply to every publish of the volume.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use nix::mount::MsFlags;
+
+use crate::volume;
+
+/// Volume context keys turning on a per-mount flag when `"true"`
+pub const CONTEXT_FLAGS: [(&str, MsFlags); 3] = [
+    (volume::NOEXEC_KEY, MsFlags::MS_NOEXEC),
+    (volume::NOSUID_KEY, MsFlags::MS_NOSUID),
+    (volume::NODEV_KEY, MsFlags::MS_NODEV),
+];
+
+/// Parse the value of one of the `CONTEXT_FLAGS` keys
+pub fn parse_context_flag(key: &str, value: &str) -> Result<bool, String> {
+    match value.trim() {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        other => Err(format!(
+            "Invalid {} `{}` (expected true or false)",
+            key, other
+        )),
+    }
+}
+
+/// Check the `CONTEXT_FLAGS` keys present in a volume context
+pub fn validate_context_flags(ctx: &HashMap<String, String>) -> Result<(), String> {
+    for (key, _) in CONTEXT_FLAGS {
+        if let Some(value) = ctx.get(key) {
+            parse_context_flag(key, value)?;
+        }
+    }
+    Ok(())
+}
+
+/// Per-mount flags of a publish: those turned on in the volume context, plus
+/// `MS_RDONLY` when `readonly`. Keys that are absent or not `"true"` are off.
+pub fn mount_flags_from_context(ctx: &HashMap<String, String>, readonly: bool) -> MsFlags {
+    let mut flags = if readonly {
+        MsFlags::MS_RDONLY
+    } else {
+        MsFlags::empty()
+    };
+    for (key, flag) in CONTEXT_FLAGS {
+        if ctx
+            .get(key)
+            .is_some_and(|value| parse_context_flag(key, value) == Ok(true))
+        {
+            flags |= flag;
+        }
+    }
+    flags
+}
+
+/// Parsed mount options
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MountOptions {
+    /// Per-mount flags, applied by remounting the bind mount
+    pub flags: MsFlags,
+    /// Propagation type, applied separately
+    pub propagation: Option<MsFlags>,
+}
+
+impl MountOptions {
+    /// Parse mount options, rejecting unknown ones
+    pub fn parse(options: &[String]) -> Result<Self, String> {
+        let mut parsed = Self {
+            flags: MsFlags::empty(),
+            propagation: None,
+        };
+        for option in options {
+            let (flag, propagation) = match option.as_str() {
+                "ro" => (MsFlags::MS_RDONLY, None),
+                "noatime" => (MsFlags::MS_NOATIME, None),
+                "nodiratime" => (MsFlags::MS_NODIRATIME, None),
+                "relatime" => (MsFlags::MS_RELATIME, None),
+                "strictatime" => (MsFlags::MS_STRICTATIME, None),
+                "nodev" => (MsFlags::MS_NODEV, None),
+                "noexec" => (MsFlags::MS_NOEXEC, None),
+                "nosuid" => (MsFlags::MS_NOSUID, None),
+                "private" => (MsFlags::empty(), Some(MsFlags::MS_PRIVATE)),
+                "rprivate" => (
+                    MsFlags::empty(),
+                    Some(MsFlags::MS_PRIVATE | MsFlags::MS_REC),
+                ),
+                "slave" => (MsFlags::empty(), Some(MsFlags::MS_SLAVE)),
+                "rslave" => (MsFlags::empty(), Some(MsFlags::MS_SLAVE | MsFlags::MS_REC)),
+                "shared" => (MsFlags::empty(), Some(MsFlags::MS_SHARED)),
+                "rshared" => (MsFlags::empty(), Some(MsFlags::MS_SHARED | MsFlags::MS_REC)),
+                other => return Err(format!("Unsupported mount option: {}", other)),
+            };
+            if let Some(propagation) = propagation {
+                if parsed.propagation.is_some_and(|p| p != propagation) {
+                    return Err("Conflicting mount propagation options".to_string());
+                }
+                parsed.propagation = Some(propagation);
+            }
+            parsed.flags |= flag;
+        }
+        Ok(parsed)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty() && self.propagation.is_none()
+    }
+
+    /// Apply the options to the bind mount at `target`. Bind mounts ignore
+    /// flags on creation, so they are set with a remount, which replaces all
+    /// per-mount flags: `readonly` must repeat whether the mount is read-only.
+    pub fn apply(&self, target: &Path, readonly: bool) -> nix::Result<()> {
+        if !self.flags.is_empty() {
+            let mut flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | self.flags;
+            if readonly {
+                flags |= MsFlags::MS_RDONLY;
+            }
+            nix::mount::mount(None::<&str>, target, None::<&str>, flags, None::<&str>)?;
+        }
+        if let Some(propagation) = self.propagation {
+            nix::mount::mount(
+                None::<&str>,
+                target,
+                None::<&str>,
+                propagation,
+                None::<&str>,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(list: &[&str]) -> Vec<String> {
+        list.iter().map(|o| o.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_mount_options() {
+        assert!(MountOptions::parse(&[]).unwrap().is_empty());
+
+        let parsed = MountOptions::parse(&options(&["noatime", "nodev", "rslave"])).unwrap();
+        assert_eq!(parsed.flags, MsFlags::MS_NOATIME | MsFlags::MS_NODEV);
+        assert_eq!(
+            parsed.propagation,
+            Some(MsFlags::MS_SLAVE | MsFlags::MS_REC)
+        );
+
+        // Repeating the same propagation is fine, mixing isn't
+        assert!(MountOptions::parse(&options(&["shared", "shared"])).is_ok());
+        assert!(MountOptions::parse(&options(&["shared", "private"])).is_err());
+
+        let err = MountOptions::parse(&options(&["noatime", "data=journal"])).unwrap_err();
+        assert!(err.contains("data=journal"));
+    }
+
+    #[test]
+    fn test_mount_flags_from_context() {
+        assert_eq!(
+            mount_flags_from_context(&HashMap::new(), false),
+            MsFlags::empty()
+        );
+        assert_eq!(
+            mount_flags_from_context(&HashMap::new(), true),
+            MsFlags::MS_RDONLY
+        );
+
+        // Every combination of the three flags, read-only or not
+        for combination in 0..8 {
+            for readonly in [false, true] {
+                let mut ctx = HashMap::new();
+                let mut expected = MsFlags::empty();
+                for (bit, (key, flag)) in CONTEXT_FLAGS.into_iter().enumerate() {
+                    let on = combination & (1 << bit) != 0;
+                    ctx.insert(key.to_string(), on.to_string());
+                    if on {
+                        expected |= flag;
+                    }
+                }
+                if readonly {
+                    expected |= MsFlags::MS_RDONLY;
+                }
+                assert_eq!(
+                    mount_flags_from_context(&ctx, readonly),
+                    expected,
+                    "{:?} readonly={}",
+                    ctx,
+                    readonly
+                );
+                assert!(validate_context_flags(&ctx).is_ok());
+            }
+        }
+
+        // Anything but "true" leaves the flag off, and fails validation
+        let ctx = HashMap::from([(volume::NOEXEC_KEY.to_string(), "yes".to_string())]);
+        assert_eq!(mount_flags_from_context(&ctx, false), MsFlags::empty());
+        let err = validate_context_flags(&ctx).unwrap_err();
+        assert!(err.contains(volume::NOEXEC_KEY));
+        // Unrelated keys are ignored
+        let ctx = HashMap::from([("noexec".to_string(), "true".to_string())]);
+        assert_eq!(mount_flags_from_context(&ctx, false), MsFlags::empty());
+    }
+}
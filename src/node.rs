@@ -1,19 +1,46 @@
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::watch;
 use tonic::{Request, Response, Status};
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
 use crate::csi::{
-    node_server::Node, NodeExpandVolumeRequest, NodeExpandVolumeResponse,
-    NodeGetCapabilitiesRequest, NodeGetCapabilitiesResponse, NodeGetInfoRequest,
-    NodeGetInfoResponse, NodeGetVolumeStatsRequest, NodeGetVolumeStatsResponse,
-    NodePublishVolumeRequest, NodePublishVolumeResponse, NodeServiceCapability,
-    NodeStageVolumeRequest, NodeStageVolumeResponse, NodeUnpublishVolumeRequest,
-    NodeUnpublishVolumeResponse, NodeUnstageVolumeRequest, NodeUnstageVolumeResponse,
+    node_server::Node, node_service_capability, volume_capability, volume_usage,
+    NodeExpandVolumeRequest, NodeExpandVolumeResponse, NodeGetCapabilitiesRequest,
+    NodeGetCapabilitiesResponse, NodeGetInfoRequest, NodeGetInfoResponse,
+    NodeGetVolumeStatsRequest, NodeGetVolumeStatsResponse, NodePublishVolumeRequest,
+    NodePublishVolumeResponse, NodeServiceCapability, NodeStageVolumeRequest,
+    NodeStageVolumeResponse, NodeUnpublishVolumeRequest, NodeUnpublishVolumeResponse,
+    NodeUnstageVolumeRequest, NodeUnstageVolumeResponse, VolumeUsage,
 };
 
 use crate::cleanup;
+use crate::clone;
+use crate::dir_size::{SizeCache, SizeSource};
+use crate::directory::DirectoryBackend;
+use crate::disk_monitor;
+use crate::history;
+use crate::hook;
+use crate::idmap;
+use crate::inventory;
+use crate::metrics;
+use crate::mount_audit::parse_mountinfo;
+use crate::mount_group;
+use crate::mount_options::{self, MountOptions};
+use crate::ownership::Ownership;
+use crate::pending_registration::{self, PendingRegistration};
+use crate::quota::{self, QuotaBackend};
+use crate::shutdown::InFlight;
+use crate::staging::{self, PublishRefs};
+use crate::tmpfs::{self, Backing};
 use crate::volume;
+use crate::volume_lock::VolumeLocks;
+
+/// Minimum interval between `PublishSkippedAlreadyMounted` events for one volume
+const PUBLISH_SKIPPED_EVENT_INTERVAL: Duration = Duration::from_secs(600);
 
 /// Optional cleanup registration context
 pub struct CleanupContext {
@@ -24,7 +51,43 @@ pub struct CleanupContext {
 pub struct NodeService {
     node_name: String,
     base_path: PathBuf,
+    directory_backend: DirectoryBackend,
+    quota_backend: QuotaBackend,
     cleanup_ctx: Option<Arc<CleanupContext>>,
+    set_owner_references: bool,
+    idmapped_mounts: bool,
+    remove_target_on_unpublish: bool,
+    /// Target directories created by NodePublishVolume (`--remove-target-on-unpublish`)
+    created_targets: Mutex<HashSet<PathBuf>>,
+    /// Shared with the cleanup loop, so a directory isn't mounted while deleted
+    volume_locks: VolumeLocks,
+    /// Last `PublishSkippedAlreadyMounted` event per volume, for throttling
+    publish_skipped_events: Mutex<HashMap<String, Instant>>,
+    /// Mount RPCs are served once this is true (`--node-singleton-lock`)
+    serving: Option<watch::Receiver<bool>>,
+    /// Device of the base path at startup (`--verify-base-device`)
+    base_device: Option<u64>,
+    /// Reject, rather than warn about, an `fs_type` the volume directory isn't on
+    strict_fs_type: bool,
+    /// Run before mounting, with its timeout (`--pre-publish-hook`)
+    pre_publish_hook: Option<(PathBuf, Duration)>,
+    /// Cached directory sizes for volume stats (`--du-interval`)
+    size_cache: Option<SizeCache>,
+    /// Directories target paths must stay below (`--allowed-target-prefix`)
+    target_roots: Vec<PathBuf>,
+    /// Other base paths volumes may ask for (`--allowed-base-path`)
+    allowed_base_paths: Vec<PathBuf>,
+    /// Publish/unpublish calls being served, drained on shutdown
+    in_flight: InFlight,
+    /// Serve NodeStageVolume/NodeUnstageVolume (`--enable-staging`)
+    staging: bool,
+    /// Directories staging target paths must stay below (`--allowed-staging-prefix`)
+    staging_roots: Vec<PathBuf>,
+    /// Publishes of each staged volume, so it isn't unstaged while in use
+    publish_refs: PublishRefs,
+    /// Evicts when a volume directory can't be created for lack of space
+    /// (`--eviction-threshold`)
+    eviction: Option<Arc<EvictionManager>>,
 }
 
 impl NodeService {
@@ -32,94 +95,343 @@ impl NodeService {
         Self {
             node_name,
             base_path,
+            directory_backend: DirectoryBackend::default(),
+            quota_backend: QuotaBackend::default(),
             cleanup_ctx: None,
+            set_owner_references: false,
+            idmapped_mounts: false,
+            remove_target_on_unpublish: false,
+            created_targets: Mutex::new(HashSet::new()),
+            volume_locks: VolumeLocks::default(),
+            publish_skipped_events: Mutex::new(HashMap::new()),
+            serving: None,
+            base_device: None,
+            strict_fs_type: false,
+            pre_publish_hook: None,
+            size_cache: None,
+            target_roots: Vec::new(),
+            allowed_base_paths: Vec::new(),
+            in_flight: InFlight::new(),
+            staging: false,
+            staging_roots: Vec::new(),
+            publish_refs: PublishRefs::new(),
+            eviction: None,
         }
     }
 
+    pub fn with_directory_backend(mut self, backend: DirectoryBackend) -> Self {
+        self.directory_backend = backend;
+        self
+    }
+
+    /// Enforce the capacity in the volume context of newly published volumes
+    pub fn with_quota_backend(mut self, backend: QuotaBackend) -> Self {
+        self.quota_backend = backend;
+        self
+    }
+
     pub fn with_cleanup(mut self, client: kube::Client, namespace: String) -> Self {
         self.cleanup_ctx = Some(Arc::new(CleanupContext { client, namespace }));
         self
     }
-}
 
-#[tonic::async_trait]
-impl Node for NodeService {
-    async fn node_publish_volume(
-        &self,
-        request: Request<NodePublishVolumeRequest>,
-    ) -> Result<Response<NodePublishVolumeResponse>, Status> {
-        let req = request.into_inner();
-        let volume_id = &req.volume_id;
-        let target_path = PathBuf::from(&req.target_path);
-        let readonly = req.readonly;
+    /// Idmap bind mounts of volumes whose context carries an ID mapping
+    pub fn with_idmapped_mounts(mut self, enabled: bool) -> Self {
+        self.idmapped_mounts = enabled;
+        self
+    }
 
-        info!(
+    /// Set each volume's PV as owner of its tracking ConfigMap
+    pub fn with_owner_references(mut self, enabled: bool) -> Self {
+        self.set_owner_references = enabled;
+        self
+    }
+
+    /// Refuse to publish when the base path or a volume directory is no longer
+    /// on `device` (the base path's device at startup), e.g. because the cache
+    /// disk failed to mount and the base path fell back to the root filesystem
+    pub fn with_base_device(mut self, device: Option<u64>) -> Self {
+        self.base_device = device;
+        self
+    }
+
+    /// Refuse to mount on target paths that aren't below one of `roots`,
+    /// also once symlinks are resolved. Any target is accepted when empty
+    pub fn with_target_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.target_roots = roots;
+        self
+    }
+
+    /// Let volumes whose context names one of `paths` (`volume::BASE_PATH_KEY`)
+    /// be stored there instead of under the base path
+    pub fn with_allowed_base_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.allowed_base_paths = paths;
+        self
+    }
+
+    /// Base path a volume is stored under: the one its context asks for, if
+    /// allowed, or the node's
+    #[allow(clippy::result_large_err)]
+    fn volume_base_path(&self, requested: Option<&PathBuf>) -> Result<&PathBuf, Status> {
+        match requested {
+            None => Ok(&self.base_path),
+            Some(path) if *path == self.base_path => Ok(&self.base_path),
+            Some(path) => self
+                .allowed_base_paths
+                .iter()
+                .find(|allowed| *allowed == path)
+                .ok_or_else(|| {
+                    Status::invalid_argument(format!(
+                        "Base path {} is not allowed on node {} (--allowed-base-path)",
+                        path.display(),
+                        self.node_name
+                    ))
+                }),
+        }
+    }
+
+    /// This node's directory of the volume `volume_id`, under whichever of
+    /// the base paths holds it: a clone's source may be stored elsewhere
+    /// than the clone
+    fn existing_volume_path(&self, volume_id: &str) -> Option<PathBuf> {
+        std::iter::once(&self.base_path)
+            .chain(&self.allowed_base_paths)
+            .map(|base| volume::volume_path(base, volume_id))
+            .find(|path| std::fs::symlink_metadata(path).is_ok_and(|m| m.is_dir()))
+    }
+
+    /// Reject publishes whose `fs_type` doesn't match the volume directory's
+    /// filesystem (`--strict-fstype`); by default a mismatch is only logged
+    pub fn with_strict_fs_type(mut self, strict: bool) -> Self {
+        self.strict_fs_type = strict;
+        self
+    }
+
+    /// Run `hook` (see `hook`) before each new mount, failing the publish if
+    /// it fails or runs longer than `timeout`
+    pub fn with_pre_publish_hook(mut self, hook: Option<PathBuf>, timeout: Duration) -> Self {
+        self.pre_publish_hook = hook.map(|hook| (hook, timeout));
+        self
+    }
+
+    /// Report used bytes and inodes of volumes without a quota from `cache`
+    /// rather than their whole filesystem's
+    pub fn with_size_cache(mut self, cache: Option<SizeCache>) -> Self {
+        self.size_cache = cache;
+        self
+    }
+
+    /// On running out of space creating a volume directory, run an eviction
+    /// pass with `eviction` and try once more
+    pub fn with_eviction(mut self, eviction: Option<Arc<EvictionManager>>) -> Self {
+        self.eviction = eviction;
+        self
+    }
+
+    /// Prepare volumes once per node in NodeStageVolume and publish them
+    /// from their staging target path (see `staging`)
+    pub fn with_staging(mut self, enabled: bool) -> Self {
+        self.staging = enabled;
+        self
+    }
+
+    /// Refuse to stage on paths that aren't below one of `roots`, like
+    /// `with_target_roots` for publish targets
+    pub fn with_staging_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.staging_roots = roots;
+        self
+    }
+
+    /// Count NodePublishVolume/NodeUnpublishVolume calls in `in_flight`
+    pub fn with_in_flight(mut self, in_flight: InFlight) -> Self {
+        self.in_flight = in_flight;
+        self
+    }
+
+    /// Check a requested `fs_type` against the filesystem `path` is really on.
+    /// Volumes are bind-mounted directories, so the type can't be chosen.
+    #[allow(clippy::result_large_err)]
+    fn check_fs_type(&self, volume_id: &str, requested: &str, path: &Path) -> Result<(), Status> {
+        if requested.is_empty() {
+            return Ok(());
+        }
+        let actual = match volume::filesystem_type(path) {
+            Ok(Some(actual)) => actual,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Failed to detect filesystem type");
+                return Ok(());
+            }
+        };
+        if volume::fs_type_matches(requested, &actual) {
+            return Ok(());
+        }
+
+        let message = format!(
+            "Volume requests fs_type {} but {} is on {}",
+            requested,
+            path.display(),
+            actual
+        );
+        if self.strict_fs_type {
+            error!(volume_id = %volume_id, requested = %requested, actual = %actual, "fs_type mismatch");
+            return Err(Status::invalid_argument(message));
+        }
+        warn!(
             volume_id = %volume_id,
-            target_path = %target_path.display(),
-            readonly = readonly,
-            "NodePublishVolume called"
+            requested = %requested,
+            actual = %actual,
+            "{}; ignoring (--strict-fstype rejects this)",
+            message
         );
+        Ok(())
+    }
 
-        // Validate volume ID
-        if !volume::validate_volume_id(volume_id) {
-            return Err(Status::invalid_argument(format!(
-                "Invalid volume ID: {}",
-                volume_id
+    #[allow(clippy::result_large_err)]
+    fn verify_base_device(&self, path: &Path) -> Result<(), Status> {
+        let Some(expected) = self.base_device else {
+            return Ok(());
+        };
+        let actual = volume::device_id(path)
+            .map_err(|e| Status::internal(format!("Failed to stat {}: {}", path.display(), e)))?;
+        if actual != expected {
+            error!(
+                path = %path.display(),
+                expected_device = expected,
+                actual_device = actual,
+                "Path is not on the base path's device"
+            );
+            return Err(Status::failed_precondition(format!(
+                "{} is on device {:#x}, expected the base path's device {:#x}; \
+                 is the cache disk mounted?",
+                path.display(),
+                actual,
+                expected
             )));
         }
+        Ok(())
+    }
 
-        // Construct source path
-        let source_path = volume::volume_path(&self.base_path, volume_id);
+    /// Share per-volume locks with the cleanup loop
+    pub fn with_volume_locks(mut self, locks: VolumeLocks) -> Self {
+        self.volume_locks = locks;
+        self
+    }
 
-        // Create source directory if it doesn't exist (technically staging, but done here for simplicity)
-        if let Err(e) = std::fs::create_dir_all(&source_path) {
-            error!(path = %source_path.display(), error = %e, "Failed to create source directory");
-            return Err(Status::internal(format!(
-                "Failed to create volume directory: {}",
-                e
-            )));
-        }
+    /// Reject mount RPCs until `serving` turns true, i.e. this instance holds
+    /// the node singleton lock
+    pub fn with_serving(mut self, serving: watch::Receiver<bool>) -> Self {
+        self.serving = Some(serving);
+        self
+    }
 
-        // Create target directory parent if needed
-        if let Some(parent) = target_path.parent() {
-            if let Err(e) = std::fs::create_dir_all(parent) {
-                error!(path = %parent.display(), error = %e, "Failed to create target parent directory");
-                return Err(Status::internal(format!(
-                    "Failed to create target parent directory: {}",
-                    e
-                )));
-            }
+    #[allow(clippy::result_large_err)]
+    fn check_serving(&self) -> Result<(), Status> {
+        match &self.serving {
+            Some(serving) if !*serving.borrow() => Err(Status::unavailable(
+                "Waiting for another node plugin instance to release the node lock",
+            )),
+            _ => Ok(()),
         }
+    }
 
-        // Create target mount point (directory for volume mount)
-        if !target_path.exists() {
-            if let Err(e) = std::fs::create_dir_all(&target_path) {
-                error!(path = %target_path.display(), error = %e, "Failed to create target directory");
-                return Err(Status::internal(format!(
-                    "Failed to create target directory: {}",
-                    e
-                )));
+    /// Remove target directories this plugin created once they're unmounted
+    pub fn with_remove_target_on_unpublish(mut self, enabled: bool) -> Self {
+        self.remove_target_on_unpublish = enabled;
+        self
+    }
+
+    /// Remove an unmounted target directory if it was created by this plugin
+    /// and is empty. Returns whether it was removed.
+    fn remove_created_target(&self, target_path: &Path) -> bool {
+        if !self.created_targets.lock().unwrap().remove(target_path) {
+            debug!(path = %target_path.display(), "Target directory not created by us, leaving it");
+            return false;
+        }
+        // remove_dir only succeeds on an empty directory
+        match std::fs::remove_dir(target_path) {
+            Ok(()) => {
+                debug!(path = %target_path.display(), "Removed target directory");
+                true
+            }
+            Err(e) => {
+                warn!(path = %target_path.display(), error = %e, "Failed to remove target directory");
+                false
             }
         }
+    }
 
-        // Check if already mounted
-        if volume::is_mounted(&target_path)? {
-            info!(target_path = %target_path.display(), "Already mounted, skipping");
-            return Ok(Response::new(NodePublishVolumeResponse {}));
+    /// Whether a `PublishSkippedAlreadyMounted` event is due for this volume.
+    /// Records the emission when it is, so at most one event per interval fires.
+    fn publish_skipped_event_due(&self, volume_id: &str) -> bool {
+        let now = Instant::now();
+        let mut last = self.publish_skipped_events.lock().unwrap();
+        last.retain(|_, at| now.duration_since(*at) < PUBLISH_SKIPPED_EVENT_INTERVAL);
+        if last.contains_key(volume_id) {
+            return false;
         }
+        last.insert(volume_id.to_string(), now);
+        true
+    }
 
-        // Perform bind mount
-        let mount_flags = if readonly {
-            nix::mount::MsFlags::MS_BIND | nix::mount::MsFlags::MS_RDONLY
-        } else {
-            nix::mount::MsFlags::MS_BIND
+    /// Mount options to publish `volume_id` with: with cleanup tracking, those
+    /// recorded at the volume's first publish, otherwise `requested`
+    async fn effective_mount_options(
+        &self,
+        volume_id: &str,
+        requested: Vec<String>,
+    ) -> Result<MountOptions, Status> {
+        let options = match &self.cleanup_ctx {
+            Some(ctx) => match cleanup::settle_mount_options(
+                &ctx.client,
+                &ctx.namespace,
+                volume_id,
+                &requested,
+            )
+            .await
+            {
+                Ok(recorded) => {
+                    if recorded != requested {
+                        info!(
+                            volume_id = %volume_id,
+                            recorded = ?recorded,
+                            requested = ?requested,
+                            "Using the volume's recorded mount options"
+                        );
+                    }
+                    recorded
+                }
+                Err(e) => {
+                    // Best-effort, like cleanup tracking
+                    warn!(
+                        volume_id = %volume_id,
+                        error = %e,
+                        "Failed to get recorded mount options, using the requested ones"
+                    );
+                    requested
+                }
+            },
+            None => requested,
         };
+        MountOptions::parse(&options).map_err(|e| {
+            Status::failed_precondition(format!("Recorded mount options are invalid: {}", e))
+        })
+    }
 
+    /// Plain bind mount of `source_path` on `target_path`
+    async fn bind_mount(
+        &self,
+        volume_id: &str,
+        pvc: Option<&cleanup::PvcRef>,
+        source_path: &Path,
+        target_path: &Path,
+        flags: nix::mount::MsFlags,
+    ) -> Result<(), Status> {
         if let Err(e) = nix::mount::mount(
-            Some(&source_path),
-            &target_path,
+            Some(source_path),
+            target_path,
             None::<&str>,
-            mount_flags,
+            nix::mount::MsFlags::MS_BIND | flags,
             None::<&str>,
         ) {
             error!(
@@ -131,28 +443,43 @@ impl Node for NodeService {
             return Err(Status::internal(format!("Failed to bind mount: {}", e)));
         }
 
-        // For readonly, we need to remount with readonly flag.
-        // Linux bind mounts ignore MS_RDONLY on initial mount - see mount(2):
+        // Per-mount flags (readonly, noexec, ...) need a remount.
+        // Linux bind mounts ignore them on initial mount - see mount(2):
         // "The remaining bits (other than MS_REC) in the mountflags argument are also ignored."
         // Remount with MS_RDONLY is supported since Linux 2.6.26.
-        if readonly {
-            let remount_flags = nix::mount::MsFlags::MS_BIND
-                | nix::mount::MsFlags::MS_REMOUNT
-                | nix::mount::MsFlags::MS_RDONLY;
+        if !flags.is_empty() {
+            let remount_flags =
+                nix::mount::MsFlags::MS_BIND | nix::mount::MsFlags::MS_REMOUNT | flags;
 
             if let Err(e) = nix::mount::mount(
                 None::<&str>,
-                &target_path,
+                target_path,
                 None::<&str>,
                 remount_flags,
                 None::<&str>,
             ) {
-                warn!(error = %e, "Failed to remount readonly, continuing anyway");
+                // Nothing checks noexec/nosuid/nodev afterwards: fail here
+                if flags != nix::mount::MsFlags::MS_RDONLY {
+                    error!(
+                        target = %target_path.display(),
+                        flags = ?flags,
+                        error = %e,
+                        "Failed to remount with mount flags"
+                    );
+                    let _ = nix::mount::umount(target_path);
+                    return Err(Status::internal(format!(
+                        "Failed to remount with mount flags: {}",
+                        e
+                    )));
+                }
+                // The read-only check after mounting fails the publish
+                warn!(error = %e, "Failed to remount readonly");
                 if let Some(ctx) = &self.cleanup_ctx {
                     cleanup::emit_event(
                         &ctx.client,
                         &ctx.namespace,
                         volume_id,
+                        pvc,
                         "ReadonlyRemountFailed",
                         &format!(
                             "Failed to remount volume readonly on node {}: {}",
@@ -165,149 +492,2387 @@ impl Node for NodeService {
             }
         }
 
-        info!(
-            source = %source_path.display(),
-            target = %target_path.display(),
-            "Volume mounted successfully"
-        );
+        Ok(())
+    }
 
-        // Register this node as having the volume for cleanup tracking
-        if let Some(ctx) = &self.cleanup_ctx {
-            if let Err(e) = cleanup::register_node_publish(
-                &ctx.client,
-                &ctx.namespace,
-                volume_id,
-                &self.node_name,
-            )
-            .await
-            {
-                // Log but don't fail - cleanup tracking is best-effort
-                warn!(
-                    volume_id = %volume_id,
+    /// NodeExpandVolume: raise the limit of the live volume at `volume_path`
+    /// (its tmpfs size or project quota). Without a quota backend capacity
+    /// is advisory and only the new capacity is returned.
+    async fn expand_volume(
+        &self,
+        req: NodeExpandVolumeRequest,
+    ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
+        info!(volume_id = %req.volume_id, volume_path = %req.volume_path, "NodeExpandVolume called");
+
+        if req.volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID is required"));
+        }
+        if !volume::validate_volume_id(&req.volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                req.volume_id
+            )));
+        }
+        if req.volume_path.is_empty() {
+            return Err(Status::invalid_argument("Volume path is required"));
+        }
+        if req.capacity_range.is_none() {
+            return Err(Status::invalid_argument("Capacity range is required"));
+        }
+        let capacity =
+            volume::expanded_capacity(req.capacity_range.as_ref()).map_err(Status::out_of_range)?;
+        let volume_path = PathBuf::from(&req.volume_path);
+        if !volume_path.exists() || !volume::is_mounted(&volume_path)? {
+            return Err(Status::not_found(format!(
+                "Volume {} is not mounted at {}",
+                req.volume_id,
+                volume_path.display()
+            )));
+        }
+
+        let (backend, base, volume_id) = (
+            self.quota_backend,
+            self.base_path.clone(),
+            req.volume_id.clone(),
+        );
+        let path = volume_path.clone();
+        let result = tokio::task::spawn_blocking(move || -> std::io::Result<bool> {
+            // A tmpfs-backed volume is a filesystem of its own; a base path
+            // that happens to be on tmpfs is not the volume's to resize
+            let own_filesystem = std::fs::metadata(&path)?.dev() != std::fs::metadata(&base)?.dev();
+            if own_filesystem && tmpfs::is_tmpfs_mount(&path)? {
+                tmpfs::resize(&path, capacity)?;
+                return Ok(true);
+            }
+            backend.expand(&path, &volume_id, capacity)?;
+            Ok(backend != QuotaBackend::None)
+        })
+        .await
+        .map_err(|e| Status::internal(format!("Expand task failed: {}", e)))?;
+        match result {
+            Ok(true) => info!(
+                volume_id = %req.volume_id,
+                path = %volume_path.display(),
+                capacity = capacity,
+                "Expanded volume"
+            ),
+            Ok(false) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                return Err(Status::failed_precondition(e.to_string()))
+            }
+            Err(e) => {
+                error!(
+                    volume_id = %req.volume_id,
+                    path = %volume_path.display(),
+                    capacity = capacity,
                     error = %e,
-                    "Failed to register node for cleanup tracking"
+                    "Failed to expand volume"
                 );
-                cleanup::emit_event(
-                    &ctx.client,
-                    &ctx.namespace,
-                    volume_id,
-                    "CleanupRegistrationFailed",
-                    &format!(
-                        "Failed to register node {} for cleanup tracking: {}",
-                        self.node_name, e
-                    ),
-                    "Warning",
-                )
-                .await;
+                return Err(Status::internal(format!(
+                    "Failed to expand volume to {} bytes: {}",
+                    capacity, e
+                )));
             }
-
-            // Emit event for visibility
-            cleanup::emit_event(
-                &ctx.client,
-                &ctx.namespace,
-                volume_id,
-                "VolumePublished",
-                &format!(
-                    "Volume mounted on node {} at {}",
-                    self.node_name,
-                    target_path.display()
-                ),
-                "Normal",
-            )
-            .await;
         }
 
-        Ok(Response::new(NodePublishVolumeResponse {}))
+        Ok(Response::new(NodeExpandVolumeResponse {
+            capacity_bytes: capacity as i64,
+        }))
     }
 
-    async fn node_unpublish_volume(
+    /// NodePublishVolume; the RPC handler records its result in the history
+    async fn publish_volume(
         &self,
-        request: Request<NodeUnpublishVolumeRequest>,
-    ) -> Result<Response<NodeUnpublishVolumeResponse>, Status> {
-        let req = request.into_inner();
+        req: NodePublishVolumeRequest,
+    ) -> Result<Response<NodePublishVolumeResponse>, Status> {
+        self.mount_volume(req, false).await
+    }
+
+    /// NodeStageVolume (`--enable-staging`): prepare the volume directory and
+    /// bind-mount it at the staging target path, which publishes then mount
+    /// from. The RPC handler records its result in the history
+    async fn stage_volume(
+        &self,
+        req: NodeStageVolumeRequest,
+    ) -> Result<Response<NodeStageVolumeResponse>, Status> {
+        if !self.staging {
+            return Err(Status::unimplemented(
+                "NodeStageVolume not supported (--enable-staging)",
+            ));
+        }
+        if req.staging_target_path.is_empty() {
+            return Err(Status::invalid_argument("Staging target path is required"));
+        }
+        if req.volume_capability.is_none() {
+            return Err(Status::invalid_argument("Volume capability is required"));
+        }
+        self.mount_volume(
+            NodePublishVolumeRequest {
+                volume_id: req.volume_id,
+                publish_context: req.publish_context,
+                staging_target_path: String::new(),
+                target_path: req.staging_target_path,
+                volume_capability: req.volume_capability,
+                readonly: false,
+                secrets: req.secrets,
+                volume_context: req.volume_context,
+            },
+            true,
+        )
+        .await?;
+        Ok(Response::new(NodeStageVolumeResponse {}))
+    }
+
+    /// Mount a volume at `req.target_path`. With `staging`, that is a staging
+    /// target path the volume directory is bind-mounted on as is, without
+    /// the pod's mount flags and options. A publish of a staged volume mounts
+    /// from its staging target path instead of preparing the directory.
+    async fn mount_volume(
+        &self,
+        req: NodePublishVolumeRequest,
+        staging: bool,
+    ) -> Result<Response<NodePublishVolumeResponse>, Status> {
+        self.check_serving()?;
         let volume_id = &req.volume_id;
         let target_path = PathBuf::from(&req.target_path);
+        let readonly = req.readonly && !staging;
+        let staged_source = (self.staging && !staging && !req.staging_target_path.is_empty())
+            .then(|| PathBuf::from(&req.staging_target_path));
 
         info!(
             volume_id = %volume_id,
             target_path = %target_path.display(),
-            "NodeUnpublishVolume called"
+            readonly = readonly,
+            "{} called",
+            if staging { "NodeStageVolume" } else { "NodePublishVolume" }
         );
 
-        // Check if mounted
-        if !volume::is_mounted(&target_path)? {
-            info!(target_path = %target_path.display(), "Not mounted, nothing to do");
-            return Ok(Response::new(NodeUnpublishVolumeResponse {}));
+        // Validate volume ID
+        if !volume::validate_volume_id(volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                volume_id
+            )));
+        }
+        // Before creating anything along it
+        let roots = if staging {
+            &self.staging_roots
+        } else {
+            &self.target_roots
+        };
+        if !volume::is_safe_target(&target_path, roots) {
+            warn!(target_path = %target_path.display(), "Refusing to mount on an unsafe target path");
+            return Err(Status::invalid_argument(format!(
+                "Target path {} is not below an allowed directory ({:?}) or leads out of it",
+                target_path.display(),
+                roots
+            )));
         }
 
-        // Unmount
-        if let Err(e) = nix::mount::umount(&target_path) {
-            // Try lazy unmount if regular unmount fails
-            warn!(error = %e, "Regular unmount failed, trying lazy unmount");
-            if let Err(e) = nix::mount::umount2(&target_path, nix::mount::MntFlags::MNT_DETACH) {
-                error!(error = %e, "Lazy unmount also failed");
-                return Err(Status::internal(format!("Failed to unmount: {}", e)));
+        // Volumes naming a shared cache all bind-mount the same per-node directory
+        let shared_name = req.volume_context.get(volume::SHARED_NAME_KEY);
+        if let Some(name) = shared_name {
+            if !volume::validate_shared_name(name) {
+                return Err(Status::invalid_argument(format!(
+                    "Invalid shared cache name: {}",
+                    name
+                )));
             }
         }
 
-        info!(target_path = %target_path.display(), "Volume unmounted successfully");
+        // ID mappings of the pod's user namespace, for an idmapped bind mount
+        let id_mappings =
+            idmap::from_volume_context(&req.volume_context).map_err(Status::invalid_argument)?;
+        let pvc = cleanup::PvcRef::from_volume_context(&req.volume_context);
+        let capacity = volume::capacity_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let readonly_subpaths = volume::readonly_subpaths_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let wait_for = volume::wait_for_path_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let requested_base = volume::base_path_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let base_path = self.volume_base_path(requested_base.as_ref())?.clone();
+        let backing =
+            Backing::from_volume_context(&req.volume_context).map_err(Status::invalid_argument)?;
+        let ownership = Ownership::from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let clone_from = volume::clone_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        let eviction_priority = volume::eviction_priority_from_volume_context(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        // An unlimited tmpfs could take all of the node's memory
+        let tmpfs_size = match (backing, capacity) {
+            (Backing::Tmpfs, None) => {
+                return Err(Status::invalid_argument(
+                    "tmpfs-backed volumes need a requested capacity",
+                ))
+            }
+            (Backing::Tmpfs, Some(size)) => Some(size),
+            (Backing::Disk, _) => None,
+        };
+        let (requested_options, mount_group, fs_type) = match req
+            .volume_capability
+            .as_ref()
+            .and_then(|c| c.access_type.as_ref())
+        {
+            Some(volume_capability::AccessType::Mount(mount)) => (
+                mount.mount_flags.clone(),
+                mount.volume_mount_group.as_str(),
+                mount.fs_type.as_str(),
+            ),
+            _ => (Vec::new(), "", ""),
+        };
+        MountOptions::parse(&requested_options).map_err(Status::invalid_argument)?;
+        mount_options::validate_context_flags(&req.volume_context)
+            .map_err(Status::invalid_argument)?;
+        // The staging mount is shared by the volume's pods, which each get
+        // their own flags
+        let mount_flags = if staging {
+            nix::mount::MsFlags::empty()
+        } else {
+            mount_options::mount_flags_from_context(&req.volume_context, readonly)
+        };
+        let mount_group =
+            mount_group::parse_group(mount_group).map_err(Status::invalid_argument)?;
+        if id_mappings.is_some() && !self.idmapped_mounts {
+            return Err(Status::failed_precondition(
+                "Volume requests an ID mapping but idmapped mounts are disabled \
+                 (--enable-idmapped-mounts)",
+            ));
+        }
+        // On disk the group is the host GID the pod's group maps to
+        let mount_group = match (mount_group, &id_mappings) {
+            (Some(gid), Some(maps)) => {
+                Some(idmap::to_host(&maps.gid_map, gid).ok_or_else(|| {
+                    Status::invalid_argument(format!(
+                        "Volume mount group {} is not mapped by the volume's GID map",
+                        gid
+                    ))
+                })?)
+            }
+            (gid, _) => gid,
+        };
 
-        Ok(Response::new(NodeUnpublishVolumeResponse {}))
-    }
+        // Construct source path
+        let tracking_id = match shared_name {
+            Some(name) => volume::shared_volume_id(name),
+            None => volume_id.clone(),
+        };
+        let source_path = match &staged_source {
+            Some(staging_path) => staging_path.clone(),
+            None => volume::volume_path(&base_path, &tracking_id),
+        };
 
-    async fn node_get_capabilities(
-        &self,
-        _request: Request<NodeGetCapabilitiesRequest>,
-    ) -> Result<Response<NodeGetCapabilitiesResponse>, Status> {
-        info!("NodeGetCapabilities called");
+        // Held until registered, so cleanup can't delete the directory meanwhile
+        let _guard = self.volume_locks.lock(&tracking_id).await;
 
-        // We don't need staging - return empty capabilities
-        let capabilities: Vec<NodeServiceCapability> = vec![];
+        if let Some(staging_path) = &staged_source {
+            // Prepared by NodeStageVolume
+            if !volume::is_mounted(staging_path)? {
+                return Err(Status::failed_precondition(format!(
+                    "Volume {} is not staged at {}",
+                    volume_id,
+                    staging_path.display()
+                )));
+            }
+        } else {
+            // The cache disk may be mounted after the driver started; don't
+            // create the volume directory underneath it
+            if let Some(path) = &wait_for {
+                if !volume::wait_for_path(
+                    path,
+                    volume::WAIT_FOR_PATH_TIMEOUT,
+                    volume::WAIT_FOR_PATH_POLL,
+                )
+                .await
+                {
+                    return Err(Status::failed_precondition(format!(
+                        "Path {} the volume waits for did not appear within {}s",
+                        path.display(),
+                        volume::WAIT_FOR_PATH_TIMEOUT.as_secs()
+                    )));
+                }
+            }
 
-        Ok(Response::new(NodeGetCapabilitiesResponse { capabilities }))
+            // Don't create volume directories on the wrong filesystem. Only the
+            // node's own base path has a device recorded at startup
+            let own_base = base_path == self.base_path;
+            if own_base {
+                self.verify_base_device(&base_path)?;
+            }
+
+            let created = !source_path.exists();
+            // Create source directory if it doesn't exist (technically staging, but done here for simplicity)
+            let mut result = self.directory_backend.create(&source_path);
+            if let (Err(e), Some(eviction)) = (&result, &self.eviction) {
+                if storage_full(e) {
+                    warn!(path = %source_path.display(), error = %e, "No space for the volume directory, evicting before retrying");
+                    match eviction.check_for(&tracking_id).await {
+                        Ok(evicted) => {
+                            info!(evicted = evicted, "Evicted to make room for a volume")
+                        }
+                        Err(e) => warn!(error = %e, "Eviction to make room for a volume failed"),
+                    }
+                    result = self.directory_backend.create(&source_path);
+                }
+            }
+            if let Err(e) = result {
+                error!(path = %source_path.display(), error = %e, "Failed to create source directory");
+                return Err(directory_creation_error(&base_path, &e));
+            }
+            // btrfs subvolumes each have their own device ID, and so does a
+            // tmpfs or loopback image mounted on the directory by an earlier
+            // publish
+            if own_base
+                && self.directory_backend == DirectoryBackend::Dir
+                && tmpfs_size.is_none()
+                && self.quota_backend != QuotaBackend::Loopback
+                && !volume::is_mounted(&source_path)?
+            {
+                self.verify_base_device(&source_path)?;
+            }
+            // Never bind-mount something outside the base path into a pod
+            let inside_base = match (source_path.canonicalize(), base_path.canonicalize()) {
+                (Ok(source), Ok(base)) => source.starts_with(&base) && source != base,
+                _ => false,
+            };
+            if !inside_base {
+                error!(path = %source_path.display(), "Volume directory leads out of the base path");
+                return Err(Status::invalid_argument(format!(
+                    "Volume directory {} leads out of the base path",
+                    source_path.display()
+                )));
+            }
+
+            if let Some(size) = tmpfs_size {
+                // Limited by its size, the quota backend doesn't apply
+                let source = source_path.clone();
+                let result =
+                    tokio::task::spawn_blocking(move || tmpfs::ensure_mounted(&source, size))
+                        .await
+                        .map_err(|e| Status::internal(format!("tmpfs task failed: {}", e)))?;
+                match result {
+                    Ok(true) => {
+                        info!(path = %source_path.display(), size = size, "Mounted tmpfs for volume")
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(path = %source_path.display(), error = %e, "Failed to mount tmpfs");
+                        return Err(Status::internal(e.to_string()));
+                    }
+                }
+            } else if let Some(capacity) =
+                capacity.filter(|_| self.quota_backend != QuotaBackend::None)
+            {
+                // Size limit (--quota-backend); a no-op for already limited directories
+                let (backend, base, source) =
+                    (self.quota_backend, base_path.clone(), source_path.clone());
+                let id = tracking_id.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    backend.apply(&base, &source, &id, capacity)
+                })
+                .await
+                .map_err(|e| Status::internal(format!("Quota task failed: {}", e)))?;
+                if let Err(e) = result {
+                    error!(
+                        path = %source_path.display(),
+                        capacity = capacity,
+                        error = %e,
+                        "Failed to limit volume directory size"
+                    );
+                    return Err(Status::internal(format!(
+                        "Failed to limit volume directory to {} bytes: {}",
+                        capacity, e
+                    )));
+                }
+            }
+
+            // After the quota backend or tmpfs, which may have mounted a filesystem there
+            self.check_fs_type(volume_id, fs_type, &source_path)?;
+
+            // Pre-warm a new clone, within its quota, from this node's copy of
+            // the source volume
+            if let Some(clone_source) =
+                clone_from.filter(|_| created || clone::interrupted(&source_path))
+            {
+                let result = match self.existing_volume_path(clone_source) {
+                    Some(from) => {
+                        let to = source_path.clone();
+                        tokio::task::spawn_blocking(move || clone::clone_volume(&from, &to))
+                            .await
+                            .map_err(|e| Status::internal(format!("Clone task failed: {}", e)))?
+                    }
+                    None => Ok(None),
+                };
+                // A cold cache still works, so the publish goes on regardless
+                match result {
+                    Ok(Some(stats)) => info!(
+                        volume_id = %volume_id,
+                        source_volume_id = %clone_source,
+                        files = stats.files,
+                        bytes = stats.bytes,
+                        "Cloned volume"
+                    ),
+                    Ok(None) => info!(
+                        volume_id = %volume_id,
+                        source_volume_id = %clone_source,
+                        "Clone source not on this node, starting empty"
+                    ),
+                    Err(e) => warn!(
+                        volume_id = %volume_id,
+                        source_volume_id = %clone_source,
+                        error = %e,
+                        "Failed to clone volume, starting empty"
+                    ),
+                }
+            }
+
+            // Owner and permissions from the StorageClass, before the fsGroup's
+            if !ownership.is_empty() {
+                let source = source_path.clone();
+                let result = tokio::task::spawn_blocking(move || ownership.apply(&source))
+                    .await
+                    .map_err(|e| Status::internal(format!("Ownership task failed: {}", e)))?;
+                match result {
+                    Ok(true) => info!(
+                        path = %source_path.display(),
+                        uid = ?ownership.uid,
+                        gid = ?ownership.gid,
+                        mode = ?ownership.mode.map(|mode| format!("{:o}", mode)),
+                        "Set volume directory ownership"
+                    ),
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(path = %source_path.display(), error = %e, "Failed to set volume directory ownership");
+                        return Err(Status::internal(format!(
+                            "Failed to set volume directory ownership: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+
+            // Delegated fsGroup (VOLUME_MOUNT_GROUP)
+            if let Some(gid) = mount_group {
+                let source = source_path.clone();
+                let result =
+                    tokio::task::spawn_blocking(move || mount_group::apply_group(&source, gid))
+                        .await
+                        .map_err(|e| Status::internal(format!("Mount group task failed: {}", e)))?;
+                match result {
+                    Ok(true) => {
+                        info!(path = %source_path.display(), gid = gid, "Applied volume mount group")
+                    }
+                    Ok(false) => {}
+                    Err(e) => {
+                        error!(path = %source_path.display(), gid = gid, error = %e, "Failed to apply volume mount group");
+                        return Err(Status::internal(format!(
+                            "Failed to apply volume mount group: {}",
+                            e
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Create target directory parent if needed
+        if let Some(parent) = target_path.parent() {
+            if let Err(e) = std::fs::create_dir_all(parent) {
+                error!(path = %parent.display(), error = %e, "Failed to create target parent directory");
+                return Err(Status::internal(format!(
+                    "Failed to create target parent directory: {}",
+                    e
+                )));
+            }
+        }
+
+        // Create target mount point (directory for volume mount)
+        if !target_path.exists() {
+            if let Err(e) = std::fs::create_dir_all(&target_path) {
+                error!(path = %target_path.display(), error = %e, "Failed to create target directory");
+                return Err(Status::internal(format!(
+                    "Failed to create target directory: {}",
+                    e
+                )));
+            }
+            if self.remove_target_on_unpublish {
+                self.created_targets
+                    .lock()
+                    .unwrap()
+                    .insert(target_path.clone());
+            }
+        }
+
+        // Check if already mounted
+        if volume::is_mounted(&target_path)? {
+            info!(target_path = %target_path.display(), "Already mounted, skipping");
+            if staged_source.is_some() {
+                self.publish_refs.add(volume_id, &target_path);
+            }
+            metrics::metrics().publish_skipped_already_mounted.inc();
+
+            if let Some(ctx) = &self.cleanup_ctx {
+                if self.publish_skipped_event_due(volume_id) {
+                    cleanup::emit_event(
+                        &ctx.client,
+                        &ctx.namespace,
+                        volume_id,
+                        pvc.as_ref(),
+                        "PublishSkippedAlreadyMounted",
+                        &format!(
+                            "Volume already mounted on node {} at {}, publish skipped",
+                            self.node_name,
+                            target_path.display()
+                        ),
+                        "Normal",
+                    )
+                    .await;
+                }
+            }
+            return Ok(Response::new(NodePublishVolumeResponse {}));
+        }
+
+        if let Some((hook, timeout)) = self.pre_publish_hook.as_ref().filter(|_| !staging) {
+            if let Err(e) = hook::run_pre_publish(
+                hook.clone(),
+                *timeout,
+                volume_id,
+                &source_path,
+                &target_path,
+            )
+            .await
+            {
+                error!(volume_id = %volume_id, error = %e, "Pre-publish hook failed");
+                return Err(Status::internal(e));
+            }
+        }
+
+        // Mount options are recorded and applied at publish
+        let mut mount_options = if staging {
+            MountOptions {
+                flags: nix::mount::MsFlags::empty(),
+                propagation: None,
+            }
+        } else {
+            self.effective_mount_options(volume_id, requested_options)
+                .await?
+        };
+        // Remounting with the mount options replaces all per-mount flags, and
+        // idmapped mounts only take readonly: carry the volume's flags along
+        mount_options.flags |= mount_flags - nix::mount::MsFlags::MS_RDONLY;
+
+        if let Some(maps) = id_mappings.filter(|_| !staging) {
+            let (source, target) = (source_path.clone(), target_path.clone());
+            let result = tokio::task::spawn_blocking(move || {
+                idmap::idmapped_bind_mount(&source, &target, &maps, readonly)
+            })
+            .await
+            .map_err(|e| Status::internal(format!("Mount task failed: {}", e)))?;
+
+            if let Err(e) = result {
+                error!(
+                    source = %source_path.display(),
+                    target = %target_path.display(),
+                    error = %e,
+                    "Failed to create idmapped bind mount"
+                );
+                return Err(Status::internal(format!(
+                    "Failed to create idmapped bind mount: {}",
+                    e
+                )));
+            }
+        } else {
+            self.bind_mount(
+                volume_id,
+                pvc.as_ref(),
+                &source_path,
+                &target_path,
+                mount_flags,
+            )
+            .await?;
+        }
+
+        if !mount_options.is_empty() {
+            if let Err(e) = mount_options.apply(&target_path, readonly) {
+                error!(
+                    target = %target_path.display(),
+                    error = %e,
+                    "Failed to apply mount options"
+                );
+                // Don't leave a mount that a retry would take as done
+                let _ = nix::mount::umount(&target_path);
+                return Err(Status::internal(format!(
+                    "Failed to apply mount options: {}",
+                    e
+                )));
+            }
+        }
+
+        // Trust the mount table, not the remount syscall
+        if readonly {
+            match volume::mount_is_readonly(&target_path) {
+                Ok(Some(true)) => {}
+                result => {
+                    error!(
+                        target = %target_path.display(),
+                        result = ?result,
+                        "Mount requested readonly is not readonly"
+                    );
+                    let _ = nix::mount::umount(&target_path);
+                    return Err(Status::internal(format!(
+                        "Volume mounted at {} is not readonly",
+                        target_path.display()
+                    )));
+                }
+            }
+        }
+
+        // Seeded subdirectories of a writable volume; moot if it's all readonly
+        if !readonly && !staging && !readonly_subpaths.is_empty() {
+            if let Err(e) =
+                mount_readonly_subpaths(&source_path, &target_path, &readonly_subpaths, mount_flags)
+            {
+                error!(
+                    target = %target_path.display(),
+                    error = %e,
+                    "Failed to mount readonly subpaths"
+                );
+                let _ = unmount_submounts(&target_path);
+                let _ = nix::mount::umount(&target_path);
+                return Err(Status::internal(format!(
+                    "Failed to mount readonly subpaths: {}",
+                    e
+                )));
+            }
+        }
+
+        info!(
+            source = %source_path.display(),
+            target = %target_path.display(),
+            "Volume mounted successfully"
+        );
+        if staged_source.is_some() {
+            self.publish_refs.add(volume_id, &target_path);
+        }
+
+        // Register this node as having the volume for cleanup tracking
+        if let Some(ctx) = &self.cleanup_ctx {
+            let registration = PendingRegistration {
+                volume_id: volume_id.clone(),
+                shared_name: shared_name.cloned(),
+                pvc: pvc.clone(),
+                labels: volume::labels_from_parameters(&req.volume_context),
+                capacity_bytes: capacity.map_or(0, |bytes| bytes as i64),
+                base_path: Some(base_path).filter(|path| *path != self.base_path),
+                eviction_priority,
+            };
+            if let Err(e) = cleanup::register_node_publish(
+                &ctx.client,
+                &ctx.namespace,
+                &self.node_name,
+                &registration,
+            )
+            .await
+            {
+                // Don't fail the mount; the cleanup loop retries the registration
+                warn!(
+                    volume_id = %volume_id,
+                    error = %e,
+                    "Failed to register node for cleanup tracking, will retry"
+                );
+                if let Err(e) = pending_registration::record(&self.base_path, &registration) {
+                    error!(
+                        volume_id = %volume_id,
+                        error = %e,
+                        "Failed to persist pending registration"
+                    );
+                }
+                cleanup::emit_event(
+                    &ctx.client,
+                    &ctx.namespace,
+                    volume_id,
+                    pvc.as_ref(),
+                    "CleanupRegistrationFailed",
+                    &format!(
+                        "Failed to register node {} for cleanup tracking: {}",
+                        self.node_name, e
+                    ),
+                    "Warning",
+                )
+                .await;
+            }
+
+            if self.set_owner_references {
+                match req.volume_context.get(volume::PV_NAME_KEY) {
+                    Some(pv_name) => {
+                        if let Err(e) = cleanup::set_volume_owner(
+                            &ctx.client,
+                            &ctx.namespace,
+                            volume_id,
+                            pv_name,
+                        )
+                        .await
+                        {
+                            warn!(
+                                volume_id = %volume_id,
+                                error = %e,
+                                "Failed to set owner reference on tracking ConfigMap"
+                            );
+                        }
+                    }
+                    None => debug!(
+                        volume_id = %volume_id,
+                        "No PV name in volume context, skipping owner reference"
+                    ),
+                }
+            }
+
+            // Emit event for visibility
+            cleanup::emit_event(
+                &ctx.client,
+                &ctx.namespace,
+                volume_id,
+                pvc.as_ref(),
+                "VolumePublished",
+                &format!(
+                    "Volume mounted on node {} at {}",
+                    self.node_name,
+                    target_path.display()
+                ),
+                "Normal",
+            )
+            .await;
+        }
+
+        Ok(Response::new(NodePublishVolumeResponse {}))
     }
 
-    async fn node_get_info(
+    /// NodeUnpublishVolume; the RPC handler records its result in the history
+    async fn unpublish_volume(
         &self,
-        _request: Request<NodeGetInfoRequest>,
-    ) -> Result<Response<NodeGetInfoResponse>, Status> {
-        info!(node_name = %self.node_name, "NodeGetInfo called");
+        req: NodeUnpublishVolumeRequest,
+    ) -> Result<Response<NodeUnpublishVolumeResponse>, Status> {
+        self.check_serving()?;
+        let volume_id = &req.volume_id;
+        let target_path = PathBuf::from(&req.target_path);
 
-        Ok(Response::new(NodeGetInfoResponse {
-            node_id: self.node_name.clone(),
-            max_volumes_per_node: 0, // No limit
-            // No topology - volumes accessible from any node
-            accessible_topology: None,
-        }))
-    }
+        info!(
+            volume_id = %volume_id,
+            target_path = %target_path.display(),
+            "NodeUnpublishVolume called"
+        );
+
+        // Check if mounted
+        if !volume::is_mounted(&target_path)? {
+            info!(target_path = %target_path.display(), "Not mounted, nothing to do");
+            self.publish_refs.remove(volume_id, &target_path);
+            return Ok(Response::new(NodeUnpublishVolumeResponse {}));
+        }
 
-    // Staging not implemented - not needed for bind mounts
+        // Readonly subpaths first, or the volume's mount is busy
+        if let Err(e) = unmount_submounts(&target_path) {
+            error!(error = %e, "Failed to unmount readonly subpaths");
+            return Err(Status::internal(format!(
+                "Failed to unmount readonly subpaths: {}",
+                e
+            )));
+        }
 
-    async fn node_stage_volume(
-        &self,
-        _request: Request<NodeStageVolumeRequest>,
-    ) -> Result<Response<NodeStageVolumeResponse>, Status> {
-        Err(Status::unimplemented("NodeStageVolume not supported"))
+        // Unmount, lazily if a regular unmount fails
+        if let Err(e) = volume::unmount(&target_path) {
+            error!(error = %e, "Lazy unmount also failed");
+            return Err(Status::internal(format!("Failed to unmount: {}", e)));
+        }
+
+        info!(target_path = %target_path.display(), "Volume unmounted successfully");
+        self.publish_refs.remove(volume_id, &target_path);
+
+        if self.remove_target_on_unpublish {
+            self.remove_created_target(&target_path);
+        }
+
+        Ok(Response::new(NodeUnpublishVolumeResponse {}))
     }
 
-    async fn node_unstage_volume(
+    /// NodeUnstageVolume (`--enable-staging`): unmount the staging target
+    /// path once none of the volume's publishes remain. The RPC handler
+    /// records its result in the history
+    async fn unstage_volume(
         &self,
-        _request: Request<NodeUnstageVolumeRequest>,
+        req: NodeUnstageVolumeRequest,
     ) -> Result<Response<NodeUnstageVolumeResponse>, Status> {
-        Err(Status::unimplemented("NodeUnstageVolume not supported"))
+        if !self.staging {
+            return Err(Status::unimplemented(
+                "NodeUnstageVolume not supported (--enable-staging)",
+            ));
+        }
+        self.check_serving()?;
+        let volume_id = &req.volume_id;
+        let staging_path = PathBuf::from(&req.staging_target_path);
+
+        info!(
+            volume_id = %volume_id,
+            staging_target_path = %staging_path.display(),
+            "NodeUnstageVolume called"
+        );
+
+        if !volume::validate_volume_id(volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                volume_id
+            )));
+        }
+        if req.staging_target_path.is_empty() {
+            return Err(Status::invalid_argument("Staging target path is required"));
+        }
+
+        let published = self.publish_refs.count(volume_id);
+        if published > 0 {
+            warn!(volume_id = %volume_id, published = published, "Volume still published, not unstaging");
+            return Err(Status::failed_precondition(format!(
+                "Volume {} is still published at {} target(s)",
+                volume_id, published
+            )));
+        }
+
+        if !volume::is_mounted(&staging_path)? {
+            info!(staging_target_path = %staging_path.display(), "Not staged, nothing to do");
+            return Ok(Response::new(NodeUnstageVolumeResponse {}));
+        }
+        // Publishes from before a restart aren't counted
+        let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+            .map_err(|e| Status::internal(format!("Failed to read /proc/self/mountinfo: {}", e)))?;
+        let base_paths: Vec<&Path> = std::iter::once(&self.base_path)
+            .chain(&self.allowed_base_paths)
+            .map(PathBuf::as_path)
+            .collect();
+        let mounted =
+            staging::mounted_publishes(&parse_mountinfo(&mountinfo), &staging_path, &base_paths);
+        if !mounted.is_empty() {
+            warn!(volume_id = %volume_id, targets = ?mounted, "Volume still mounted by pods, not unstaging");
+            return Err(Status::failed_precondition(format!(
+                "Volume {} is still published at {} target(s)",
+                volume_id,
+                mounted.len()
+            )));
+        }
+        if let Err(e) = volume::unmount(&staging_path) {
+            error!(error = %e, "Lazy unmount also failed");
+            return Err(Status::internal(format!("Failed to unmount: {}", e)));
+        }
+
+        info!(staging_target_path = %staging_path.display(), "Volume unstaged successfully");
+
+        if self.remove_target_on_unpublish {
+            self.remove_created_target(&staging_path);
+        }
+
+        Ok(Response::new(NodeUnstageVolumeResponse {}))
     }
+}
 
-    async fn node_get_volume_stats(
+#[tonic::async_trait]
+impl Node for NodeService {
+    async fn node_publish_volume(
         &self,
-        _request: Request<NodeGetVolumeStatsRequest>,
-    ) -> Result<Response<NodeGetVolumeStatsResponse>, Status> {
-        Err(Status::unimplemented("NodeGetVolumeStats not supported"))
+        request: Request<NodePublishVolumeRequest>,
+    ) -> Result<Response<NodePublishVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = req.volume_id.clone();
+        let result = self.publish_volume(req).await;
+        history::history().record(
+            "publish",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
     }
 
-    async fn node_expand_volume(
+    async fn node_unpublish_volume(
         &self,
-        _request: Request<NodeExpandVolumeRequest>,
-    ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
-        Err(Status::unimplemented("NodeExpandVolume not supported"))
+        request: Request<NodeUnpublishVolumeRequest>,
+    ) -> Result<Response<NodeUnpublishVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = req.volume_id.clone();
+        let result = self.unpublish_volume(req).await;
+        history::history().record(
+            "unpublish",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
+    }
+
+    async fn node_get_capabilities(
+        &self,
+        _request: Request<NodeGetCapabilitiesRequest>,
+    ) -> Result<Response<NodeGetCapabilitiesResponse>, Status> {
+        info!("NodeGetCapabilities called");
+
+        // Staging is optional: bind mounts work without it
+        let staging = self
+            .staging
+            .then_some(node_service_capability::rpc::Type::StageUnstageVolume);
+        let capabilities = [
+            node_service_capability::rpc::Type::GetVolumeStats,
+            node_service_capability::rpc::Type::VolumeMountGroup,
+            node_service_capability::rpc::Type::ExpandVolume,
+        ]
+        .into_iter()
+        .chain(staging)
+        .map(|rpc| NodeServiceCapability {
+            r#type: Some(node_service_capability::Type::Rpc(
+                node_service_capability::Rpc { r#type: rpc as i32 },
+            )),
+        })
+        .collect();
+
+        Ok(Response::new(NodeGetCapabilitiesResponse { capabilities }))
+    }
+
+    async fn node_get_info(
+        &self,
+        _request: Request<NodeGetInfoRequest>,
+    ) -> Result<Response<NodeGetInfoResponse>, Status> {
+        info!(node_name = %self.node_name, "NodeGetInfo called");
+
+        Ok(Response::new(NodeGetInfoResponse {
+            node_id: self.node_name.clone(),
+            max_volumes_per_node: 0, // No limit
+            // No topology - volumes accessible from any node
+            accessible_topology: None,
+        }))
+    }
+
+    async fn node_stage_volume(
+        &self,
+        request: Request<NodeStageVolumeRequest>,
+    ) -> Result<Response<NodeStageVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = req.volume_id.clone();
+        let result = self.stage_volume(req).await;
+        history::history().record(
+            "stage",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
+    }
+
+    async fn node_unstage_volume(
+        &self,
+        request: Request<NodeUnstageVolumeRequest>,
+    ) -> Result<Response<NodeUnstageVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = req.volume_id.clone();
+        let result = self.unstage_volume(req).await;
+        history::history().record(
+            "unstage",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
+    }
+
+    async fn node_get_volume_stats(
+        &self,
+        request: Request<NodeGetVolumeStatsRequest>,
+    ) -> Result<Response<NodeGetVolumeStatsResponse>, Status> {
+        let req = request.into_inner();
+        if req.volume_id.is_empty() {
+            return Err(Status::invalid_argument("Volume ID is required"));
+        }
+        if !volume::validate_volume_id(&req.volume_id) {
+            return Err(Status::invalid_argument(format!(
+                "Invalid volume ID: {}",
+                req.volume_id
+            )));
+        }
+        if req.volume_path.is_empty() {
+            return Err(Status::invalid_argument("Volume path is required"));
+        }
+        let volume_path = PathBuf::from(&req.volume_path);
+        if !volume_path.exists() {
+            return Err(Status::not_found(format!(
+                "Volume path {} does not exist",
+                volume_path.display()
+            )));
+        }
+        // Otherwise the stats would be those of whatever holds the empty target
+        if !volume::is_mounted(&volume_path)? {
+            return Err(Status::not_found(format!(
+                "Volume {} is not mounted at {}",
+                req.volume_id,
+                volume_path.display()
+            )));
+        }
+
+        // Project quota usage when the volume has one, filesystem usage otherwise
+        let mut usage = tokio::task::spawn_blocking(move || quota::volume_usage(&volume_path))
+            .await
+            .map_err(|e| Status::internal(format!("Stats task failed: {}", e)))?
+            .map_err(|e| Status::internal(format!("Failed to get volume stats: {}", e)))?;
+        // The filesystem's usage isn't the volume's: use its walked size
+        let walked = self
+            .size_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&req.volume_id))
+            .filter(|size| size.source == SizeSource::Walk);
+        if let Some(size) = walked {
+            usage.used_bytes = size.bytes;
+            usage.used_inodes = size.inodes;
+        }
+
+        Ok(Response::new(NodeGetVolumeStatsResponse {
+            usage: usage_to_csi(&usage),
+            volume_condition: None,
+        }))
+    }
+
+    async fn node_expand_volume(
+        &self,
+        request: Request<NodeExpandVolumeRequest>,
+    ) -> Result<Response<NodeExpandVolumeResponse>, Status> {
+        let _in_flight = self.in_flight.start();
+        let req = request.into_inner();
+        let volume_id = req.volume_id.clone();
+        let result = self.expand_volume(req).await;
+        history::history().record(
+            "expand",
+            &volume_id,
+            &result.as_ref().map_err(Status::message),
+        );
+        result
+    }
+}
+
+/// Bind-remount `subpaths` of the volume mounted at `target_path` readonly,
+/// creating missing directories in the volume directory `source_path`
+fn mount_readonly_subpaths(
+    source_path: &Path,
+    target_path: &Path,
+    subpaths: &[PathBuf],
+    flags: nix::mount::MsFlags,
+) -> Result<(), String> {
+    use nix::mount::MsFlags;
+
+    for subpath in subpaths {
+        volume::resolve_subpath(source_path, subpath).map_err(|e| e.to_string())?;
+        let path = target_path.join(subpath);
+        nix::mount::mount(
+            Some(&path),
+            &path,
+            None::<&str>,
+            MsFlags::MS_BIND,
+            None::<&str>,
+        )
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        // Same as the volume itself: the bind ignores MS_RDONLY, and the
+        // remount drops the volume's other flags unless repeated
+        nix::mount::mount(
+            None::<&str>,
+            &path,
+            None::<&str>,
+            MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | flags,
+            None::<&str>,
+        )
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+        match volume::mount_is_readonly(&path) {
+            Ok(Some(true)) => {}
+            result => return Err(format!("{} is not readonly: {:?}", path.display(), result)),
+        }
+    }
+    Ok(())
+}
+
+/// Unmount everything mounted below `target_path`, deepest first
+fn unmount_submounts(target_path: &Path) -> Result<(), String> {
+    for path in volume::submounts(target_path).map_err(|e| e.to_string())? {
+        volume::unmount(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    }
+    Ok(())
+}
+
+/// Whether `e` is ENOSPC or EDQUOT
+fn storage_full(e: &std::io::Error) -> bool {
+    e.kind() == std::io::ErrorKind::StorageFull || e.raw_os_error() == Some(nix::libc::EDQUOT)
+}
+
+/// Status for a failure to create a volume directory. A full base path is
+/// reported as RESOURCE_EXHAUSTED, so it reads as a capacity problem rather
+/// than a driver bug.
+fn directory_creation_error(base_path: &Path, e: &std::io::Error) -> Status {
+    if storage_full(e) {
+        return Status::resource_exhausted(format!(
+            "No space left on {} to create the volume directory: {}",
+            base_path.display(),
+            e
+        ));
+    }
+    Status::internal(format!("Failed to create volume directory: {}", e))
+}
+
+fn usage_to_csi(usage: &quota::Usage) -> Vec<VolumeUsage> {
+    let clamp = |v: u64| i64::try_from(v).unwrap_or(i64::MAX);
+    vec![
+        VolumeUsage {
+            available: clamp(usage.available_bytes),
+            total: clamp(usage.total_bytes),
+            used: clamp(usage.used_bytes),
+            unit: volume_usage::Unit::Bytes as i32,
+        },
+        VolumeUsage {
+            available: clamp(usage.available_inodes),
+            total: clamp(usage.total_inodes),
+            used: clamp(usage.used_inodes),
+            unit: volume_usage::Unit::Inodes as i32,
+        },
+    ]
+}
+
+/// A volume directory disk pressure eviction may delete
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvictionCandidate {
+    /// Volume ID, or the shared cache's tracking ID
+    pub tracking_id: String,
+    /// Base path the directory is under
+    pub base_path: PathBuf,
+    /// Last access or modification of the directory itself; reads deeper in
+    /// the tree don't update it, listing or changing its entries does
+    pub last_used: SystemTime,
+    /// Lower priorities are evicted first (`volume::EVICTION_PRIORITY_KEY`)
+    pub priority: u8,
+}
+
+/// The volume directories under `base_path`, as eviction candidates with
+/// the priority recorded in `priorities`, or the default one
+fn eviction_candidates(
+    base_path: &Path,
+    priorities: &HashMap<String, u8>,
+) -> std::io::Result<Vec<EvictionCandidate>> {
+    let mut candidates = Vec::new();
+    for tracking_id in inventory::volume_directories(base_path)? {
+        let path = volume::volume_path(base_path, &tracking_id);
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            // Deleted since listed
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        let priority = priorities
+            .get(&tracking_id)
+            .copied()
+            .unwrap_or(volume::DEFAULT_EVICTION_PRIORITY);
+        candidates.push(EvictionCandidate {
+            tracking_id,
+            base_path: base_path.to_path_buf(),
+            last_used: metadata.accessed()?.max(metadata.modified()?),
+            priority,
+        });
+    }
+    Ok(candidates)
+}
+
+/// `candidates` in eviction order, lowest priority first and least recently
+/// used first within a priority, leaving out those in `in_use`
+fn eviction_order(
+    mut candidates: Vec<EvictionCandidate>,
+    in_use: &HashSet<String>,
+) -> Vec<EvictionCandidate> {
+    candidates.retain(|candidate| !in_use.contains(&candidate.tracking_id));
+    candidates.sort_by(|a, b| {
+        a.priority
+            .cmp(&b.priority)
+            .then_with(|| a.last_used.cmp(&b.last_used))
+            .then_with(|| a.tracking_id.cmp(&b.tracking_id))
+    });
+    candidates
+}
+
+/// Deletes the least recently used volume directories that aren't mounted
+/// once a base path fills up past a threshold (`--eviction-threshold`),
+/// until usage is back under the low watermark, starting with the lowest
+/// eviction priority recorded on their tracking ConfigMaps. A cache's
+/// content can be rebuilt, so dropping an idle one beats pods failing with
+/// ENOSPC; its next publish starts it over empty.
+///
+/// The node's base path and its `--allowed-base-path`s are checked by
+/// device: base paths on the same disk fill up, and are evicted from,
+/// together.
+pub struct EvictionManager {
+    node_name: String,
+    base_path: PathBuf,
+    allowed_base_paths: Vec<PathBuf>,
+    threshold_percent: u8,
+    low_watermark_percent: u8,
+    directory_backend: DirectoryBackend,
+    /// Size limits to undo before deleting a directory
+    quota_backend: QuotaBackend,
+    /// Shared with NodePublishVolume, so a directory isn't mounted while deleted
+    volume_locks: VolumeLocks,
+    /// Where to report evictions and read eviction priorities
+    cleanup_ctx: Option<Arc<CleanupContext>>,
+}
+
+impl EvictionManager {
+    pub fn new(
+        node_name: String,
+        base_path: PathBuf,
+        threshold_percent: u8,
+        low_watermark_percent: u8,
+    ) -> Self {
+        Self {
+            node_name,
+            base_path,
+            allowed_base_paths: Vec::new(),
+            threshold_percent,
+            low_watermark_percent,
+            directory_backend: DirectoryBackend::default(),
+            quota_backend: QuotaBackend::default(),
+            volume_locks: VolumeLocks::default(),
+            cleanup_ctx: None,
+        }
+    }
+
+    /// Also evict from these base paths (`--allowed-base-path`)
+    pub fn with_allowed_base_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.allowed_base_paths = paths;
+        self
+    }
+
+    pub fn with_directory_backend(mut self, backend: DirectoryBackend) -> Self {
+        self.directory_backend = backend;
+        self
+    }
+
+    pub fn with_quota_backend(mut self, backend: QuotaBackend) -> Self {
+        self.quota_backend = backend;
+        self
+    }
+
+    /// Share per-volume locks with the node service and the cleanup loop
+    pub fn with_volume_locks(mut self, locks: VolumeLocks) -> Self {
+        self.volume_locks = locks;
+        self
+    }
+
+    /// Report each eviction as a `VolumeEvicted` warning event, and evict in
+    /// the priority order recorded on the volumes' ConfigMaps
+    pub fn with_cleanup(mut self, client: kube::Client, namespace: String) -> Self {
+        self.cleanup_ctx = Some(Arc::new(CleanupContext { client, namespace }));
+        self
+    }
+
+    async fn usage_percent(&self, path: &Path) -> std::io::Result<f64> {
+        let path = path.to_path_buf();
+        let usage = tokio::task::spawn_blocking(move || quota::Usage::from_statvfs(&path))
+            .await
+            .map_err(std::io::Error::other)??;
+        Ok(disk_monitor::usage_ratio(&usage) * 100.0)
+    }
+
+    fn base_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        std::iter::once(&self.base_path).chain(&self.allowed_base_paths)
+    }
+
+    /// The base paths grouped by device, the node's own first. Allowed base
+    /// paths that can't be checked (e.g. their disk isn't mounted yet) are
+    /// left out.
+    fn device_groups(&self) -> Vec<Vec<PathBuf>> {
+        let mut groups: Vec<(Option<u64>, Vec<PathBuf>)> = Vec::new();
+        for path in self.base_paths() {
+            let device = match volume::device_id(path) {
+                Ok(device) => Some(device),
+                // Fails the check, reporting why
+                Err(_) if *path == self.base_path => None,
+                Err(e) => {
+                    debug!(path = %path.display(), error = %e, "Not checking base path for eviction");
+                    continue;
+                }
+            };
+            match groups
+                .iter_mut()
+                .find(|(d, _)| device.is_some() && *d == device)
+            {
+                Some((_, paths)) => paths.push(path.clone()),
+                None => groups.push((device, vec![path.clone()])),
+            }
+        }
+        groups.into_iter().map(|(_, paths)| paths).collect()
+    }
+
+    /// Volumes under any base path that are mounted or not registered yet
+    fn volumes_in_use(&self) -> std::io::Result<HashSet<String>> {
+        let mut in_use = HashSet::new();
+        for path in self.base_paths() {
+            in_use.extend(cleanup::volumes_in_use(path)?);
+        }
+        Ok(in_use)
+    }
+
+    /// Check each base path's device once, evicting from those above the
+    /// threshold. Returns the number of volume directories deleted.
+    pub async fn check(&self) -> std::io::Result<usize> {
+        self.check_sparing(None).await
+    }
+
+    /// `check` on behalf of a publish of `tracking_id` that ran out of
+    /// space; its own directory, locked by the publish, is spared
+    pub async fn check_for(&self, tracking_id: &str) -> std::io::Result<usize> {
+        self.check_sparing(Some(tracking_id)).await
+    }
+
+    async fn check_sparing(&self, spared: Option<&str>) -> std::io::Result<usize> {
+        let (mut evicted, mut failed) = (0, None);
+        // A failing disk doesn't hold up eviction on the others
+        for paths in self.device_groups() {
+            match self.check_device(&paths, spared).await {
+                Ok(count) => evicted += count,
+                Err(e) => {
+                    let e = std::io::Error::new(e.kind(), format!("{}: {}", paths[0].display(), e));
+                    failed = failed.or(Some(e));
+                }
+            }
+        }
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(evicted),
+        }
+    }
+
+    /// Evict from `paths`, base paths on one device, if it is above the
+    /// threshold
+    async fn check_device(
+        &self,
+        paths: &[PathBuf],
+        spared: Option<&str>,
+    ) -> std::io::Result<usize> {
+        let device_path = &paths[0];
+        let mut percent = self.usage_percent(device_path).await?;
+        if percent < f64::from(self.threshold_percent) {
+            debug!(path = %device_path.display(), percent = percent, "Base path below eviction threshold");
+            return Ok(0);
+        }
+        warn!(
+            path = %device_path.display(),
+            percent = format!("{:.1}", percent),
+            threshold_percent = self.threshold_percent,
+            low_watermark_percent = self.low_watermark_percent,
+            "Base path above eviction threshold, evicting unused volumes"
+        );
+
+        let priorities = match &self.cleanup_ctx {
+            Some(ctx) => cleanup::eviction_priorities(&ctx.client, &ctx.namespace)
+                .await
+                .unwrap_or_else(|e| {
+                    // Disk pressure doesn't wait for the API server
+                    warn!(error = %e, "Failed to get eviction priorities, using the default");
+                    HashMap::new()
+                }),
+            None => HashMap::new(),
+        };
+        let base_paths = paths.to_vec();
+        let candidates = tokio::task::spawn_blocking(move || {
+            let mut candidates = Vec::new();
+            for base_path in &base_paths {
+                candidates.extend(eviction_candidates(base_path, &priorities)?);
+            }
+            Ok::<_, std::io::Error>(candidates)
+        })
+        .await
+        .map_err(std::io::Error::other)??;
+        let mut in_use = self.volumes_in_use()?;
+        in_use.extend(spared.map(String::from));
+        let mut evicted = 0;
+        for candidate in eviction_order(candidates, &in_use) {
+            if percent < f64::from(self.low_watermark_percent) {
+                break;
+            }
+            match self.evict(&candidate, percent).await {
+                Ok(true) => evicted += 1,
+                Ok(false) => {}
+                Err(e) => error!(
+                    volume_id = %candidate.tracking_id,
+                    error = %e,
+                    "Failed to evict volume directory"
+                ),
+            }
+            percent = self.usage_percent(device_path).await?;
+        }
+        if percent >= f64::from(self.low_watermark_percent) {
+            warn!(
+                path = %device_path.display(),
+                percent = format!("{:.1}", percent),
+                evicted = evicted,
+                "Base path still above eviction low watermark, no unused volumes left"
+            );
+        }
+        Ok(evicted)
+    }
+
+    /// Delete `candidate`'s directory, unless it is in use by now. Returns
+    /// whether it was deleted.
+    async fn evict(&self, candidate: &EvictionCandidate, percent: f64) -> std::io::Result<bool> {
+        let volume_id = &candidate.tracking_id;
+        // Re-check under the lock: a publish may have just mounted it
+        let _guard = self.volume_locks.lock(volume_id).await;
+        let path = volume::volume_path(&candidate.base_path, volume_id);
+        let mounted = volume::is_mounted(&path)
+            .map_err(|status| std::io::Error::other(status.message().to_string()))?;
+        if mounted || self.volumes_in_use()?.contains(volume_id) {
+            debug!(volume_id = %volume_id, "Volume in use, not evicting it");
+            return Ok(false);
+        }
+
+        let (base_path, backend, quota) = (
+            candidate.base_path.clone(),
+            self.directory_backend,
+            self.quota_backend,
+        );
+        let remove_path = path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            quota.release(&base_path, &remove_path)?;
+            backend.remove(&remove_path)
+        })
+        .await
+        .map_err(std::io::Error::other)?;
+        history::history().record("evict", volume_id, &result);
+        result?;
+
+        let idle = SystemTime::now()
+            .duration_since(candidate.last_used)
+            .unwrap_or_default();
+        warn!(
+            volume_id = %volume_id,
+            path = %path.display(),
+            idle_secs = idle.as_secs(),
+            priority = candidate.priority,
+            "Evicted volume directory under disk pressure"
+        );
+        if let Some(ctx) = &self.cleanup_ctx {
+            cleanup::emit_event(
+                &ctx.client,
+                &ctx.namespace,
+                volume_id,
+                None,
+                "VolumeEvicted",
+                &format!(
+                    "Volume directory deleted on node {}: {} is {:.1}% full \
+                     (eviction threshold {}%), and the volume was unused for {}s",
+                    self.node_name,
+                    candidate.base_path.display(),
+                    percent,
+                    self.threshold_percent,
+                    idle.as_secs()
+                ),
+                "Warning",
+            )
+            .await;
+        }
+        Ok(true)
+    }
+
+    /// Run the eviction loop
+    pub async fn run(&self, interval: Duration) {
+        info!(
+            path = %self.base_path.display(),
+            allowed_base_paths = ?self.allowed_base_paths,
+            threshold_percent = self.threshold_percent,
+            low_watermark_percent = self.low_watermark_percent,
+            "Starting disk pressure eviction"
+        );
+
+        loop {
+            match self.check().await {
+                Ok(count) if count > 0 => info!(count = count, "Evicted volume directories"),
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "Failed to check for disk pressure eviction"),
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_target(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("nlc-node-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_remove_created_empty_target() {
+        let node = NodeService::new("node1".into(), PathBuf::from("/unused"))
+            .with_remove_target_on_unpublish(true);
+        let target = temp_target("empty");
+        node.created_targets.lock().unwrap().insert(target.clone());
+
+        assert!(node.remove_created_target(&target));
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_keep_non_empty_target() {
+        let node = NodeService::new("node1".into(), PathBuf::from("/unused"))
+            .with_remove_target_on_unpublish(true);
+        let target = temp_target("non-empty");
+        std::fs::write(target.join("leftover"), b"data").unwrap();
+        node.created_targets.lock().unwrap().insert(target.clone());
+
+        assert!(!node.remove_created_target(&target));
+        assert!(target.join("leftover").exists());
+        let _ = std::fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn test_keep_target_not_created_by_us() {
+        let node = NodeService::new("node1".into(), PathBuf::from("/unused"))
+            .with_remove_target_on_unpublish(true);
+        let target = temp_target("foreign");
+
+        assert!(!node.remove_created_target(&target));
+        assert!(target.exists());
+        let _ = std::fs::remove_dir_all(target);
+    }
+
+    #[test]
+    fn test_directory_creation_error() {
+        let base = Path::new("/var/node-local-cache");
+        let enospc = std::io::Error::from_raw_os_error(nix::libc::ENOSPC);
+        let status = directory_creation_error(base, &enospc);
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.message().contains("/var/node-local-cache"));
+
+        let edquot = std::io::Error::from_raw_os_error(nix::libc::EDQUOT);
+        assert_eq!(
+            directory_creation_error(base, &edquot).code(),
+            tonic::Code::ResourceExhausted
+        );
+
+        let eacces = std::io::Error::from_raw_os_error(nix::libc::EACCES);
+        assert_eq!(
+            directory_creation_error(base, &eacces).code(),
+            tonic::Code::Internal
+        );
+    }
+
+    #[test]
+    fn test_readonly_subpaths() {
+        if !nix::unistd::geteuid().is_root() {
+            // Skip test unless running as root
+            return;
+        }
+        let base = temp_target("subpaths");
+        let (source, target) = (base.join("source"), base.join("target"));
+        std::fs::create_dir_all(source.join("seed")).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        let bind = nix::mount::mount(
+            Some(&source),
+            &target,
+            None::<&str>,
+            nix::mount::MsFlags::MS_BIND,
+            None::<&str>,
+        );
+        if bind.is_err() {
+            // No mount privileges (e.g. unprivileged container)
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+
+        let subpaths = volume::parse_readonly_subpaths("seed,models/base").unwrap();
+        mount_readonly_subpaths(&source, &target, &subpaths, nix::mount::MsFlags::empty()).unwrap();
+        assert!(std::fs::write(target.join("seed/file"), b"x").is_err());
+        assert!(std::fs::write(target.join("models/base/file"), b"x").is_err());
+        std::fs::write(target.join("models/file"), b"x").unwrap();
+        assert_eq!(volume::submounts(&target).unwrap().len(), 2);
+
+        unmount_submounts(&target).unwrap();
+        assert!(volume::submounts(&target).unwrap().is_empty());
+        std::fs::write(target.join("seed/file"), b"x").unwrap();
+        nix::mount::umount(&target).unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_verify_base_device() {
+        let base = temp_target("base-device");
+        std::fs::create_dir_all(base.join("nlc-vol")).unwrap();
+        let device = volume::device_id(&base).unwrap();
+        assert_eq!(volume::device_id(&base.join("nlc-vol")).unwrap(), device);
+
+        let node = NodeService::new("node1".into(), base.clone());
+        // Not verified unless enabled
+        assert!(node.verify_base_device(Path::new("/nonexistent")).is_ok());
+
+        let node = node.with_base_device(Some(device));
+        assert!(node.verify_base_device(&base).is_ok());
+        assert!(node.verify_base_device(&base.join("nlc-vol")).is_ok());
+
+        // The base path's device changed since startup
+        let node = node.with_base_device(Some(device + 1));
+        let err = node.verify_base_device(&base).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_mount_rpcs_wait_for_serving() {
+        let (serving_tx, serving) = watch::channel(false);
+        let node = NodeService::new("node1".into(), PathBuf::from("/unused")).with_serving(serving);
+
+        let err = node
+            .unpublish_volume(NodeUnpublishVolumeRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        serving_tx.send_replace(true);
+        // Served now: nothing is mounted at the (empty) target
+        assert!(node
+            .unpublish_volume(NodeUnpublishVolumeRequest::default())
+            .await
+            .is_ok());
+    }
+
+    async fn advertises_staging(node: &NodeService) -> bool {
+        let capabilities = node
+            .node_get_capabilities(Request::new(NodeGetCapabilitiesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .capabilities;
+        capabilities.iter().any(|c| {
+            c.r#type
+                == Some(node_service_capability::Type::Rpc(
+                    node_service_capability::Rpc {
+                        r#type: node_service_capability::rpc::Type::StageUnstageVolume as i32,
+                    },
+                ))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_staging_refcounts() {
+        let dir = temp_target("staging");
+        let staging_path = dir.join("plugins/pv/globalmount");
+        std::fs::create_dir_all(dir.join("plugins")).unwrap();
+        let volume_id = volume::generate_volume_id("pvc-staging");
+        let capability = || {
+            Some(crate::csi::VolumeCapability {
+                access_type: Some(volume_capability::AccessType::Mount(
+                    volume_capability::MountVolume::default(),
+                )),
+                ..Default::default()
+            })
+        };
+        let stage = NodeStageVolumeRequest {
+            volume_id: volume_id.clone(),
+            staging_target_path: staging_path.to_string_lossy().into_owned(),
+            volume_capability: capability(),
+            ..Default::default()
+        };
+        let unstage = NodeUnstageVolumeRequest {
+            volume_id: volume_id.clone(),
+            staging_target_path: staging_path.to_string_lossy().into_owned(),
+        };
+        let publish = |target: &str| NodePublishVolumeRequest {
+            volume_id: volume_id.clone(),
+            staging_target_path: staging_path.to_string_lossy().into_owned(),
+            target_path: dir.join(target).to_string_lossy().into_owned(),
+            volume_capability: capability(),
+            ..Default::default()
+        };
+        let unpublish = |target: &str| NodeUnpublishVolumeRequest {
+            volume_id: volume_id.clone(),
+            target_path: dir.join(target).to_string_lossy().into_owned(),
+        };
+        // Off by default
+        let node = NodeService::new("node1".into(), dir.join("base"));
+        assert!(!advertises_staging(&node).await);
+        let err = node.stage_volume(stage.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unimplemented);
+
+        let node = NodeService::new("node1".into(), dir.join("base"))
+            .with_staging(true)
+            .with_staging_roots(vec![dir.join("plugins")]);
+        assert!(advertises_staging(&node).await);
+        let err = node
+            .stage_volume(NodeStageVolumeRequest {
+                staging_target_path: dir.join("pods/pv/mount").to_string_lossy().into_owned(),
+                ..stage.clone()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        // Not staged yet
+        let err = node.publish_volume(publish("pod-a")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        if let Err(e) = node.stage_volume(stage.clone()).await {
+            // No mount privileges
+            assert_eq!(e.code(), tonic::Code::Internal, "{:?}", e);
+            let _ = std::fs::remove_dir_all(dir);
+            return;
+        }
+        node.publish_volume(publish("pod-a")).await.unwrap();
+        node.publish_volume(publish("pod-b")).await.unwrap();
+        // Repeated publish counts once
+        node.publish_volume(publish("pod-b")).await.unwrap();
+        std::fs::write(dir.join("pod-a/cached"), b"data").unwrap();
+        assert_eq!(std::fs::read(dir.join("pod-b/cached")).unwrap(), b"data");
+
+        let err = node.unstage_volume(unstage.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        // After a restart, the counts are gone but the pods' mounts aren't
+        let restarted = NodeService::new("node1".into(), dir.join("base"))
+            .with_staging(true)
+            .with_staging_roots(vec![dir.join("plugins")]);
+        let err = restarted.unstage_volume(unstage.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        assert!(volume::is_mounted(&staging_path).unwrap());
+        node.unpublish_volume(unpublish("pod-a")).await.unwrap();
+        let err = node.unstage_volume(unstage.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        node.unpublish_volume(unpublish("pod-b")).await.unwrap();
+
+        node.unstage_volume(unstage.clone()).await.unwrap();
+        assert!(!volume::is_mounted(&staging_path).unwrap());
+        // Idempotent
+        node.unstage_volume(unstage).await.unwrap();
+        // The data stays in the volume directory
+        assert!(volume::volume_path(&dir.join("base"), &volume_id)
+            .join("cached")
+            .exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_volume_mount_group() {
+        let node = NodeService::new("node1".into(), PathBuf::from("/unused"));
+        let capabilities = node
+            .node_get_capabilities(Request::new(NodeGetCapabilitiesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .capabilities;
+        assert!(capabilities.iter().any(|c| c.r#type
+            == Some(node_service_capability::Type::Rpc(
+                node_service_capability::Rpc {
+                    r#type: node_service_capability::rpc::Type::VolumeMountGroup as i32,
+                }
+            ))));
+
+        // A malformed group is rejected before anything is created
+        let req = NodePublishVolumeRequest {
+            volume_id: volume::generate_volume_id("pvc-mount-group"),
+            target_path: "/unused/target".into(),
+            volume_capability: Some(crate::csi::VolumeCapability {
+                access_type: Some(volume_capability::AccessType::Mount(
+                    volume_capability::MountVolume {
+                        volume_mount_group: "staff".into(),
+                        ..Default::default()
+                    },
+                )),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let err = node.publish_volume(req).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_unsafe_target() {
+        let dir = temp_target("unsafe");
+        let (base, pods, outside) = (dir.join("base"), dir.join("pods"), dir.join("outside"));
+        std::fs::create_dir_all(pods.join("uid")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, pods.join("uid/volumes")).unwrap();
+        let node = NodeService::new("node1".into(), base.clone()).with_target_roots(vec![pods]);
+        let publish = |target: PathBuf| NodePublishVolumeRequest {
+            volume_id: volume::generate_volume_id("pvc-unsafe"),
+            target_path: target.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        for target in [outside.join("mount"), dir.join("pods/uid/volumes/pv/mount")] {
+            let err = node.publish_volume(publish(target)).await.unwrap_err();
+            assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        }
+        // Refused before anything was created
+        assert!(!base.exists());
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_volume_base_path() {
+        let dir = temp_target("base-path");
+        let (base, nvme, other) = (dir.join("base"), dir.join("nvme"), dir.join("other"));
+        let node = NodeService::new("node1".into(), base.clone())
+            .with_allowed_base_paths(vec![nvme.clone()]);
+
+        assert_eq!(node.volume_base_path(None).unwrap(), &base);
+        assert_eq!(node.volume_base_path(Some(&base)).unwrap(), &base);
+        assert_eq!(node.volume_base_path(Some(&nvme)).unwrap(), &nvme);
+        let err = node.volume_base_path(Some(&other)).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+
+        // Refused before anything was created
+        let err = node
+            .publish_volume(NodePublishVolumeRequest {
+                volume_id: volume::generate_volume_id("pvc-base-path"),
+                target_path: dir.join("target").to_string_lossy().into_owned(),
+                volume_context: HashMap::from([(
+                    volume::BASE_PATH_KEY.to_string(),
+                    other.to_string_lossy().into_owned(),
+                )]),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(!other.exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_clone_from_other_base_path() {
+        let dir = temp_target("clone-base-path");
+        let (base, nvme) = (dir.join("base"), dir.join("nvme"));
+        let node = NodeService::new("node1".into(), base.clone())
+            .with_allowed_base_paths(vec![nvme.clone()]);
+        let source_id = volume::generate_volume_id("pvc-clone-source");
+        let source = volume::volume_path(&nvme, &source_id);
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("data"), b"warm").unwrap();
+
+        assert_eq!(node.existing_volume_path(&source_id), Some(source));
+        assert_eq!(node.existing_volume_path("nlc-missing"), None);
+
+        // The clone lives under the node's base path, its source doesn't
+        let volume_id = volume::generate_volume_id("pvc-clone");
+        let target = dir.join("target");
+        let result = node
+            .publish_volume(NodePublishVolumeRequest {
+                volume_id: volume_id.clone(),
+                target_path: target.to_string_lossy().into_owned(),
+                volume_context: HashMap::from([(
+                    volume::CLONE_FROM_KEY.to_string(),
+                    source_id.clone(),
+                )]),
+                ..Default::default()
+            })
+            .await;
+        if let Err(e) = result {
+            // No mount privileges
+            assert_eq!(e.code(), tonic::Code::Internal, "{:?}", e);
+            let _ = std::fs::remove_dir_all(dir);
+            return;
+        }
+        assert_eq!(std::fs::read(target.join("data")).unwrap(), b"warm");
+        volume::unmount(&target).unwrap();
+        assert_eq!(
+            std::fs::read(volume::volume_path(&base, &volume_id).join("data")).unwrap(),
+            b"warm"
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_republish_tmpfs_with_base_device() {
+        let base = temp_target("tmpfs-base-device");
+        let node = NodeService::new("node1".into(), base.clone())
+            .with_base_device(Some(volume::device_id(&base).unwrap()));
+        let volume_id = volume::generate_volume_id("pvc-tmpfs-device");
+        let source = volume::volume_path(&base, &volume_id);
+        let publish = |target: PathBuf| {
+            node.publish_volume(NodePublishVolumeRequest {
+                volume_id: volume_id.clone(),
+                target_path: target.to_string_lossy().into_owned(),
+                volume_context: HashMap::from([
+                    (volume::BACKING_KEY.to_string(), "tmpfs".to_string()),
+                    (volume::CAPACITY_KEY.to_string(), (1u64 << 20).to_string()),
+                ]),
+                ..Default::default()
+            })
+        };
+
+        let (first, second) = (base.join("target1"), base.join("target2"));
+        if let Err(e) = publish(first.clone()).await {
+            // No mount privileges
+            assert_eq!(e.code(), tonic::Code::Internal, "{:?}", e);
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        // The tmpfs is on a device of its own by now
+        assert_ne!(
+            volume::device_id(&source).unwrap(),
+            volume::device_id(&base).unwrap()
+        );
+        let result = publish(second.clone()).await;
+        volume::unmount(&first).unwrap();
+        if result.is_ok() {
+            volume::unmount(&second).unwrap();
+        }
+        tmpfs::release(&source).unwrap();
+        result.unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_republish_loopback_with_base_device() {
+        if crate::loopback::check_supported().is_err() || !nix::unistd::geteuid().is_root() {
+            // Needs root with loop devices and mkfs.ext4
+            return;
+        }
+        let base = temp_target("loopback-base-device");
+        let node = NodeService::new("node1".into(), base.clone())
+            .with_quota_backend(QuotaBackend::Loopback)
+            .with_base_device(Some(volume::device_id(&base).unwrap()));
+        let volume_id = volume::generate_volume_id("pvc-loopback-device");
+        let source = volume::volume_path(&base, &volume_id);
+        let publish = |target: PathBuf| {
+            node.publish_volume(NodePublishVolumeRequest {
+                volume_id: volume_id.clone(),
+                target_path: target.to_string_lossy().into_owned(),
+                volume_context: HashMap::from([(
+                    volume::CAPACITY_KEY.to_string(),
+                    crate::loopback::MIN_IMAGE_SIZE.to_string(),
+                )]),
+                ..Default::default()
+            })
+        };
+
+        let (first, second) = (base.join("target1"), base.join("target2"));
+        if let Err(e) = publish(first.clone()).await {
+            // e.g. a sandbox with /dev/loop-control but no usable devices
+            eprintln!("skipping: {:?}", e);
+            let _ = crate::loopback::release(&base, &source);
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        // The image is on a device of its own by now
+        assert_ne!(
+            volume::device_id(&source).unwrap(),
+            volume::device_id(&base).unwrap()
+        );
+        let result = publish(second.clone()).await;
+        volume::unmount(&first).unwrap();
+        if result.is_ok() {
+            volume::unmount(&second).unwrap();
+        }
+        crate::loopback::release(&base, &source).unwrap();
+        result.unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_volume_stats() {
+        let base = temp_target("stats");
+        let volume_id = volume::generate_volume_id("pvc-stats");
+        let source = volume::volume_path(&base, &volume_id);
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a"), vec![1u8; 64 * 1024]).unwrap();
+        std::fs::write(source.join("b"), vec![1u8; 64 * 1024]).unwrap();
+        let target = base.join("target");
+        std::fs::create_dir_all(&target).unwrap();
+        let cache = SizeCache::new(base.clone(), Duration::from_secs(600));
+        cache.refresh(Instant::now()).unwrap();
+        let node = NodeService::new("node1".into(), base.clone()).with_size_cache(Some(cache));
+        let stats = |volume_id: &str, volume_path: &Path| {
+            node.node_get_volume_stats(Request::new(NodeGetVolumeStatsRequest {
+                volume_id: volume_id.to_string(),
+                volume_path: volume_path.display().to_string(),
+                staging_target_path: String::new(),
+            }))
+        };
+
+        let err = stats("../etc", &target).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = stats(&volume_id, &base.join("missing")).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+        // Exists, but nothing mounted there
+        let err = stats(&volume_id, &target).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        let mounted = nix::mount::mount(
+            Some(&source),
+            &target,
+            None::<&str>,
+            nix::mount::MsFlags::MS_BIND,
+            None::<&str>,
+        );
+        if mounted.is_err() {
+            // Not root
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        let usage = stats(&volume_id, &target).await.map(Response::into_inner);
+        volume::unmount(&target).unwrap();
+        let usage = usage.unwrap().usage;
+
+        let bytes = usage
+            .iter()
+            .find(|u| u.unit == volume_usage::Unit::Bytes as i32)
+            .unwrap();
+        assert!(bytes.used >= 2 * 64 * 1024, "{:?}", bytes);
+        assert!(bytes.used < 4 * 64 * 1024, "{:?}", bytes);
+        assert!(bytes.total > 0);
+        let inodes = usage
+            .iter()
+            .find(|u| u.unit == volume_usage::Unit::Inodes as i32)
+            .unwrap();
+        // The directory and its two files
+        assert_eq!(inodes.used, 3);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_bind_mount_flags() {
+        use nix::mount::MsFlags;
+        use nix::sys::statvfs::{statvfs, FsFlags};
+
+        let base = temp_target("mount-flags");
+        let source = base.join("source");
+        let target = base.join("target");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        let node = NodeService::new("node1".into(), base.clone());
+
+        let flags = MsFlags::MS_NOEXEC | MsFlags::MS_NODEV;
+        if node
+            .bind_mount("nlc-a", None, &source, &target, flags)
+            .await
+            .is_err()
+        {
+            // Not root
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        let mounted = statvfs(&target).unwrap().flags();
+        volume::unmount(&target).unwrap();
+        assert!(mounted.contains(FsFlags::ST_NOEXEC | FsFlags::ST_NODEV));
+        assert!(!mounted.contains(FsFlags::ST_NOSUID));
+        assert!(!mounted.contains(FsFlags::ST_RDONLY));
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_expand_volume() {
+        let base = temp_target("expand");
+        let node = NodeService::new("node1".into(), base.clone());
+        let capabilities = node
+            .node_get_capabilities(Request::new(NodeGetCapabilitiesRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+            .capabilities;
+        assert!(capabilities.iter().any(|c| c.r#type
+            == Some(node_service_capability::Type::Rpc(
+                node_service_capability::Rpc {
+                    r#type: node_service_capability::rpc::Type::ExpandVolume as i32,
+                }
+            ))));
+
+        let volume_id = volume::generate_volume_id("pvc-expand");
+        let source = volume::volume_path(&base, &volume_id);
+        let target = base.join("target");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        let expand = |required_bytes| {
+            node.node_expand_volume(Request::new(NodeExpandVolumeRequest {
+                volume_id: volume_id.clone(),
+                volume_path: target.display().to_string(),
+                capacity_range: Some(crate::csi::CapacityRange {
+                    required_bytes,
+                    limit_bytes: 0,
+                }),
+                ..Default::default()
+            }))
+        };
+
+        // Nothing mounted there
+        let err = expand(2 << 20).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::NotFound);
+
+        if tmpfs::ensure_mounted(&source, 1 << 20).is_err() {
+            // Not root
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        node.bind_mount(
+            &volume_id,
+            None,
+            &source,
+            &target,
+            nix::mount::MsFlags::empty(),
+        )
+        .await
+        .unwrap();
+        let expanded = expand(4 << 20).await.map(Response::into_inner);
+        let size = quota::Usage::from_statvfs(&target).map(|usage| usage.total_bytes);
+        volume::unmount(&target).unwrap();
+        tmpfs::release(&source).unwrap();
+        assert_eq!(expanded.unwrap().capacity_bytes, 4 << 20);
+        assert_eq!(size.unwrap(), 4 << 20);
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_check_fs_type() {
+        let base = temp_target("fs-type");
+        let actual = volume::filesystem_type(&base).unwrap().unwrap();
+        let node = NodeService::new("node1".into(), base.clone());
+
+        assert!(node.check_fs_type("nlc-a", "", &base).is_ok());
+        assert!(node.check_fs_type("nlc-a", &actual, &base).is_ok());
+        // Warn-only by default
+        assert!(node.check_fs_type("nlc-a", "bogusfs", &base).is_ok());
+
+        let node = node.with_strict_fs_type(true);
+        let err = node.check_fs_type("nlc-a", "bogusfs", &base).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        assert!(node.check_fs_type("nlc-a", &actual, &base).is_ok());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_eviction_order() {
+        let epoch = SystemTime::UNIX_EPOCH;
+        let candidate = |id: &str, secs, priority| EvictionCandidate {
+            tracking_id: id.to_string(),
+            base_path: PathBuf::from("/var/node-local-cache"),
+            last_used: epoch + Duration::from_secs(secs),
+            priority,
+        };
+        let default = volume::DEFAULT_EVICTION_PRIORITY;
+        let listing = vec![
+            candidate("nlc-recent", 300, default),
+            candidate("nlc-mounted", 100, default),
+            candidate("nlc-b", 200, default),
+            candidate("nlc-a", 200, default),
+            candidate("shared-models", 50, default),
+        ];
+        let in_use = HashSet::from(["nlc-mounted".to_string()]);
+
+        let order = |listing| -> Vec<String> {
+            eviction_order(listing, &in_use)
+                .into_iter()
+                .map(|c| c.tracking_id)
+                .collect()
+        };
+        assert_eq!(
+            order(listing),
+            ["shared-models", "nlc-a", "nlc-b", "nlc-recent"]
+        );
+        assert!(order(Vec::new()).is_empty());
+
+        // Lower priorities go first, however recently used
+        let listing = vec![
+            candidate("nlc-expensive", 10, 90),
+            candidate("nlc-scratch-new", 400, 0),
+            candidate("nlc-old", 100, default),
+            candidate("nlc-scratch-old", 200, 0),
+            candidate("nlc-new", 300, default),
+            candidate("nlc-mounted", 0, 0),
+            candidate("nlc-pinned", 5, 100),
+        ];
+        assert_eq!(
+            order(listing),
+            [
+                "nlc-scratch-old",
+                "nlc-scratch-new",
+                "nlc-old",
+                "nlc-new",
+                "nlc-expensive",
+                "nlc-pinned"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_evict_when_full_on_create() {
+        let dir = temp_target("evict-on-create");
+        let (base, target) = (dir.join("base"), dir.join("target"));
+        std::fs::create_dir_all(&base).unwrap();
+        // Room for the root, an idle volume and its two files, nothing more
+        let mounted = nix::unistd::geteuid().is_root()
+            && nix::mount::mount(
+                Some("tmpfs"),
+                &base,
+                Some("tmpfs"),
+                nix::mount::MsFlags::empty(),
+                Some("nr_inodes=4"),
+            )
+            .is_ok();
+        if !mounted {
+            let _ = std::fs::remove_dir_all(dir);
+            return;
+        }
+        let idle = volume::volume_path(&base, &volume::generate_volume_id("pvc-idle"));
+        std::fs::create_dir(&idle).unwrap();
+        std::fs::write(idle.join("a"), b"").unwrap();
+        std::fs::write(idle.join("b"), b"").unwrap();
+        let publish = NodePublishVolumeRequest {
+            volume_id: volume::generate_volume_id("pvc-new"),
+            target_path: target.to_string_lossy().into_owned(),
+            ..Default::default()
+        };
+
+        // Without eviction, a capacity problem
+        let node = NodeService::new("node1".into(), base.clone());
+        let err = node.publish_volume(publish.clone()).await.unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted, "{:?}", err);
+        assert!(idle.exists());
+
+        // With it, the idle volume makes room
+        let eviction = EvictionManager::new("node1".into(), base.clone(), 0, 0);
+        let node = node.with_eviction(Some(Arc::new(eviction)));
+        let result = node.publish_volume(publish).await;
+        let published = volume::is_mounted(&target).unwrap();
+        if published {
+            volume::unmount(&target).unwrap();
+        }
+        nix::mount::umount(&base).unwrap();
+        result.unwrap();
+        assert!(published);
+        assert!(!idle.exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_across_base_paths() {
+        let dir = temp_target("eviction-base-paths");
+        let (base, nvme, tmp, missing) = (
+            dir.join("base"),
+            dir.join("nvme"),
+            dir.join("tmp"),
+            dir.join("missing"),
+        );
+        let volumes = [(&base, "pvc-on-base"), (&nvme, "pvc-on-nvme")].map(|(base_path, name)| {
+            volume::volume_path(base_path, &volume::generate_volume_id(name))
+        });
+        for path in &volumes {
+            std::fs::create_dir_all(path).unwrap();
+            std::fs::write(path.join("cached"), b"data").unwrap();
+        }
+        std::fs::create_dir_all(&tmp).unwrap();
+        // Another device
+        let mounted = nix::unistd::geteuid().is_root()
+            && nix::mount::mount(
+                Some("tmpfs"),
+                &tmp,
+                Some("tmpfs"),
+                nix::mount::MsFlags::empty(),
+                None::<&str>,
+            )
+            .is_ok();
+
+        let manager = EvictionManager::new("node1".into(), base.clone(), 0, 0)
+            .with_allowed_base_paths(vec![nvme.clone(), tmp.clone(), missing]);
+        let groups = manager.device_groups();
+        if mounted {
+            assert_eq!(
+                groups,
+                vec![vec![base.clone(), nvme.clone()], vec![tmp.clone()]]
+            );
+        } else {
+            assert_eq!(groups, vec![vec![base.clone(), nvme.clone(), tmp.clone()]]);
+        }
+
+        // Volumes under an allowed base path are evicted too
+        assert_eq!(manager.check().await.unwrap(), 2);
+        assert!(volumes.iter().all(|path| !path.exists()));
+
+        if mounted {
+            nix::mount::umount(&tmp).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_eviction_with_api() {
+        let api = crate::fake_api::FakeApiServer::new(&["node1"]);
+        let base = temp_target("eviction");
+        let (idle, busy) = (
+            volume::generate_volume_id("pvc-idle"),
+            volume::generate_volume_id("pvc-busy"),
+        );
+        for volume_id in [&idle, &busy] {
+            let path = volume::volume_path(&base, volume_id);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("cached"), b"data").unwrap();
+        }
+        // Only the idle volume asked for a priority
+        cleanup::register_node_publish(
+            &api.client(),
+            "default",
+            "node1",
+            &PendingRegistration {
+                eviction_priority: Some(90),
+                ..PendingRegistration::new(&idle)
+            },
+        )
+        .await
+        .unwrap();
+        let priorities = cleanup::eviction_priorities(&api.client(), "default")
+            .await
+            .unwrap();
+        assert_eq!(priorities, HashMap::from([(idle.clone(), 90)]));
+        let mut candidates = eviction_candidates(&base, &priorities).unwrap();
+        candidates.sort_by_key(|c| c.priority);
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|c| (c.tracking_id.as_str(), c.priority))
+                .collect::<Vec<_>>(),
+            [
+                (busy.as_str(), volume::DEFAULT_EVICTION_PRIORITY),
+                (idle.as_str(), 90)
+            ]
+        );
+
+        let manager = |threshold| {
+            EvictionManager::new("node1".into(), base.clone(), threshold, 0)
+                .with_cleanup(api.client(), "default".into())
+        };
+        // Below the threshold
+        assert_eq!(manager(100).check().await.unwrap(), 0);
+        assert!(volume::volume_path(&base, &idle).exists());
+
+        // A tmpfs backing makes the directory a mount point
+        let busy_path = volume::volume_path(&base, &busy);
+        let mounted = nix::unistd::geteuid().is_root()
+            && nix::mount::mount(
+                Some("tmpfs"),
+                &busy_path,
+                Some("tmpfs"),
+                nix::mount::MsFlags::empty(),
+                None::<&str>,
+            )
+            .is_ok();
+
+        // With a low watermark of 0%, all unused volumes go
+        let evicted = manager(0).check().await.unwrap();
+        assert!(!volume::volume_path(&base, &idle).exists());
+        assert_eq!(busy_path.exists(), mounted);
+        assert_eq!(evicted, if mounted { 1 } else { 2 });
+        assert_eq!(api.event_reasons(), vec!["VolumeEvicted"; evicted]);
+
+        if mounted {
+            nix::mount::umount(&busy_path).unwrap();
+        }
+        let _ = std::fs::remove_dir_all(base);
     }
 }
@@ -0,0 +1,154 @@
+//! Node singleton lock (`--node-singleton-lock`).
+//!
+//! During a DaemonSet rollover two node plugin pods can briefly run on the
+//! same node. With the singleton lock, each takes an exclusive `flock` on a
+//! file in the base path before it mounts, unmounts or cleans anything; the
+//! newcomer waits until the old instance exits. A waiting instance keeps
+//! answering health checks (Probe, `/healthz`) but rejects NodePublishVolume
+//! and NodeUnpublishVolume with UNAVAILABLE, which kubelet retries.
+//!
+//! The lock is released by the kernel when the process exits, so a crashed
+//! instance never leaves it stale.
+
+use std::fs::File;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nix::errno::Errno;
+use nix::fcntl::{Flock, FlockArg};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Lock file, in the base path
+pub const LOCK_FILE: &str = ".node-plugin.lock";
+
+/// How often a waiting instance retries the lock
+pub const LOCK_RETRY_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Exclusive lock on a node's base path, held until dropped
+pub struct NodeLock {
+    _lock: Flock<File>,
+}
+
+impl NodeLock {
+    /// Take the lock without waiting. Returns `None` if another process holds it.
+    pub fn try_acquire(base_path: &Path) -> std::io::Result<Option<Self>> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lock_path(base_path))?;
+        match Flock::lock(file, FlockArg::LockExclusiveNonblock) {
+            Ok(lock) => Ok(Some(Self { _lock: lock })),
+            Err((_, Errno::EWOULDBLOCK)) => Ok(None),
+            Err((_, errno)) => Err(errno.into()),
+        }
+    }
+
+    /// Wait until the lock is ours, polling every `retry`
+    pub async fn acquire(base_path: PathBuf, retry: Duration) -> Self {
+        let mut waiting = false;
+        loop {
+            let path = base_path.clone();
+            let attempt = tokio::task::spawn_blocking(move || Self::try_acquire(&path))
+                .await
+                .map_err(std::io::Error::other)
+                .and_then(|r| r);
+            match attempt {
+                Ok(Some(lock)) => {
+                    info!(path = %lock_path(&base_path).display(), "Acquired node singleton lock");
+                    return lock;
+                }
+                Ok(None) if !waiting => {
+                    info!(
+                        path = %lock_path(&base_path).display(),
+                        "Another node plugin instance holds the node lock, waiting"
+                    );
+                    waiting = true;
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        path = %lock_path(&base_path).display(),
+                        error = %e,
+                        "Failed to take node singleton lock"
+                    );
+                }
+            }
+            tokio::time::sleep(retry).await;
+        }
+    }
+}
+
+fn lock_path(base_path: &Path) -> PathBuf {
+    base_path.join(LOCK_FILE)
+}
+
+/// Run `task` once this instance is serving
+pub async fn when_serving<F: Future<Output = ()>>(mut serving: watch::Receiver<bool>, task: F) {
+    if serving.wait_for(|serving| *serving).await.is_ok() {
+        task.await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_base(name: &str) -> PathBuf {
+        let base =
+            std::env::temp_dir().join(format!("nlc-node-lock-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let base = temp_base("exclusive");
+        let lock = NodeLock::try_acquire(&base).unwrap().unwrap();
+        // flock locks belong to the open file, so a second open conflicts
+        // even within one process
+        assert!(NodeLock::try_acquire(&base).unwrap().is_none());
+
+        drop(lock);
+        assert!(NodeLock::try_acquire(&base).unwrap().is_some());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_waiting_instance_takes_over() {
+        let base = temp_base("takeover");
+        let (serving_tx, serving) = watch::channel(false);
+        let held = NodeLock::try_acquire(&base).unwrap().unwrap();
+
+        let waiter = {
+            let base = base.clone();
+            tokio::spawn(async move {
+                let lock = NodeLock::acquire(base, Duration::from_millis(10)).await;
+                serving_tx.send_replace(true);
+                lock
+            })
+        };
+        let (ran_tx, mut ran) = tokio::sync::oneshot::channel();
+        tokio::spawn(when_serving(serving, async move {
+            let _ = ran_tx.send(());
+        }));
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        assert!(ran.try_recv().is_err());
+
+        drop(held);
+        let _lock = tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+        tokio::time::timeout(Duration::from_secs(5), ran)
+            .await
+            .unwrap()
+            .unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,171 @@
+//! Owner and permissions of a volume directory.
+//!
+//! Volume directories are created by the node plugin, so they belong to root
+//! with mode 0755, and pods running as another user can't write to them. The
+//! `uid`, `gid` and `mode` StorageClass parameters, passed on in the volume
+//! context, set the directory's owner, group and permissions on every publish
+//! (only the directory itself: its content belongs to whoever wrote it).
+//! Without them the directory is left as it is.
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+
+use nix::unistd::{Gid, Uid};
+
+use crate::volume;
+
+/// Owner, group and permissions asked for in the volume context
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Ownership {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub mode: Option<u32>,
+}
+
+/// Parse a numeric user or group ID
+pub fn parse_id(key: &str, value: &str) -> Result<u32, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid {} `{}` (expected a numeric ID)", key, value))
+}
+
+/// Parse permissions given as an octal string, e.g. `0775` or `2770`
+pub fn parse_mode(value: &str) -> Result<u32, String> {
+    match u32::from_str_radix(value.trim(), 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(format!(
+            "Invalid {} `{}` (expected octal permissions, e.g. 0775)",
+            volume::MODE_KEY,
+            value
+        )),
+    }
+}
+
+impl Ownership {
+    /// The ownership from the volume context; empty when not given
+    pub fn from_volume_context(context: &HashMap<String, String>) -> Result<Self, String> {
+        Ok(Self {
+            uid: context
+                .get(volume::UID_KEY)
+                .map(|value| parse_id(volume::UID_KEY, value))
+                .transpose()?,
+            gid: context
+                .get(volume::GID_KEY)
+                .map(|value| parse_id(volume::GID_KEY, value))
+                .transpose()?,
+            mode: context
+                .get(volume::MODE_KEY)
+                .map(|value| parse_mode(value))
+                .transpose()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.uid.is_none() && self.gid.is_none() && self.mode.is_none()
+    }
+
+    /// Give `dir` the owner, group and permissions, where they differ.
+    /// Returns whether anything changed.
+    pub fn apply(&self, dir: &Path) -> io::Result<bool> {
+        let metadata = std::fs::metadata(dir)?;
+        let uid = self.uid.filter(|&uid| uid != metadata.uid());
+        let gid = self.gid.filter(|&gid| gid != metadata.gid());
+        let mut changed = false;
+        if uid.is_some() || gid.is_some() {
+            nix::unistd::chown(dir, uid.map(Uid::from_raw), gid.map(Gid::from_raw))?;
+            changed = true;
+        }
+        // After chown, which clears the setuid/setgid bits
+        if let Some(mode) = self.mode {
+            if changed || metadata.mode() & 0o7777 != mode {
+                std::fs::set_permissions(dir, std::fs::Permissions::from_mode(mode))?;
+                changed = true;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(parse_mode("0775"), Ok(0o775));
+        assert_eq!(parse_mode("775"), Ok(0o775));
+        assert_eq!(parse_mode("2770"), Ok(0o2770));
+        assert_eq!(parse_mode("0"), Ok(0));
+        assert!(parse_mode("0o775").is_err());
+        assert!(parse_mode("789").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert!(parse_mode("").is_err());
+    }
+
+    #[test]
+    fn test_ownership_from_volume_context() {
+        let context = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let ownership = Ownership::from_volume_context(&HashMap::new()).unwrap();
+        assert!(ownership.is_empty());
+
+        let ownership = Ownership::from_volume_context(&context(&[
+            (volume::UID_KEY, "1000"),
+            (volume::GID_KEY, "2000"),
+            (volume::MODE_KEY, "0770"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            ownership,
+            Ownership {
+                uid: Some(1000),
+                gid: Some(2000),
+                mode: Some(0o770),
+            }
+        );
+
+        let ownership =
+            Ownership::from_volume_context(&context(&[(volume::GID_KEY, "2000")])).unwrap();
+        assert_eq!(ownership.uid, None);
+        assert_eq!(ownership.gid, Some(2000));
+
+        for pairs in [
+            [(volume::UID_KEY, "nobody")],
+            [(volume::GID_KEY, "-1")],
+            [(volume::MODE_KEY, "rwxr-x---")],
+        ] {
+            let err = Ownership::from_volume_context(&context(&pairs)).unwrap_err();
+            assert!(err.contains(pairs[0].0), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_apply_ownership() {
+        let dir = std::env::temp_dir().join(format!("nlc-ownership-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert!(!Ownership::default().apply(&dir).unwrap());
+
+        // Our own IDs, so this works without privileges
+        let ownership = Ownership {
+            uid: Some(nix::unistd::geteuid().as_raw()),
+            gid: Some(nix::unistd::getegid().as_raw()),
+            mode: Some(0o2770),
+        };
+        assert!(ownership.apply(&dir).unwrap());
+        assert_eq!(std::fs::metadata(&dir).unwrap().mode() & 0o7777, 0o2770);
+        // Already matches
+        assert!(!ownership.apply(&dir).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,234 @@
+//! Publish registrations waiting for the API server.
+//!
+//! NodePublishVolume doesn't fail when registering the node on the volume's
+//! tracking ConfigMap fails (e.g. the API server is unreachable), but an
+//! unregistered copy would never be cleaned up. So the registration is
+//! written to `<base>/.pending-registrations/<volume_id>` instead, and the
+//! node cleanup loop retries it until it goes through, then removes the
+//! record. Records survive restarts of the node plugin.
+
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use kube::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::cleanup::{self, PvcRef};
+use crate::volume;
+
+/// Directory under the base path holding the records
+pub const PENDING_DIR: &str = ".pending-registrations";
+
+/// What `cleanup::register_node_publish` registers, and is retried with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingRegistration {
+    pub volume_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shared_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pvc: Option<PvcRef>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    #[serde(default)]
+    pub capacity_bytes: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_path: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eviction_priority: Option<u8>,
+}
+
+impl PendingRegistration {
+    /// A registration of `volume_id` alone
+    pub fn new(volume_id: &str) -> Self {
+        Self {
+            volume_id: volume_id.to_string(),
+            shared_name: None,
+            pvc: None,
+            labels: BTreeMap::new(),
+            capacity_bytes: 0,
+            base_path: None,
+            eviction_priority: None,
+        }
+    }
+
+    /// ID of the directory the volume is published from
+    fn tracking_id(&self) -> String {
+        match &self.shared_name {
+            Some(name) => volume::shared_volume_id(name),
+            None => self.volume_id.clone(),
+        }
+    }
+}
+
+fn record_path(base_path: &Path, volume_id: &str) -> PathBuf {
+    base_path.join(PENDING_DIR).join(volume_id)
+}
+
+/// Persist a registration to retry. A later record for the same volume
+/// replaces the earlier one.
+pub fn record(base_path: &Path, registration: &PendingRegistration) -> io::Result<()> {
+    let path = record_path(base_path, &registration.volume_id);
+    std::fs::create_dir_all(base_path.join(PENDING_DIR))?;
+    let json = serde_json::to_vec(registration).map_err(io::Error::other)?;
+    // Never leave a half-written record behind
+    let tmp = path.with_file_name(format!("{}.tmp", registration.volume_id));
+    std::fs::write(&tmp, json)?;
+    std::fs::rename(&tmp, &path)
+}
+
+/// Drop the record of a volume, if any
+pub fn remove(base_path: &Path, volume_id: &str) -> io::Result<()> {
+    match std::fs::remove_file(record_path(base_path, volume_id)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Registrations waiting to be retried. Unreadable records are skipped.
+pub fn pending(base_path: &Path) -> io::Result<Vec<PendingRegistration>> {
+    let entries = match std::fs::read_dir(base_path.join(PENDING_DIR)) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut registrations = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "tmp") {
+            continue;
+        }
+        let parsed = std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| {
+                serde_json::from_slice::<PendingRegistration>(&data).map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(registration) => registrations.push(registration),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "Skipping unreadable pending registration")
+            }
+        }
+    }
+    registrations.sort_by(|a, b| a.volume_id.cmp(&b.volume_id));
+    Ok(registrations)
+}
+
+/// Retry the pending registrations of `node_name`, removing those that went
+/// through. Records of volumes whose directory is gone are dropped. Stops at
+/// the first API error, as the others would likely fail the same way.
+/// Returns the number registered.
+pub async fn retry(
+    client: &Client,
+    namespace: &str,
+    node_name: &str,
+    base_path: &Path,
+) -> Result<usize, kube::Error> {
+    let registrations = match pending(base_path) {
+        Ok(r) => r,
+        Err(e) => {
+            warn!(error = %e, "Failed to read pending registrations");
+            return Ok(0);
+        }
+    };
+    let mut registered = 0;
+
+    for registration in registrations {
+        let volume_id = &registration.volume_id;
+        let volume_base = registration.base_path.as_deref().unwrap_or(base_path);
+        if !volume::volume_path(volume_base, &registration.tracking_id()).exists() {
+            debug!(volume_id = %volume_id, "Volume directory gone, dropping pending registration");
+        } else {
+            cleanup::register_node_publish(client, namespace, node_name, &registration).await?;
+            info!(volume_id = %volume_id, node = %node_name, "Registered pending publish");
+            registered += 1;
+        }
+        if let Err(e) = remove(base_path, volume_id) {
+            warn!(volume_id = %volume_id, error = %e, "Failed to remove pending registration");
+        }
+    }
+    Ok(registered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup::VolumeStatus;
+    use crate::fake_api::FakeApiServer;
+    use k8s_openapi::api::core::v1::ConfigMap;
+    use kube::Api;
+
+    fn temp_base(name: &str) -> PathBuf {
+        let base = std::env::temp_dir().join(format!(
+            "nlc-pending-registration-test-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+        base
+    }
+
+    #[test]
+    fn test_record_and_remove() {
+        let base = temp_base("record");
+        let mut registration = PendingRegistration {
+            volume_id: "nlc-a".to_string(),
+            shared_name: None,
+            pvc: Some(PvcRef {
+                namespace: "ns".to_string(),
+                name: "claim".to_string(),
+            }),
+            labels: BTreeMap::new(),
+            capacity_bytes: 0,
+            base_path: None,
+            eviction_priority: None,
+        };
+        record(&base, &registration).unwrap();
+        registration
+            .labels
+            .insert("team".to_string(), "ml".to_string());
+        record(&base, &registration).unwrap();
+        std::fs::write(base.join(PENDING_DIR).join("nlc-garbage"), b"{").unwrap();
+
+        assert_eq!(pending(&base).unwrap(), vec![registration]);
+        remove(&base, "nlc-a").unwrap();
+        remove(&base, "nlc-a").unwrap();
+        assert!(pending(&base).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_api() {
+        let base = temp_base("retry");
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let volume_id = volume::generate_volume_id("pvc-pending");
+        std::fs::create_dir_all(volume::volume_path(&base, &volume_id)).unwrap();
+        let gone = volume::generate_volume_id("pvc-gone");
+        for id in [&volume_id, &gone] {
+            let registration = PendingRegistration {
+                volume_id: id.clone(),
+                shared_name: None,
+                pvc: None,
+                labels: BTreeMap::new(),
+                capacity_bytes: 1 << 30,
+                base_path: None,
+                eviction_priority: None,
+            };
+            record(&base, &registration).unwrap();
+        }
+
+        assert_eq!(retry(&client, "default", "node1", &base).await.unwrap(), 1);
+        assert!(pending(&base).unwrap().is_empty());
+        let configmaps: Api<ConfigMap> = Api::namespaced(client, "default");
+        let cms = configmaps.list(&Default::default()).await.unwrap().items;
+        assert_eq!(cms.len(), 1);
+        let status = VolumeStatus::from_configmap(&cms[0]).unwrap();
+        assert_eq!(status.volume_id, volume_id);
+        assert_eq!(status.nodes_with_volume, vec!["node1"]);
+        assert_eq!(status.capacity_bytes, 1 << 30);
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
@@ -0,0 +1,443 @@
+//! Project quota accounting for volume directories.
+//!
+//! When a volume directory carries an XFS (or ext4) project ID with a block
+//! limit, its usage is read from the project quota instead of `statvfs`, which
+//! would report the whole filesystem. Quotas are read with `quotactl_fd`
+//! (Linux 5.14+), so no block device path has to be looked up.
+//!
+//! With `--quota-backend`, the node also enforces a volume's requested
+//! capacity: `xfs-project` gives the volume directory its own project ID with
+//! a block limit, `loopback` mounts a sized ext4 image there instead. Either
+//! way NodeGetVolumeStats then reports the volume's own usage.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+
+use clap::ValueEnum;
+use nix::libc;
+use serde::Serialize;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::loopback;
+use crate::volume;
+
+/// `Q_GETQUOTA` quotactl command
+const Q_GETQUOTA: u32 = 0x800007;
+/// `Q_SETQUOTA` quotactl command
+const Q_SETQUOTA: u32 = 0x800008;
+/// `QIF_BLIMITS`: the block limits of a `struct if_dqblk` are set
+const QIF_BLIMITS: u32 = 1;
+/// `FS_XFLAG_PROJINHERIT`: new files and directories inherit the project ID
+const FS_XFLAG_PROJINHERIT: u32 = 0x200;
+/// Project quota type (`PRJQUOTA`)
+const PRJQUOTA: u32 = 2;
+/// Size of a quota block as used by `dqb_bhardlimit`/`dqb_bsoftlimit` (`QIF_DQBLKSIZE`)
+const QIF_DQBLKSIZE: u64 = 1024;
+
+/// `struct fsxattr`
+#[repr(C)]
+#[derive(Default)]
+pub struct Fsxattr {
+    fsx_xflags: u32,
+    fsx_extsize: u32,
+    fsx_nextents: u32,
+    fsx_projid: u32,
+    fsx_cowextsize: u32,
+    fsx_pad: [u8; 8],
+}
+
+/// `struct if_dqblk`
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IfDqblk {
+    pub dqb_bhardlimit: u64,
+    pub dqb_bsoftlimit: u64,
+    pub dqb_curspace: u64,
+    pub dqb_ihardlimit: u64,
+    pub dqb_isoftlimit: u64,
+    pub dqb_curinodes: u64,
+    pub dqb_btime: u64,
+    pub dqb_itime: u64,
+    pub dqb_valid: u32,
+}
+
+nix::ioctl_read!(fs_ioc_fsgetxattr, b'X', 31, Fsxattr);
+nix::ioctl_write_ptr!(fs_ioc_fssetxattr, b'X', 32, Fsxattr);
+
+/// How a volume's requested capacity is enforced on the node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum QuotaBackend {
+    /// Capacity is advisory
+    #[default]
+    None,
+    /// XFS (or ext4) project quota on the volume directory
+    XfsProject,
+    /// Preallocated ext4 image mounted at the volume directory (see `loopback`)
+    Loopback,
+}
+
+impl QuotaBackend {
+    /// Check that `base` can host this backend (called once at startup)
+    pub fn check_supported(&self, base: &Path) -> Result<(), String> {
+        match self {
+            QuotaBackend::None => Ok(()),
+            QuotaBackend::XfsProject => {
+                // Project 0 always exists once project quotas are on
+                quotactl(base, Q_GETQUOTA, 0, &mut IfDqblk::default()).map_err(|e| {
+                    format!(
+                        "--quota-backend xfs-project requires project quotas enabled on {} \
+                         (mount option prjquota): {}",
+                        base.display(),
+                        e
+                    )
+                })
+            }
+            QuotaBackend::Loopback => loopback::check_supported(),
+        }
+    }
+
+    /// `XfsProject` if `base` supports it, otherwise `None` with a warning
+    /// (`--enable-xfs-quota`)
+    pub fn xfs_project_if_supported(base: &Path) -> Self {
+        match Self::XfsProject.check_supported(base) {
+            Ok(()) => Self::XfsProject,
+            Err(e) => {
+                warn!(error = %e, "XFS project quotas unavailable, volume capacity stays advisory");
+                Self::None
+            }
+        }
+    }
+
+    /// Limit the freshly created volume directory `dir` to `capacity` bytes
+    pub fn apply(
+        &self,
+        base: &Path,
+        dir: &Path,
+        tracking_id: &str,
+        capacity: u64,
+    ) -> io::Result<()> {
+        match self {
+            QuotaBackend::None => Ok(()),
+            QuotaBackend::XfsProject => {
+                // An expanded volume keeps its larger limit when published again
+                let capacity = capacity.max(project_limit(dir)?.unwrap_or(0));
+                set_project_quota(dir, project_id_for(tracking_id), capacity)
+            }
+            QuotaBackend::Loopback => {
+                let has_image = loopback::image_path(base, dir).exists();
+                if !has_image && std::fs::read_dir(dir)?.next().is_some() {
+                    // Mounting over it would hide the existing cache
+                    warn!(
+                        path = %dir.display(),
+                        "Volume directory predates the loopback backend, leaving it unlimited"
+                    );
+                    return Ok(());
+                }
+                loopback::ensure_mounted(base, dir, capacity).map(|_| ())
+            }
+        }
+    }
+
+    /// Raise the limit of the volume mounted at `path` (its directory, or a
+    /// bind mount of it) to `capacity` bytes (NodeExpandVolume). Limits are
+    /// never lowered.
+    pub fn expand(&self, path: &Path, volume_id: &str, capacity: u64) -> io::Result<()> {
+        match self {
+            // Capacity is advisory, there's nothing to raise
+            QuotaBackend::None => Ok(()),
+            QuotaBackend::XfsProject => match project_limit(path)? {
+                Some(limit) if limit >= capacity => Ok(()),
+                // Keeps the directory's project ID if it has one
+                _ => set_project_quota(path, project_id_for(volume_id), capacity),
+            },
+            QuotaBackend::Loopback => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "volumes of the loopback quota backend can't be expanded",
+            )),
+        }
+    }
+
+    /// Undo `apply` before the volume directory `dir` is deleted
+    pub fn release(&self, base: &Path, dir: &Path) -> io::Result<()> {
+        match self {
+            QuotaBackend::None => Ok(()),
+            QuotaBackend::XfsProject if !dir.exists() => Ok(()),
+            QuotaBackend::XfsProject => {
+                // A leftover limit is harmless, the project ID just goes unused
+                if let Err(e) = clear_project_quota(dir) {
+                    warn!(path = %dir.display(), error = %e, "Failed to clear project quota");
+                }
+                Ok(())
+            }
+            QuotaBackend::Loopback => loopback::release(base, dir),
+        }
+    }
+}
+
+/// Project ID of a volume, derived from its tracking ID so every node agrees
+pub fn project_id_for(tracking_id: &str) -> u32 {
+    let uuid = Uuid::new_v5(&volume::VOLUME_ID_NAMESPACE, tracking_id.as_bytes());
+    let bytes = uuid.as_bytes();
+    // Project ID 0 means "no project"
+    (u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) & 0x7fff_ffff).max(1)
+}
+
+/// Usage of a volume in bytes and inodes, as reported by NodeGetVolumeStats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub total_bytes: u64,
+    pub used_bytes: u64,
+    pub available_bytes: u64,
+    pub total_inodes: u64,
+    pub used_inodes: u64,
+    pub available_inodes: u64,
+}
+
+impl Usage {
+    /// Usage from a project quota, or `None` if the quota has no block limit.
+    /// Without an inode limit, inodes are reported as used only (total = used).
+    pub fn from_quota(dq: &IfDqblk) -> Option<Self> {
+        let total_bytes = match (dq.dqb_bhardlimit, dq.dqb_bsoftlimit) {
+            (0, 0) => return None,
+            (0, soft) => soft,
+            (hard, _) => hard,
+        }
+        .saturating_mul(QIF_DQBLKSIZE);
+        let total_inodes = match (dq.dqb_ihardlimit, dq.dqb_isoftlimit) {
+            (0, 0) => dq.dqb_curinodes,
+            (0, soft) => soft,
+            (hard, _) => hard,
+        };
+
+        Some(Self {
+            total_bytes,
+            used_bytes: dq.dqb_curspace,
+            available_bytes: total_bytes.saturating_sub(dq.dqb_curspace),
+            total_inodes,
+            used_inodes: dq.dqb_curinodes,
+            available_inodes: total_inodes.saturating_sub(dq.dqb_curinodes),
+        })
+    }
+
+    /// Usage of the whole filesystem containing `path`
+    pub fn from_statvfs(path: &Path) -> io::Result<Self> {
+        let st = nix::sys::statvfs::statvfs(path).map_err(io::Error::from)?;
+        let frsize = st.fragment_size() as u64;
+        let total_bytes = st.blocks() as u64 * frsize;
+        let free_bytes = st.blocks_free() as u64 * frsize;
+        let total_inodes = st.files() as u64;
+        let free_inodes = st.files_free() as u64;
+
+        Ok(Self {
+            total_bytes,
+            used_bytes: total_bytes.saturating_sub(free_bytes),
+            available_bytes: st.blocks_available() as u64 * frsize,
+            total_inodes,
+            used_inodes: total_inodes.saturating_sub(free_inodes),
+            available_inodes: st.files_available() as u64,
+        })
+    }
+}
+
+/// Usage of the volume at `path`: its project quota when one is set,
+/// otherwise the containing filesystem.
+pub fn volume_usage(path: &Path) -> io::Result<Usage> {
+    match project_quota(path)? {
+        Some(dq) => match Usage::from_quota(&dq) {
+            Some(usage) => Ok(usage),
+            None => Usage::from_statvfs(path),
+        },
+        None => Usage::from_statvfs(path),
+    }
+}
+
+/// Project ID of `path`, or `None` if the filesystem doesn't support project IDs
+pub fn project_id(path: &Path) -> io::Result<Option<u32>> {
+    let file = File::open(path)?;
+    let mut attr = Fsxattr::default();
+    // SAFETY: `attr` is a valid, writable `struct fsxattr`
+    match unsafe { fs_ioc_fsgetxattr(file.as_raw_fd(), &mut attr) } {
+        Ok(_) => Ok(Some(attr.fsx_projid)),
+        Err(nix::errno::Errno::ENOTTY) | Err(nix::errno::Errno::EOPNOTSUPP) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Give the directory `dir` the project `projid` (unless it has one already)
+/// and limit that project to `capacity` bytes
+pub fn set_project_quota(dir: &Path, projid: u32, capacity: u64) -> io::Result<()> {
+    let file = File::open(dir)?;
+    let mut attr = Fsxattr::default();
+    // SAFETY: `attr` is a valid, writable `struct fsxattr`
+    unsafe { fs_ioc_fsgetxattr(file.as_raw_fd(), &mut attr) }?;
+    let projid = match attr.fsx_projid {
+        0 => {
+            attr.fsx_projid = projid;
+            attr.fsx_xflags |= FS_XFLAG_PROJINHERIT;
+            // SAFETY: `attr` is a valid `struct fsxattr`
+            unsafe { fs_ioc_fssetxattr(file.as_raw_fd(), &attr) }?;
+            projid
+        }
+        existing => existing,
+    };
+
+    let mut dq = IfDqblk {
+        dqb_bhardlimit: capacity.div_ceil(QIF_DQBLKSIZE),
+        dqb_valid: QIF_BLIMITS,
+        ..Default::default()
+    };
+    quotactl(dir, Q_SETQUOTA, projid, &mut dq)
+}
+
+/// Drop the block limit of the project of `dir`
+pub fn clear_project_quota(dir: &Path) -> io::Result<()> {
+    match project_id(dir)? {
+        Some(0) | None => Ok(()),
+        Some(projid) => {
+            let mut dq = IfDqblk {
+                dqb_valid: QIF_BLIMITS,
+                ..Default::default()
+            };
+            quotactl(dir, Q_SETQUOTA, projid, &mut dq)
+        }
+    }
+}
+
+/// `quotactl_fd` on the filesystem of `path` for a project quota
+fn quotactl(path: &Path, cmd: u32, projid: u32, dq: &mut IfDqblk) -> io::Result<()> {
+    let file = File::open(path)?;
+    let cmd = (cmd << 8) | PRJQUOTA;
+    // SAFETY: quotactl_fd(fd, cmd, id, addr) with `addr` a valid, writable `struct if_dqblk`
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_quotactl_fd,
+            file.as_raw_fd(),
+            cmd,
+            projid,
+            dq as *mut IfDqblk,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Block hard limit in bytes of the project quota of `path`, or `None` if it
+/// has none
+pub fn project_limit(path: &Path) -> io::Result<Option<u64>> {
+    Ok(project_quota(path)?
+        .map(|dq| dq.dqb_bhardlimit.saturating_mul(QIF_DQBLKSIZE))
+        .filter(|&limit| limit > 0))
+}
+
+/// Project quota of `path`, or `None` if it has no project ID or quotas aren't enabled
+pub fn project_quota(path: &Path) -> io::Result<Option<IfDqblk>> {
+    let projid = match project_id(path)? {
+        Some(0) | None => return Ok(None),
+        Some(id) => id,
+    };
+
+    let mut dq = IfDqblk::default();
+    let err = match quotactl(path, Q_GETQUOTA, projid, &mut dq) {
+        Ok(()) => return Ok(Some(dq)),
+        Err(err) => err,
+    };
+    match err.raw_os_error() {
+        // Quotas not enabled, no quota for this ID, or kernel without quotactl_fd
+        Some(libc::ESRCH)
+        | Some(libc::ENOENT)
+        | Some(libc::ENOSYS)
+        | Some(libc::EINVAL)
+        | Some(libc::EOPNOTSUPP) => Ok(None),
+        _ => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_from_quota() {
+        let dq = IfDqblk {
+            dqb_bhardlimit: 1024 * 1024, // 1 GiB in 1 KiB blocks
+            dqb_curspace: 256 * 1024 * 1024,
+            dqb_ihardlimit: 1000,
+            dqb_curinodes: 10,
+            ..Default::default()
+        };
+        let usage = Usage::from_quota(&dq).unwrap();
+        assert_eq!(usage.total_bytes, 1 << 30);
+        assert_eq!(usage.used_bytes, 256 << 20);
+        assert_eq!(usage.available_bytes, 768 << 20);
+        assert_eq!(usage.total_inodes, 1000);
+        assert_eq!(usage.available_inodes, 990);
+    }
+
+    #[test]
+    fn test_usage_from_quota_soft_limit_and_over_quota() {
+        let dq = IfDqblk {
+            dqb_bsoftlimit: 1024,
+            dqb_curspace: 4096 * 1024,
+            dqb_curinodes: 7,
+            ..Default::default()
+        };
+        let usage = Usage::from_quota(&dq).unwrap();
+        assert_eq!(usage.total_bytes, 1024 * 1024);
+        assert_eq!(usage.available_bytes, 0);
+        assert_eq!(usage.total_inodes, 7);
+        assert_eq!(usage.available_inodes, 0);
+    }
+
+    #[test]
+    fn test_no_block_limit_is_not_a_quota() {
+        let dq = IfDqblk {
+            dqb_curspace: 4096,
+            dqb_ihardlimit: 100,
+            ..Default::default()
+        };
+        assert_eq!(Usage::from_quota(&dq), None);
+    }
+
+    #[test]
+    fn test_project_id_for() {
+        let id = project_id_for("nlc-7a3e8f2b-5c41-4d9a-b86f-1e4a9c2d7b5e");
+        assert_eq!(
+            id,
+            project_id_for("nlc-7a3e8f2b-5c41-4d9a-b86f-1e4a9c2d7b5e")
+        );
+        assert_ne!(id, project_id_for("shared-maven"));
+        assert!(id > 0 && id <= 0x7fff_ffff);
+    }
+
+    #[test]
+    fn test_volume_usage_without_quota_uses_statvfs() {
+        let dir = std::env::temp_dir().join(format!("nlc-quota-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // Fresh temp directories have project ID 0 (or no project IDs at all)
+        assert!(project_quota(&dir).unwrap().is_none());
+        let usage = volume_usage(&dir).unwrap();
+        let fs = Usage::from_statvfs(&dir).unwrap();
+        assert_eq!(usage.total_bytes, fs.total_bytes);
+        assert!(usage.total_bytes > 0);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_xfs_project_falls_back() {
+        // A path that can't have project quotas
+        let missing =
+            std::env::temp_dir().join(format!("nlc-quota-missing-{}", std::process::id()));
+        assert_eq!(
+            QuotaBackend::xfs_project_if_supported(&missing),
+            QuotaBackend::None
+        );
+    }
+}
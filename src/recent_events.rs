@@ -0,0 +1,155 @@
+//! Recently emitted volume events, for aggregating bursts.
+//!
+//! When a gang of pods publishes the same volume at once, each publish emits
+//! the same event. Like the core controllers' event aggregation, an event
+//! with the same volume and reason as one emitted less than
+//! `EVENT_DEDUP_WINDOW` ago bumps that Event's `count` and `lastTimestamp`
+//! instead of creating another Event. The Events are found through a small
+//! least-recently-used map, kept in memory only.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long after the last identical event one is aggregated into it
+pub const EVENT_DEDUP_WINDOW: Duration = Duration::from_secs(60);
+
+/// Number of recent events remembered
+pub const RECENT_EVENTS_CAPACITY: usize = 512;
+
+/// An Event to bump
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentEvent {
+    pub namespace: String,
+    pub name: String,
+    /// Number of events it stands for, including the one being emitted
+    pub count: i32,
+}
+
+struct Entry {
+    event: RecentEvent,
+    last_emitted: Instant,
+}
+
+/// Recent Events by volume ID and reason
+pub struct RecentEvents {
+    entries: Mutex<HashMap<(String, String), Entry>>,
+    window: Duration,
+    capacity: usize,
+}
+
+impl RecentEvents {
+    pub fn new(window: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            window,
+            capacity,
+        }
+    }
+
+    /// The Event emitted for `volume_id` and `reason` within the window
+    /// before `now`, counting one more event in it
+    pub fn bump(&self, volume_id: &str, reason: &str, now: Instant) -> Option<RecentEvent> {
+        let mut entries = self.entries.lock().unwrap();
+        let key = (volume_id.to_string(), reason.to_string());
+        let entry = entries.get_mut(&key)?;
+        if now.saturating_duration_since(entry.last_emitted) >= self.window {
+            entries.remove(&key);
+            return None;
+        }
+        entry.event.count += 1;
+        entry.last_emitted = now;
+        Some(entry.event.clone())
+    }
+
+    /// Remember the Event `namespace`/`name` just created for `volume_id`
+    /// and `reason`, dropping the least recently used one when full
+    pub fn insert(&self, volume_id: &str, reason: &str, namespace: &str, name: &str, now: Instant) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity {
+            entries
+                .retain(|_, entry| now.saturating_duration_since(entry.last_emitted) < self.window);
+        }
+        if entries.len() >= self.capacity {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_emitted)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                entries.remove(&key);
+            }
+        }
+        entries.insert(
+            (volume_id.to_string(), reason.to_string()),
+            Entry {
+                event: RecentEvent {
+                    namespace: namespace.to_string(),
+                    name: name.to_string(),
+                    count: 1,
+                },
+                last_emitted: now,
+            },
+        );
+    }
+
+    /// Forget the Event of `volume_id` and `reason`, e.g. once it expired
+    pub fn forget(&self, volume_id: &str, reason: &str) {
+        self.entries
+            .lock()
+            .unwrap()
+            .remove(&(volume_id.to_string(), reason.to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_events() {
+        let recent = RecentEvents::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert_eq!(recent.bump("nlc-a", "VolumePublished", start), None);
+        recent.insert("nlc-a", "VolumePublished", "driver", "nlc-1", start);
+        let event = recent.bump("nlc-a", "VolumePublished", at(30)).unwrap();
+        assert_eq!((event.name.as_str(), event.count), ("nlc-1", 2));
+        // The window starts over at each event
+        assert_eq!(
+            recent
+                .bump("nlc-a", "VolumePublished", at(80))
+                .unwrap()
+                .count,
+            3
+        );
+        // Other reasons and volumes are apart
+        assert_eq!(recent.bump("nlc-a", "NodeCleanupComplete", at(80)), None);
+        assert_eq!(recent.bump("nlc-b", "VolumePublished", at(80)), None);
+
+        // Expired
+        assert_eq!(recent.bump("nlc-a", "VolumePublished", at(140)), None);
+        assert_eq!(recent.bump("nlc-a", "VolumePublished", at(141)), None);
+
+        recent.insert("nlc-a", "VolumePublished", "driver", "nlc-2", at(200));
+        recent.forget("nlc-a", "VolumePublished");
+        assert_eq!(recent.bump("nlc-a", "VolumePublished", at(201)), None);
+    }
+
+    #[test]
+    fn test_recent_events_capacity() {
+        let recent = RecentEvents::new(Duration::from_secs(60), 2);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        recent.insert("nlc-a", "VolumePublished", "driver", "nlc-1", start);
+        recent.insert("nlc-b", "VolumePublished", "driver", "nlc-2", at(1));
+        // Used most recently
+        recent.bump("nlc-a", "VolumePublished", at(2)).unwrap();
+        recent.insert("nlc-c", "VolumePublished", "driver", "nlc-3", at(3));
+
+        assert!(recent.bump("nlc-b", "VolumePublished", at(4)).is_none());
+        assert!(recent.bump("nlc-a", "VolumePublished", at(4)).is_some());
+        assert!(recent.bump("nlc-c", "VolumePublished", at(4)).is_some());
+    }
+}
@@ -0,0 +1,209 @@
+//! `node-local-cache selftest`: smoke-test mounting on a node.
+//!
+//! Goes through what NodePublishVolume and NodeUnpublishVolume do to the
+//! filesystem, without Kubernetes: create a scratch volume directory under
+//! the base path, bind-mount it on a scratch target, write a file through
+//! the mount and read it back from the directory, remount read-only, then
+//! unmount and remove both. Needs root, like the node plugin.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use nix::mount::MsFlags;
+
+use crate::volume;
+
+/// Content written through the mount and read back
+const PROBE: &[u8] = b"node-local-cache selftest\n";
+
+#[derive(Parser, Debug)]
+#[command(name = "node-local-cache selftest")]
+#[command(about = "Mount and unmount a scratch volume to check the node can serve volumes")]
+pub struct SelftestArgs {
+    /// Base path for cache volumes, as given to the node plugin
+    #[arg(long, default_value = "/var/node-local-cache")]
+    pub base_path: PathBuf,
+}
+
+/// A passed step and how long it took
+#[derive(Debug)]
+pub struct Step {
+    pub name: &'static str,
+    pub elapsed: Duration,
+}
+
+/// A failed step, with the steps that passed before it
+#[derive(Debug)]
+pub struct Failure {
+    pub passed: Vec<Step>,
+    pub step: &'static str,
+    pub error: String,
+}
+
+/// Scratch directories, unmounted and removed when dropped
+struct Scratch {
+    source: PathBuf,
+    target: PathBuf,
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        if volume::is_mounted(&self.target).unwrap_or(false) {
+            let _ = volume::unmount(&self.target);
+        }
+        let _ = std::fs::remove_dir_all(&self.source);
+        let _ = std::fs::remove_dir(&self.target);
+    }
+}
+
+/// Run the self-test against `base_path`
+pub fn run(base_path: &Path) -> Result<Vec<Step>, Failure> {
+    let id = format!("selftest-{}", std::process::id());
+    // Hidden, so the node plugin never takes it for a volume
+    let scratch = Scratch {
+        source: base_path.join(format!(".{}", id)),
+        target: std::env::temp_dir().join(format!("nlc-{}-target", id)),
+    };
+    let mut passed = Vec::new();
+    let mut step = |name: &'static str, f: &mut dyn FnMut() -> Result<(), String>| {
+        let started = Instant::now();
+        match f() {
+            Ok(()) => {
+                passed.push(Step {
+                    name,
+                    elapsed: started.elapsed(),
+                });
+                Ok(())
+            }
+            Err(error) => Err((name, error)),
+        }
+    };
+    let (source, target) = (&scratch.source, &scratch.target);
+    let file = "probe";
+
+    let result = (|| {
+        step("create volume directory", &mut || {
+            std::fs::create_dir_all(source).map_err(|e| format!("{}: {}", source.display(), e))
+        })?;
+        step("create target directory", &mut || {
+            std::fs::create_dir_all(target).map_err(|e| format!("{}: {}", target.display(), e))
+        })?;
+        step("bind mount", &mut || {
+            nix::mount::mount(
+                Some(source.as_path()),
+                target.as_path(),
+                None::<&str>,
+                MsFlags::MS_BIND,
+                None::<&str>,
+            )
+            .map_err(|e| e.to_string())?;
+            match volume::is_mounted(target) {
+                Ok(true) => Ok(()),
+                Ok(false) => Err("mount succeeded but the target isn't in /proc/mounts".into()),
+                Err(e) => Err(e.message().to_string()),
+            }
+        })?;
+        step("write through mount", &mut || {
+            std::fs::write(target.join(file), PROBE).map_err(|e| e.to_string())?;
+            match std::fs::read(source.join(file)) {
+                Ok(read) if read == PROBE => Ok(()),
+                Ok(_) => Err("the volume directory holds different content".into()),
+                Err(e) => Err(format!("not visible in the volume directory: {}", e)),
+            }
+        })?;
+        step("remount readonly", &mut || {
+            nix::mount::mount(
+                None::<&str>,
+                target.as_path(),
+                None::<&str>,
+                MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY,
+                None::<&str>,
+            )
+            .map_err(|e| e.to_string())?;
+            match volume::mount_is_readonly(target) {
+                Ok(Some(true)) => {}
+                result => return Err(format!("mount isn't readonly: {:?}", result)),
+            }
+            match std::fs::write(target.join(file), PROBE) {
+                Err(e) if e.raw_os_error() == Some(nix::libc::EROFS) => Ok(()),
+                Err(e) => Err(format!("write failed, but not as read-only: {}", e)),
+                Ok(()) => Err("write succeeded on a readonly mount".into()),
+            }
+        })?;
+        step("unmount", &mut || {
+            volume::unmount(target).map_err(|e| e.to_string())?;
+            match volume::is_mounted(target) {
+                Ok(false) => Ok(()),
+                Ok(true) => Err("target still mounted".into()),
+                Err(e) => Err(e.message().to_string()),
+            }
+        })?;
+        Ok(())
+    })();
+
+    drop(scratch);
+    match result {
+        Ok(()) => Ok(passed),
+        Err((step, error)) => Err(Failure {
+            passed,
+            step,
+            error,
+        }),
+    }
+}
+
+/// Run the self-test, print a report and return the process exit code
+pub fn main(args: &SelftestArgs) -> i32 {
+    let started = Instant::now();
+    let print = |steps: &[Step]| {
+        for step in steps {
+            println!("ok      {} ({:.1?})", step.name, step.elapsed);
+        }
+    };
+    match run(&args.base_path) {
+        Ok(steps) => {
+            print(&steps);
+            println!(
+                "selftest passed for {} in {:.1?}",
+                args.base_path.display(),
+                started.elapsed()
+            );
+            0
+        }
+        Err(failure) => {
+            print(&failure.passed);
+            println!("FAILED  {}: {}", failure.step, failure.error);
+            if !nix::unistd::geteuid().is_root() {
+                println!("note: the self-test mounts, which needs root");
+            }
+            println!("selftest failed for {}", args.base_path.display());
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selftest() {
+        let base = std::env::temp_dir().join(format!("nlc-selftest-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+
+        if !nix::unistd::geteuid().is_root() {
+            let failure = run(&base).unwrap_err();
+            assert_eq!(failure.step, "bind mount");
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+
+        let steps = run(&base).unwrap();
+        assert_eq!(steps.len(), 6);
+        assert_eq!(steps.last().unwrap().name, "unmount");
+        // Scratch directories are gone
+        assert_eq!(std::fs::read_dir(&base).unwrap().count(), 0);
+        let _ = std::fs::remove_dir_all(base);
+    }
+}
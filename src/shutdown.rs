@@ -0,0 +1,189 @@
+//! Graceful shutdown on SIGTERM/SIGINT.
+//!
+//! When the DaemonSet pod is rolled, the kubelet sends SIGTERM. Rather than
+//! dying halfway through a bind mount, the gRPC server stops accepting new
+//! requests, lets the ones in flight finish, and only then are the background
+//! loops cancelled and the socket removed. Last, the log is flushed, so the
+//! audit lines of the final requests reach the container runtime before the
+//! process exits. Metrics are scraped, so there is nothing to push for them.
+
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinSet;
+use tracing::info;
+
+/// Number of RPCs in flight that change state (mounts, volume creation and
+/// deletion)
+#[derive(Debug, Clone, Default)]
+pub struct InFlight(Arc<AtomicUsize>);
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a request until the guard is dropped
+    pub fn start(&self) -> InFlightGuard {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.0.clone())
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+pub struct InFlightGuard(Arc<AtomicUsize>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Wait for SIGTERM or SIGINT. Returns the number of requests in flight at
+/// that point, which the server then drains.
+pub async fn signalled(in_flight: &InFlight) -> usize {
+    let (mut sigterm, mut sigint) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!(error = %e, "Failed to install signal handlers");
+            return std::future::pending().await;
+        }
+    };
+    let name = tokio::select! {
+        _ = sigterm.recv() => "SIGTERM",
+        _ = sigint.recv() => "SIGINT",
+    };
+    let count = in_flight.count();
+    info!(
+        signal = name,
+        in_flight = count,
+        "Shutting down: no longer accepting requests, draining those in flight"
+    );
+    count
+}
+
+/// After the server drained the `drained` requests in flight on shutdown:
+/// cancel the background loops and remove the socket, so nothing connects to
+/// a server that's gone. Then flush `log`, the writer the log (audit lines
+/// included) goes to.
+pub async fn stop(
+    mut background: JoinSet<()>,
+    socket: &Path,
+    drained: usize,
+    log: &mut impl Write,
+) {
+    let tasks = background.len();
+    background.shutdown().await;
+    if let Err(e) = std::fs::remove_file(socket) {
+        tracing::warn!(socket = %socket.display(), error = %e, "Failed to remove socket");
+    }
+    info!(
+        drained_requests = drained,
+        background_tasks = tasks,
+        "Drained in-flight requests and shut down"
+    );
+    if let Err(e) = log.flush() {
+        // Nowhere left to log it but stderr
+        eprintln!("Failed to flush the log on shutdown: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Log writer that, like a buffered stdout, only keeps what was flushed
+    #[derive(Clone, Default)]
+    struct BufferedLog {
+        pending: Arc<Mutex<Vec<u8>>>,
+        flushed: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl Write for BufferedLog {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.pending.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            let pending = std::mem::take(&mut *self.pending.lock().unwrap());
+            self.flushed.lock().unwrap().extend(pending);
+            Ok(())
+        }
+    }
+
+    impl BufferedLog {
+        fn flushed(&self) -> Vec<serde_json::Value> {
+            String::from_utf8(self.flushed.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_in_flight() {
+        let in_flight = InFlight::new();
+        let first = in_flight.start();
+        let second = in_flight.clone().start();
+        assert_eq!(in_flight.count(), 2);
+        drop(first);
+        assert_eq!(in_flight.count(), 1);
+        drop(second);
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_stop_flushes_log() {
+        let log = BufferedLog::default();
+        let writer = log.clone();
+        let _subscriber = tracing::subscriber::set_default(
+            tracing_subscriber::FmtSubscriber::builder()
+                .json()
+                .with_writer(move || writer.clone())
+                .finish(),
+        );
+        let socket =
+            std::env::temp_dir().join(format!("nlc-shutdown-test-{}.sock", std::process::id()));
+        std::fs::write(&socket, "").unwrap();
+
+        // An operation that leaves an audit line, then a loop still running
+        let mut background = JoinSet::new();
+        background.spawn(async {
+            info!(
+                target: crate::cleanup::AUDIT_TARGET,
+                volume_id = "vol-1",
+                reason = "Deleted",
+                "Volume deleted"
+            );
+        });
+        while background.join_next().await.is_some() {}
+        background.spawn(std::future::pending());
+        assert!(log.flushed().is_empty());
+
+        stop(background, &socket, 1, &mut log.clone()).await;
+
+        let lines = log.flushed();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["target"], crate::cleanup::AUDIT_TARGET);
+        assert_eq!(lines[0]["fields"]["volume_id"], "vol-1");
+        assert_eq!(
+            lines[1]["fields"]["message"],
+            "Drained in-flight requests and shut down"
+        );
+        assert_eq!(lines[1]["fields"]["background_tasks"], 1);
+        assert!(log.pending.lock().unwrap().is_empty());
+        assert!(!socket.exists());
+    }
+}
@@ -0,0 +1,156 @@
+//! Staged volumes (`--enable-staging`).
+//!
+//! With staging, NodeStageVolume prepares a volume's directory once per node
+//! and bind-mounts it at the kubelet's staging target path; each
+//! NodePublishVolume then bind-mounts the staging target into its pod.
+//! NodeUnstageVolume only unmounts the staging target once none of the
+//! volume's publishes remain, counted here per volume.
+//!
+//! The counts live in memory and start empty after a restart, so
+//! NodeUnstageVolume also looks for publishes in /proc/self/mountinfo: bind
+//! mounts of what the staging target has mounted, elsewhere than under a
+//! base path.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::mount_audit::MountEntry;
+
+/// Target paths each staged volume is published at
+#[derive(Default)]
+pub struct PublishRefs {
+    targets: Mutex<HashMap<String, HashSet<PathBuf>>>,
+}
+
+impl PublishRefs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a publish of `volume_id` at `target`; publishing the same target
+    /// again counts once. Returns the volume's number of publishes.
+    pub fn add(&self, volume_id: &str, target: &Path) -> usize {
+        let mut targets = self.targets.lock().unwrap();
+        let volume = targets.entry(volume_id.to_string()).or_default();
+        volume.insert(target.to_path_buf());
+        volume.len()
+    }
+
+    /// Forget the publish of `volume_id` at `target`, if counted. Returns the
+    /// volume's number of remaining publishes.
+    pub fn remove(&self, volume_id: &str, target: &Path) -> usize {
+        let mut targets = self.targets.lock().unwrap();
+        let Some(volume) = targets.get_mut(volume_id) else {
+            return 0;
+        };
+        volume.remove(target);
+        let remaining = volume.len();
+        if remaining == 0 {
+            targets.remove(volume_id);
+        }
+        remaining
+    }
+
+    /// Number of publishes of `volume_id`
+    pub fn count(&self, volume_id: &str) -> usize {
+        self.targets
+            .lock()
+            .unwrap()
+            .get(volume_id)
+            .map_or(0, HashSet::len)
+    }
+}
+
+/// Mount points publishing the volume staged at `staging_path`, from
+/// `entries` (/proc/self/mountinfo): other mounts of the same directory of
+/// the same filesystem. Those under `base_paths` (the volume directory's own
+/// tmpfs or loopback mount) aren't publishes.
+pub fn mounted_publishes(
+    entries: &[MountEntry],
+    staging_path: &Path,
+    base_paths: &[&Path],
+) -> Vec<PathBuf> {
+    // The topmost mount is the one that was bind-mounted from
+    let Some(staged) = entries.iter().rev().find(|e| e.mount_point == staging_path) else {
+        return Vec::new();
+    };
+    entries
+        .iter()
+        .filter(|e| e.device == staged.device && e.root == staged.root)
+        .filter(|e| e.mount_point != staging_path)
+        .filter(|e| {
+            !base_paths
+                .iter()
+                .any(|base| e.mount_point.starts_with(base))
+        })
+        .map(|e| e.mount_point.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_refs_lifecycle() {
+        let refs = PublishRefs::new();
+        let (pod_a, pod_b) = (Path::new("/pods/a/mount"), Path::new("/pods/b/mount"));
+        assert_eq!(refs.count("nlc-a"), 0);
+
+        assert_eq!(refs.add("nlc-a", pod_a), 1);
+        assert_eq!(refs.add("nlc-a", pod_b), 2);
+        // Repeated publish is idempotent
+        assert_eq!(refs.add("nlc-a", pod_a), 2);
+        // Other volumes are counted apart
+        assert_eq!(refs.add("nlc-b", pod_a), 1);
+
+        assert_eq!(refs.remove("nlc-a", pod_a), 1);
+        assert_eq!(refs.remove("nlc-a", pod_a), 1);
+        assert_eq!(refs.count("nlc-a"), 1);
+        assert_eq!(refs.remove("nlc-a", pod_b), 0);
+        assert_eq!(refs.count("nlc-a"), 0);
+        assert!(!refs.targets.lock().unwrap().contains_key("nlc-a"));
+
+        // Unpublish of a volume that isn't staged
+        assert_eq!(refs.remove("nlc-c", pod_a), 0);
+        assert_eq!(refs.count("nlc-b"), 1);
+    }
+
+    #[test]
+    fn test_mounted_publishes() {
+        let entry = |device: &str, root: &str, mount_point: &str| MountEntry {
+            device: device.to_string(),
+            root: PathBuf::from(root),
+            mount_point: PathBuf::from(mount_point),
+            readonly: false,
+        };
+        let staging = Path::new("/var/lib/kubelet/plugins/pv/globalmount");
+        let base = Path::new("/var/node-local-cache");
+        let entries = vec![
+            entry("8:1", "/", "/"),
+            entry(
+                "8:1",
+                "/var/node-local-cache/nlc-a",
+                staging.to_str().unwrap(),
+            ),
+            entry("8:1", "/var/node-local-cache/nlc-a", "/pods/p1/mount"),
+            // Readonly subpath of the publish
+            entry("8:1", "/var/node-local-cache/nlc-a/ro", "/pods/p1/mount/ro"),
+            entry("8:1", "/var/node-local-cache/nlc-b", "/pods/p2/mount"),
+        ];
+        assert_eq!(
+            mounted_publishes(&entries, staging, &[base]),
+            vec![PathBuf::from("/pods/p1/mount")]
+        );
+        // Not staged
+        assert!(mounted_publishes(&entries[..1], staging, &[base]).is_empty());
+
+        // A tmpfs-backed volume: its own mount under the base path isn't a publish
+        let entries = vec![
+            entry("0:50", "/", "/var/node-local-cache/nlc-t"),
+            entry("0:50", "/", staging.to_str().unwrap()),
+        ];
+        assert!(mounted_publishes(&entries, staging, &[base]).is_empty());
+    }
+}
@@ -0,0 +1,276 @@
+//! Startup event (`--emit-startup-event`).
+//!
+//! Each driver instance emits one `DriverStarted` event when it starts,
+//! describing its mode, node, version, base path and enabled features, so
+//! `kubectl get events` shows when each instance started and with what
+//! configuration. The event is attached to the driver's pod (`POD_NAME`), or
+//! to the Node when the pod name isn't known.
+//!
+//! The event has a fixed name per object, so a crash loop doesn't pile up
+//! events: a restart updates the existing one (bumping its count), and only
+//! if it was last updated `STARTUP_EVENT_MIN_INTERVAL` ago or more. Failures
+//! are logged and otherwise ignored.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use k8s_openapi::api::core::v1::{Event, ObjectReference};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+use kube::{
+    api::{Api, PostParams},
+    Client,
+};
+use tracing::{debug, info, warn};
+
+use crate::cleanup;
+use crate::config::{Args, Mode};
+use crate::disk_monitor::NODE_EVENT_NAMESPACE;
+
+/// Minimum time between two updates of the startup event of an object
+pub const STARTUP_EVENT_MIN_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+pub const STARTUP_EVENT_REASON: &str = "DriverStarted";
+
+/// The startup event of a driver instance
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartupEvent {
+    pub involved_object: ObjectReference,
+    pub message: String,
+}
+
+impl StartupEvent {
+    /// The startup event for the configuration in `args`; `None` when there
+    /// is nothing to attach it to (controller mode without a pod name)
+    pub fn from_args(args: &Args) -> Option<Self> {
+        let involved_object = match (&args.pod_name, &args.mode, &args.node_name) {
+            (Some(pod), _, _) => ObjectReference {
+                api_version: Some("v1".to_string()),
+                kind: Some("Pod".to_string()),
+                name: Some(pod.clone()),
+                namespace: Some(args.namespace.clone()),
+                ..Default::default()
+            },
+            (None, Mode::Node, Some(node)) => ObjectReference {
+                api_version: Some("v1".to_string()),
+                kind: Some("Node".to_string()),
+                name: Some(node.clone()),
+                ..Default::default()
+            },
+            _ => return None,
+        };
+        Some(Self {
+            involved_object,
+            message: startup_message(args),
+        })
+    }
+
+    fn namespace(&self) -> &str {
+        self.involved_object
+            .namespace
+            .as_deref()
+            .unwrap_or(NODE_EVENT_NAMESPACE)
+    }
+
+    /// Fixed, so restarts find the event of the previous start
+    fn name(&self) -> String {
+        format!(
+            "{}.nlc-startup",
+            self.involved_object.name.as_deref().unwrap_or_default()
+        )
+    }
+
+    /// Create the event, or update it unless it was updated less than
+    /// `STARTUP_EVENT_MIN_INTERVAL` before `now`. Returns whether it was written.
+    pub async fn emit(&self, client: &Client, now: DateTime<Utc>) -> Result<bool, kube::Error> {
+        let events: Api<Event> = Api::namespaced(client.clone(), self.namespace());
+        let name = self.name();
+
+        let Some(mut event) = events.get_opt(&name).await? else {
+            let event = Event {
+                metadata: kube::api::ObjectMeta {
+                    name: Some(name),
+                    namespace: Some(self.namespace().to_string()),
+                    ..Default::default()
+                },
+                involved_object: self.involved_object.clone(),
+                reason: Some(STARTUP_EVENT_REASON.to_string()),
+                message: Some(self.message.clone()),
+                type_: Some("Normal".to_string()),
+                count: Some(1),
+                first_timestamp: Some(Time(now)),
+                last_timestamp: Some(Time(now)),
+                ..Default::default()
+            };
+            events.create(&PostParams::default(), &event).await?;
+            return Ok(true);
+        };
+
+        let last = event
+            .last_timestamp
+            .as_ref()
+            .or(event.first_timestamp.as_ref())
+            .map(|t| t.0);
+        let recent = last.is_some_and(|last| {
+            now.signed_duration_since(last)
+                .to_std()
+                .is_ok_and(|since| since < STARTUP_EVENT_MIN_INTERVAL)
+        });
+        if recent {
+            return Ok(false);
+        }
+        event.message = Some(self.message.clone());
+        event.count = Some(event.count.unwrap_or(1) + 1);
+        event.last_timestamp = Some(Time(now));
+        events
+            .replace(&name, &PostParams::default(), &event)
+            .await?;
+        Ok(true)
+    }
+}
+
+/// Describe the instance configured by `args`
+pub fn startup_message(args: &Args) -> String {
+    let mut message = format!(
+        "node-local-cache {} started in {} mode",
+        env!("CARGO_PKG_VERSION"),
+        match args.mode {
+            Mode::Controller => "controller",
+            Mode::Node => "node",
+        }
+    );
+    let settings = &args.effective_config()["settings"];
+    if matches!(args.mode, Mode::Node) {
+        if let Some(node) = &args.node_name {
+            message.push_str(&format!(" on node {}", node));
+        }
+        message.push_str(&format!(
+            ", base path {}, directory backend {}, quota backend {}",
+            args.base_path.display(),
+            settings["directory-backend"].as_str().unwrap_or_default(),
+            settings["quota-backend"].as_str().unwrap_or_default()
+        ));
+    }
+    message.push_str(&format!(", features: {}", enabled_features(settings)));
+    message
+}
+
+/// Boolean settings that are on, e.g. `disk-usage-monitor, node-singleton-lock`
+fn enabled_features(settings: &serde_json::Value) -> String {
+    let features: Vec<&str> = settings
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(_, value)| value.as_bool() == Some(true))
+        .map(|(key, _)| key.as_str())
+        .collect();
+    if features.is_empty() {
+        "none".to_string()
+    } else {
+        features.join(", ")
+    }
+}
+
+/// Emit the startup event, logging instead of failing
+pub async fn emit(client: Client, event: StartupEvent) {
+    if !cleanup::events_enabled() {
+        info!(
+            reason = STARTUP_EVENT_REASON,
+            message = %event.message,
+            "Event (not emitted, events disabled)"
+        );
+        return;
+    }
+    match event.emit(&client, Utc::now()).await {
+        Ok(true) => debug!(name = %event.name(), "Emitted startup event"),
+        Ok(false) => debug!(
+            name = %event.name(),
+            "Startup event updated recently, not updating it again"
+        ),
+        Err(e) => warn!(error = %e, "Failed to emit startup event"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_api::FakeApiServer;
+
+    #[test]
+    fn test_startup_message_reflects_config() {
+        let args = Args::try_load_from([
+            "nlc",
+            "--mode",
+            "node",
+            "--node-name",
+            "node1",
+            "--base-path",
+            "/mnt/cache",
+            "--quota-backend",
+            "xfs-project",
+            "--disk-usage-monitor",
+            "--node-singleton-lock",
+        ])
+        .unwrap();
+        let event = StartupEvent::from_args(&args).unwrap();
+        assert_eq!(event.involved_object.kind.as_deref(), Some("Node"));
+        assert_eq!(event.involved_object.name.as_deref(), Some("node1"));
+        assert_eq!(
+            event.message,
+            format!(
+                "node-local-cache {} started in node mode on node node1, base path /mnt/cache, \
+                 directory backend dir, quota backend xfs-project, \
+                 features: disk-usage-monitor, node-singleton-lock",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        let args = Args::try_load_from([
+            "nlc",
+            "--mode",
+            "controller",
+            "--namespace",
+            "nlc",
+            "--pod-name",
+            "nlc-controller-0",
+        ])
+        .unwrap();
+        let event = StartupEvent::from_args(&args).unwrap();
+        assert_eq!(event.involved_object.kind.as_deref(), Some("Pod"));
+        assert_eq!(event.namespace(), "nlc");
+        assert!(event
+            .message
+            .ends_with("started in controller mode, features: none"));
+
+        // Nothing to attach it to
+        let args = Args::try_load_from(["nlc", "--mode", "controller"]).unwrap();
+        assert_eq!(StartupEvent::from_args(&args), None);
+    }
+
+    #[tokio::test]
+    async fn test_emit_rate_limited_with_api() {
+        let api = FakeApiServer::new(&["node1"]);
+        let client = api.client();
+        let args =
+            Args::try_load_from(["nlc", "--mode", "node", "--pod-name", "nlc-node-abc"]).unwrap();
+        let event = StartupEvent::from_args(&args).unwrap();
+        // Event timestamps have second precision
+        let start: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+
+        assert!(event.emit(&client, start).await.unwrap());
+        // Crash loop: restarts within the interval don't touch it
+        let soon = start + chrono::Duration::minutes(1);
+        assert!(!event.emit(&client, soon).await.unwrap());
+        let later = start + chrono::Duration::minutes(15);
+        assert!(event.emit(&client, later).await.unwrap());
+
+        let events = api.events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            events[0].metadata.name.as_deref(),
+            Some("nlc-node-abc.nlc-startup")
+        );
+        assert_eq!(events[0].reason.as_deref(), Some(STARTUP_EVENT_REASON));
+        assert_eq!(events[0].count, Some(2));
+        assert_eq!(events[0].last_timestamp, Some(Time(later)));
+    }
+}
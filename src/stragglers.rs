@@ -0,0 +1,178 @@
+//! Cleanups left to nodes that missed the cleanup quorum (`--cleanup-quorum`).
+//!
+//! When the controller prunes a volume's ConfigMap once a quorum of its nodes
+//! reported, the nodes that didn't are listed in the `nlc-cleanup-stragglers`
+//! ConfigMap, one key per node holding the volume IDs left to it. Each node's
+//! cleanup loop deletes those directories on its own and drops them from its
+//! key; nobody waits for that or tracks whether it succeeded. A volume that
+//! has a tracking ConfigMap again (a shared cache taken back into use) is
+//! dropped without touching its directory.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::Client;
+
+use crate::cleanup;
+use crate::coordination::{self, CoordinationBackend};
+
+/// Name of the ConfigMap listing stragglers' volumes
+pub const STRAGGLERS_CM: &str = "nlc-cleanup-stragglers";
+
+/// Maximum retries for optimistic concurrency conflicts
+const MAX_RETRIES: u32 = 10;
+
+/// Volume IDs of one node's key (one per line)
+fn parse_ids(value: &str) -> BTreeSet<String> {
+    value
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+fn format_ids(ids: &BTreeSet<String>) -> String {
+    ids.iter().cloned().collect::<Vec<_>>().join("\n")
+}
+
+/// Apply `f` to the node → volume IDs map, creating the ConfigMap if missing.
+/// Nodes left without volumes are dropped; nothing is written if nothing changed.
+async fn update<F>(client: &Client, namespace: &str, f: F) -> Result<(), kube::Error>
+where
+    F: Fn(&mut BTreeMap<String, BTreeSet<String>>),
+{
+    let configmaps = coordination::configmaps(client, namespace);
+    let mut attempt = 0;
+    loop {
+        let existing = configmaps.get_opt(STRAGGLERS_CM).await?;
+        let data = existing
+            .as_ref()
+            .and_then(|cm| cm.data.clone())
+            .unwrap_or_default();
+        let mut nodes: BTreeMap<String, BTreeSet<String>> = data
+            .iter()
+            .map(|(node, ids)| (node.clone(), parse_ids(ids)))
+            .collect();
+        f(&mut nodes);
+        let updated: BTreeMap<String, String> = nodes
+            .iter()
+            .filter(|(_, ids)| !ids.is_empty())
+            .map(|(node, ids)| (node.clone(), format_ids(ids)))
+            .collect();
+        if updated == data {
+            return Ok(());
+        }
+
+        let result = match existing {
+            Some(mut cm) => {
+                cm.data = Some(updated);
+                configmaps.replace(STRAGGLERS_CM, &cm).await
+            }
+            None => {
+                let cm = ConfigMap {
+                    metadata: kube::api::ObjectMeta {
+                        name: Some(STRAGGLERS_CM.to_string()),
+                        namespace: Some(namespace.to_string()),
+                        ..Default::default()
+                    },
+                    data: Some(updated),
+                    ..Default::default()
+                };
+                configmaps.create(&cm).await
+            }
+        };
+        match result {
+            Err(kube::Error::Api(err)) if err.code == 409 && attempt < MAX_RETRIES => {
+                attempt += 1;
+                cleanup::backoff_sleep(attempt).await;
+            }
+            result => return result.map(drop),
+        }
+    }
+}
+
+/// Leave the cleanup of `volume_id` to `nodes`
+pub async fn record(
+    client: &Client,
+    namespace: &str,
+    volume_id: &str,
+    nodes: &[String],
+) -> Result<(), kube::Error> {
+    update(client, namespace, |map| {
+        for node in nodes {
+            map.entry(node.clone())
+                .or_default()
+                .insert(volume_id.to_string());
+        }
+    })
+    .await
+}
+
+/// Volumes left to `node`
+pub async fn pending(
+    client: &Client,
+    namespace: &str,
+    node: &str,
+) -> Result<BTreeSet<String>, kube::Error> {
+    let configmaps = coordination::configmaps(client, namespace);
+    Ok(configmaps
+        .get_opt(STRAGGLERS_CM)
+        .await?
+        .and_then(|cm| cm.data)
+        .and_then(|data| data.get(node).map(|ids| parse_ids(ids)))
+        .unwrap_or_default())
+}
+
+/// Drop `volume_ids` from the volumes left to `node`
+pub async fn remove(
+    client: &Client,
+    namespace: &str,
+    node: &str,
+    volume_ids: &[String],
+) -> Result<(), kube::Error> {
+    update(client, namespace, |map| {
+        if let Some(ids) = map.get_mut(node) {
+            for id in volume_ids {
+                ids.remove(id);
+            }
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake_api::FakeApiServer;
+
+    #[tokio::test]
+    async fn test_record_and_remove_with_api() {
+        let api = FakeApiServer::new(&["node1", "node2"]);
+        let client = api.client();
+        let nodes = ["node1".to_string(), "node2".to_string()];
+
+        assert!(pending(&client, "default", "node1")
+            .await
+            .unwrap()
+            .is_empty());
+        record(&client, "default", "nlc-a", &nodes).await.unwrap();
+        record(&client, "default", "nlc-b", &nodes[..1])
+            .await
+            .unwrap();
+        assert_eq!(
+            pending(&client, "default", "node1").await.unwrap(),
+            BTreeSet::from(["nlc-a".to_string(), "nlc-b".to_string()])
+        );
+
+        remove(&client, "default", "node2", &["nlc-a".to_string()])
+            .await
+            .unwrap();
+        let configmaps: kube::Api<ConfigMap> = kube::Api::namespaced(client.clone(), "default");
+        let data = configmaps.get(STRAGGLERS_CM).await.unwrap().data.unwrap();
+        // Nodes without volumes left are dropped
+        assert_eq!(
+            data,
+            BTreeMap::from([("node1".to_string(), "nlc-a\nnlc-b".to_string())])
+        );
+    }
+}
@@ -0,0 +1,169 @@
+//! Supervision of long-running background tasks.
+//!
+//! The cleanup loops are expected to run forever. If one panics (or returns),
+//! the supervisor logs it, counts a restart and respawns it with backoff, so
+//! cleanup doesn't silently stop until the pod restarts.
+
+use std::any::Any;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use tracing::{error, warn};
+
+use crate::metrics;
+
+/// Delay before the first respawn
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Maximum delay between respawns
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Run the task produced by `make_task` forever, respawning it whenever it
+/// panics or exits.
+pub async fn supervise<F, Fut>(task: &'static str, make_task: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    supervise_with_backoff(task, INITIAL_BACKOFF, MAX_BACKOFF, make_task).await
+}
+
+async fn supervise_with_backoff<F, Fut>(
+    task: &'static str,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    make_task: F,
+) where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let mut backoff = initial_backoff;
+
+    loop {
+        let started = Instant::now();
+        let handle = tokio::spawn(make_task());
+        // Cancelling the supervisor (on shutdown) cancels the task too
+        let _abort = AbortOnDrop(handle.abort_handle());
+        match handle.await {
+            Ok(()) => warn!(task = task, "Background task exited unexpectedly"),
+            Err(e) if e.is_panic() => {
+                error!(
+                    task = task,
+                    panic = %panic_message(e.into_panic().as_ref()),
+                    "Background task panicked"
+                );
+            }
+            Err(e) => error!(task = task, error = %e, "Background task was cancelled"),
+        }
+
+        metrics::metrics().record_task_restart(task);
+
+        // A task that ran for a while before failing starts over with a short backoff
+        if started.elapsed() > max_backoff {
+            backoff = initial_backoff;
+        }
+        warn!(
+            task = task,
+            backoff_ms = backoff.as_millis() as u64,
+            "Restarting background task"
+        );
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+struct AbortOnDrop(tokio::task::AbortHandle);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "<non-string panic payload>".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_panicking_task_is_respawned() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let done_tx = Arc::new(std::sync::Mutex::new(Some(done_tx)));
+
+        let task_runs = runs.clone();
+        let supervisor = tokio::spawn(supervise_with_backoff(
+            "test-task",
+            Duration::from_millis(1),
+            Duration::from_millis(10),
+            move || {
+                let runs = task_runs.clone();
+                let done_tx = done_tx.clone();
+                async move {
+                    if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first run fails");
+                    }
+                    // Second run keeps going, like a healthy loop
+                    if let Some(tx) = done_tx.lock().unwrap().take() {
+                        let _ = tx.send(());
+                    }
+                    std::future::pending::<()>().await;
+                }
+            },
+        ));
+
+        tokio::time::timeout(Duration::from_secs(5), done_rx)
+            .await
+            .expect("task was not respawned")
+            .unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+        supervisor.abort();
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_supervisor_cancels_task() {
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let started_tx = Arc::new(std::sync::Mutex::new(Some(started_tx)));
+        let (dropped_tx, dropped_rx) = tokio::sync::oneshot::channel::<()>();
+        let dropped_tx = Arc::new(std::sync::Mutex::new(Some(dropped_tx)));
+
+        let supervisor = tokio::spawn(supervise("test-task", move || {
+            let started_tx = started_tx.lock().unwrap().take();
+            // Dropped (closing the channel) when the task is cancelled
+            let dropped_tx = dropped_tx.lock().unwrap().take();
+            async move {
+                let _dropped_tx = dropped_tx;
+                if let Some(tx) = started_tx {
+                    let _ = tx.send(());
+                }
+                std::future::pending::<()>().await;
+            }
+        }));
+        started_rx.await.unwrap();
+        supervisor.abort();
+
+        // Closed without a value once the task is gone
+        tokio::time::timeout(Duration::from_secs(5), dropped_rx)
+            .await
+            .expect("task outlived its supervisor")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_panic_message() {
+        let payload: Box<dyn Any + Send> = Box::new("boom");
+        assert_eq!(panic_message(payload.as_ref()), "boom");
+        let payload: Box<dyn Any + Send> = Box::new(String::from("bang"));
+        assert_eq!(panic_message(payload.as_ref()), "bang");
+    }
+}
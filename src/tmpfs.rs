@@ -0,0 +1,188 @@
+//! tmpfs-backed volumes (`node-local-cache.csi.io/backing: tmpfs`).
+//!
+//! A volume whose StorageClass asks for tmpfs backing gets a tmpfs mounted at
+//! its volume directory, limited to the volume's requested capacity, which is
+//! then bind-mounted into pods like any other volume directory. Its content
+//! lives in memory and is lost when the tmpfs is unmounted or the node
+//! reboots. Unpublishing only removes the pod's bind mount, so the cache
+//! outlives pod restarts like a disk-backed one; node cleanup unmounts the
+//! tmpfs, which frees the memory, and only then deletes the empty directory.
+
+use std::collections::HashMap;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use nix::mount::MsFlags;
+
+use crate::volume;
+
+/// What a volume directory is stored on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backing {
+    /// A directory under the base path
+    #[default]
+    Disk,
+    /// A tmpfs mounted at the volume directory
+    Tmpfs,
+}
+
+impl Backing {
+    /// Parse a `volume::BACKING_KEY` value: `disk` or `tmpfs`
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "disk" => Ok(Self::Disk),
+            "tmpfs" => Ok(Self::Tmpfs),
+            other => Err(format!(
+                "Invalid {} `{}` (expected disk or tmpfs)",
+                volume::BACKING_KEY,
+                other
+            )),
+        }
+    }
+
+    /// The backing from the volume context; disk when not given
+    pub fn from_volume_context(context: &HashMap<String, String>) -> Result<Self, String> {
+        context
+            .get(volume::BACKING_KEY)
+            .map_or(Ok(Self::Disk), |value| Self::parse(value))
+    }
+}
+
+/// Mount options of a tmpfs of `size` bytes whose root has permissions `mode`
+pub fn mount_data(size: u64, mode: u32) -> String {
+    format!("size={},mode={:o}", size, mode & 0o7777)
+}
+
+/// Whether `dir` is itself a tmpfs mount point
+pub fn is_tmpfs_mount(dir: &Path) -> io::Result<bool> {
+    if !volume::is_mounted(dir).map_err(|s| io::Error::other(s.message().to_string()))? {
+        return Ok(false);
+    }
+    Ok(volume::filesystem_type(dir)?.as_deref() == Some("tmpfs"))
+}
+
+/// Mount a `size` bytes tmpfs at the volume directory `dir`, keeping its
+/// permissions. Returns whether a mount was made (false if `dir` was
+/// already mounted).
+pub fn ensure_mounted(dir: &Path, size: u64) -> io::Result<bool> {
+    if volume::is_mounted(dir).map_err(|s| io::Error::other(s.message().to_string()))? {
+        return Ok(false);
+    }
+    let mode = std::fs::metadata(dir)?.permissions().mode();
+    nix::mount::mount(
+        Some("nlc-tmpfs"),
+        dir,
+        Some("tmpfs"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(mount_data(size, mode).as_str()),
+    )
+    .map_err(|e| {
+        io::Error::new(
+            io::Error::from(e).kind(),
+            format!("Failed to mount tmpfs at {}: {}", dir.display(), e),
+        )
+    })?;
+    Ok(true)
+}
+
+/// Change the size of the tmpfs mounted at `path` (the volume directory, or
+/// a bind mount of it) to `size` bytes, keeping its content
+pub fn resize(path: &Path, size: u64) -> io::Result<()> {
+    nix::mount::mount(
+        None::<&str>,
+        path,
+        None::<&str>,
+        MsFlags::MS_REMOUNT | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+        Some(format!("size={}", size).as_str()),
+    )
+    .map_err(|e| {
+        io::Error::new(
+            io::Error::from(e).kind(),
+            format!("Failed to resize tmpfs at {}: {}", path.display(), e),
+        )
+    })
+}
+
+/// Unmount the tmpfs at the volume directory `dir`, dropping its content,
+/// so the directory left behind is empty. Returns whether there was one.
+pub fn release(dir: &Path) -> io::Result<bool> {
+    if !dir.exists() || !is_tmpfs_mount(dir)? {
+        return Ok(false);
+    }
+    volume::unmount(dir).map_err(|e| {
+        io::Error::new(
+            io::Error::from(e).kind(),
+            format!("Failed to unmount tmpfs at {}: {}", dir.display(), e),
+        )
+    })?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backing_from_volume_context() {
+        let context =
+            |value: &str| HashMap::from([(volume::BACKING_KEY.to_string(), value.into())]);
+
+        assert_eq!(
+            Backing::from_volume_context(&HashMap::new()),
+            Ok(Backing::Disk)
+        );
+        assert_eq!(
+            Backing::from_volume_context(&context("disk")),
+            Ok(Backing::Disk)
+        );
+        assert_eq!(
+            Backing::from_volume_context(&context("tmpfs")),
+            Ok(Backing::Tmpfs)
+        );
+        assert!(Backing::from_volume_context(&context("ram")).is_err());
+        assert!(Backing::from_volume_context(&context("")).is_err());
+    }
+
+    #[test]
+    fn test_mount_data() {
+        assert_eq!(mount_data(1 << 30, 0o40755), "size=1073741824,mode=755");
+        assert_eq!(mount_data(4096, 0o2775), "size=4096,mode=2775");
+    }
+
+    #[test]
+    fn test_tmpfs_mount() {
+        let dir = std::env::temp_dir().join(format!("nlc-tmpfs-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        if ensure_mounted(&dir, 1 << 20).is_err() {
+            // Not root
+            let _ = std::fs::remove_dir_all(dir);
+            return;
+        }
+        assert!(is_tmpfs_mount(&dir).unwrap());
+        assert_eq!(
+            std::fs::metadata(&dir).unwrap().permissions().mode() & 0o7777,
+            0o750
+        );
+        std::fs::write(dir.join("cached"), b"data").unwrap();
+        // Already mounted
+        assert!(!ensure_mounted(&dir, 1 << 20).unwrap());
+        // Limited to its size
+        let err = std::fs::write(dir.join("big"), vec![0u8; 2 << 20]).unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(nix::libc::ENOSPC));
+        let _ = std::fs::remove_file(dir.join("big"));
+        // Expanded in place
+        resize(&dir, 4 << 20).unwrap();
+        std::fs::write(dir.join("big"), vec![0u8; 2 << 20]).unwrap();
+        assert_eq!(std::fs::read(dir.join("cached")).unwrap(), b"data");
+
+        assert!(release(&dir).unwrap());
+        assert!(!is_tmpfs_mount(&dir).unwrap());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(!release(&dir).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -1,24 +1,124 @@
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use tonic::Status;
 use uuid::Uuid;
 
 /// Volume ID prefix
-const VOLUME_ID_PREFIX: &str = "nlc-";
+pub const VOLUME_ID_PREFIX: &str = "nlc-";
 
-/// Namespace UUID for generating deterministic volume IDs (UUIDv5)
+/// Volume context / StorageClass parameter naming a per-node cache shared by several volumes
+pub const SHARED_NAME_KEY: &str = "node-local-cache.csi.io/shared-name";
+
+/// Volume context / StorageClass parameters with the pod user namespace's UID
+/// and GID mappings, for idmapped mounts (see `idmap`)
+pub const UID_MAP_KEY: &str = "node-local-cache.csi.io/uid-map";
+pub const GID_MAP_KEY: &str = "node-local-cache.csi.io/gid-map";
+
+/// Volume context key with the capacity requested at CreateVolume, in bytes,
+/// enforced by the node's `--quota-backend`
+pub const CAPACITY_KEY: &str = "node-local-cache.csi.io/capacity-bytes";
+
+/// Volume context / StorageClass parameter listing subdirectories of the
+/// volume (comma-separated, relative) mounted read-only in a writable volume
+pub const READONLY_SUBPATHS_KEY: &str = "node-local-cache.csi.io/readonly-subpaths";
+
+/// Volume context / StorageClass parameter selecting what a volume is stored
+/// on: `disk` (default) or `tmpfs` (see `tmpfs`)
+pub const BACKING_KEY: &str = "node-local-cache.csi.io/backing";
+
+/// Volume context / StorageClass parameters (`"true"`/`"false"`, default
+/// false) mounting the volume into pods `noexec`, `nosuid` and `nodev`
+pub const NOEXEC_KEY: &str = "node-local-cache.csi.io/noexec";
+pub const NOSUID_KEY: &str = "node-local-cache.csi.io/nosuid";
+pub const NODEV_KEY: &str = "node-local-cache.csi.io/nodev";
+
+/// Volume context / StorageClass parameters setting the owner, group and
+/// (octal) permissions of the volume directory (see `ownership`)
+pub const UID_KEY: &str = "node-local-cache.csi.io/uid";
+pub const GID_KEY: &str = "node-local-cache.csi.io/gid";
+pub const MODE_KEY: &str = "node-local-cache.csi.io/mode";
+
+/// Volume context / StorageClass parameter naming an absolute host path that
+/// must exist before the volume is published (e.g. the cache disk's mountpoint)
+pub const WAIT_FOR_PATH_KEY: &str = "node-local-cache.csi.io/wait-for-path";
+
+/// Volume context / StorageClass parameter storing the volume under another
+/// base path than the node's `--base-path`; the node only accepts paths given
+/// with `--allowed-base-path`
+pub const BASE_PATH_KEY: &str = "node-local-cache.csi.io/base-path";
+
+/// Volume context key with the ID of the volume a volume was cloned from
+/// (see `clone`)
+pub const CLONE_FROM_KEY: &str = "node-local-cache.csi.io/clone-from";
+
+/// Volume context / StorageClass parameter (0-100) ordering disk pressure
+/// eviction: lower priorities are evicted first, so caches that are
+/// expensive to rebuild can be kept longest
+pub const EVICTION_PRIORITY_KEY: &str = "node-local-cache.csi.io/eviction-priority";
+/// Eviction priority of volumes without `EVICTION_PRIORITY_KEY`
+pub const DEFAULT_EVICTION_PRIORITY: u8 = 50;
+/// Highest eviction priority
+pub const MAX_EVICTION_PRIORITY: u8 = 100;
+
+/// How long NodePublishVolume waits for `WAIT_FOR_PATH_KEY` to appear
+pub const WAIT_FOR_PATH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(60);
+/// How often the path is checked while waiting
+pub const WAIT_FOR_PATH_POLL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Prefix of StorageClass parameters / volume context keys carrying a label
+/// for the volume's tracking ConfigMap (`<prefix><label key>: <value>`)
+pub const LABEL_PARAM_PREFIX: &str = "node-local-cache.csi.io/label.";
+
+/// Maximum length of a label value, and of a label key's name part
+const LABEL_NAME_MAX_LEN: usize = 63;
+/// Maximum length of a label key's prefix (a DNS subdomain)
+const LABEL_PREFIX_MAX_LEN: usize = 253;
+
+/// StorageClass parameter (from the provisioner's `--extra-create-metadata`) and
+/// volume context key holding the PersistentVolume name
+pub const PV_NAME_KEY: &str = "csi.storage.k8s.io/pv/name";
+/// Same, for the PersistentVolumeClaim's name and namespace (`--event-namespace pvc`)
+pub const PVC_NAME_KEY: &str = "csi.storage.k8s.io/pvc/name";
+pub const PVC_NAMESPACE_KEY: &str = "csi.storage.k8s.io/pvc/namespace";
+
+/// Prefix of the tracking ID used for a shared cache (e.g. `shared-maven`)
+const SHARED_ID_PREFIX: &str = "shared-";
+
+/// Directory under the base path holding shared caches
+pub const SHARED_DIR: &str = "shared";
+
+/// Directory under the base path holding volume directories whose cleanup
+/// failed (`--quarantine-failed`)
+pub const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Default namespace UUID for generating deterministic volume IDs (UUIDv5)
 /// Generated specifically for this driver: uuidgen output for "node-local-cache.csi.io"
-const VOLUME_ID_NAMESPACE: Uuid = Uuid::from_bytes([
+pub const VOLUME_ID_NAMESPACE: Uuid = Uuid::from_bytes([
     0x7a, 0x3e, 0x8f, 0x2b, 0x5c, 0x41, 0x4d, 0x9a, 0xb8, 0x6f, 0x1e, 0x4a, 0x9c, 0x2d, 0x7b, 0x5e,
 ]);
 
+/// Namespace UUID in use (`--id-namespace`), set once at startup
+static ID_NAMESPACE: OnceLock<Uuid> = OnceLock::new();
+
+/// Set the namespace UUID for volume IDs. Only the first call has an effect.
+pub fn set_id_namespace(namespace: Uuid) {
+    let _ = ID_NAMESPACE.set(namespace);
+}
+
 /// Generate a deterministic volume ID from a PVC name
 /// Uses UUIDv5 to ensure idempotency - same name always produces same ID
 pub fn generate_volume_id(name: &str) -> String {
-    let uuid = Uuid::new_v5(&VOLUME_ID_NAMESPACE, name.as_bytes());
+    volume_id_in(ID_NAMESPACE.get().unwrap_or(&VOLUME_ID_NAMESPACE), name)
+}
+
+fn volume_id_in(namespace: &Uuid, name: &str) -> String {
+    let uuid = Uuid::new_v5(namespace, name.as_bytes());
     format!("{}{}", VOLUME_ID_PREFIX, uuid)
 }
 
-/// Validate a volume ID format
+/// Validate a volume ID format.
+/// The namespace can't be recovered from an ID, so IDs generated with any
+/// `--id-namespace` are accepted.
 pub fn validate_volume_id(id: &str) -> bool {
     if !id.starts_with(VOLUME_ID_PREFIX) {
         return false;
@@ -28,14 +128,397 @@ pub fn validate_volume_id(id: &str) -> bool {
     Uuid::parse_str(uuid_part).is_ok()
 }
 
-/// Construct the volume directory path
+/// Validate a shared cache name: a DNS label (lowercase alphanumerics and '-',
+/// at most 63 chars), so it is safe both as a directory and a ConfigMap name suffix
+pub fn validate_shared_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 63
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !name.starts_with('-')
+        && !name.ends_with('-')
+}
+
+/// Tracking ID for a shared cache. Shared caches are tracked like volumes so
+/// they go through the same cleanup coordination.
+pub fn shared_volume_id(name: &str) -> String {
+    format!("{}{}", SHARED_ID_PREFIX, name)
+}
+
+/// Validate an ID tracked by a cleanup ConfigMap: a volume ID or a shared cache ID
+pub fn validate_tracking_id(id: &str) -> bool {
+    validate_volume_id(id)
+        || id
+            .strip_prefix(SHARED_ID_PREFIX)
+            .is_some_and(validate_shared_name)
+}
+
+/// Labels from `LABEL_PARAM_PREFIX` parameters (StorageClass parameters or
+/// volume context). Invalid labels are skipped with a warning.
+pub fn labels_from_parameters(
+    parameters: &std::collections::HashMap<String, String>,
+) -> std::collections::BTreeMap<String, String> {
+    parameters
+        .iter()
+        .filter_map(|(param, value)| Some((param.strip_prefix(LABEL_PARAM_PREFIX)?, value)))
+        .filter(|(key, value)| {
+            let valid = is_valid_label_key(key) && is_valid_label_value(value);
+            if !valid {
+                tracing::warn!(key = %key, value = %value, "Skipping invalid label parameter");
+            }
+            valid
+        })
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect()
+}
+
+/// Kubernetes label key: `[prefix/]name`, with a DNS subdomain prefix
+pub fn is_valid_label_key(key: &str) -> bool {
+    let (prefix, name) = match key.rsplit_once('/') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, key),
+    };
+    let valid_prefix = prefix.is_none_or(|prefix| {
+        !prefix.is_empty()
+            && prefix.len() <= LABEL_PREFIX_MAX_LEN
+            && prefix.split('.').all(|part| {
+                !part.is_empty()
+                    && part
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                    && !part.starts_with('-')
+                    && !part.ends_with('-')
+            })
+    });
+    valid_prefix && !name.is_empty() && is_valid_label_value(name)
+}
+
+/// Kubernetes label value: up to 63 alphanumerics, `-`, `_` and `.`,
+/// starting and ending with an alphanumeric; may be empty
+pub fn is_valid_label_value(value: &str) -> bool {
+    let alphanumeric = |c: Option<char>| c.is_none_or(|c| c.is_ascii_alphanumeric());
+    value.len() <= LABEL_NAME_MAX_LEN
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        && alphanumeric(value.chars().next())
+        && alphanumeric(value.chars().last())
+}
+
+/// Requested capacity from the volume context; `None` when absent or zero
+pub fn capacity_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Option<u64>, String> {
+    match context.get(CAPACITY_KEY) {
+        None => Ok(None),
+        Some(value) => match value.parse::<u64>() {
+            Ok(0) => Ok(None),
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(_) => Err(format!("Invalid {}: {}", CAPACITY_KEY, value)),
+        },
+    }
+}
+
+/// Check a CreateVolume capacity range: a `limit_bytes` of 0 means no limit,
+/// and `required_bytes` may not exceed `max` (`--max-volume-bytes`)
+pub fn check_capacity_range(
+    range: Option<&crate::csi::CapacityRange>,
+    max: Option<i64>,
+) -> Result<(), String> {
+    let (required, limit) = range.map_or((0, 0), |r| (r.required_bytes, r.limit_bytes));
+    if required < 0 || limit < 0 {
+        return Err("Capacity must not be negative".to_string());
+    }
+    if limit > 0 && required > limit {
+        return Err(format!(
+            "Required capacity {} exceeds the limit {}",
+            required, limit
+        ));
+    }
+    match max {
+        Some(max) if required > max => Err(format!(
+            "Required capacity {} exceeds the maximum volume size of {} bytes",
+            required, max
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// New capacity asked for by an expand request: at least `required_bytes`,
+/// and `limit_bytes` when only that is given
+pub fn expanded_capacity(range: Option<&crate::csi::CapacityRange>) -> Result<u64, String> {
+    let (required, limit) = range.map_or((0, 0), |r| (r.required_bytes, r.limit_bytes));
+    if required < 0 || limit < 0 {
+        return Err("Capacity must not be negative".to_string());
+    }
+    if limit > 0 && required > limit {
+        return Err(format!(
+            "Required capacity {} exceeds the limit {}",
+            required, limit
+        ));
+    }
+    match (required, limit) {
+        (0, 0) => Err("A capacity range is required".to_string()),
+        (0, limit) => Ok(limit as u64),
+        (required, _) => Ok(required as u64),
+    }
+}
+
+/// Parse a `READONLY_SUBPATHS_KEY` value: relative paths that stay inside
+/// the volume, deduplicated and sorted so parents come before children
+pub fn parse_readonly_subpaths(value: &str) -> Result<Vec<PathBuf>, String> {
+    use std::path::Component;
+
+    let mut subpaths = Vec::new();
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut subpath = PathBuf::new();
+        for component in Path::new(entry).components() {
+            match component {
+                Component::Normal(name) => subpath.push(name),
+                Component::CurDir => {}
+                _ => {
+                    return Err(format!(
+                        "Readonly subpath `{}` must be relative and stay inside the volume",
+                        entry
+                    ))
+                }
+            }
+        }
+        if subpath.as_os_str().is_empty() {
+            return Err(format!(
+                "Readonly subpath `{}` is the whole volume; publish it readonly instead",
+                entry
+            ));
+        }
+        subpaths.push(subpath);
+    }
+    subpaths.sort();
+    subpaths.dedup();
+    Ok(subpaths)
+}
+
+/// Readonly subpaths from the volume context; empty when absent
+pub fn readonly_subpaths_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Vec<PathBuf>, String> {
+    context
+        .get(READONLY_SUBPATHS_KEY)
+        .map_or(Ok(Vec::new()), |value| parse_readonly_subpaths(value))
+}
+
+/// Parse a `WAIT_FOR_PATH_KEY` value: an absolute path without `..`
+pub fn parse_wait_for_path(value: &str) -> Result<PathBuf, String> {
+    use std::path::Component;
+
+    let path = Path::new(value.trim());
+    if !path.is_absolute() || path.components().any(|c| c == Component::ParentDir) {
+        return Err(format!(
+            "Path to wait for `{}` must be absolute, without `..`",
+            value
+        ));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// The path to wait for from the volume context, if any
+pub fn wait_for_path_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Option<PathBuf>, String> {
+    context
+        .get(WAIT_FOR_PATH_KEY)
+        .map(|value| parse_wait_for_path(value))
+        .transpose()
+}
+
+/// The ID of the volume to clone from the volume context, if any
+pub fn clone_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Option<&str>, String> {
+    match context.get(CLONE_FROM_KEY) {
+        Some(id) if !validate_volume_id(id) => {
+            Err(format!("Invalid {} value: {}", CLONE_FROM_KEY, id))
+        }
+        id => Ok(id.map(String::as_str)),
+    }
+}
+
+/// Parse an `EVICTION_PRIORITY_KEY` value: an integer from 0 to
+/// `MAX_EVICTION_PRIORITY`
+pub fn parse_eviction_priority(value: &str) -> Result<u8, String> {
+    match value.trim().parse::<u8>() {
+        Ok(priority) if priority <= MAX_EVICTION_PRIORITY => Ok(priority),
+        _ => Err(format!(
+            "Eviction priority `{}` must be an integer from 0 to {}",
+            value, MAX_EVICTION_PRIORITY
+        )),
+    }
+}
+
+/// The eviction priority from the volume context, if any
+pub fn eviction_priority_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Option<u8>, String> {
+    context
+        .get(EVICTION_PRIORITY_KEY)
+        .map(|value| parse_eviction_priority(value))
+        .transpose()
+}
+
+/// Parse a `BASE_PATH_KEY` value: an absolute path without `..`, other than `/`
+pub fn parse_base_path(value: &str) -> Result<PathBuf, String> {
+    use std::path::Component;
+
+    let path = Path::new(value.trim());
+    if !path.is_absolute()
+        || path.parent().is_none()
+        || path.components().any(|c| c == Component::ParentDir)
+    {
+        return Err(format!(
+            "Base path `{}` must be absolute, without `..`, and not `/`",
+            value
+        ));
+    }
+    Ok(path.to_path_buf())
+}
+
+/// The base path from the volume context, if any
+pub fn base_path_from_volume_context(
+    context: &std::collections::HashMap<String, String>,
+) -> Result<Option<PathBuf>, String> {
+    context
+        .get(BASE_PATH_KEY)
+        .map(|value| parse_base_path(value))
+        .transpose()
+}
+
+/// Wait until `path` exists, checking every `poll`, for at most `timeout`.
+/// Returns whether it exists.
+pub async fn wait_for_path(
+    path: &Path,
+    timeout: std::time::Duration,
+    poll: std::time::Duration,
+) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if tokio::fs::try_exists(path).await.unwrap_or(false) {
+            return true;
+        }
+        let now = tokio::time::Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        tokio::time::sleep(poll.min(deadline - now)).await;
+    }
+}
+
+/// The directory `subpath` (from `parse_readonly_subpaths`) of the volume
+/// at `root`, creating missing directories. Symlinks are refused, so the
+/// result can't lead out of the volume whatever the volume holds.
+pub fn resolve_subpath(root: &Path, subpath: &Path) -> std::io::Result<PathBuf> {
+    use std::io::{Error, ErrorKind};
+
+    let mut path = root.to_path_buf();
+    for component in subpath.components() {
+        path.push(component);
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} is a symlink", path.display()),
+                ))
+            }
+            Ok(metadata) if !metadata.is_dir() => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{} is not a directory", path.display()),
+                ))
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => std::fs::create_dir(&path)?,
+            Err(e) => return Err(e),
+        }
+    }
+    if !path.canonicalize()?.starts_with(root.canonicalize()?) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} leads out of the volume", path.display()),
+        ));
+    }
+    Ok(path)
+}
+
+/// Whether the kubelet's `target` can be mounted on: an absolute path
+/// without `..`, strictly below one of `roots` (e.g. the kubelet pods
+/// directory) even once the symlinks along its existing part are resolved.
+/// Any target passes the root check when `roots` is empty.
+pub fn is_safe_target(target: &Path, roots: &[PathBuf]) -> bool {
+    use std::path::Component;
+
+    if !target.is_absolute() || target.components().any(|c| c == Component::ParentDir) {
+        return false;
+    }
+    if roots.is_empty() {
+        return true;
+    }
+    let Some(resolved) = resolve_existing(target) else {
+        return false;
+    };
+    roots.iter().any(|root| {
+        target.starts_with(root)
+            && target != root.as_path()
+            && root
+                .canonicalize()
+                .is_ok_and(|root| resolved.starts_with(&root) && resolved != root)
+    })
+}
+
+/// `path` with its deepest existing ancestor canonicalized, and the missing
+/// components appended as they are. `None` if a dangling symlink is on the
+/// way, as creating the missing part would follow it.
+fn resolve_existing(path: &Path) -> Option<PathBuf> {
+    let mut missing = Vec::new();
+    let mut existing = path;
+    loop {
+        match existing.canonicalize() {
+            Ok(resolved) => {
+                return Some(missing.iter().rev().fold(resolved, |p, c| p.join(c)));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if std::fs::symlink_metadata(existing).is_ok() {
+                    return None;
+                }
+                missing.push(existing.file_name()?);
+                existing = existing.parent()?;
+            }
+            Err(_) => return None,
+        }
+    }
+}
+
+/// Construct the volume directory path.
+/// Shared cache IDs resolve to `<base>/shared/<name>`.
 pub fn volume_path(base: &Path, volume_id: &str) -> PathBuf {
-    base.join(volume_id)
+    match volume_id.strip_prefix(SHARED_ID_PREFIX) {
+        Some(name) => base.join(SHARED_DIR).join(name),
+        None => base.join(volume_id),
+    }
+}
+
+/// Device ID (`st_dev`) of the filesystem holding `path`
+pub fn device_id(path: &Path) -> std::io::Result<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    Ok(std::fs::metadata(path)?.dev())
 }
 
 /// Check if a path is a mount point by reading /proc/mounts
 /// Uses proc-mounts crate which handles the simpler /proc/mounts format
-/// (more robust than /proc/self/mountinfo parsing in complex container environments)
+/// (more robust than /proc/self/mountinfo parsing in complex container environments).
+/// A non-empty directory not listed there is looked up in /proc/self/mountinfo
+/// as well, since some runtimes list mount points in a form that doesn't
+/// compare equal (escaped characters), and a bind mount must not be taken for
+/// a plain directory.
 #[allow(clippy::result_large_err)]
 pub fn is_mounted(path: &Path) -> Result<bool, Status> {
     use proc_mounts::MountIter;
@@ -57,13 +540,603 @@ pub fn is_mounted(path: &Path) -> Result<bool, Status> {
         }
     }
 
-    Ok(false)
+    // An empty directory has nothing to lose from being taken for unmounted
+    if !std::fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some()) {
+        return Ok(false);
+    }
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| Status::internal(format!("Failed to read /proc/self/mountinfo: {}", e)))?;
+    match mountinfo_mount(&mountinfo, path) {
+        Some((mount_id, parent_id)) => {
+            tracing::debug!(
+                path = %path.display(),
+                mount_id = mount_id,
+                parent_id = parent_id,
+                "Mount point only found in /proc/self/mountinfo"
+            );
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Mount ID and parent mount ID of the topmost mount at `path`, according
+/// to `mountinfo`
+fn mountinfo_mount(mountinfo: &str, path: &Path) -> Option<(u32, u32)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = path.as_os_str().as_bytes();
+    mountinfo.lines().rev().find_map(|line| {
+        // id parent major:minor root mount-point ...
+        let mut fields = line.split(' ');
+        let mount_id = fields.next()?.parse().ok()?;
+        let parent_id = fields.next()?.parse().ok()?;
+        let mount_point = fields.nth(2)?;
+        (unescape_mountinfo(mount_point) == path).then_some((mount_id, parent_id))
+    })
+}
+
+/// Filesystem type of the mount holding `path` (its longest mount point
+/// prefix), as listed in /proc/mounts
+pub fn filesystem_type(path: &Path) -> std::io::Result<Option<String>> {
+    let path = path.canonicalize()?;
+    let mut best: Option<(PathBuf, String)> = None;
+    for mount in proc_mounts::MountIter::new()?.flatten() {
+        let longer = best
+            .as_ref()
+            .is_none_or(|(dest, _)| mount.dest.as_os_str().len() >= dest.as_os_str().len());
+        if path.starts_with(&mount.dest) && longer {
+            best = Some((mount.dest, mount.fstype));
+        }
+    }
+    Ok(best.map(|(_, fstype)| fstype))
+}
+
+/// Whether a requested `fs_type` is satisfied by an actual filesystem type.
+/// ext2/ext3 are served by the ext4 driver and show up as `ext4`.
+pub fn fs_type_matches(requested: &str, actual: &str) -> bool {
+    let family = |t: &str| match t.to_ascii_lowercase().as_str() {
+        "ext2" | "ext3" | "ext4" => "ext4".to_string(),
+        other => other.to_string(),
+    };
+    family(requested) == family(actual)
+}
+
+/// Whether the mount at `path` is read-only, from its per-mount options in
+/// /proc/self/mountinfo (the topmost mount if several are stacked). `None`
+/// if nothing is mounted there.
+pub fn mount_is_readonly(path: &Path) -> std::io::Result<Option<bool>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(mountinfo_readonly(&mountinfo, path))
+}
+
+fn mountinfo_readonly(mountinfo: &str, path: &Path) -> Option<bool> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = path.as_os_str().as_bytes();
+    mountinfo.lines().rev().find_map(|line| {
+        // id parent major:minor root mount-point mount-options ...
+        let mut fields = line.split(' ');
+        let mount_point = fields.nth(4)?;
+        let options = fields.next()?;
+        (unescape_mountinfo(mount_point) == path)
+            .then(|| options.split(',').any(|option| option == "ro"))
+    })
+}
+
+/// Mount points strictly below `path` (e.g. a volume's readonly subpaths),
+/// deepest first so they can be unmounted in order
+pub fn submounts(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")?;
+    Ok(mountinfo_submounts(&mountinfo, path))
+}
+
+fn mountinfo_submounts(mountinfo: &str, path: &Path) -> Vec<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    let mut mounts: Vec<PathBuf> = mountinfo
+        .lines()
+        .filter_map(|line| {
+            let mount_point = line.split(' ').nth(4)?;
+            let mount_point = PathBuf::from(std::ffi::OsString::from_vec(unescape_mountinfo(
+                mount_point,
+            )));
+            (mount_point != path && mount_point.starts_with(path)).then_some(mount_point)
+        })
+        .collect();
+    mounts.sort_by(|a, b| b.cmp(a));
+    mounts.dedup();
+    mounts
+}
+
+/// Undo mountinfo's octal escapes (`\040` for a space, ...)
+pub fn unescape_mountinfo(field: &str) -> Vec<u8> {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).and_then(|digits| {
+            let digits = std::str::from_utf8(digits).ok()?;
+            u8::from_str_radix(digits, 8).ok()
+        });
+        match (bytes[i], octal) {
+            (b'\\', Some(byte)) => {
+                out.push(byte);
+                i += 4;
+            }
+            (byte, _) => {
+                out.push(byte);
+                i += 1;
+            }
+        }
+    }
+    out
+}
+
+/// How `unmount` got a mount off its path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Unmounted {
+    Regular,
+    /// Lazy (`MNT_DETACH`) after the regular unmount failed
+    Lazy,
+}
+
+/// Unmount `path`, falling back to a lazy unmount if it is busy or wedged
+pub fn unmount(path: &Path) -> nix::Result<Unmounted> {
+    match nix::mount::umount(path) {
+        Ok(()) => Ok(Unmounted::Regular),
+        Err(e) => {
+            tracing::warn!(path = %path.display(), error = %e, "Regular unmount failed, trying lazy unmount");
+            nix::mount::umount2(path, nix::mount::MntFlags::MNT_DETACH)?;
+            Ok(Unmounted::Lazy)
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_mountinfo_readonly() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 8:2 /cache /var/lib/kubelet/pods/a/mount ro,relatime shared:2 - xfs /dev/sdb1 rw
+31 22 8:2 /cache /var/lib/kubelet/pods/b/my\\040mount rw,nosuid - xfs /dev/sdb1 rw
+32 30 8:2 /cache /var/lib/kubelet/pods/a/mount rw,relatime - xfs /dev/sdb1 rw
+";
+        // The topmost of stacked mounts counts
+        assert_eq!(
+            mountinfo_readonly(mountinfo, Path::new("/var/lib/kubelet/pods/a/mount")),
+            Some(false)
+        );
+        assert_eq!(
+            mountinfo_readonly(mountinfo, Path::new("/var/lib/kubelet/pods/b/my mount")),
+            Some(false)
+        );
+        assert_eq!(
+            mountinfo_readonly(
+                &mountinfo.replace("32 30", "#"),
+                Path::new("/var/lib/kubelet/pods/a/mount")
+            ),
+            Some(true)
+        );
+        assert_eq!(mountinfo_readonly(mountinfo, Path::new("/var/lib")), None);
+    }
+
+    #[test]
+    fn test_mountinfo_mount() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 8:2 /nlc-a /var/lib/kubelet/pods/a/volumes/kubernetes.io~csi/my\\040pv/mount rw - xfs /dev/sdb1 rw
+31 22 8:2 /nlc-b /var/lib/kubelet/pods/b/tab\\011and\\134backslash rw - xfs /dev/sdb1 rw
+32 30 8:2 /nlc-a /var/lib/kubelet/pods/a/volumes/kubernetes.io~csi/my\\040pv/mount ro - xfs /dev/sdb1 rw
+bogus line
+";
+        // The topmost of stacked mounts
+        assert_eq!(
+            mountinfo_mount(
+                mountinfo,
+                Path::new("/var/lib/kubelet/pods/a/volumes/kubernetes.io~csi/my pv/mount")
+            ),
+            Some((32, 30))
+        );
+        assert_eq!(
+            mountinfo_mount(
+                mountinfo,
+                Path::new("/var/lib/kubelet/pods/b/tab\tand\\backslash")
+            ),
+            Some((31, 22))
+        );
+        // Escaped in mountinfo, so the literal form is a plain directory
+        assert_eq!(
+            mountinfo_mount(
+                mountinfo,
+                Path::new("/var/lib/kubelet/pods/a/volumes/kubernetes.io~csi/my\\040pv/mount")
+            ),
+            None
+        );
+        assert_eq!(
+            mountinfo_mount(mountinfo, Path::new("/var/lib/kubelet/pods/a")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_mounted_plain_directory() {
+        let dir = std::env::temp_dir().join(format!("nlc-volume-plain-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(!is_mounted(&dir).unwrap());
+        // Non-empty: also checked against mountinfo
+        std::fs::write(dir.join("cached"), b"data").unwrap();
+        assert!(!is_mounted(&dir).unwrap());
+        assert!(is_mounted(Path::new("/")).unwrap());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_mountinfo_submounts() {
+        let mountinfo = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+30 22 8:2 /cache /var/lib/kubelet/pods/a/mount rw,relatime - xfs /dev/sdb1 rw
+31 30 8:2 /cache/seed /var/lib/kubelet/pods/a/mount/seed ro,relatime - xfs /dev/sdb1 rw
+32 31 8:2 /cache/seed/x /var/lib/kubelet/pods/a/mount/seed/x ro,relatime - xfs /dev/sdb1 rw
+33 30 8:2 /cache/my\\040dir /var/lib/kubelet/pods/a/mount/my\\040dir ro - xfs /dev/sdb1 rw
+34 22 8:2 /cache /var/lib/kubelet/pods/a/mount2 rw - xfs /dev/sdb1 rw
+";
+        assert_eq!(
+            mountinfo_submounts(mountinfo, Path::new("/var/lib/kubelet/pods/a/mount")),
+            vec![
+                PathBuf::from("/var/lib/kubelet/pods/a/mount/seed/x"),
+                PathBuf::from("/var/lib/kubelet/pods/a/mount/seed"),
+                PathBuf::from("/var/lib/kubelet/pods/a/mount/my dir"),
+            ]
+        );
+        assert!(
+            mountinfo_submounts(mountinfo, Path::new("/var/lib/kubelet/pods/a/mount2")).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_mount_is_readonly() {
+        if !nix::unistd::geteuid().is_root() {
+            // Skip test unless running as root
+            return;
+        }
+        let base = std::env::temp_dir().join(format!("nlc-volume-ro-{}", std::process::id()));
+        let (source, target) = (base.join("source"), base.join("target"));
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::create_dir_all(&target).unwrap();
+        assert_eq!(mount_is_readonly(&target).unwrap(), None);
+
+        let bind =
+            |flags| nix::mount::mount(Some(&source), &target, None::<&str>, flags, None::<&str>);
+        if bind(nix::mount::MsFlags::MS_BIND).is_err() {
+            // No mount privileges (e.g. unprivileged container)
+            let _ = std::fs::remove_dir_all(base);
+            return;
+        }
+        assert_eq!(mount_is_readonly(&target).unwrap(), Some(false));
+
+        nix::mount::mount(
+            None::<&str>,
+            &target,
+            None::<&str>,
+            nix::mount::MsFlags::MS_BIND
+                | nix::mount::MsFlags::MS_REMOUNT
+                | nix::mount::MsFlags::MS_RDONLY,
+            None::<&str>,
+        )
+        .unwrap();
+        assert_eq!(mount_is_readonly(&target).unwrap(), Some(true));
+
+        nix::mount::umount(&target).unwrap();
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_fs_type_matches() {
+        assert!(fs_type_matches("xfs", "xfs"));
+        assert!(fs_type_matches("XFS", "xfs"));
+        assert!(fs_type_matches("ext3", "ext4"));
+        assert!(!fs_type_matches("ext4", "xfs"));
+        assert!(!fs_type_matches("btrfs", "tmpfs"));
+    }
+
+    #[test]
+    fn test_filesystem_type() {
+        // The mount holding / always has a type; a path below it at least as specific
+        let root = filesystem_type(Path::new("/")).unwrap().unwrap();
+        assert!(!root.is_empty());
+        assert!(filesystem_type(&std::env::temp_dir()).unwrap().is_some());
+        assert!(filesystem_type(Path::new("/nonexistent/path")).is_err());
+    }
+
+    #[test]
+    fn test_labels_from_parameters() {
+        let params = std::collections::HashMap::from(
+            [
+                ("node-local-cache.csi.io/label.team", "data"),
+                (
+                    "node-local-cache.csi.io/label.example.com/cost-center",
+                    "cc-42",
+                ),
+                ("node-local-cache.csi.io/label.empty", ""),
+                ("node-local-cache.csi.io/label.bad key", "x"),
+                ("node-local-cache.csi.io/label.team2", "-bad"),
+                ("node-local-cache.csi.io/shared-name", "maven"),
+            ]
+            .map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        let labels = labels_from_parameters(&params);
+        assert_eq!(
+            labels,
+            std::collections::BTreeMap::from(
+                [
+                    ("empty", ""),
+                    ("example.com/cost-center", "cc-42"),
+                    ("team", "data"),
+                ]
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+            )
+        );
+    }
+
+    #[test]
+    fn test_label_validation() {
+        assert!(is_valid_label_key("team"));
+        assert!(is_valid_label_key("app.kubernetes.io/name"));
+        assert!(!is_valid_label_key(""));
+        assert!(!is_valid_label_key("/team"));
+        assert!(!is_valid_label_key("Example.com/team"));
+        assert!(!is_valid_label_key("example.com/"));
+        assert!(!is_valid_label_key(&"a".repeat(64)));
+
+        assert!(is_valid_label_value(""));
+        assert!(is_valid_label_value("cc-42_x.y"));
+        assert!(!is_valid_label_value("x-"));
+        assert!(!is_valid_label_value("a b"));
+        assert!(!is_valid_label_value(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn test_capacity_from_volume_context() {
+        let context = |value: &str| {
+            std::collections::HashMap::from([(CAPACITY_KEY.to_string(), value.to_string())])
+        };
+        assert_eq!(capacity_from_volume_context(&Default::default()), Ok(None));
+        assert_eq!(capacity_from_volume_context(&context("0")), Ok(None));
+        assert_eq!(
+            capacity_from_volume_context(&context("1073741824")),
+            Ok(Some(1 << 30))
+        );
+        assert!(capacity_from_volume_context(&context("1Gi")).is_err());
+    }
+
+    #[test]
+    fn test_check_capacity_range() {
+        let range = |required_bytes, limit_bytes| crate::csi::CapacityRange {
+            required_bytes,
+            limit_bytes,
+        };
+        let max = Some(10 << 30);
+        assert!(check_capacity_range(None, None).is_ok());
+        assert!(check_capacity_range(None, max).is_ok());
+        // A zero limit is no limit
+        assert!(check_capacity_range(Some(&range(1 << 40, 0)), None).is_ok());
+        assert!(check_capacity_range(Some(&range(10 << 30, 0)), max).is_ok());
+        assert!(check_capacity_range(Some(&range(10 << 30, 20 << 30)), max).is_ok());
+        // Only the required capacity counts against the maximum
+        assert!(check_capacity_range(Some(&range(0, 20 << 30)), max).is_ok());
+        let err = check_capacity_range(Some(&range((10 << 30) + 1, 0)), max).unwrap_err();
+        assert!(err.contains("10737418240"), "{}", err);
+        assert!(check_capacity_range(Some(&range(2 << 30, 1 << 30)), None).is_err());
+        assert!(check_capacity_range(Some(&range(2 << 30, 2 << 30)), None).is_ok());
+        assert!(check_capacity_range(Some(&range(-1, 0)), None).is_err());
+        assert!(check_capacity_range(Some(&range(0, -1)), None).is_err());
+    }
+
+    #[test]
+    fn test_expanded_capacity() {
+        let range = |required_bytes, limit_bytes| crate::csi::CapacityRange {
+            required_bytes,
+            limit_bytes,
+        };
+        assert_eq!(expanded_capacity(Some(&range(2 << 30, 0))), Ok(2 << 30));
+        assert_eq!(
+            expanded_capacity(Some(&range(2 << 30, 4 << 30))),
+            Ok(2 << 30)
+        );
+        assert_eq!(expanded_capacity(Some(&range(0, 4 << 30))), Ok(4 << 30));
+        assert!(expanded_capacity(None).is_err());
+        assert!(expanded_capacity(Some(&range(0, 0))).is_err());
+        assert!(expanded_capacity(Some(&range(4 << 30, 2 << 30))).is_err());
+        assert!(expanded_capacity(Some(&range(-1, 0))).is_err());
+    }
+
+    #[test]
+    fn test_parse_wait_for_path() {
+        assert_eq!(
+            parse_wait_for_path("/mnt/nvme0/"),
+            Ok(PathBuf::from("/mnt/nvme0"))
+        );
+        for invalid in ["mnt/nvme0", "", "/mnt/../etc"] {
+            assert!(parse_wait_for_path(invalid).is_err(), "{}", invalid);
+        }
+        let context = std::collections::HashMap::from([(
+            WAIT_FOR_PATH_KEY.to_string(),
+            "/mnt/nvme0".to_string(),
+        )]);
+        assert_eq!(
+            wait_for_path_from_volume_context(&context),
+            Ok(Some(PathBuf::from("/mnt/nvme0")))
+        );
+        assert_eq!(
+            wait_for_path_from_volume_context(&Default::default()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_parse_base_path() {
+        assert_eq!(
+            parse_base_path("/mnt/nvme1/cache/"),
+            Ok(PathBuf::from("/mnt/nvme1/cache"))
+        );
+        for invalid in ["mnt/nvme1", "", "/", "/mnt/../etc"] {
+            assert!(parse_base_path(invalid).is_err(), "{}", invalid);
+        }
+        let context = std::collections::HashMap::from([(
+            BASE_PATH_KEY.to_string(),
+            "/mnt/nvme1".to_string(),
+        )]);
+        assert_eq!(
+            base_path_from_volume_context(&context),
+            Ok(Some(PathBuf::from("/mnt/nvme1")))
+        );
+        assert_eq!(base_path_from_volume_context(&Default::default()), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_eviction_priority() {
+        assert_eq!(parse_eviction_priority("0"), Ok(0));
+        assert_eq!(parse_eviction_priority(" 90 "), Ok(90));
+        assert_eq!(parse_eviction_priority("100"), Ok(100));
+        for invalid in ["101", "-1", "high", ""] {
+            assert!(parse_eviction_priority(invalid).is_err(), "{}", invalid);
+        }
+        let context = std::collections::HashMap::from([(
+            EVICTION_PRIORITY_KEY.to_string(),
+            "80".to_string(),
+        )]);
+        assert_eq!(
+            eviction_priority_from_volume_context(&context),
+            Ok(Some(80))
+        );
+        assert_eq!(
+            eviction_priority_from_volume_context(&Default::default()),
+            Ok(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_path() {
+        let dir = std::env::temp_dir().join(format!("nlc-wait-for-path-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let poll = std::time::Duration::from_millis(10);
+        let timeout = std::time::Duration::from_secs(5);
+
+        // Present immediately
+        assert!(wait_for_path(&dir, timeout, poll).await);
+
+        // Appears later
+        let later = dir.join("mounted");
+        let create = tokio::spawn({
+            let later = later.clone();
+            async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                std::fs::create_dir(later).unwrap();
+            }
+        });
+        assert!(wait_for_path(&later, timeout, poll).await);
+        create.await.unwrap();
+
+        // Never appears: gives up after the timeout
+        let started = std::time::Instant::now();
+        let never = dir.join("never");
+        assert!(!wait_for_path(&never, std::time::Duration::from_millis(100), poll).await);
+        assert!(started.elapsed() >= std::time::Duration::from_millis(100));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_readonly_subpaths() {
+        assert_eq!(
+            parse_readonly_subpaths(" seed/models, ./seed ,,seed/models/,base").unwrap(),
+            ["base", "seed", "seed/models"].map(PathBuf::from)
+        );
+        assert!(parse_readonly_subpaths("").unwrap().is_empty());
+        // Escaping the volume
+        assert!(parse_readonly_subpaths("seed,../other").is_err());
+        assert!(parse_readonly_subpaths("seed/../../other").is_err());
+        assert!(parse_readonly_subpaths("/etc").is_err());
+        // The whole volume
+        assert!(parse_readonly_subpaths("./").is_err());
+    }
+
+    #[test]
+    fn test_resolve_subpath() {
+        let base = std::env::temp_dir().join(format!("nlc-volume-subpath-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (root, outside) = (base.join("volume"), base.join("outside"));
+        std::fs::create_dir_all(root.join("seed")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+
+        // Missing directories are created
+        let path = resolve_subpath(&root, Path::new("seed/models")).unwrap();
+        assert_eq!(path, root.join("seed/models"));
+        assert!(path.is_dir());
+
+        // A symlink planted in the volume doesn't lead out of it
+        std::os::unix::fs::symlink(&outside, root.join("escape")).unwrap();
+        std::os::unix::fs::symlink("../outside", root.join("seed/relative")).unwrap();
+        let err = resolve_subpath(&root, Path::new("escape/data")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(resolve_subpath(&root, Path::new("seed/relative")).is_err());
+        assert!(!outside.join("data").exists());
+
+        std::fs::write(root.join("file"), b"data").unwrap();
+        assert!(resolve_subpath(&root, Path::new("file")).is_err());
+        let _ = std::fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_is_safe_target() {
+        let base = std::env::temp_dir().join(format!("nlc-volume-target-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        let (pods, outside) = (base.join("pods"), base.join("outside"));
+        std::fs::create_dir_all(pods.join("uid/volumes")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let roots = [pods.clone()];
+
+        assert!(is_safe_target(&pods.join("uid/volumes/pv/mount"), &roots));
+        assert!(is_safe_target(&pods.join("uid/volumes"), &roots));
+        assert!(!is_safe_target(&pods, &roots));
+        assert!(!is_safe_target(&outside.join("mount"), &roots));
+        assert!(!is_safe_target(&pods.join("uid/../../outside"), &roots));
+        assert!(!is_safe_target(Path::new("uid/volumes/pv/mount"), &roots));
+
+        // Symlinks escaping the pods directory are refused, wherever they are
+        std::os::unix::fs::symlink(&outside, pods.join("uid/escape")).unwrap();
+        std::os::unix::fs::symlink("../../../outside", pods.join("uid/volumes/relative")).unwrap();
+        std::os::unix::fs::symlink(outside.join("missing"), pods.join("uid/dangling")).unwrap();
+        assert!(!is_safe_target(&pods.join("uid/escape/mount"), &roots));
+        assert!(!is_safe_target(&pods.join("uid/escape"), &roots));
+        assert!(!is_safe_target(
+            &pods.join("uid/volumes/relative/mount"),
+            &roots
+        ));
+        assert!(!is_safe_target(&pods.join("uid/dangling/mount"), &roots));
+        // Those staying inside are fine
+        std::os::unix::fs::symlink(pods.join("uid"), pods.join("alias")).unwrap();
+        assert!(is_safe_target(&pods.join("alias/volumes/pv/mount"), &roots));
+
+        // A symlinked pods directory itself is fine (e.g. a relocated kubelet root)
+        let linked = base.join("linked-pods");
+        std::os::unix::fs::symlink(&pods, &linked).unwrap();
+        assert!(is_safe_target(
+            &linked.join("uid/volumes/pv/mount"),
+            std::slice::from_ref(&linked)
+        ));
+
+        // No roots: only the path itself is checked
+        assert!(is_safe_target(&outside.join("mount"), &[]));
+        assert!(!is_safe_target(Path::new("/a/../b"), &[]));
+        let _ = std::fs::remove_dir_all(base);
+    }
+
     #[test]
     fn test_generate_volume_id() {
         let id = generate_volume_id("pvc-12345");
@@ -83,6 +1156,20 @@ mod tests {
         assert_ne!(id1, id3);
     }
 
+    #[test]
+    fn test_volume_id_namespace() {
+        assert_eq!(
+            volume_id_in(&VOLUME_ID_NAMESPACE, "pvc-abc-123"),
+            generate_volume_id("pvc-abc-123")
+        );
+
+        // Another instance's namespace gives a different, still valid ID
+        let other = Uuid::parse_str("0b6e4c2a-3f1d-4e8b-9a7c-5d2f1e0a9b3c").unwrap();
+        let id = volume_id_in(&other, "pvc-abc-123");
+        assert_ne!(id, generate_volume_id("pvc-abc-123"));
+        assert!(validate_volume_id(&id));
+    }
+
     #[test]
     fn test_validate_volume_id() {
         // Valid IDs
@@ -111,6 +1198,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_validate_shared_name() {
+        assert!(validate_shared_name("maven"));
+        assert!(validate_shared_name("pip-cache-2"));
+
+        assert!(!validate_shared_name(""));
+        assert!(!validate_shared_name("Maven"));
+        assert!(!validate_shared_name("../etc"));
+        assert!(!validate_shared_name("a/b"));
+        assert!(!validate_shared_name("-leading"));
+        assert!(!validate_shared_name(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn test_shared_volume_path() {
+        let base = Path::new("/var/node-local-cache");
+        let id = shared_volume_id("maven");
+        assert_eq!(id, "shared-maven");
+        assert!(!validate_volume_id(&id));
+        assert!(validate_tracking_id(&id));
+        assert!(!validate_tracking_id("shared-../x"));
+        assert_eq!(
+            volume_path(base, &id),
+            PathBuf::from("/var/node-local-cache/shared/maven")
+        );
+    }
+
     #[test]
     fn test_parse_k3s_mounts() {
         // Test that proc-mounts can parse a synthetic k3s /proc/mounts file
@@ -0,0 +1,75 @@
+//! Per-volume locks serializing work on a node's volume directory.
+//!
+//! NodePublishVolume and the node cleanup loop both touch a volume's source
+//! directory: one creates and mounts it, the other deletes or clears it. Both
+//! hold the volume's lock while doing so, keyed by tracking ID (the shared
+//! cache ID for shared caches), so a directory is never mounted while it is
+//! being deleted.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::OwnedMutexGuard;
+
+/// Async locks keyed by volume tracking ID. Clones share the same locks.
+#[derive(Clone, Default)]
+pub struct VolumeLocks {
+    locks: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+}
+
+impl VolumeLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for and take the lock of `volume_id`, held until the guard drops
+    pub async fn lock(&self, volume_id: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // Drop locks nobody holds or waits for, so the map stays small
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(volume_id.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_same_volume_is_serialized() {
+        let locks = VolumeLocks::new();
+        let guard = locks.lock("nlc-a").await;
+
+        let waiter = {
+            let locks = locks.clone();
+            tokio::spawn(async move {
+                let _guard = locks.lock("nlc-a").await;
+            })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        // Other volumes aren't blocked
+        drop(locks.lock("nlc-b").await);
+
+        drop(guard);
+        tokio::time::timeout(Duration::from_secs(5), waiter)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unused_locks_are_dropped() {
+        let locks = VolumeLocks::new();
+        for i in 0..10 {
+            drop(locks.lock(&format!("nlc-{}", i)).await);
+        }
+        drop(locks.lock("nlc-last").await);
+        assert!(locks.locks.lock().unwrap().len() <= 1);
+    }
+}
@@ -12,12 +12,32 @@ mod csi {
 
 use csi::controller_client::ControllerClient;
 use csi::identity_client::IdentityClient;
-use csi::{CapacityRange, CreateVolumeRequest, DeleteVolumeRequest, GetPluginInfoRequest};
+use tonic_reflection::pb::v1::server_reflection_client::ServerReflectionClient;
+use tonic_reflection::pb::v1::server_reflection_request::MessageRequest;
+use tonic_reflection::pb::v1::server_reflection_response::MessageResponse;
+use tonic_reflection::pb::v1::ServerReflectionRequest;
+
+use csi::{
+    volume_capability, CapacityRange, CreateVolumeRequest, DeleteVolumeRequest,
+    GetPluginInfoRequest, ProbeRequest, VolumeCapability,
+};
 
 use std::sync::atomic::{AtomicU32, Ordering};
 
 static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
 
+/// A filesystem mount capability, as for a ReadWriteOnce PVC
+fn mount_capability() -> VolumeCapability {
+    VolumeCapability {
+        access_mode: Some(volume_capability::AccessMode {
+            mode: volume_capability::access_mode::Mode::SingleNodeWriter as i32,
+        }),
+        access_type: Some(volume_capability::AccessType::Mount(
+            volume_capability::MountVolume::default(),
+        )),
+    }
+}
+
 fn socket_path() -> String {
     let id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
     format!("/tmp/csi-integration-test-{}.sock", id)
@@ -30,6 +50,10 @@ struct TestServer {
 
 impl TestServer {
     fn start(mode: &str) -> Self {
+        Self::start_with(mode, &[])
+    }
+
+    fn start_with(mode: &str, extra_args: &[&str]) -> Self {
         let socket = socket_path();
 
         // Clean up any existing socket
@@ -47,6 +71,7 @@ impl TestServer {
         if mode == "node" {
             cmd.arg("--node-name").arg("test-node");
         }
+        cmd.args(extra_args);
 
         let child = cmd.spawn().expect("Failed to start server");
 
@@ -142,7 +167,7 @@ async fn test_controller_create_delete_volume() {
                 required_bytes: 1024 * 1024 * 100, // 100MB
                 limit_bytes: 0,
             }),
-            volume_capabilities: vec![],
+            volume_capabilities: vec![mount_capability()],
             parameters: Default::default(),
             secrets: Default::default(),
             volume_content_source: None,
@@ -158,6 +183,14 @@ async fn test_controller_create_delete_volume() {
         .expect("No volume in response");
     assert!(volume.volume_id.starts_with("nlc-"));
     assert_eq!(volume.capacity_bytes, 1024 * 1024 * 100);
+    // Passed on to the node for --quota-backend
+    assert_eq!(
+        volume
+            .volume_context
+            .get("node-local-cache.csi.io/capacity-bytes")
+            .map(String::as_str),
+        Some("104857600")
+    );
     assert!(
         volume.accessible_topology.is_empty(),
         "Should have no topology constraints"
@@ -194,7 +227,7 @@ async fn test_volume_id_format() {
             .create_volume(CreateVolumeRequest {
                 name: format!("test-volume-{}", i),
                 capacity_range: None,
-                volume_capabilities: vec![],
+                volume_capabilities: vec![mount_capability()],
                 parameters: Default::default(),
                 secrets: Default::default(),
                 volume_content_source: None,
@@ -215,3 +248,78 @@ async fn test_volume_id_format() {
     assert_eq!(unique.len(), ids.len(), "Volume IDs should be unique");
     println!("✓ Generated {} unique volume IDs", ids.len());
 }
+
+#[tokio::test]
+async fn test_sigterm_shuts_down_gracefully() {
+    let mut server = TestServer::start("node");
+    let channel = connect_to_socket(server.socket_path()).await;
+    IdentityClient::new(channel)
+        .probe(ProbeRequest {})
+        .await
+        .expect("Probe failed");
+
+    nix::sys::signal::kill(
+        nix::unistd::Pid::from_raw(server.child.id() as i32),
+        nix::sys::signal::Signal::SIGTERM,
+    )
+    .unwrap();
+
+    let mut status = None;
+    for _ in 0..50 {
+        status = server.child.try_wait().unwrap();
+        if status.is_some() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(status.expect("server did not exit").success());
+    assert!(
+        !std::path::Path::new(server.socket_path()).exists(),
+        "socket should be removed on shutdown"
+    );
+}
+
+/// Names of the services listed by the reflection service at `socket`
+async fn list_services(socket: &str) -> Result<Vec<String>, tonic::Status> {
+    let channel = connect_to_socket(socket).await;
+    let request = ServerReflectionRequest {
+        host: String::new(),
+        message_request: Some(MessageRequest::ListServices(String::new())),
+    };
+    let mut responses = ServerReflectionClient::new(channel)
+        .server_reflection_info(tokio_stream::once(request))
+        .await?
+        .into_inner();
+    match responses.message().await?.and_then(|r| r.message_response) {
+        Some(MessageResponse::ListServicesResponse(list)) => {
+            Ok(list.service.into_iter().map(|s| s.name).collect())
+        }
+        other => panic!("unexpected reflection response: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_reflection_lists_services() {
+    let server = TestServer::start_with("node", &["--enable-reflection"]);
+    let services = list_services(server.socket_path()).await.unwrap();
+    assert!(
+        services.contains(&"csi.v1.Identity".to_string()),
+        "{:?}",
+        services
+    );
+    assert!(
+        services.contains(&"csi.v1.Controller".to_string()),
+        "{:?}",
+        services
+    );
+    assert!(
+        services.contains(&"csi.v1.Node".to_string()),
+        "{:?}",
+        services
+    );
+
+    // Off by default
+    let server = TestServer::start("controller");
+    let status = list_services(server.socket_path()).await.unwrap_err();
+    assert_eq!(status.code(), tonic::Code::Unimplemented);
+}